@@ -23,6 +23,10 @@ pub struct CliCommands {
     #[arg(short, long)]
     pub verbose: bool,
 
+    /// Wallet passphrase (prefer the environment variable over the flag)
+    #[arg(long, global = true, env = "ARMORY_PASSPHRASE", hide_env_values = true)]
+    pub passphrase: Option<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -50,17 +54,39 @@ pub enum Commands {
     Create {
         /// Wallet name
         name: String,
-        /// Use mnemonic seed phrase (default: generate new)
-        #[arg(long)]
+        /// Restore from this mnemonic instead of generating one; "-" reads it
+        /// from stdin (prefer stdin or the environment variable over the flag)
+        #[arg(long, env = "ARMORY_MNEMONIC", hide_env_values = true)]
         mnemonic: Option<String>,
-        /// Encrypt wallet with password
+        /// Number of words when generating a new mnemonic (12-24)
+        #[arg(long, default_value = "24")]
+        words: usize,
+        /// Optional BIP-39 passphrase ("25th word") mixed into the seed; "-"
+        /// reads it from stdin (prefer stdin or the environment variable over the flag)
+        #[arg(long, env = "ARMORY_MNEMONIC_PASSPHRASE", hide_env_values = true)]
+        mnemonic_passphrase: Option<String>,
+        /// Store the wallet without a passphrase; anyone who can read its files can use it
         #[arg(long)]
-        encrypt: bool,
-        /// Account number for HD derivation (default: 0)
+        no_passphrase: bool,
+        /// Account number for HD derivation (default: 0); accounts above 0 need
+        /// history on the previous account
         #[arg(long, default_value = "0")]
         account: u32,
     },
 
+    /// Create a watch-only wallet from account descriptors
+    WatchOnly {
+        /// Wallet name
+        name: String,
+        /// Account descriptors, e.g. "wpkh([fingerprint/84'/0'/0']xpub.../<0;1>/*)",
+        /// or the JSON written by `export --format descriptors`
+        #[arg(required = true)]
+        descriptors: Vec<String>,
+        /// Store the wallet without a passphrase; anyone who can read its files can use it
+        #[arg(long)]
+        no_passphrase: bool,
+    },
+
     /// List all wallets
     List,
 
@@ -83,6 +109,9 @@ pub enum Commands {
         /// List existing addresses instead of generating new ones
         #[arg(long)]
         list: bool,
+        /// Account to use (default: 0)
+        #[arg(long, default_value = "0")]
+        account: u32,
     },
 
     /// Check wallet balance
@@ -92,6 +121,75 @@ pub enum Commands {
         /// Include unconfirmed transactions
         #[arg(long)]
         include_unconfirmed: bool,
+        /// Only show this account (default: all accounts)
+        #[arg(long)]
+        account: Option<u32>,
+    },
+
+    /// Account management
+    Account {
+        #[command(subcommand)]
+        command: AccountCommands,
+    },
+
+    /// Descriptor keychain management
+    Keychain {
+        #[command(subcommand)]
+        command: KeychainCommands,
+    },
+
+    /// Spending policy enforced before signing
+    Policy {
+        #[command(subcommand)]
+        command: PolicyCommands,
+    },
+
+    /// Wallet passphrase management
+    Passphrase {
+        #[command(subcommand)]
+        command: PassphraseCommands,
+    },
+
+    /// Coin control
+    Utxo {
+        #[command(subcommand)]
+        command: UtxoCommands,
+    },
+
+    /// Show transaction history
+    History {
+        /// Wallet name
+        wallet: String,
+        /// Only transactions on or after this date (YYYY-MM-DD, UTC)
+        #[arg(long)]
+        from: Option<String>,
+        /// Only transactions on or before this date (YYYY-MM-DD, UTC)
+        #[arg(long)]
+        to: Option<String>,
+        /// Minimum absolute amount in satoshis
+        #[arg(long)]
+        min_amount: Option<u64>,
+        /// Maximum absolute amount in satoshis
+        #[arg(long)]
+        max_amount: Option<u64>,
+        /// Only transactions whose label contains this text
+        #[arg(long)]
+        label: Option<String>,
+        /// Output format
+        #[arg(long, default_value = "table")]
+        format: HistoryFormat,
+        /// Output file path
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Report address reuse, change leaks, merged labels and dust
+    PrivacyReport {
+        /// Wallet name
+        wallet: String,
+        /// Output format
+        #[arg(long, default_value = "text")]
+        format: ReportFormat,
     },
 
     /// Send Bitcoin transaction
@@ -108,6 +206,33 @@ pub enum Commands {
         /// Create unsigned PSBT instead of broadcasting
         #[arg(long)]
         psbt_only: bool,
+        /// Spend exactly these UTXOs (txid:vout, repeatable) instead of coin selection
+        #[arg(long = "input", value_name = "TXID:VOUT")]
+        inputs: Vec<String>,
+    },
+
+    /// Merge small UTXOs while fees are low
+    Consolidate {
+        /// Wallet name
+        wallet: String,
+        /// Fee rate of the consolidations in sat/vB
+        #[arg(long)]
+        fee_rate: f64,
+        /// Highest fee rate a consolidation may pay, in sat/vB
+        #[arg(long, default_value = "5")]
+        max_fee_rate: f64,
+        /// Fee rate expected when the coins would otherwise be spent, in sat/vB
+        #[arg(long, default_value = "20")]
+        future_fee_rate: f64,
+        /// Fewest UTXOs merged in one transaction
+        #[arg(long, default_value = "3")]
+        min_inputs: usize,
+        /// Only merge UTXOs up to this many satoshis
+        #[arg(long)]
+        max_value: Option<u64>,
+        /// Create unsigned PSBTs for the proposed consolidations
+        #[arg(long)]
+        psbt: bool,
     },
 
     /// Sign a PSBT (Partially Signed Bitcoin Transaction)
@@ -119,6 +244,32 @@ pub enum Commands {
         /// Output file for signed PSBT
         #[arg(long)]
         output: Option<PathBuf>,
+        /// The spend was approved; lifts the policy's approval threshold
+        #[arg(long)]
+        approve: bool,
+    },
+
+    /// Sign a message to prove control of a wallet address
+    SignMessage {
+        /// Wallet name
+        wallet: String,
+        /// Wallet address to sign with
+        address: String,
+        /// Message text
+        message: String,
+        /// Signature format
+        #[arg(long, value_enum, default_value = "simple")]
+        format: MessageFormatArg,
+    },
+
+    /// Verify a BIP-137 or BIP-322 message signature
+    VerifyMessage {
+        /// Address the message was signed with
+        address: String,
+        /// Message text
+        message: String,
+        /// Base64 signature
+        signature: String,
     },
 
     /// Import a PSBT
@@ -140,6 +291,9 @@ pub enum Commands {
         /// Output file path
         #[arg(long)]
         output: Option<PathBuf>,
+        /// Only export this account (default: all accounts)
+        #[arg(long)]
+        account: Option<u32>,
     },
 
     /// Multi-signature operations
@@ -148,6 +302,14 @@ pub enum Commands {
         command: MultisigCommands,
     },
 
+    /// Import BIP-329 labels (JSON Lines) from another wallet
+    ImportLabels {
+        /// Wallet name
+        wallet: String,
+        /// Label file path
+        file: PathBuf,
+    },
+
     /// Import legacy Armory wallet
     LegacyImport {
         /// Path to legacy wallet file
@@ -173,6 +335,21 @@ pub enum Commands {
         backup: PathBuf,
         /// New wallet name
         new_name: String,
+        /// Store the wallet without a passphrase; anyone who can read its files can use it
+        #[arg(long)]
+        no_passphrase: bool,
+    },
+
+    /// Automatic wallet snapshots
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommands,
+    },
+
+    /// Wallet storage backends
+    Storage {
+        #[command(subcommand)]
+        command: StorageCommands,
     },
 }
 
@@ -190,6 +367,232 @@ pub enum ExportFormat {
     Descriptors,
     Xpub,
     Addresses,
+    /// BIP-329 labels (JSON Lines)
+    Labels,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum MessageFormatArg {
+    /// BIP-137 signature (P2PKH, P2SH-P2WPKH and P2WPKH)
+    Legacy,
+    /// BIP-322 simple signature (native segwit and Taproot)
+    Simple,
+    /// BIP-322 full signature (any address)
+    Full,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum HistoryFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum BackendArg {
+    Sled,
+    Sqlite,
+}
+
+#[derive(Subcommand)]
+pub enum UtxoCommands {
+    /// List UTXOs with their coin control state
+    List {
+        /// Wallet name
+        wallet: String,
+    },
+
+    /// Exclude a UTXO from coin selection
+    Freeze {
+        /// Wallet name
+        wallet: String,
+        /// Outpoint (txid:vout)
+        outpoint: String,
+    },
+
+    /// Make a frozen UTXO spendable again
+    Unfreeze {
+        /// Wallet name
+        wallet: String,
+        /// Outpoint (txid:vout)
+        outpoint: String,
+    },
+
+    /// Label a UTXO and optionally tag its source
+    Label {
+        /// Wallet name
+        wallet: String,
+        /// Outpoint (txid:vout)
+        outpoint: String,
+        /// New label (empty to clear)
+        label: String,
+        /// Source tag, e.g. "exchange" (empty to clear)
+        #[arg(long)]
+        source: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AccountCommands {
+    /// Create a new account
+    Create {
+        /// Wallet name
+        wallet: String,
+        /// Account index (default: the one after the highest); the previous
+        /// account needs history
+        #[arg(long)]
+        index: Option<u32>,
+        /// Account label
+        #[arg(long)]
+        label: Option<String>,
+    },
+
+    /// List accounts with their balances
+    List {
+        /// Wallet name
+        wallet: String,
+    },
+
+    /// Set an account label
+    Label {
+        /// Wallet name
+        wallet: String,
+        /// Account index
+        account: u32,
+        /// New label
+        label: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PolicyCommands {
+    /// Show the wallet's spending policy
+    Show {
+        /// Wallet name
+        wallet: String,
+    },
+
+    /// Replace the wallet's spending policy
+    Set {
+        /// Wallet name
+        wallet: String,
+        /// Maximum sats leaving the wallet per transaction, fee included
+        #[arg(long)]
+        max_per_tx: Option<u64>,
+        /// Maximum sats leaving the wallet within 24 hours
+        #[arg(long)]
+        daily_limit: Option<u64>,
+        /// Allowed destination address (repeatable; none allows any)
+        #[arg(long = "allow", value_name = "ADDRESS")]
+        allowed_destinations: Vec<String>,
+        /// Maximum fee rate in sat/vB
+        #[arg(long)]
+        max_fee_rate: Option<f64>,
+        /// Maximum fee as a percentage of the amount sent
+        #[arg(long)]
+        max_fee_percent: Option<f64>,
+        /// Reject outputs that claim wallet keys but do not pay the wallet
+        #[arg(long)]
+        forbid_unknown_change: bool,
+        /// Spends above this many sats need `sign --approve`
+        #[arg(long)]
+        approval_threshold: Option<u64>,
+    },
+
+    /// Remove the wallet's spending policy
+    Clear {
+        /// Wallet name
+        wallet: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PassphraseCommands {
+    /// Re-encrypt a wallet under a new passphrase (the current one is --passphrase)
+    Change {
+        /// Wallet name
+        wallet: String,
+        /// New passphrase (prefer the environment variable over the flag)
+        #[arg(long, env = "ARMORY_NEW_PASSPHRASE", hide_env_values = true)]
+        new_passphrase: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SnapshotCommands {
+    /// List a wallet's snapshots, newest first
+    List {
+        /// Wallet name
+        wallet: String,
+    },
+    /// Show what changed since a snapshot
+    Diff {
+        /// Wallet name
+        wallet: String,
+        /// Unix time; the newest snapshot taken at or before it is used
+        timestamp: u64,
+    },
+    /// Roll a wallet back to a snapshot
+    Rollback {
+        /// Wallet name
+        wallet: String,
+        /// Unix time; the newest snapshot taken at or before it is used
+        timestamp: u64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum StorageCommands {
+    /// Copy a wallet's records to another storage backend and switch to it
+    Migrate {
+        /// Wallet name
+        wallet: String,
+        /// Target backend
+        #[arg(long)]
+        to: BackendArg,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum KeychainCommands {
+    /// Add a ranged descriptor keychain (e.g. wsh(sortedmulti(...)) or tr(..., {...}))
+    Add {
+        /// Wallet name
+        wallet: String,
+        /// Keychain name
+        name: String,
+        /// Receive descriptor, or a multipath <0;1> descriptor for both chains
+        descriptor: String,
+        /// Change descriptor
+        #[arg(long)]
+        change: Option<String>,
+    },
+
+    /// List keychains with their descriptors
+    List {
+        /// Wallet name
+        wallet: String,
+    },
+
+    /// Generate addresses from a keychain
+    Address {
+        /// Wallet name
+        wallet: String,
+        /// Keychain name
+        name: String,
+        /// Generate change addresses
+        #[arg(long)]
+        change: bool,
+        /// Number of addresses to generate
+        #[arg(long, default_value = "1")]
+        count: u32,
+    },
 }
 
 #[derive(Subcommand)]
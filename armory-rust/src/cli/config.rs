@@ -4,14 +4,31 @@
 use crate::error::WalletResult;
 use bitcoin::Network;
 use std::env;
+use std::fmt;
 use std::path::PathBuf;
 
 /// CLI configuration structure
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct CliConfig {
     pub data_dir: PathBuf,
     pub network: Network,
     pub verbose: bool,
+    /// Wallet passphrase, if one was supplied
+    pub passphrase: Option<String>,
+}
+
+impl fmt::Debug for CliConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CliConfig")
+            .field("data_dir", &self.data_dir)
+            .field("network", &self.network)
+            .field("verbose", &self.verbose)
+            .field(
+                "passphrase",
+                &self.passphrase.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
 }
 
 impl CliConfig {
@@ -24,6 +41,7 @@ impl CliConfig {
             data_dir,
             network: Network::Bitcoin,
             verbose: false,
+            passphrase: None,
         })
     }
 
@@ -45,6 +63,12 @@ impl CliConfig {
         Ok(config)
     }
 
+    /// Set the wallet passphrase
+    pub fn with_passphrase(mut self, passphrase: Option<String>) -> Self {
+        self.passphrase = passphrase;
+        self
+    }
+
     /// Get wallet storage directory
     pub fn wallet_dir(&self) -> PathBuf {
         self.data_dir.join("wallets")
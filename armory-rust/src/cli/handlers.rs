@@ -2,12 +2,26 @@
 ///
 /// Implementation of CLI command execution logic
 use crate::cli::{
-    AddressType as CliAddressType, CliConfig, Commands, ExportFormat, MultisigCommands,
+    AccountCommands, AddressType as CliAddressType, BackendArg, CliConfig, Commands, ExportFormat,
+    HistoryFormat, KeychainCommands, MessageFormatArg, MultisigCommands, PassphraseCommands,
+    PolicyCommands, ReportFormat, SnapshotCommands, StorageCommands, UtxoCommands,
 };
-use crate::error::{WalletError, WalletResult};
-use crate::storage::WalletStorage;
-use crate::wallet::{descriptor_wallet::AddressType, Wallet};
+use crate::error::{StorageError, WalletError, WalletResult};
+use crate::storage::{snapshot, BackendKind, Snapshot, SnapshotKind, WalletBackup, WalletStorage};
+use crate::transaction::builder::{FeeStrategy, TransactionBuilder};
+use crate::transaction::consolidation::{ConsolidationConfig, ConsolidationPlanner, ScriptKind};
+use crate::wallet::{
+    descriptor_wallet::{AddressType, DescriptorImport},
+    history::{self, HistoryEntry, HistoryFilter},
+    labels,
+    message::{self, MessageFormat},
+    SpendingPolicy, Wallet,
+};
+use bip39::Mnemonic;
+use bitcoin::{Address, Amount, FeeRate, OutPoint, Psbt};
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
 
 impl From<CliAddressType> for AddressType {
     fn from(cli_type: CliAddressType) -> Self {
@@ -20,6 +34,25 @@ impl From<CliAddressType> for AddressType {
     }
 }
 
+impl From<BackendArg> for BackendKind {
+    fn from(backend: BackendArg) -> Self {
+        match backend {
+            BackendArg::Sled => BackendKind::Sled,
+            BackendArg::Sqlite => BackendKind::Sqlite,
+        }
+    }
+}
+
+impl From<MessageFormatArg> for MessageFormat {
+    fn from(format: MessageFormatArg) -> Self {
+        match format {
+            MessageFormatArg::Legacy => MessageFormat::Legacy,
+            MessageFormatArg::Simple => MessageFormat::Simple,
+            MessageFormatArg::Full => MessageFormat::Full,
+        }
+    }
+}
+
 /// CLI command handler
 pub struct CliHandler {
     config: CliConfig,
@@ -38,9 +71,29 @@ impl CliHandler {
             Commands::Create {
                 name,
                 mnemonic,
-                encrypt,
+                words,
+                mnemonic_passphrase,
+                no_passphrase,
                 account,
-            } => self.handle_create(name, mnemonic, encrypt, account).await,
+            } => {
+                self.handle_create(
+                    name,
+                    mnemonic,
+                    words,
+                    mnemonic_passphrase,
+                    no_passphrase,
+                    account,
+                )
+                .await
+            }
+            Commands::WatchOnly {
+                name,
+                descriptors,
+                no_passphrase,
+            } => {
+                self.handle_watch_only(name, descriptors, no_passphrase)
+                    .await
+            }
             Commands::List => self.handle_list().await,
             Commands::Info { wallet } => self.handle_info(wallet).await,
             Commands::Address {
@@ -48,33 +101,108 @@ impl CliHandler {
                 address_type,
                 count,
                 list,
-            } => self.handle_address(wallet, address_type, count, list).await,
+                account,
+            } => {
+                self.handle_address(wallet, address_type, count, list, account)
+                    .await
+            }
             Commands::Balance {
                 wallet,
                 include_unconfirmed,
-            } => self.handle_balance(wallet, include_unconfirmed).await,
+                account,
+            } => {
+                self.handle_balance(wallet, include_unconfirmed, account)
+                    .await
+            }
+            Commands::Account { command } => self.handle_account(command).await,
+            Commands::Keychain { command } => self.handle_keychain(command).await,
+            Commands::History {
+                wallet,
+                from,
+                to,
+                min_amount,
+                max_amount,
+                label,
+                format,
+                output,
+            } => {
+                let filter = HistoryFilter {
+                    from: from.as_deref().map(history::parse_date).transpose()?,
+                    to: to.as_deref().map(history::parse_date_end).transpose()?,
+                    min_amount,
+                    max_amount,
+                    label,
+                    kind: None,
+                };
+                self.handle_history(wallet, filter, format, output).await
+            }
+            Commands::PrivacyReport { wallet, format } => {
+                self.handle_privacy_report(wallet, format).await
+            }
             Commands::Send {
                 wallet,
                 to,
                 amount,
                 fee_rate,
                 psbt_only,
+                inputs,
+            } => {
+                self.handle_send(wallet, to, amount, fee_rate, psbt_only, inputs)
+                    .await
+            }
+            Commands::Consolidate {
+                wallet,
+                fee_rate,
+                max_fee_rate,
+                future_fee_rate,
+                min_inputs,
+                max_value,
+                psbt,
             } => {
-                self.handle_send(wallet, to, amount, fee_rate, psbt_only)
+                let config = ConsolidationConfig {
+                    max_fee_rate: parse_fee_rate(max_fee_rate)?,
+                    future_fee_rate: parse_fee_rate(future_fee_rate)?,
+                    min_inputs,
+                    max_utxo_value: max_value.map(Amount::from_sat),
+                    ..Default::default()
+                };
+                self.handle_consolidate(wallet, parse_fee_rate(fee_rate)?, config, psbt)
                     .await
             }
+            Commands::Policy { command } => self.handle_policy(command).await,
+            Commands::Passphrase { command } => self.handle_passphrase(command).await,
+            Commands::Utxo { command } => self.handle_utxo(command).await,
             Commands::Sign {
                 wallet,
                 psbt,
                 output,
-            } => self.handle_sign(wallet, psbt, output).await,
+                approve,
+            } => self.handle_sign(wallet, psbt, output, approve).await,
+            Commands::SignMessage {
+                wallet,
+                address,
+                message,
+                format,
+            } => {
+                self.handle_sign_message(wallet, address, message, format)
+                    .await
+            }
+            Commands::VerifyMessage {
+                address,
+                message,
+                signature,
+            } => self.handle_verify_message(address, message, signature),
             Commands::Import { psbt, output } => self.handle_import(psbt, output).await,
             Commands::Export {
                 wallet,
                 format,
                 output,
-            } => self.handle_export(wallet, format, output).await,
+                account,
+            } => self.handle_export(wallet, format, output, account).await,
             Commands::Multisig { command } => self.handle_multisig(command).await,
+            Commands::ImportLabels { wallet, file } => {
+                self.handle_import_labels(wallet, file).await
+            }
             Commands::LegacyImport {
                 wallet_file,
                 new_name,
@@ -84,7 +212,13 @@ impl CliHandler {
                     .await
             }
             Commands::Backup { wallet, output } => self.handle_backup(wallet, output).await,
-            Commands::Restore { backup, new_name } => self.handle_restore(backup, new_name).await,
+            Commands::Restore {
+                backup,
+                new_name,
+                no_passphrase,
+            } => self.handle_restore(backup, new_name, no_passphrase).await,
+            Commands::Snapshot { command } => self.handle_snapshot(command).await,
+            Commands::Storage { command } => self.handle_storage(command).await,
         }
     }
 
@@ -93,7 +227,9 @@ impl CliHandler {
         &self,
         name: String,
         mnemonic: Option<String>,
-        encrypt: bool,
+        words: usize,
+        mnemonic_passphrase: Option<String>,
+        no_passphrase: bool,
         account: u32,
     ) -> WalletResult<()> {
         if self.config.verbose {
@@ -115,35 +251,55 @@ impl CliHandler {
             )));
         }
 
-        // Create wallet
-        let wallet = if let Some(_mnemonic_str) = mnemonic {
+        let passphrase = self.new_wallet_passphrase(no_passphrase)?;
+        let mnemonic = mnemonic.map(read_secret).transpose()?;
+        let mnemonic_passphrase = mnemonic_passphrase.map(read_secret).transpose()?;
+
+        // Restore from the given mnemonic or generate a new one
+        let restoring = mnemonic.is_some();
+        let mnemonic = if let Some(mnemonic_str) = mnemonic {
             if self.config.verbose {
                 println!("Using provided mnemonic seed phrase");
             }
-            println!("⚠️  Mnemonic import not yet implemented, creating new wallet instead");
-            Wallet::create_new(name.clone(), self.config.network.into(), storage)?
+            Mnemonic::parse(mnemonic_str.as_str())
+                .map_err(|e| WalletError::InvalidInput(format!("Invalid mnemonic: {e}")))?
         } else {
             if self.config.verbose {
                 println!("Generating new mnemonic seed phrase");
             }
-            Wallet::create_new(name.clone(), self.config.network.into(), storage)?
+            Wallet::generate_mnemonic(words)?
         };
 
+        // Create wallet
+        let mut wallet = Wallet::from_mnemonic(
+            name.clone(),
+            self.config.network.into(),
+            &mnemonic,
+            mnemonic_passphrase.as_deref().unwrap_or(""),
+            passphrase,
+            storage,
+        )?;
+
+        if account != 0 {
+            wallet.create_account(account, None)?;
+        }
+
         // Save the wallet
         wallet.save()?;
 
-        // Handle encryption
-        if encrypt {
-            // In a real implementation, we'd prompt for password
-            println!("⚠️  Wallet encryption not yet implemented in CLI");
+        // Display a newly generated mnemonic for backup
+        if !restoring {
+            println!("\n🔐 IMPORTANT: Save your mnemonic seed phrase:");
+            for (i, word) in mnemonic.words().enumerate() {
+                println!("   {:>2}. {word}", i + 1);
+            }
+            if mnemonic_passphrase.is_some() {
+                println!("   The BIP-39 passphrase is also required for recovery!");
+            }
+            println!("   Keep this safe - it's needed to recover your wallet!");
+            println!();
         }
 
-        // Display the mnemonic for backup (placeholder)
-        println!("\n🔐 IMPORTANT: Save your mnemonic seed phrase:");
-        println!("   [Mnemonic display not yet implemented]");
-        println!("   Keep this safe - it's needed to recover your wallet!");
-        println!();
-
         println!("✅ Wallet '{name}' created successfully");
         println!("   Network: {:?}", self.config.network);
         println!("   Account: {account}");
@@ -155,6 +311,75 @@ impl CliHandler {
         Ok(())
     }
 
+    /// Handle watch-only wallet creation
+    async fn handle_watch_only(
+        &self,
+        name: String,
+        descriptors: Vec<String>,
+        no_passphrase: bool,
+    ) -> WalletResult<()> {
+        if self.config.verbose {
+            println!(
+                "Creating watch-only wallet '{name}' from {} descriptor(s)",
+                descriptors.len()
+            );
+        }
+
+        let storage_config = crate::storage::wallet_storage::StorageConfig {
+            storage_path: self.config.wallet_dir().join(&name),
+            auto_backup: true,
+            backup_count: 5,
+        };
+
+        // Exported `importdescriptors` requests are accepted as well
+        let mut parsed = Vec::new();
+        for descriptor in descriptors {
+            if descriptor.trim_start().starts_with('[') {
+                let imports: Vec<DescriptorImport> =
+                    serde_json::from_str(&descriptor).map_err(|e| {
+                        WalletError::InvalidInput(format!("Invalid descriptor import JSON: {e}"))
+                    })?;
+                parsed.extend(imports.into_iter().map(|import| import.desc));
+            } else {
+                parsed.push(descriptor);
+            }
+        }
+        let descriptors = parsed;
+
+        let mut storage = WalletStorage::new(storage_config)?;
+
+        if storage.wallet_exists(&name)? {
+            return Err(WalletError::InvalidInput(format!(
+                "Wallet '{name}' already exists"
+            )));
+        }
+        // Descriptors and xpubs are encrypted under the passphrase too
+        storage.unlock(self.new_wallet_passphrase(no_passphrase)?)?;
+
+        let wallet = Wallet::from_descriptors(
+            name.clone(),
+            self.config.network.into(),
+            &descriptors,
+            storage,
+        )?;
+        wallet.save()?;
+
+        println!("👀 Watch-only wallet '{name}' created successfully");
+        println!("   Network: {:?}", self.config.network);
+        for account in wallet.account_xpubs() {
+            println!(
+                "   {:?} account {}: [{}/{}]",
+                account.address_type,
+                account.account(),
+                account.fingerprint,
+                account.origin
+            );
+        }
+        println!("   Transactions must be signed by the wallet holding the private keys");
+
+        Ok(())
+    }
+
     /// Handle wallet listing
     async fn handle_list(&self) -> WalletResult<()> {
         if self.config.verbose {
@@ -245,15 +470,22 @@ impl CliHandler {
 
         // Address counts
         println!("📍 Addresses");
-        for &addr_type in &[
-            AddressType::Legacy,
-            AddressType::NestedSegwit,
-            AddressType::NativeSegwit,
-            AddressType::Taproot,
-        ] {
-            let receiving_count = wallet.get_addresses(addr_type, false).len();
-            let change_count = wallet.get_addresses(addr_type, true).len();
-            println!("  {addr_type:?}: {receiving_count} receiving, {change_count} change");
+        for account in wallet.accounts() {
+            println!("  Account {} ({}):", account.index, account.label);
+            for &addr_type in &[
+                AddressType::Legacy,
+                AddressType::NestedSegwit,
+                AddressType::NativeSegwit,
+                AddressType::Taproot,
+            ] {
+                let receiving_count = wallet
+                    .get_account_addresses(account.index, addr_type, false)
+                    .len();
+                let change_count = wallet
+                    .get_account_addresses(account.index, addr_type, true)
+                    .len();
+                println!("    {addr_type:?}: {receiving_count} receiving, {change_count} change");
+            }
         }
         println!();
 
@@ -275,20 +507,21 @@ impl CliHandler {
         address_type: CliAddressType,
         count: u32,
         list: bool,
+        account: u32,
     ) -> WalletResult<()> {
         if self.config.verbose {
-            println!("Handling addresses for wallet '{wallet_name}'");
+            println!("Handling addresses for wallet '{wallet_name}' account {account}");
         }
 
         let mut wallet = self.load_wallet(&wallet_name)?;
         let wallet_address_type: AddressType = address_type.clone().into();
 
         if list {
-            println!("📍 Addresses for wallet '{wallet_name}':");
+            println!("📍 Addresses for wallet '{wallet_name}' account {account}:");
 
             // Get existing receiving addresses
-            let receiving_addrs = wallet.get_addresses(wallet_address_type, false);
-            let change_addrs = wallet.get_addresses(wallet_address_type, true);
+            let receiving_addrs = wallet.get_account_addresses(account, wallet_address_type, false);
+            let change_addrs = wallet.get_account_addresses(account, wallet_address_type, true);
 
             if receiving_addrs.is_empty() && change_addrs.is_empty() {
                 println!("  No {address_type:?} addresses found");
@@ -310,7 +543,7 @@ impl CliHandler {
         } else {
             println!("📍 Generated {count} new {address_type:?} address(es):");
             for i in 0..count {
-                let address = wallet.get_new_address(wallet_address_type)?;
+                let address = wallet.get_new_account_address(account, wallet_address_type)?;
                 println!("  {}. {}", i + 1, address);
             }
 
@@ -330,20 +563,363 @@ impl CliHandler {
         &self,
         wallet_name: String,
         include_unconfirmed: bool,
+        account: Option<u32>,
     ) -> WalletResult<()> {
         if self.config.verbose {
             println!("Checking balance for wallet '{wallet_name}'");
         }
 
-        let _wallet = self.load_wallet(&wallet_name)?;
+        let wallet = self.load_wallet(&wallet_name)?;
+
+        let utxos = match account {
+            Some(index) => {
+                let account = wallet.account(index).ok_or_else(|| {
+                    WalletError::InvalidInput(format!("Account {index} does not exist"))
+                })?;
+                println!(
+                    "💰 Balance for wallet '{wallet_name}' account {index} ({}):",
+                    account.label
+                );
+                wallet.account_utxos(index)
+            }
+            None => {
+                println!("💰 Balance for wallet '{wallet_name}':");
+                wallet.utxos()
+            }
+        };
 
-        println!("💰 Balance for wallet '{wallet_name}':");
-        println!("  Confirmed: 0.00000000 BTC");
+        let confirmed: u64 = utxos
+            .iter()
+            .filter(|utxo| utxo.block_height.is_some())
+            .map(|utxo| utxo.value)
+            .sum();
+        let unconfirmed: u64 = utxos
+            .iter()
+            .filter(|utxo| utxo.block_height.is_none())
+            .map(|utxo| utxo.value)
+            .sum();
+
+        println!("  Confirmed: {:.8} BTC", confirmed as f64 / 100_000_000.0);
         if include_unconfirmed {
-            println!("  Unconfirmed: 0.00000000 BTC");
-            println!("  Total: 0.00000000 BTC");
+            println!(
+                "  Unconfirmed: {:.8} BTC",
+                unconfirmed as f64 / 100_000_000.0
+            );
+            println!(
+                "  Total: {:.8} BTC",
+                (confirmed + unconfirmed) as f64 / 100_000_000.0
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Handle account commands
+    async fn handle_account(&self, command: AccountCommands) -> WalletResult<()> {
+        match command {
+            AccountCommands::Create {
+                wallet: wallet_name,
+                index,
+                label,
+            } => {
+                let mut wallet = self.load_wallet(&wallet_name)?;
+                let index = index.unwrap_or_else(|| wallet.next_account_index());
+                let account = wallet.create_account(index, label)?.clone();
+                wallet.save()?;
+
+                println!(
+                    "✅ Account {} ({}) created in wallet '{wallet_name}'",
+                    account.index, account.label
+                );
+                for descriptor in wallet.account_descriptors(index) {
+                    println!("   {descriptor}");
+                }
+            }
+            AccountCommands::List {
+                wallet: wallet_name,
+            } => {
+                let wallet = self.load_wallet(&wallet_name)?;
+
+                println!("🗂️  Accounts in wallet '{wallet_name}':");
+                for account in wallet.accounts() {
+                    let balance = wallet.account_balance(account.index);
+                    println!(
+                        "  {:>3}. {} - {} sats ({:.8} BTC), {} UTXOs",
+                        account.index,
+                        account.label,
+                        balance,
+                        balance as f64 / 100_000_000.0,
+                        wallet.account_utxos(account.index).len()
+                    );
+                }
+            }
+            AccountCommands::Label {
+                wallet: wallet_name,
+                account,
+                label,
+            } => {
+                let mut wallet = self.load_wallet(&wallet_name)?;
+                wallet.set_account_label(account, label.clone())?;
+                wallet.save()?;
+
+                println!("🏷️  Account {account} in '{wallet_name}' labelled '{label}'");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle descriptor keychain commands
+    async fn handle_keychain(&self, command: KeychainCommands) -> WalletResult<()> {
+        match command {
+            KeychainCommands::Add {
+                wallet: wallet_name,
+                name,
+                descriptor,
+                change,
+            } => {
+                let mut wallet = self.load_wallet(&wallet_name)?;
+                let descriptors = wallet
+                    .add_keychain(&name, &descriptor, change.as_deref())?
+                    .descriptors();
+                wallet.save()?;
+
+                println!("🔑 Keychain '{name}' added to wallet '{wallet_name}'");
+                for descriptor in descriptors {
+                    println!("   {descriptor}");
+                }
+            }
+            KeychainCommands::List {
+                wallet: wallet_name,
+            } => {
+                let wallet = self.load_wallet(&wallet_name)?;
+
+                println!("🔑 Keychains in wallet '{wallet_name}':");
+                if wallet.keychains().is_empty() {
+                    println!("  (none)");
+                }
+                for keychain in wallet.keychains() {
+                    println!(
+                        "  {} - {} receive, {} change addresses",
+                        keychain.name,
+                        wallet.keychain_addresses(&keychain.name, false)?.len(),
+                        wallet.keychain_addresses(&keychain.name, true)?.len()
+                    );
+                    for descriptor in keychain.descriptors() {
+                        println!("      {descriptor}");
+                    }
+                }
+            }
+            KeychainCommands::Address {
+                wallet: wallet_name,
+                name,
+                change,
+                count,
+            } => {
+                let mut wallet = self.load_wallet(&wallet_name)?;
+                let addresses = (0..count)
+                    .map(|_| wallet.keychain_address(&name, change))
+                    .collect::<WalletResult<Vec<_>>>()?;
+                wallet.save()?;
+
+                println!(
+                    "📍 {} addresses from keychain '{name}':",
+                    if change { "Change" } else { "Receive" }
+                );
+                for address in addresses {
+                    println!("  {address}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle spending policy commands
+    async fn handle_policy(&self, command: PolicyCommands) -> WalletResult<()> {
+        match command {
+            PolicyCommands::Show {
+                wallet: wallet_name,
+            } => {
+                let wallet = self.load_wallet(&wallet_name)?;
+                let Some(policy) = wallet.policy() else {
+                    println!("🛡️  Wallet '{wallet_name}' has no spending policy");
+                    return Ok(());
+                };
+
+                let limit = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+                println!("🛡️  Spending policy of wallet '{wallet_name}':");
+                println!(
+                    "  Per transaction: {}",
+                    limit(
+                        policy
+                            .max_per_transaction
+                            .map(|sats| format!("{sats} sats"))
+                    )
+                );
+                println!(
+                    "  Daily: {}",
+                    limit(policy.daily_limit.map(|sats| format!("{sats} sats")))
+                );
+                println!(
+                    "  Max fee rate: {}",
+                    limit(policy.max_fee_rate.map(|rate| format!("{rate} sat/vB")))
+                );
+                println!(
+                    "  Max fee: {}",
+                    limit(policy.max_fee_percent.map(|percent| format!("{percent}%")))
+                );
+                println!(
+                    "  Approval above: {}",
+                    limit(policy.approval_threshold.map(|sats| format!("{sats} sats")))
+                );
+                println!(
+                    "  Unknown change outputs: {}",
+                    if policy.forbid_unknown_change {
+                        "rejected"
+                    } else {
+                        "allowed"
+                    }
+                );
+                if policy.allowed_destinations.is_empty() {
+                    println!("  Destinations: any");
+                } else {
+                    println!("  Destinations:");
+                    for destination in &policy.allowed_destinations {
+                        println!("    {destination}");
+                    }
+                }
+            }
+            PolicyCommands::Set {
+                wallet: wallet_name,
+                max_per_tx,
+                daily_limit,
+                allowed_destinations,
+                max_fee_rate,
+                max_fee_percent,
+                forbid_unknown_change,
+                approval_threshold,
+            } => {
+                let mut wallet = self.load_wallet(&wallet_name)?;
+                wallet.set_policy(Some(SpendingPolicy {
+                    max_per_transaction: max_per_tx,
+                    daily_limit,
+                    allowed_destinations,
+                    max_fee_rate,
+                    max_fee_percent,
+                    forbid_unknown_change,
+                    approval_threshold,
+                }))?;
+                wallet.save()?;
+                println!("🛡️  Spending policy set for wallet '{wallet_name}'");
+            }
+            PolicyCommands::Clear {
+                wallet: wallet_name,
+            } => {
+                let mut wallet = self.load_wallet(&wallet_name)?;
+                wallet.set_policy(None)?;
+                wallet.save()?;
+                println!("🛡️  Spending policy removed from wallet '{wallet_name}'");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle passphrase commands
+    async fn handle_passphrase(&self, command: PassphraseCommands) -> WalletResult<()> {
+        match command {
+            PassphraseCommands::Change {
+                wallet: wallet_name,
+                new_passphrase,
+            } => {
+                let new_passphrase = new_passphrase.ok_or_else(|| {
+                    WalletError::InvalidInput(
+                        "A new passphrase is required (--new-passphrase or ARMORY_NEW_PASSPHRASE)"
+                            .to_string(),
+                    )
+                })?;
+                let mut wallet = self.load_wallet(&wallet_name)?;
+                wallet.change_passphrase(self.passphrase(), &new_passphrase)?;
+                println!("🔑 Passphrase changed for wallet '{wallet_name}'");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle coin control commands
+    async fn handle_utxo(&self, command: UtxoCommands) -> WalletResult<()> {
+        match command {
+            UtxoCommands::List {
+                wallet: wallet_name,
+            } => {
+                let wallet = self.load_wallet(&wallet_name)?;
+                let mut utxos = wallet.utxos();
+                utxos.sort_by_key(|utxo| utxo.outpoint());
+
+                println!("🪙 UTXOs in wallet '{wallet_name}':");
+                if utxos.is_empty() {
+                    println!("  (none)");
+                }
+                for utxo in utxos {
+                    let outpoint = utxo.outpoint();
+                    let metadata = wallet.utxo_metadata(&outpoint).cloned().unwrap_or_default();
+                    let height = utxo
+                        .block_height
+                        .map_or("unconfirmed".to_string(), |height| {
+                            format!("height {height}")
+                        });
+                    println!(
+                        "  {}{outpoint} - {} sats ({height}) {}",
+                        if metadata.frozen { "❄️  " } else { "" },
+                        utxo.value,
+                        utxo.address
+                    );
+                    if let Some(label) = &metadata.label {
+                        println!("      Label: {label}");
+                    }
+                    if let Some(source) = &metadata.source {
+                        println!("      Source: {source}");
+                    }
+                }
+            }
+            UtxoCommands::Freeze {
+                wallet: wallet_name,
+                outpoint,
+            } => {
+                let mut wallet = self.load_wallet(&wallet_name)?;
+                let outpoint = parse_outpoint(&outpoint)?;
+                wallet.freeze_utxo(&outpoint)?;
+                wallet.save()?;
+                println!("❄️  {outpoint} frozen: it will not be selected for spending");
+            }
+            UtxoCommands::Unfreeze {
+                wallet: wallet_name,
+                outpoint,
+            } => {
+                let mut wallet = self.load_wallet(&wallet_name)?;
+                let outpoint = parse_outpoint(&outpoint)?;
+                wallet.unfreeze_utxo(&outpoint)?;
+                wallet.save()?;
+                println!("✅ {outpoint} unfrozen");
+            }
+            UtxoCommands::Label {
+                wallet: wallet_name,
+                outpoint,
+                label,
+                source,
+            } => {
+                let mut wallet = self.load_wallet(&wallet_name)?;
+                let outpoint = parse_outpoint(&outpoint)?;
+                wallet.set_utxo_label(&outpoint, Some(label).filter(|l| !l.is_empty()))?;
+                if let Some(source) = source {
+                    wallet.set_utxo_source(&outpoint, Some(source).filter(|s| !s.is_empty()))?;
+                }
+                wallet.save()?;
+                println!("🏷️  {outpoint} labelled");
+            }
         }
-        println!("  (Balance checking requires network integration)");
 
         Ok(())
     }
@@ -356,12 +932,18 @@ impl CliHandler {
         amount: u64,
         fee_rate: Option<f64>,
         psbt_only: bool,
+        inputs: Vec<String>,
     ) -> WalletResult<()> {
         if self.config.verbose {
             println!("Sending {amount} sats from '{wallet_name}' to '{to}'");
         }
 
-        let _wallet = self.load_wallet(&wallet_name)?;
+        let wallet = self.load_wallet(&wallet_name)?;
+        let recipient = parse_address(&to, self.config.network)?;
+        let inputs = inputs
+            .iter()
+            .map(|input| parse_outpoint(input))
+            .collect::<WalletResult<Vec<_>>>()?;
 
         println!("🚀 Transaction preparation:");
         println!("  From: {wallet_name}");
@@ -370,12 +952,148 @@ impl CliHandler {
         if let Some(rate) = fee_rate {
             println!("  Fee rate: {rate} sat/vB");
         }
+        for input in &inputs {
+            println!("  Input: {input}");
+        }
 
-        if psbt_only {
-            println!("  Creating unsigned PSBT...");
-            println!("  (PSBT creation not yet implemented)");
-        } else {
-            println!("  (Transaction creation and broadcasting not yet implemented)");
+        if !psbt_only {
+            println!("  (Signing and broadcasting not yet implemented, use --psbt-only)");
+            return Ok(());
+        }
+
+        let wallet = Arc::new(RwLock::new(wallet));
+        let psbt = {
+            let mut builder = TransactionBuilder::new(wallet.clone())?;
+            builder.add_recipient(recipient, Amount::from_sat(amount))?;
+            if let Some(rate) = fee_rate {
+                // 1 sat/vB = 250 sat/kwu
+                let rate = FeeRate::from_sat_per_kwu((rate * 250.0).round() as u64);
+                builder.fee_strategy(FeeStrategy::FeeRate(rate));
+            }
+            if !inputs.is_empty() {
+                builder.pin_inputs(inputs);
+            }
+            let psbt = builder.build_psbt()?.to_psbt()?;
+            if let Some(fee) = builder.fee() {
+                println!("  Fee: {} sats", fee.to_sat());
+            }
+            psbt
+        };
+        // Fail early instead of handing out a PSBT the signer would refuse
+        wallet
+            .read()
+            .map_err(|_| WalletError::InvalidInput("Wallet lock poisoned".to_string()))?
+            .check_policy(&psbt, false)?;
+
+        // Persist the change address handed out by the builder
+        let wallet = Arc::try_unwrap(wallet)
+            .map_err(|_| WalletError::InvalidInput("Wallet still in use".to_string()))?
+            .into_inner()
+            .map_err(|_| WalletError::InvalidInput("Wallet lock poisoned".to_string()))?;
+        wallet.save()?;
+
+        println!("✅ Unsigned PSBT:");
+        println!("{psbt}");
+
+        Ok(())
+    }
+
+    /// Handle UTXO consolidation
+    async fn handle_consolidate(
+        &self,
+        wallet_name: String,
+        fee_rate: FeeRate,
+        config: ConsolidationConfig,
+        psbt: bool,
+    ) -> WalletResult<()> {
+        let wallet = self.load_wallet(&wallet_name)?;
+        let current_height = wallet.tip_height().ok_or_else(|| {
+            WalletError::InvalidInput(format!(
+                "Wallet '{wallet_name}' has no chain tip yet; sync it before consolidating"
+            ))
+        })?;
+
+        let wallet = Arc::new(RwLock::new(wallet));
+        let planner = ConsolidationPlanner::new(wallet.clone(), config);
+        let plan = planner.plan(fee_rate, current_height)?;
+
+        println!(
+            "🧹 Consolidation plan for '{wallet_name}' at {} sat/vB:",
+            fee_rate.to_sat_per_vb_ceil()
+        );
+        if plan.groups.is_empty() {
+            println!("  Nothing worth consolidating");
+            return Ok(());
+        }
+        for (number, group) in plan.groups.iter().enumerate() {
+            let boundary = &group.boundary;
+            let kind = match &boundary.kind {
+                ScriptKind::AddressType(address_type) => format!("{address_type:?}"),
+                ScriptKind::Keychain(name) => format!("keychain '{name}'"),
+                ScriptKind::SilentPayment => "silent payments".to_string(),
+            };
+            println!(
+                "  Group {}: {} UTXOs, {} sats ({kind}{}{}{})",
+                number + 1,
+                group.utxos.len(),
+                group.total().to_sat(),
+                boundary
+                    .account
+                    .map(|account| format!(", account {account}"))
+                    .unwrap_or_default(),
+                boundary
+                    .label
+                    .as_ref()
+                    .map(|label| format!(", label '{label}'"))
+                    .unwrap_or_default(),
+                boundary
+                    .source
+                    .as_ref()
+                    .map(|source| format!(", source '{source}'"))
+                    .unwrap_or_default(),
+            );
+            println!(
+                "    Fee: {} sats ({} vB), estimated savings: {} sats",
+                group.fee.to_sat(),
+                group.vsize,
+                group.savings.to_sat()
+            );
+            if self.config.verbose {
+                for utxo in &group.utxos {
+                    println!("    Input: {} ({} sats)", utxo.outpoint(), utxo.value);
+                }
+            }
+        }
+        println!(
+            "  Total fee: {} sats, estimated savings: {} sats",
+            plan.fee().to_sat(),
+            plan.savings().to_sat()
+        );
+
+        if !psbt {
+            return Ok(());
+        }
+        let mut psbts = Vec::new();
+        for group in &plan.groups {
+            let psbt = planner.build_psbt(group, fee_rate)?.to_psbt()?;
+            wallet
+                .read()
+                .map_err(|_| WalletError::InvalidInput("Wallet lock poisoned".to_string()))?
+                .check_policy(&psbt, false)?;
+            psbts.push(psbt);
+        }
+        drop(planner);
+
+        // Persist the consolidation addresses handed out by the planner
+        let wallet = Arc::try_unwrap(wallet)
+            .map_err(|_| WalletError::InvalidInput("Wallet still in use".to_string()))?
+            .into_inner()
+            .map_err(|_| WalletError::InvalidInput("Wallet lock poisoned".to_string()))?;
+        wallet.save()?;
+
+        for (number, psbt) in psbts.iter().enumerate() {
+            println!("✅ Unsigned PSBT for group {}:", number + 1);
+            println!("{psbt}");
         }
 
         Ok(())
@@ -387,27 +1105,76 @@ impl CliHandler {
         wallet_name: String,
         psbt: String,
         output: Option<PathBuf>,
+        approve: bool,
     ) -> WalletResult<()> {
         if self.config.verbose {
             println!("Signing PSBT with wallet '{wallet_name}'");
         }
 
-        let _wallet = self.load_wallet(&wallet_name)?;
+        let mut wallet = self.load_wallet(&wallet_name)?;
+        let mut psbt = read_psbt(&psbt)?;
+        let signed = if approve {
+            wallet.sign_approved_psbt(&mut psbt)?
+        } else {
+            wallet.sign_psbt(&mut psbt)?
+        };
+        // Persist the spend counted against the daily limit
+        wallet.save()?;
 
-        println!("✍️  PSBT Signing:");
-        println!("  Wallet: {wallet_name}");
         println!(
-            "  PSBT: {}",
-            if psbt.len() > 50 { &psbt[..50] } else { &psbt }
+            "✍️  Signed {signed} of {} inputs with wallet '{wallet_name}'",
+            psbt.inputs.len()
         );
         if let Some(output_path) = output {
-            println!("  Output: {}", output_path.display());
+            std::fs::write(&output_path, format!("{psbt}\n"))?;
+            println!("💾 Signed PSBT written to {}", output_path.display());
+        } else {
+            println!("{psbt}");
+        }
+
+        Ok(())
+    }
+
+    /// Handle message signing
+    async fn handle_sign_message(
+        &self,
+        wallet_name: String,
+        address: String,
+        message: String,
+        format: MessageFormatArg,
+    ) -> WalletResult<()> {
+        if self.config.verbose {
+            println!("Signing message with {address} from wallet '{wallet_name}'");
         }
-        println!("  (PSBT signing not yet implemented)");
+
+        let mut wallet = self.load_wallet(&wallet_name)?;
+        let address = parse_address(&address, self.config.network)?;
+        let signature = wallet.sign_message(&address, &message, format.into())?;
+
+        println!("✍️  Signed message with {address}:");
+        println!("{signature}");
 
         Ok(())
     }
 
+    /// Handle message signature verification
+    fn handle_verify_message(
+        &self,
+        address: String,
+        message: String,
+        signature: String,
+    ) -> WalletResult<()> {
+        let address = parse_address(&address, self.config.network)?;
+        if message::verify_message(&address, &message, &signature)? {
+            println!("✅ Signature is valid for {address}");
+            Ok(())
+        } else {
+            Err(WalletError::InvalidInput(format!(
+                "Signature is not valid for {address}"
+            )))
+        }
+    }
+
     /// Handle PSBT import
     async fn handle_import(&self, psbt: String, output: Option<PathBuf>) -> WalletResult<()> {
         if self.config.verbose {
@@ -433,24 +1200,177 @@ impl CliHandler {
         wallet_name: String,
         format: ExportFormat,
         output: Option<PathBuf>,
+        account: Option<u32>,
     ) -> WalletResult<()> {
         if self.config.verbose {
             println!("Exporting wallet '{wallet_name}' as {format:?}");
         }
 
-        let _wallet = self.load_wallet(&wallet_name)?;
+        let wallet = self.load_wallet(&wallet_name)?;
+        let account_keys: Vec<_> = wallet
+            .account_xpubs()
+            .into_iter()
+            .filter(|key| account.is_none_or(|index| key.account() == index))
+            .collect();
+
+        let lines: Vec<String> = match format {
+            ExportFormat::Xpub => account_keys
+                .iter()
+                .map(|account| {
+                    format!(
+                        "{:?}: [{}/{}]{}",
+                        account.address_type, account.fingerprint, account.origin, account.xpub
+                    )
+                })
+                .collect(),
+            // Argument of Bitcoin Core's `importdescriptors`
+            ExportFormat::Descriptors => {
+                let imports = wallet.descriptor_imports(account);
+                vec![serde_json::to_string_pretty(&imports).map_err(StorageError::from)?]
+            }
+            ExportFormat::Labels => labels::to_jsonl(&wallet.export_labels())?
+                .lines()
+                .map(String::from)
+                .collect(),
+            _ => {
+                println!("📤 Wallet Export:");
+                println!("  Wallet: {wallet_name}");
+                println!("  Format: {format:?}");
+                println!("  (Export format not yet implemented)");
+                return Ok(());
+            }
+        };
 
-        println!("📤 Wallet Export:");
-        println!("  Wallet: {wallet_name}");
-        println!("  Format: {format:?}");
         if let Some(output_path) = output {
-            println!("  Output: {}", output_path.display());
+            std::fs::write(&output_path, lines.join("\n") + "\n")?;
+            println!(
+                "📤 Exported {format:?} for '{wallet_name}' to {}",
+                output_path.display()
+            );
+        } else {
+            println!("📤 Wallet Export ({format:?}):");
+            for line in lines {
+                println!("{line}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle transaction history listing and export
+    async fn handle_history(
+        &self,
+        wallet_name: String,
+        filter: HistoryFilter,
+        format: HistoryFormat,
+        output: Option<PathBuf>,
+    ) -> WalletResult<()> {
+        if self.config.verbose {
+            println!("Listing history of wallet '{wallet_name}' with {filter:?}");
+        }
+
+        let wallet = self.load_wallet(&wallet_name)?;
+        let entries: Vec<HistoryEntry> = wallet
+            .history(&filter)
+            .into_iter()
+            .map(HistoryEntry::from)
+            .collect();
+
+        let rendered = match format {
+            HistoryFormat::Csv => history::to_csv(&entries),
+            HistoryFormat::Json => history::to_json(&entries)? + "\n",
+            HistoryFormat::Table => {
+                let mut table = String::new();
+                for entry in &entries {
+                    let date = if entry.date.is_empty() {
+                        "unknown date"
+                    } else {
+                        &entry.date
+                    };
+                    let fee = entry
+                        .fee
+                        .map_or(String::new(), |fee| format!(" (fee {fee} sats)"));
+                    table.push_str(&format!(
+                        "  {date}  {:<13} {:>+14} sats{fee}  {}\n",
+                        entry.kind.to_string(),
+                        entry.amount,
+                        entry.txid
+                    ));
+                    if let Some(label) = &entry.label {
+                        table.push_str(&format!("      Label: {label}\n"));
+                    }
+                }
+                table
+            }
+        };
+
+        if let Some(output_path) = output {
+            std::fs::write(&output_path, rendered)?;
+            println!(
+                "📜 Exported {} transactions from '{wallet_name}' to {}",
+                entries.len(),
+                output_path.display()
+            );
+        } else if matches!(format, HistoryFormat::Table) {
+            println!("📜 Transaction history of '{wallet_name}':");
+            if entries.is_empty() {
+                println!("  (none)");
+            }
+            print!("{rendered}");
+        } else {
+            print!("{rendered}");
+        }
+
+        Ok(())
+    }
+
+    /// Handle privacy report
+    async fn handle_privacy_report(
+        &self,
+        wallet_name: String,
+        format: ReportFormat,
+    ) -> WalletResult<()> {
+        let wallet = self.load_wallet(&wallet_name)?;
+        let report = wallet.privacy_report();
+
+        if matches!(format, ReportFormat::Json) {
+            let json = serde_json::to_string_pretty(&report).map_err(StorageError::from)?;
+            println!("{json}");
+            return Ok(());
+        }
+
+        println!(
+            "🕵️  Privacy report for '{wallet_name}' ({} transactions, {} UTXOs):",
+            report.transactions, report.utxos
+        );
+        if report.findings.is_empty() {
+            println!("  ✅ No issues found");
+        }
+        for finding in &report.findings {
+            println!("  ⚠️  {finding}");
         }
-        println!("  (Wallet export not yet implemented)");
 
         Ok(())
     }
 
+    /// Handle BIP-329 label import
+    async fn handle_import_labels(&self, wallet_name: String, file: PathBuf) -> WalletResult<()> {
+        if self.config.verbose {
+            println!(
+                "Importing labels from {} into '{wallet_name}'",
+                file.display()
+            );
+        }
+
+        let mut wallet = self.load_wallet(&wallet_name)?;
+        let labels = labels::parse_jsonl(&std::fs::read_to_string(&file)?)?;
+        let count = wallet.import_labels(labels)?;
+        wallet.save()?;
+
+        println!("🏷️  Imported {count} labels into '{wallet_name}'");
+        Ok(())
+    }
+
     /// Handle multisig commands
     async fn handle_multisig(&self, command: MultisigCommands) -> WalletResult<()> {
         match command {
@@ -534,39 +1454,183 @@ impl CliHandler {
             );
         }
 
-        let _wallet = self.load_wallet(&wallet_name)?;
+        if output.exists() {
+            return Err(WalletError::InvalidInput(format!(
+                "{} already exists",
+                output.display()
+            )));
+        }
 
-        println!("💾 Wallet Backup:");
-        println!("  Wallet: {wallet_name}");
-        println!("  Output: {}", output.display());
-        println!("  (Wallet backup not yet implemented)");
+        let wallet = self.load_wallet(&wallet_name)?;
+        let backup = wallet.backup(self.passphrase())?;
+        std::fs::write(&output, backup.to_bytes())?;
+
+        println!("💾 Wallet '{wallet_name}' backed up");
+        println!("   File: {}", output.display());
+        println!("   Network: {:?}", wallet.network);
+        if self.config.passphrase.is_none() {
+            println!("⚠️  No passphrase set: anyone with the file can restore the wallet");
+        } else {
+            println!("   Restoring needs the wallet passphrase");
+        }
 
         Ok(())
     }
 
     /// Handle wallet restore
-    async fn handle_restore(&self, backup: PathBuf, new_name: String) -> WalletResult<()> {
+    async fn handle_restore(
+        &self,
+        backup: PathBuf,
+        new_name: String,
+        no_passphrase: bool,
+    ) -> WalletResult<()> {
         if self.config.verbose {
             println!("Restoring wallet from {}", backup.display());
         }
+        let passphrase = self.new_wallet_passphrase(no_passphrase)?;
 
-        println!("📥 Wallet Restore:");
-        println!("  Backup: {}", backup.display());
-        println!("  New name: {new_name}");
-        println!("  (Wallet restore not yet implemented)");
+        let backup_file = WalletBackup::from_bytes(&std::fs::read(&backup)?)?;
+        let storage_config = crate::storage::wallet_storage::StorageConfig {
+            storage_path: self.config.wallet_dir().join(&new_name),
+            auto_backup: true,
+            backup_count: 5,
+        };
+        let storage = WalletStorage::new(storage_config)?;
+        let wallet = Wallet::restore(new_name.clone(), &backup_file, passphrase, storage)?;
+
+        println!("📥 Wallet '{new_name}' restored from {}", backup.display());
+        println!("   Network: {:?}", wallet.network);
+        println!("   Watch-only: {}", wallet.is_watch_only());
+        println!(
+            "   Storage: {}",
+            self.config.wallet_dir().join(&new_name).display()
+        );
 
         Ok(())
     }
 
+    /// Handle snapshot commands
+    async fn handle_snapshot(&self, command: SnapshotCommands) -> WalletResult<()> {
+        match command {
+            SnapshotCommands::List {
+                wallet: wallet_name,
+            } => {
+                let storage = self.unlocked_storage(&wallet_name)?;
+                let snapshots = storage.list_snapshots(&wallet_name)?;
+                if snapshots.is_empty() {
+                    println!("No snapshots of wallet '{wallet_name}'");
+                    return Ok(());
+                }
+
+                println!("🗂️  Snapshots of wallet '{wallet_name}':");
+                for snapshot in &snapshots {
+                    let kind = match snapshot.kind {
+                        SnapshotKind::Automatic => "automatic".to_string(),
+                        SnapshotKind::PreMigration { version } => {
+                            format!("before migration from v{version}")
+                        }
+                    };
+                    let status = match storage.load_snapshot(snapshot) {
+                        Ok(_) => "✅",
+                        Err(_) => "⚠️  unreadable (damaged or sealed under an earlier passphrase)",
+                    };
+                    println!(
+                        "  {} ({}) {kind} {status}",
+                        snapshot.timestamp,
+                        format_timestamp(snapshot.timestamp)
+                    );
+                }
+            }
+            SnapshotCommands::Diff {
+                wallet: wallet_name,
+                timestamp,
+            } => {
+                let storage = self.unlocked_storage(&wallet_name)?;
+                let snapshot = find_snapshot(&storage, &wallet_name, timestamp)?;
+                let diffs = snapshot::diff(
+                    &storage.load_snapshot(&snapshot)?,
+                    &storage.load_wallet_data(&wallet_name)?,
+                )?;
+
+                println!(
+                    "🔍 Changes to wallet '{wallet_name}' since snapshot {}:",
+                    snapshot.timestamp
+                );
+                if diffs.is_empty() {
+                    println!("  No changes");
+                }
+                for diff in diffs {
+                    println!("  {}:", diff.field);
+                    for entry in diff.removed {
+                        println!("    - {}", truncate(&entry, 100));
+                    }
+                    for entry in diff.added {
+                        println!("    + {}", truncate(&entry, 100));
+                    }
+                }
+            }
+            SnapshotCommands::Rollback {
+                wallet: wallet_name,
+                timestamp,
+            } => {
+                let storage = self.unlocked_storage(&wallet_name)?;
+                let snapshot = find_snapshot(&storage, &wallet_name, timestamp)?;
+                storage.rollback(&snapshot)?;
+                println!(
+                    "⏪ Wallet '{wallet_name}' rolled back to snapshot {}",
+                    snapshot.timestamp
+                );
+                println!("   The replaced state was kept as a new snapshot");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle storage commands
+    async fn handle_storage(&self, command: StorageCommands) -> WalletResult<()> {
+        match command {
+            StorageCommands::Migrate {
+                wallet: wallet_name,
+                to,
+            } => {
+                let mut storage = self.wallet_storage(&wallet_name)?;
+                let (from, to) = (storage.backend_kind(), BackendKind::from(to));
+                let records = storage.migrate_backend(to)?;
+                println!("🗄️  Wallet '{wallet_name}' migrated from {from} to {to}");
+                println!("   Records copied: {records}");
+                println!("   The old database was kept next to the new one");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Open a wallet's storage, unlocked with the passphrase
+    fn unlocked_storage(&self, name: &str) -> WalletResult<WalletStorage> {
+        let mut storage = self.wallet_storage(name)?;
+        storage.unlock(self.passphrase()).map_err(|e| match e {
+            StorageError::DecryptionFailed => WalletError::InvalidPassphrase,
+            e => WalletError::Storage(e),
+        })?;
+        Ok(storage)
+    }
+
     /// Load wallet by name
     fn load_wallet(&self, name: &str) -> WalletResult<Wallet> {
+        let storage = self.wallet_storage(name)?;
+        Wallet::load(name.to_string(), self.passphrase(), storage)
+    }
+
+    /// Open the storage of an existing wallet
+    fn wallet_storage(&self, name: &str) -> WalletResult<WalletStorage> {
         let storage_config = crate::storage::wallet_storage::StorageConfig {
             storage_path: self.config.wallet_dir().join(name),
             auto_backup: true,
             backup_count: 5,
         };
 
-        let storage = WalletStorage::new(storage_config.clone())?;
+        let storage = WalletStorage::new(storage_config)?;
 
         // Check if wallet exists
         if !storage.wallet_exists(name)? {
@@ -575,19 +1639,56 @@ impl CliHandler {
             )));
         }
 
-        // Try to load the wallet (will fail for now due to unimplemented loading)
-        match Wallet::load(name.to_string(), storage) {
-            Ok(wallet) => Ok(wallet),
-            Err(WalletError::NotImplemented(_)) => {
-                // Fallback: create a dummy wallet for now until loading is implemented
-                if self.config.verbose {
-                    println!("⚠️  Wallet loading not yet implemented, creating temporary wallet");
-                }
-                let new_storage = WalletStorage::new(storage_config.clone())?;
-                Wallet::create_new(name.to_string(), self.config.network.into(), new_storage)
+        Ok(storage)
+    }
+
+    /// Passphrase for wallet key envelopes (empty when none was supplied)
+    fn passphrase(&self) -> &str {
+        self.config.passphrase.as_deref().unwrap_or("")
+    }
+
+    /// Passphrase a new wallet is sealed under
+    ///
+    /// Storage is keyed from the passphrase, so an empty one is only accepted
+    /// when the user opted out with `--no-passphrase`.
+    fn new_wallet_passphrase(&self, no_passphrase: bool) -> WalletResult<&str> {
+        let passphrase = self.passphrase();
+        if passphrase.is_empty() {
+            if !no_passphrase {
+                return Err(WalletError::InvalidInput(
+                    "A passphrase is required for a new wallet (--passphrase or ARMORY_PASSPHRASE); \
+                     pass --no-passphrase to store it unprotected"
+                        .to_string(),
+                ));
             }
-            Err(e) => Err(e),
+            println!("⚠️  No passphrase set: anyone who can read the wallet files can use them");
         }
+        Ok(passphrase)
+    }
+}
+
+/// Newest snapshot of a wallet taken at or before `timestamp`
+fn find_snapshot(
+    storage: &WalletStorage,
+    wallet_name: &str,
+    timestamp: u64,
+) -> WalletResult<Snapshot> {
+    storage
+        .list_snapshots(wallet_name)?
+        .into_iter()
+        .find(|snapshot| snapshot.timestamp <= timestamp)
+        .ok_or_else(|| {
+            WalletError::InvalidInput(format!(
+                "No snapshot of wallet '{wallet_name}' at or before {timestamp}"
+            ))
+        })
+}
+
+/// Shorten a line for display, on a character boundary
+fn truncate(line: &str, max_chars: usize) -> String {
+    match line.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
     }
 }
 
@@ -614,3 +1715,56 @@ fn format_timestamp(timestamp: u64) -> String {
         None => "Invalid timestamp".to_string(),
     }
 }
+
+/// Convert a fee rate in sat/vB into a `FeeRate`
+fn parse_fee_rate(rate: f64) -> WalletResult<FeeRate> {
+    if !rate.is_finite() || rate < 0.0 {
+        return Err(WalletError::InvalidInput(format!(
+            "Invalid fee rate: {rate} sat/vB"
+        )));
+    }
+    // 1 sat/vB = 250 sat/kwu
+    Ok(FeeRate::from_sat_per_kwu((rate * 250.0).round() as u64))
+}
+
+/// Parse a `txid:vout` outpoint argument
+fn parse_outpoint(outpoint: &str) -> WalletResult<OutPoint> {
+    OutPoint::from_str(outpoint)
+        .map_err(|e| WalletError::InvalidInput(format!("Invalid outpoint '{outpoint}': {e}")))
+}
+
+/// Read a PSBT given as a file path, base64 or hex string
+/// Parse an address and check it belongs to the configured network
+fn parse_address(input: &str, network: bitcoin::Network) -> WalletResult<Address> {
+    Address::from_str(input.trim())
+        .map_err(|e| WalletError::InvalidInput(format!("Invalid address: {e}")))?
+        .require_network(network)
+        .map_err(|e| WalletError::InvalidInput(format!("Invalid address: {e}")))
+}
+
+/// Secret given on the command line, or the next stdin line when it is "-"
+fn read_secret(value: String) -> WalletResult<String> {
+    if value != "-" {
+        return Ok(value);
+    }
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn read_psbt(input: &str) -> WalletResult<Psbt> {
+    let path = std::path::Path::new(input);
+    let encoded = if path.is_file() {
+        std::fs::read_to_string(path)?
+    } else {
+        input.to_string()
+    };
+    let encoded = encoded.trim();
+
+    if let Ok(bytes) = hex::decode(encoded) {
+        if let Ok(psbt) = Psbt::deserialize(&bytes) {
+            return Ok(psbt);
+        }
+    }
+    Psbt::from_str(encoded).map_err(|e| WalletError::InvalidInput(format!("Invalid PSBT: {e}")))
+}
@@ -9,6 +9,10 @@ pub mod handlers;
 pub mod tests;
 
 // Re-exports for convenience
-pub use commands::{AddressType, CliCommands, Commands, ExportFormat, MultisigCommands};
+pub use commands::{
+    AccountCommands, AddressType, BackendArg, CliCommands, Commands, ExportFormat, HistoryFormat,
+    KeychainCommands, MessageFormatArg, MultisigCommands, PassphraseCommands, PolicyCommands,
+    ReportFormat, SnapshotCommands, StorageCommands, UtxoCommands,
+};
 pub use config::CliConfig;
 pub use handlers::CliHandler;
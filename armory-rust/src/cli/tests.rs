@@ -2,10 +2,15 @@
 ///
 /// Tests the command-line interface functionality including wallet operations,
 /// address generation, and basic CLI workflow as specified in the PRP.
-use crate::cli::{AddressType as CliAddressType, CliConfig, CliHandler, Commands};
-use crate::wallet::descriptor_wallet::AddressType;
+use crate::cli::{
+    AccountCommands, AddressType as CliAddressType, BackendArg, CliConfig, CliHandler, Commands,
+    ExportFormat, HistoryFormat, KeychainCommands, MessageFormatArg, PassphraseCommands,
+    PolicyCommands, ReportFormat, SnapshotCommands, StorageCommands, UtxoCommands,
+};
+use crate::wallet::descriptor_wallet::{AddressType, Utxo};
 use bitcoin::Network;
 use std::path::PathBuf;
+use std::str::FromStr;
 use tempfile::TempDir;
 
 /// Helper function to create a test CLI configuration
@@ -41,7 +46,9 @@ mod tests {
             .execute(Commands::Create {
                 name: "test_wallet".to_string(),
                 mnemonic: None,
-                encrypt: false,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
                 account: 0,
             })
             .await;
@@ -66,6 +73,7 @@ mod tests {
                 address_type: CliAddressType::NativeSegwit,
                 count: 3,
                 list: false,
+                account: 0,
             })
             .await;
         assert!(address_result.is_ok(), "Address generation should succeed");
@@ -77,6 +85,7 @@ mod tests {
                 address_type: CliAddressType::NativeSegwit,
                 count: 1,
                 list: true,
+                account: 0,
             })
             .await;
         assert!(
@@ -106,7 +115,9 @@ mod tests {
             .execute(Commands::Create {
                 name: "test_wallet".to_string(),
                 mnemonic: None,
-                encrypt: false,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
                 account: 0,
             })
             .await;
@@ -119,7 +130,9 @@ mod tests {
             .execute(Commands::Create {
                 name: "test_wallet".to_string(),
                 mnemonic: None,
-                encrypt: false,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
                 account: 0,
             })
             .await;
@@ -142,7 +155,9 @@ mod tests {
                 .execute(Commands::Create {
                     name: wallet_name.to_string(),
                     mnemonic: None,
-                    encrypt: false,
+                    words: 12,
+                    mnemonic_passphrase: None,
+                    no_passphrase: true,
                     account: 0,
                 })
                 .await;
@@ -168,6 +183,1145 @@ mod tests {
         }
     }
 
+    /// Test that wallets survive a reload and require the right passphrase
+    #[tokio::test]
+    async fn test_wallet_persists_across_loads() {
+        let (config, _temp_dir) = create_test_cli_config();
+        let handler =
+            CliHandler::new(config.clone().with_passphrase(Some("hunter2".to_string()))).unwrap();
+
+        handler
+            .execute(Commands::Create {
+                name: "persisted".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: false,
+                account: 0,
+            })
+            .await
+            .unwrap();
+        handler
+            .execute(Commands::Address {
+                wallet: "persisted".to_string(),
+                address_type: CliAddressType::NativeSegwit,
+                count: 2,
+                list: false,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        let storage = crate::storage::WalletStorage::new(crate::storage::StorageConfig {
+            storage_path: config.wallet_dir().join("persisted"),
+            auto_backup: false,
+            backup_count: 1,
+        })
+        .unwrap();
+        let wallet =
+            crate::wallet::Wallet::load("persisted".to_string(), "hunter2", storage).unwrap();
+        assert_eq!(
+            wallet.get_addresses(AddressType::NativeSegwit, false).len(),
+            3
+        );
+        drop(wallet);
+
+        let wrong_handler =
+            CliHandler::new(config.with_passphrase(Some("wrong".to_string()))).unwrap();
+        let info_result = wrong_handler
+            .execute(Commands::Info {
+                wallet: "persisted".to_string(),
+            })
+            .await;
+        assert!(info_result.is_err(), "Wrong passphrase should be rejected");
+    }
+
+    /// Test that new wallets need a passphrase unless the user opts out
+    #[tokio::test]
+    async fn test_new_wallets_need_a_passphrase() {
+        let (handler, _temp_dir) = create_test_cli_handler();
+        let create = |name: &str, no_passphrase: bool| Commands::Create {
+            name: name.to_string(),
+            mnemonic: None,
+            words: 12,
+            mnemonic_passphrase: None,
+            no_passphrase,
+            account: 0,
+        };
+        let refused = |result: crate::error::WalletResult<()>| {
+            assert!(result.unwrap_err().to_string().contains("--no-passphrase"));
+        };
+        refused(handler.execute(create("sealed", false)).await);
+        assert!(handler
+            .execute(Commands::Info {
+                wallet: "sealed".to_string(),
+            })
+            .await
+            .is_err());
+        refused(
+            handler
+            .execute(Commands::WatchOnly {
+                name: "watcher".to_string(),
+                descriptors: vec![
+                    "wpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*)"
+                        .to_string()
+                ],
+                no_passphrase: false,
+            })
+            .await,
+        );
+        handler.execute(create("open", true)).await.unwrap();
+    }
+
+    /// Test restoring a wallet from an existing mnemonic
+    #[tokio::test]
+    async fn test_create_from_mnemonic() {
+        let (handler, _temp_dir) = create_test_cli_handler();
+
+        let restore_result = handler
+            .execute(Commands::Create {
+                name: "restored".to_string(),
+                mnemonic: Some(
+                    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+                        .to_string(),
+                ),
+                words: 24,
+                mnemonic_passphrase: Some("TREZOR".to_string()),
+                no_passphrase: true,
+                account: 0,
+            })
+            .await;
+        assert!(
+            restore_result.is_ok(),
+            "Restoring from mnemonic should succeed"
+        );
+
+        let invalid_result = handler
+            .execute(Commands::Create {
+                name: "invalid".to_string(),
+                mnemonic: Some("abandon abandon abandon".to_string()),
+                words: 24,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
+                account: 0,
+            })
+            .await;
+        assert!(
+            invalid_result.is_err(),
+            "Invalid mnemonic should be rejected"
+        );
+    }
+
+    /// Test coin control commands and pinned inputs for send
+    #[tokio::test]
+    async fn test_utxo_commands() {
+        let (config, _temp_dir) = create_test_cli_config();
+        let handler = CliHandler::new(config.clone()).unwrap();
+        handler
+            .execute(Commands::Create {
+                name: "coins".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        // Fund the wallet directly through the library
+        let storage = || {
+            crate::storage::WalletStorage::new(crate::storage::StorageConfig {
+                storage_path: config.wallet_dir().join("coins"),
+                auto_backup: false,
+                backup_count: 1,
+            })
+            .unwrap()
+        };
+        let mut wallet = crate::wallet::Wallet::load("coins".to_string(), "", storage()).unwrap();
+        let address = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        let txid = bitcoin::Txid::from_str(&format!("{:064x}", 1)).unwrap();
+        wallet.add_utxo(Utxo {
+            txid,
+            vout: 1,
+            value: 100_000,
+            script_pubkey: address.script_pubkey(),
+            derivation_path: wallet.owns_address(&address).unwrap(),
+            address,
+            block_height: Some(100),
+        });
+        wallet.save().unwrap();
+        drop(wallet);
+
+        let outpoint = format!("{txid}:1");
+        let send = |inputs: Vec<String>| Commands::Send {
+            wallet: "coins".to_string(),
+            to: "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080".to_string(),
+            amount: 40_000,
+            fee_rate: Some(2.0),
+            psbt_only: true,
+            inputs,
+        };
+
+        handler
+            .execute(Commands::Utxo {
+                command: UtxoCommands::Freeze {
+                    wallet: "coins".to_string(),
+                    outpoint: outpoint.clone(),
+                },
+            })
+            .await
+            .unwrap();
+        handler
+            .execute(Commands::Utxo {
+                command: UtxoCommands::Label {
+                    wallet: "coins".to_string(),
+                    outpoint: outpoint.clone(),
+                    label: "Savings".to_string(),
+                    source: Some("mining".to_string()),
+                },
+            })
+            .await
+            .unwrap();
+        assert!(handler
+            .execute(Commands::Utxo {
+                command: UtxoCommands::List {
+                    wallet: "coins".to_string(),
+                },
+            })
+            .await
+            .is_ok());
+        assert!(
+            handler.execute(send(Vec::new())).await.is_err(),
+            "Frozen coins must not be selected"
+        );
+        assert!(
+            handler.execute(send(vec![outpoint.clone()])).await.is_err(),
+            "Frozen coins must not be pinned"
+        );
+        assert!(handler
+            .execute(Commands::Utxo {
+                command: UtxoCommands::Freeze {
+                    wallet: "coins".to_string(),
+                    outpoint: "not-an-outpoint".to_string(),
+                },
+            })
+            .await
+            .is_err());
+
+        handler
+            .execute(Commands::Utxo {
+                command: UtxoCommands::Unfreeze {
+                    wallet: "coins".to_string(),
+                    outpoint: outpoint.clone(),
+                },
+            })
+            .await
+            .unwrap();
+        handler.execute(send(vec![outpoint.clone()])).await.unwrap();
+
+        let wallet = crate::wallet::Wallet::load("coins".to_string(), "", storage()).unwrap();
+        let metadata = wallet
+            .utxo_metadata(&bitcoin::OutPoint::new(txid, 1))
+            .unwrap();
+        assert_eq!(metadata.label.as_deref(), Some("Savings"));
+        assert_eq!(metadata.source.as_deref(), Some("mining"));
+        assert!(!metadata.frozen);
+        // The PSBT's change address was persisted
+        assert_eq!(
+            wallet.get_addresses(AddressType::NativeSegwit, true).len(),
+            1
+        );
+    }
+
+    /// Test moving BIP-329 labels between wallets
+    #[tokio::test]
+    async fn test_label_export_and_import() {
+        let (config, temp_dir) = create_test_cli_config();
+        let handler = CliHandler::new(config).unwrap();
+        for name in ["source", "target"] {
+            handler
+                .execute(Commands::Create {
+                    name: name.to_string(),
+                    mnemonic: None,
+                    words: 12,
+                    mnemonic_passphrase: None,
+                    no_passphrase: true,
+                    account: 0,
+                })
+                .await
+                .unwrap();
+        }
+
+        let label_file = temp_dir.path().join("labels.jsonl");
+        std::fs::write(
+            &label_file,
+            concat!(
+                r#"{"type":"tx","ref":"f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd","label":"Transaction"}"#,
+                "\n",
+                r#"{"type":"addr","ref":"bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c","label":"Address"}"#,
+                "\n"
+            ),
+        )
+        .unwrap();
+        handler
+            .execute(Commands::ImportLabels {
+                wallet: "source".to_string(),
+                file: label_file,
+            })
+            .await
+            .unwrap();
+
+        let exported = temp_dir.path().join("exported.jsonl");
+        handler
+            .execute(Commands::Export {
+                wallet: "source".to_string(),
+                format: ExportFormat::Labels,
+                output: Some(exported.clone()),
+                account: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&exported).unwrap().lines().count(),
+            2
+        );
+
+        handler
+            .execute(Commands::ImportLabels {
+                wallet: "target".to_string(),
+                file: exported,
+            })
+            .await
+            .unwrap();
+
+        let bad_file = temp_dir.path().join("bad.jsonl");
+        std::fs::write(&bad_file, "not json\n").unwrap();
+        assert!(handler
+            .execute(Commands::ImportLabels {
+                wallet: "target".to_string(),
+                file: bad_file,
+            })
+            .await
+            .is_err());
+    }
+
+    /// Test exporting descriptors for Bitcoin Core and watching them
+    #[tokio::test]
+    async fn test_descriptor_export() {
+        let (handler, temp_dir) = create_test_cli_handler();
+        handler
+            .execute(Commands::Create {
+                name: "signer".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        let exported = temp_dir.path().join("descriptors.json");
+        handler
+            .execute(Commands::Export {
+                wallet: "signer".to_string(),
+                format: ExportFormat::Descriptors,
+                output: Some(exported.clone()),
+                account: None,
+            })
+            .await
+            .unwrap();
+
+        // The file is the JSON argument of `importdescriptors`
+        let json = std::fs::read_to_string(&exported).unwrap();
+        let imports: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+        assert_eq!(imports.len(), 8);
+        for import in &imports {
+            let desc = import["desc"].as_str().unwrap();
+            assert!(desc.contains("'/0'/0']tpub") && desc.contains("/*)"));
+            assert_eq!(desc.rsplit_once('#').unwrap().1.len(), 8);
+            assert!(import["timestamp"].is_u64());
+            assert!(import["active"].as_bool().unwrap());
+            assert!(import["internal"].is_boolean());
+            assert_eq!(import["range"][0], 0);
+        }
+
+        handler
+            .execute(Commands::WatchOnly {
+                name: "watcher".to_string(),
+                descriptors: vec![json],
+                no_passphrase: true,
+            })
+            .await
+            .unwrap();
+    }
+
+    /// Test adding, listing and deriving from descriptor keychains
+    #[tokio::test]
+    async fn test_keychain_commands() {
+        let (config, _temp_dir) = create_test_cli_config();
+        let handler = CliHandler::new(config.clone()).unwrap();
+        handler
+            .execute(Commands::Create {
+                name: "vault".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let cosigner = |seed: u8| {
+            let xpriv =
+                bitcoin::bip32::Xpriv::new_master(bitcoin::Network::Regtest, &[seed; 32]).unwrap();
+            bitcoin::bip32::Xpub::from_priv(&secp, &xpriv)
+        };
+        let descriptor = format!(
+            "wsh(sortedmulti(2,{}/<0;1>/*,{}/<0;1>/*))",
+            cosigner(1),
+            cosigner(2)
+        );
+        let add = || Commands::Keychain {
+            command: KeychainCommands::Add {
+                wallet: "vault".to_string(),
+                name: "shared".to_string(),
+                descriptor: descriptor.clone(),
+                change: None,
+            },
+        };
+
+        handler.execute(add()).await.unwrap();
+        assert!(handler.execute(add()).await.is_err());
+        handler
+            .execute(Commands::Keychain {
+                command: KeychainCommands::Address {
+                    wallet: "vault".to_string(),
+                    name: "shared".to_string(),
+                    change: false,
+                    count: 2,
+                },
+            })
+            .await
+            .unwrap();
+        handler
+            .execute(Commands::Keychain {
+                command: KeychainCommands::List {
+                    wallet: "vault".to_string(),
+                },
+            })
+            .await
+            .unwrap();
+
+        let storage = crate::storage::WalletStorage::new(crate::storage::StorageConfig {
+            storage_path: config.wallet_dir().join("vault"),
+            auto_backup: false,
+            backup_count: 1,
+        })
+        .unwrap();
+        let wallet = crate::wallet::Wallet::load("vault".to_string(), "", storage).unwrap();
+        let addresses = wallet.keychain_addresses("shared", false).unwrap();
+        assert_eq!(addresses.len(), 2);
+        assert!(addresses[0].script_pubkey().is_p2wsh());
+        assert!(wallet.is_mine(&addresses[1].script_pubkey()));
+    }
+
+    /// Test spending policy commands
+    #[tokio::test]
+    async fn test_policy_commands() {
+        let (config, _temp_dir) = create_test_cli_config();
+        let handler = CliHandler::new(config).unwrap();
+        handler
+            .execute(Commands::Create {
+                name: "hot".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        let set = |allowed: &str| Commands::Policy {
+            command: PolicyCommands::Set {
+                wallet: "hot".to_string(),
+                max_per_tx: Some(100_000),
+                daily_limit: Some(500_000),
+                allowed_destinations: vec![allowed.to_string()],
+                max_fee_rate: Some(50.0),
+                max_fee_percent: Some(2.5),
+                forbid_unknown_change: true,
+                approval_threshold: None,
+            },
+        };
+        assert!(handler.execute(set("not-an-address")).await.is_err());
+        let allowed =
+            bitcoin::Address::p2wsh(&bitcoin::ScriptBuf::new(), Network::Regtest).to_string();
+        handler.execute(set(&allowed)).await.unwrap();
+
+        let show = || Commands::Policy {
+            command: PolicyCommands::Show {
+                wallet: "hot".to_string(),
+            },
+        };
+        handler.execute(show()).await.unwrap();
+        handler
+            .execute(Commands::Policy {
+                command: PolicyCommands::Clear {
+                    wallet: "hot".to_string(),
+                },
+            })
+            .await
+            .unwrap();
+        handler.execute(show()).await.unwrap();
+    }
+
+    /// Test changing a wallet passphrase
+    #[tokio::test]
+    async fn test_passphrase_change() {
+        let (config, _temp_dir) = create_test_cli_config();
+        let handler = CliHandler::new(config.clone()).unwrap();
+        handler
+            .execute(Commands::Create {
+                name: "vault".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        let change = |new_passphrase: Option<&str>| Commands::Passphrase {
+            command: PassphraseCommands::Change {
+                wallet: "vault".to_string(),
+                new_passphrase: new_passphrase.map(str::to_string),
+            },
+        };
+        assert!(handler.execute(change(None)).await.is_err());
+        handler.execute(change(Some("new secret"))).await.unwrap();
+
+        let info = || Commands::Info {
+            wallet: "vault".to_string(),
+        };
+        assert!(handler.execute(info()).await.is_err());
+        let handler =
+            CliHandler::new(config.with_passphrase(Some("new secret".to_string()))).unwrap();
+        handler.execute(info()).await.unwrap();
+    }
+
+    /// Test message signing and verification commands
+    #[tokio::test]
+    async fn test_message_commands() {
+        let (config, _temp_dir) = create_test_cli_config();
+        let handler = CliHandler::new(config.clone()).unwrap();
+        handler
+            .execute(Commands::Create {
+                name: "prover".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
+                account: 0,
+            })
+            .await
+            .unwrap();
+        handler
+            .execute(Commands::Address {
+                wallet: "prover".to_string(),
+                address_type: CliAddressType::Taproot,
+                count: 1,
+                list: false,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        // Sign directly with the wallet, releasing its database before the handler runs
+        let (address, signature) = {
+            let storage = crate::storage::WalletStorage::new(crate::storage::StorageConfig {
+                storage_path: config.wallet_dir().join("prover"),
+                auto_backup: false,
+                backup_count: 1,
+            })
+            .unwrap();
+            let mut wallet =
+                crate::wallet::Wallet::load("prover".to_string(), "", storage).unwrap();
+            let address = wallet.get_addresses(AddressType::Taproot, false)[0].clone();
+            let signature = wallet
+                .sign_message(
+                    &address,
+                    "proof of ownership",
+                    crate::wallet::MessageFormat::Simple,
+                )
+                .unwrap();
+            (address, signature)
+        };
+
+        let sign = |format| Commands::SignMessage {
+            wallet: "prover".to_string(),
+            address: address.to_string(),
+            message: "proof of ownership".to_string(),
+            format,
+        };
+        handler
+            .execute(sign(MessageFormatArg::Simple))
+            .await
+            .unwrap();
+        assert!(handler
+            .execute(sign(MessageFormatArg::Legacy))
+            .await
+            .is_err());
+
+        let verify = |message: &str| Commands::VerifyMessage {
+            address: address.to_string(),
+            message: message.to_string(),
+            signature: signature.clone(),
+        };
+        handler.execute(verify("proof of ownership")).await.unwrap();
+        assert!(handler.execute(verify("forged")).await.is_err());
+    }
+
+    /// Test history listing, filters and CSV/JSON export
+    #[tokio::test]
+    async fn test_history_command() {
+        let (config, temp_dir) = create_test_cli_config();
+        let handler = CliHandler::new(config).unwrap();
+        handler
+            .execute(Commands::Create {
+                name: "books".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        let history = |format, output, from: Option<&str>| Commands::History {
+            wallet: "books".to_string(),
+            from: from.map(String::from),
+            to: None,
+            min_amount: Some(1_000),
+            max_amount: None,
+            label: Some("rent".to_string()),
+            format,
+            output,
+        };
+
+        handler
+            .execute(history(HistoryFormat::Table, None, Some("2024-01-01")))
+            .await
+            .unwrap();
+
+        let csv = temp_dir.path().join("history.csv");
+        handler
+            .execute(history(HistoryFormat::Csv, Some(csv.clone()), None))
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&csv).unwrap(),
+            "txid,date,timestamp,block_height,kind,amount,fee,label\n"
+        );
+
+        let json = temp_dir.path().join("history.json");
+        handler
+            .execute(history(HistoryFormat::Json, Some(json.clone()), None))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&json).unwrap().trim(), "[]");
+
+        assert!(handler
+            .execute(history(HistoryFormat::Table, None, Some("01/02/2024")))
+            .await
+            .is_err());
+    }
+
+    /// Test creating, labelling and using additional accounts
+    #[tokio::test]
+    async fn test_account_commands() {
+        let (config, _temp_dir) = create_test_cli_config();
+        let handler = CliHandler::new(config.clone()).unwrap();
+
+        handler
+            .execute(Commands::Create {
+                name: "accounts".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        let create = |index| Commands::Account {
+            command: AccountCommands::Create {
+                wallet: "accounts".to_string(),
+                index,
+                label: Some("Savings".to_string()),
+            },
+        };
+        assert!(
+            handler.execute(create(None)).await.is_err(),
+            "Account 1 needs history on account 0"
+        );
+
+        // Give account 0 a coin so the next account may be opened
+        let storage = crate::storage::WalletStorage::new(crate::storage::StorageConfig {
+            storage_path: config.wallet_dir().join("accounts"),
+            auto_backup: false,
+            backup_count: 1,
+        })
+        .unwrap();
+        let mut wallet = crate::wallet::Wallet::load("accounts".to_string(), "", storage).unwrap();
+        let address = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        wallet.add_utxo(Utxo {
+            txid: bitcoin::Txid::from_str(&format!("{:064x}", 1)).unwrap(),
+            vout: 0,
+            value: 10_000,
+            script_pubkey: address.script_pubkey(),
+            derivation_path: wallet.owns_address(&address).unwrap(),
+            address,
+            block_height: Some(100),
+        });
+        wallet.save().unwrap();
+        drop(wallet);
+
+        assert!(handler.execute(create(None)).await.is_ok());
+        assert!(
+            handler.execute(create(Some(1))).await.is_err(),
+            "Existing account should be rejected"
+        );
+
+        handler
+            .execute(Commands::Account {
+                command: AccountCommands::Label {
+                    wallet: "accounts".to_string(),
+                    account: 0,
+                    label: "Spending".to_string(),
+                },
+            })
+            .await
+            .unwrap();
+        handler
+            .execute(Commands::Address {
+                wallet: "accounts".to_string(),
+                address_type: CliAddressType::NativeSegwit,
+                count: 2,
+                list: false,
+                account: 1,
+            })
+            .await
+            .unwrap();
+        assert!(handler
+            .execute(Commands::Balance {
+                wallet: "accounts".to_string(),
+                include_unconfirmed: true,
+                account: Some(1),
+            })
+            .await
+            .is_ok());
+        assert!(handler
+            .execute(Commands::Balance {
+                wallet: "accounts".to_string(),
+                include_unconfirmed: false,
+                account: Some(7),
+            })
+            .await
+            .is_err());
+        assert!(handler
+            .execute(Commands::Account {
+                command: AccountCommands::List {
+                    wallet: "accounts".to_string(),
+                },
+            })
+            .await
+            .is_ok());
+
+        let storage = crate::storage::WalletStorage::new(crate::storage::StorageConfig {
+            storage_path: config.wallet_dir().join("accounts"),
+            auto_backup: false,
+            backup_count: 1,
+        })
+        .unwrap();
+        let wallet = crate::wallet::Wallet::load("accounts".to_string(), "", storage).unwrap();
+        let labels: Vec<_> = wallet.accounts().iter().map(|a| a.label.as_str()).collect();
+        assert_eq!(labels, ["Spending", "Savings"]);
+        assert_eq!(
+            wallet
+                .get_account_addresses(1, AddressType::NativeSegwit, false)
+                .len(),
+            3
+        );
+    }
+
+    /// Test planning and building consolidations
+    #[tokio::test]
+    async fn test_consolidate_command() {
+        let (config, _temp_dir) = create_test_cli_config();
+        let handler = CliHandler::new(config.clone()).unwrap();
+        handler
+            .execute(Commands::Create {
+                name: "dust".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        let consolidate = |fee_rate, psbt| Commands::Consolidate {
+            wallet: "dust".to_string(),
+            fee_rate,
+            max_fee_rate: 5.0,
+            future_fee_rate: 20.0,
+            min_inputs: 3,
+            max_value: None,
+            psbt,
+        };
+        // The chain tip is needed to count confirmations
+        assert!(handler.execute(consolidate(2.0, false)).await.is_err());
+
+        let storage = || {
+            crate::storage::WalletStorage::new(crate::storage::StorageConfig {
+                storage_path: config.wallet_dir().join("dust"),
+                auto_backup: false,
+                backup_count: 1,
+            })
+            .unwrap()
+        };
+        let mut wallet = crate::wallet::Wallet::load("dust".to_string(), "", storage()).unwrap();
+        for value in 1..=4u64 {
+            let address = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+            wallet.add_utxo(Utxo {
+                txid: bitcoin::Txid::from_str(&format!("{value:064x}")).unwrap(),
+                vout: 0,
+                value: value * 10_000,
+                script_pubkey: address.script_pubkey(),
+                derivation_path: wallet.owns_address(&address).unwrap(),
+                address,
+                block_height: Some(100),
+            });
+        }
+        wallet.set_tip_height(110);
+        wallet.save().unwrap();
+        drop(wallet);
+
+        assert!(handler.execute(consolidate(2.0, false)).await.is_ok());
+        assert!(
+            handler.execute(consolidate(8.0, false)).await.is_err(),
+            "Fee rate above the ceiling should be rejected"
+        );
+        handler.execute(consolidate(2.0, true)).await.unwrap();
+
+        // The consolidation address was handed out and persisted
+        let wallet = crate::wallet::Wallet::load("dust".to_string(), "", storage()).unwrap();
+        assert_eq!(
+            wallet
+                .get_account_addresses(0, AddressType::NativeSegwit, true)
+                .len(),
+            1
+        );
+    }
+
+    /// Test the privacy report in both formats
+    #[tokio::test]
+    async fn test_privacy_report_command() {
+        let (config, _temp_dir) = create_test_cli_config();
+        let handler = CliHandler::new(config.clone()).unwrap();
+        handler
+            .execute(Commands::Create {
+                name: "private".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        let report = |format| Commands::PrivacyReport {
+            wallet: "private".to_string(),
+            format,
+        };
+        assert!(handler.execute(report(ReportFormat::Text)).await.is_ok());
+
+        let storage = crate::storage::WalletStorage::new(crate::storage::StorageConfig {
+            storage_path: config.wallet_dir().join("private"),
+            auto_backup: false,
+            backup_count: 1,
+        })
+        .unwrap();
+        let mut wallet = crate::wallet::Wallet::load("private".to_string(), "", storage).unwrap();
+        let address = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        for (n, value) in [(1, 50_000), (2, 600)] {
+            wallet.add_utxo(Utxo {
+                txid: bitcoin::Txid::from_str(&format!("{n:064x}")).unwrap(),
+                vout: 0,
+                value,
+                script_pubkey: address.script_pubkey(),
+                derivation_path: wallet.owns_address(&address).unwrap(),
+                address: address.clone(),
+                block_height: Some(100),
+            });
+        }
+        let findings = wallet.privacy_report().findings;
+        assert_eq!(findings.len(), 2, "Expected address reuse and dust");
+        wallet.save().unwrap();
+        drop(wallet);
+
+        assert!(handler.execute(report(ReportFormat::Text)).await.is_ok());
+        assert!(handler.execute(report(ReportFormat::Json)).await.is_ok());
+        assert!(handler
+            .execute(Commands::PrivacyReport {
+                wallet: "missing".to_string(),
+                format: ReportFormat::Text,
+            })
+            .await
+            .is_err());
+    }
+
+    /// Test backing up a wallet and restoring it elsewhere under a new name
+    #[tokio::test]
+    async fn test_backup_and_restore_commands() {
+        let (config, temp_dir) = create_test_cli_config();
+        let config = config.with_passphrase(Some("hunter2".to_string()));
+        let handler = CliHandler::new(config.clone()).unwrap();
+        handler
+            .execute(Commands::Create {
+                name: "original".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: false,
+                account: 0,
+            })
+            .await
+            .unwrap();
+        handler
+            .execute(Commands::Address {
+                wallet: "original".to_string(),
+                address_type: CliAddressType::NativeSegwit,
+                count: 2,
+                list: false,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        let file = temp_dir.path().join("original.armbak");
+        let backup = |wallet: &str| Commands::Backup {
+            wallet: wallet.to_string(),
+            output: file.clone(),
+        };
+        handler.execute(backup("original")).await.unwrap();
+        assert!(std::fs::read(&file)
+            .unwrap()
+            .starts_with(crate::storage::backup::BACKUP_MAGIC));
+        assert!(
+            handler.execute(backup("original")).await.is_err(),
+            "An existing file should not be overwritten"
+        );
+
+        // Another data directory stands in for another machine
+        let (other_config, _other_dir) = create_test_cli_config();
+        let restore = |passphrase: &str| {
+            let config = other_config
+                .clone()
+                .with_passphrase(Some(passphrase.to_string()));
+            (
+                CliHandler::new(config).unwrap(),
+                Commands::Restore {
+                    backup: file.clone(),
+                    new_name: "restored".to_string(),
+                    no_passphrase: false,
+                },
+            )
+        };
+        let (wrong_handler, command) = restore("wrong");
+        assert!(wrong_handler.execute(command).await.is_err());
+        let (other_handler, command) = restore("hunter2");
+        other_handler.execute(command).await.unwrap();
+        let (other_handler, command) = restore("hunter2");
+        assert!(
+            other_handler.execute(command).await.is_err(),
+            "Restoring over an existing wallet should fail"
+        );
+
+        let load = |config: &CliConfig, name: &str| {
+            let storage = crate::storage::WalletStorage::new(crate::storage::StorageConfig {
+                storage_path: config.wallet_dir().join(name),
+                auto_backup: false,
+                backup_count: 1,
+            })
+            .unwrap();
+            crate::wallet::Wallet::load(name.to_string(), "hunter2", storage).unwrap()
+        };
+        let original = load(&config, "original");
+        let restored = load(&other_config, "restored");
+        assert_eq!(
+            restored.account_xpub(AddressType::NativeSegwit, 0).unwrap(),
+            original.account_xpub(AddressType::NativeSegwit, 0).unwrap()
+        );
+        assert_eq!(
+            restored.get_addresses(AddressType::NativeSegwit, false),
+            original.get_addresses(AddressType::NativeSegwit, false)
+        );
+    }
+
+    /// Test listing, diffing and rolling back to wallet snapshots
+    #[tokio::test]
+    async fn test_snapshot_commands() {
+        let (config, _temp_dir) = create_test_cli_config();
+        let handler = CliHandler::new(config.clone()).unwrap();
+        handler
+            .execute(Commands::Create {
+                name: "snap".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        // Date the creation snapshot back, then label an address
+        let storage = || {
+            crate::storage::WalletStorage::new(crate::storage::StorageConfig {
+                storage_path: config.wallet_dir().join("snap"),
+                auto_backup: false,
+                backup_count: 1,
+            })
+            .unwrap()
+        };
+        let snapshot = {
+            let mut storage = storage();
+            storage.unlock("").unwrap();
+            storage.list_snapshots("snap").unwrap().remove(0)
+        };
+        let mut wallet = crate::wallet::Wallet::load("snap".to_string(), "", storage()).unwrap();
+        std::fs::rename(
+            &snapshot.path,
+            snapshot.path.with_file_name("snap_1000.backup"),
+        )
+        .unwrap();
+        let address = wallet
+            .get_new_address(AddressType::NativeSegwit)
+            .unwrap()
+            .to_string();
+        wallet.set_address_label(&address, Some("Exchange".to_string()));
+        wallet.save().unwrap();
+        drop(wallet);
+
+        let snapshot_command = |command| Commands::Snapshot { command };
+        handler
+            .execute(snapshot_command(SnapshotCommands::List {
+                wallet: "snap".to_string(),
+            }))
+            .await
+            .unwrap();
+        handler
+            .execute(snapshot_command(SnapshotCommands::Diff {
+                wallet: "snap".to_string(),
+                timestamp: 1500,
+            }))
+            .await
+            .unwrap();
+        assert!(handler
+            .execute(snapshot_command(SnapshotCommands::Diff {
+                wallet: "snap".to_string(),
+                timestamp: 999,
+            }))
+            .await
+            .is_err());
+        handler
+            .execute(snapshot_command(SnapshotCommands::Rollback {
+                wallet: "snap".to_string(),
+                timestamp: 1500,
+            }))
+            .await
+            .unwrap();
+
+        let wallet = crate::wallet::Wallet::load("snap".to_string(), "", storage()).unwrap();
+        assert_eq!(wallet.address_label(&address), None);
+        drop(wallet);
+        let mut storage = storage();
+        storage.unlock("").unwrap();
+        assert_eq!(
+            storage.list_snapshots("snap").unwrap().len(),
+            2,
+            "The labelled state should be kept as a snapshot"
+        );
+    }
+
+    /// Test migrating a wallet's storage to SQLite
+    #[tokio::test]
+    async fn test_storage_migrate_command() {
+        let (config, _temp_dir) = create_test_cli_config();
+        let handler = CliHandler::new(config.clone()).unwrap();
+        handler
+            .execute(Commands::Create {
+                name: "moved".to_string(),
+                mnemonic: None,
+                words: 12,
+                mnemonic_passphrase: None,
+                no_passphrase: true,
+                account: 0,
+            })
+            .await
+            .unwrap();
+
+        let migrate = |to| Commands::Storage {
+            command: StorageCommands::Migrate {
+                wallet: "moved".to_string(),
+                to,
+            },
+        };
+        handler.execute(migrate(BackendArg::Sqlite)).await.unwrap();
+        assert!(config.wallet_dir().join("moved/wallets.sqlite").exists());
+        assert!(handler.execute(migrate(BackendArg::Sqlite)).await.is_err());
+
+        // The wallet loads from the new backend
+        handler
+            .execute(Commands::Info {
+                wallet: "moved".to_string(),
+            })
+            .await
+            .unwrap();
+        handler.execute(migrate(BackendArg::Sled)).await.unwrap();
+    }
+
+    /// Seed words and secrets can come from the environment instead of argv
+    #[test]
+    fn test_secret_arguments_read_the_environment() {
+        use clap::CommandFactory;
+
+        let command = crate::cli::CliCommands::command();
+        let create = command.find_subcommand("create").unwrap();
+        for (id, env) in [
+            ("mnemonic", "ARMORY_MNEMONIC"),
+            ("mnemonic_passphrase", "ARMORY_MNEMONIC_PASSPHRASE"),
+        ] {
+            let argument = create
+                .get_arguments()
+                .find(|argument| argument.get_id() == id)
+                .unwrap();
+            assert_eq!(argument.get_env(), Some(std::ffi::OsStr::new(env)));
+            assert!(argument.is_hide_env_values_set());
+        }
+    }
+
     /// Test address type conversion
     #[test]
     fn test_address_type_conversion() {
@@ -1,5 +1,5 @@
 use crate::crypto::{
-    DEFAULT_ARGON2_MEMORY_COST, DEFAULT_ARGON2_PARALLELISM, DEFAULT_ARGON2_TIME_COST,
+    DEFAULT_ARGON2_MEMORY_COST, DEFAULT_ARGON2_PARALLELISM, DEFAULT_ARGON2_TIME_COST, SALT_SIZE,
 };
 /// Key Derivation Functions
 ///
@@ -13,9 +13,10 @@ use crate::crypto::{
 /// - Well-audited implementation
 use crate::error::{CryptoError, CryptoResult};
 use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Deserialize, Serialize};
 
 /// Parameters for Argon2id key derivation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct KdfParams {
     /// Memory cost in KiB (default: 64 MiB)
     pub memory_cost: u32,
@@ -93,6 +94,7 @@ impl KdfParams {
 /// let params = KdfParams::for_wallet_encryption();
 ///
 /// let derived_key = derive_key_from_password(password, salt, &params)?;
+/// # Ok::<(), armory_rust::error::CryptoError>(())
 /// ```
 pub fn derive_key_from_password(
     password: &str,
@@ -182,10 +184,50 @@ pub fn compute_optimal_params(target_time_ms: u32, max_memory_mb: u32) -> KdfPar
     params
 }
 
+/// Upper bound of the time cost chosen by `tune_params`
+pub const MAX_TUNED_TIME_COST: u32 = 16;
+
+/// Tune KDF parameters by benchmarking Argon2id on this machine
+///
+/// Uses the wallet-encryption memory cost, capped at `max_memory_mb`, and
+/// raises the time cost until one derivation takes about `target_ms`. The
+/// time cost never drops below the wallet-encryption default.
+pub fn tune_params(target_ms: u32, max_memory_mb: u32) -> CryptoResult<KdfParams> {
+    let mut params = KdfParams::for_wallet_encryption();
+    params.memory_cost = params.memory_cost.min(max_memory_mb.saturating_mul(1024));
+    let minimum_time_cost = params.time_cost;
+
+    // Time a single pass; Argon2 time scales linearly with the pass count
+    params.time_cost = 1;
+    let started = std::time::Instant::now();
+    derive_key_from_password("kdf benchmark", &[0u8; SALT_SIZE], &params)?;
+    let pass_ms = started.elapsed().as_millis().max(1);
+
+    let passes = u128::from(target_ms) / pass_ms;
+    params.time_cost = u32::try_from(passes)
+        .unwrap_or(u32::MAX)
+        .clamp(minimum_time_cost, MAX_TUNED_TIME_COST);
+    Ok(params)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_tune_params_bounds() {
+        let params = tune_params(0, 16).unwrap();
+        assert_eq!(params.memory_cost, 16 * 1024);
+        assert_eq!(
+            params.time_cost,
+            KdfParams::for_wallet_encryption().time_cost
+        );
+
+        let params = tune_params(u32::MAX, 1).unwrap();
+        assert_eq!(params.memory_cost, 1024);
+        assert_eq!(params.time_cost, MAX_TUNED_TIME_COST);
+    }
+
     #[test]
     fn test_key_derivation() {
         let password = "test_password";
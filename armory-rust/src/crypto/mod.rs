@@ -18,6 +18,9 @@ pub const DEFAULT_ARGON2_MEMORY_COST: u32 = 65536; // 64 MiB
 pub const DEFAULT_ARGON2_TIME_COST: u32 = 3; // 3 iterations
 pub const DEFAULT_ARGON2_PARALLELISM: u32 = 4; // 4 threads
 
+/// Target duration of one passphrase derivation when tuning KDF parameters
+pub const DEFAULT_KDF_TARGET_MS: u32 = 1000;
+
 /// Salt size for key derivation functions
 pub const SALT_SIZE: usize = 32;
 
@@ -29,6 +32,6 @@ pub use encryption::{
     decrypt_data, decrypt_with_password, encrypt_data, encrypt_with_password, EncryptedData,
     SecureKey,
 };
-pub use kdf::derive_key_from_password;
+pub use kdf::{derive_key_from_password, tune_params, KdfParams};
 pub use random::{generate_random_bytes, generate_salt};
 pub use signatures::{sign_ecdsa, sign_schnorr, verify_ecdsa, verify_schnorr};
@@ -29,6 +29,7 @@ use getrandom::getrandom;
 ///
 /// let random_data = generate_random_bytes(32)?;
 /// assert_eq!(random_data.len(), 32);
+/// # Ok::<(), armory_rust::error::CryptoError>(())
 /// ```
 pub fn generate_random_bytes(length: usize) -> CryptoResult<Vec<u8>> {
     let mut buffer = vec![0u8; length];
@@ -93,11 +94,12 @@ pub fn generate_private_key_bytes() -> CryptoResult<[u8; 32]> {
 ///
 /// # Example
 /// ```rust
-/// use armory_rust::crypto::fill_random;
+/// use armory_rust::crypto::random::fill_random;
 ///
 /// let mut buffer = [0u8; 16];
 /// fill_random(&mut buffer)?;
 /// // buffer now contains 16 random bytes
+/// # Ok::<(), armory_rust::error::CryptoError>(())
 /// ```
 pub fn fill_random(buffer: &mut [u8]) -> CryptoResult<()> {
     getrandom(buffer).map_err(|_| CryptoError::RandomGeneration)?;
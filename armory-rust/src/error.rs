@@ -26,6 +26,9 @@ pub enum WalletError {
     #[error("Transaction error: {0}")]
     Transaction(#[from] TransactionError),
 
+    #[error("Spending policy violation: {0}")]
+    Policy(#[from] PolicyError),
+
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -38,6 +41,21 @@ pub enum WalletError {
     #[error("Address generation failed")]
     AddressGeneration,
 
+    #[error("Invalid wallet passphrase")]
+    InvalidPassphrase,
+
+    #[error("Wallet is watch-only and holds no private keys")]
+    WatchOnly,
+
+    #[error("Wallet is locked; unlock it with the passphrase first")]
+    Locked,
+
+    #[error("{0} change addresses are still unused; sync the wallet before creating more")]
+    ChangeGapExhausted(u32),
+
+    #[error("Account {0} has no history yet; BIP-44 recovery stops at the first unused account")]
+    UnusedAccount(u32),
+
     #[error("Feature not yet implemented: {0}")]
     NotImplemented(String),
 
@@ -118,6 +136,18 @@ pub enum StorageError {
 
     #[error("Atomic update failed: {0}")]
     AtomicUpdateFailed(String),
+
+    #[error("Wallet record version {found} is newer than supported version {supported}")]
+    UnsupportedVersion { found: u32, supported: u32 },
+
+    #[error("Storage is locked; unlock it with the wallet passphrase")]
+    Locked,
+
+    #[error("Wallet data could not be decrypted: wrong passphrase or tampered record")]
+    DecryptionFailed,
+
+    #[error("Invalid backup file: {0}")]
+    InvalidBackup(String),
 }
 
 /// Transaction processing errors
@@ -157,6 +187,39 @@ pub enum TransactionError {
     HardwareWallet(String),
 }
 
+/// Spending policy violations
+#[derive(Debug, Error)]
+pub enum PolicyError {
+    #[error("Spending {amount} sats exceeds the per-transaction limit of {limit} sats")]
+    TransactionLimit { amount: u64, limit: u64 },
+
+    #[error("Spending {amount} sats exceeds the daily limit of {limit} sats ({spent} sats already spent)")]
+    DailyLimit { amount: u64, spent: u64, limit: u64 },
+
+    #[error("Destination {destination} is not on the allowed list")]
+    DestinationNotAllowed { destination: String },
+
+    #[error("Fee rate of {fee_rate:.1} sat/vB exceeds the maximum of {limit} sat/vB")]
+    FeeRate { fee_rate: f64, limit: f64 },
+
+    #[error(
+        "Fee of {fee} sats is {percent:.2}% of the amount sent, above the maximum of {limit}%"
+    )]
+    FeePercentage { fee: u64, percent: f64, limit: f64 },
+
+    #[error("Output {index} claims a wallet key but pays an unknown script")]
+    UnknownChange { index: usize },
+
+    #[error("Value of input {index} is unknown")]
+    UnknownInputValue { index: usize },
+
+    #[error("Input {index} does not match its previous output: {reason}")]
+    InputMismatch { index: usize, reason: String },
+
+    #[error("Spending {amount} sats needs approval above {threshold} sats")]
+    ApprovalRequired { amount: u64, threshold: u64 },
+}
+
 /// Result type for all wallet operations
 pub type WalletResult<T> = Result<T, WalletError>;
 
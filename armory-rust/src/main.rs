@@ -18,7 +18,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Create configuration
-    let config = CliConfig::with_options(cli.data_dir.clone(), cli.network.into(), cli.verbose)?;
+    let config = CliConfig::with_options(cli.data_dir.clone(), cli.network.into(), cli.verbose)?
+        .with_passphrase(cli.passphrase.clone());
 
     if config.verbose {
         println!("🦀 Armory Rust Bitcoin Wallet v1.0.0");
@@ -321,6 +321,19 @@ impl RpcClient {
         .await
     }
 
+    /// Get the balance changes (one per transaction input/output) for an address
+    pub async fn get_address_deltas(
+        &mut self,
+        address: &Address,
+    ) -> NetworkResult<Vec<AddressDelta>> {
+        // Note: This requires Bitcoin Core with address indexing enabled
+        self.call_with_failover(
+            "getaddressdeltas",
+            json!([{"addresses": [address.to_string()]}]),
+        )
+        .await
+    }
+
     /// Check if endpoint is reachable
     pub async fn ping(&mut self) -> NetworkResult<()> {
         let _: Option<Value> = self.call_with_failover("ping", json!([])).await?;
@@ -392,6 +405,16 @@ pub struct UtxoInfo {
     pub height: u32,
 }
 
+/// Address balance change caused by a single transaction input or output
+#[derive(Debug, Deserialize)]
+pub struct AddressDelta {
+    pub address: String,
+    pub txid: String,
+    pub index: u32,
+    pub satoshis: i64,
+    pub height: u32,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
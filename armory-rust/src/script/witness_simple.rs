@@ -3,10 +3,13 @@
 //! This is a minimal implementation to enable compilation and basic testing.
 //! Full witness generation will be implemented in later phases.
 
-use crate::error::TransactionResult;
+use crate::error::{TransactionError, TransactionResult};
 use bitcoin::{
-    secp256k1::{All, PublicKey, Secp256k1, SecretKey},
-    ScriptBuf, Transaction, TxOut, Witness,
+    hashes::Hash,
+    key::{Keypair, TapTweak},
+    secp256k1::{All, Message, PublicKey, Secp256k1, SecretKey},
+    sighash::{Prevouts, SighashCache, TapSighashType},
+    taproot, ScriptBuf, Transaction, TxOut, Witness,
 };
 
 /// Witness type for different script types
@@ -50,11 +53,11 @@ impl WitnessGenerator {
     /// This is a simplified implementation for testing
     pub fn generate_witness(
         &self,
-        _transaction: &Transaction,
-        _input_index: usize,
-        _prevout: &TxOut,
+        transaction: &Transaction,
+        input_index: usize,
+        prevout: &TxOut,
         witness_type: &WitnessType,
-        _signing_data: &SigningData,
+        signing_data: &SigningData,
     ) -> TransactionResult<Witness> {
         match witness_type {
             WitnessType::Legacy => {
@@ -69,10 +72,7 @@ impl WitnessGenerator {
                 Ok(witness)
             }
             WitnessType::TaprootKeyPath => {
-                // Simplified Taproot key-path witness
-                let mut witness = Witness::new();
-                witness.push([0u8; 64]); // Placeholder Schnorr signature
-                Ok(witness)
+                self.generate_taproot_key_witness(transaction, input_index, prevout, signing_data)
             }
             WitnessType::TaprootScriptPath => {
                 // Simplified Taproot script-path witness
@@ -111,17 +111,67 @@ impl WitnessGenerator {
     }
 
     /// Generate witness for Taproot key-path spending
+    ///
+    /// Only usable for single-input transactions, as the BIP-341 sighash
+    /// commits to every spent output; see [`Self::sign_taproot_key_path`].
     pub fn generate_taproot_key_witness(
         &self,
-        _transaction: &Transaction,
-        _input_index: usize,
-        _prevout: &TxOut,
-        _signing_data: &SigningData,
+        transaction: &Transaction,
+        input_index: usize,
+        prevout: &TxOut,
+        signing_data: &SigningData,
     ) -> TransactionResult<Witness> {
-        // Simplified implementation
-        let mut witness = Witness::new();
-        witness.push([0u8; 64]); // Placeholder Schnorr signature
-        Ok(witness)
+        self.sign_taproot_key_path(
+            transaction,
+            input_index,
+            std::slice::from_ref(prevout),
+            signing_data,
+        )
+    }
+
+    /// Sign a BIP-86 Taproot input through the key path
+    ///
+    /// `signing_data.private_key` is the untweaked internal key; it is tweaked
+    /// with an empty script tree before signing with `SIGHASH_DEFAULT`.
+    /// `prevouts` must hold the outputs spent by every input, in input order.
+    pub fn sign_taproot_key_path(
+        &self,
+        transaction: &Transaction,
+        input_index: usize,
+        prevouts: &[TxOut],
+        signing_data: &SigningData,
+    ) -> TransactionResult<Witness> {
+        let keypair = Keypair::from_secret_key(&self.secp, &signing_data.private_key);
+        let prevout = prevouts.get(input_index).ok_or_else(|| {
+            TransactionError::SigningFailed(format!("No prevout for input {input_index}"))
+        })?;
+
+        // Refuse to sign for an output that does not commit to our tweaked key
+        let (internal_key, _) = keypair.x_only_public_key();
+        if prevout.script_pubkey != ScriptBuf::new_p2tr(&self.secp, internal_key, None) {
+            return Err(TransactionError::ScriptValidation(
+                "Prevout is not a BIP-86 output for the signing key".to_string(),
+            ));
+        }
+
+        let sighash = SighashCache::new(transaction)
+            .taproot_key_spend_signature_hash(
+                input_index,
+                &Prevouts::All(prevouts),
+                TapSighashType::Default,
+            )
+            .map_err(|e| {
+                TransactionError::SigningFailed(format!("Taproot sighash calculation failed: {e}"))
+            })?;
+
+        let tweaked = keypair.tap_tweak(&self.secp, None);
+        let message = Message::from_digest(sighash.to_byte_array());
+        let signature = taproot::Signature {
+            signature: self.secp.sign_schnorr(&message, &tweaked.to_keypair()),
+            sighash_type: TapSighashType::Default,
+        };
+
+        Ok(Witness::p2tr_key_spend(&signature))
     }
 
     /// Generate witness for Taproot script-path spending
@@ -151,7 +201,26 @@ impl Default for WitnessGenerator {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bitcoin::{Amount, Network, PrivateKey};
+    use bitcoin::{
+        transaction::Version, Amount, Network, OutPoint, PrivateKey, Sequence, TxIn, Txid,
+    };
+
+    fn taproot_spend(prevout: &TxOut) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: prevout.value - Amount::from_sat(500),
+                script_pubkey: prevout.script_pubkey.clone(),
+            }],
+        }
+    }
 
     #[test]
     fn test_witness_generator_creation() {
@@ -211,4 +280,79 @@ mod tests {
 
         assert_eq!(witness.len(), 2); // signature + pubkey
     }
+
+    #[test]
+    fn test_taproot_key_path_signature_uses_bip86_tweak() {
+        let secp = Secp256k1::new();
+        let generator = WitnessGenerator::new();
+        let private_key = PrivateKey::generate(Network::Regtest);
+        let signing_data = SigningData {
+            private_key: private_key.inner,
+            public_key: private_key.public_key(&secp).inner,
+            script: None,
+        };
+
+        let (internal_key, _) = signing_data.public_key.x_only_public_key();
+        let prevout = TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: ScriptBuf::new_p2tr(&secp, internal_key, None),
+        };
+        let transaction = taproot_spend(&prevout);
+
+        let witness = generator
+            .generate_witness(
+                &transaction,
+                0,
+                &prevout,
+                &WitnessType::TaprootKeyPath,
+                &signing_data,
+            )
+            .unwrap();
+        assert_eq!(witness.len(), 1);
+
+        // The signature must verify against the tweaked output key
+        let signature = taproot::Signature::from_slice(&witness[0]).unwrap();
+        assert_eq!(signature.sighash_type, TapSighashType::Default);
+        let sighash = SighashCache::new(&transaction)
+            .taproot_key_spend_signature_hash(
+                0,
+                &Prevouts::All(std::slice::from_ref(&prevout)),
+                TapSighashType::Default,
+            )
+            .unwrap();
+        let (output_key, _) = internal_key.tap_tweak(&secp, None);
+        assert!(secp
+            .verify_schnorr(
+                &signature.signature,
+                &Message::from_digest(sighash.to_byte_array()),
+                &output_key.to_x_only_public_key(),
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_taproot_key_path_rejects_untweaked_output() {
+        let secp = Secp256k1::new();
+        let private_key = PrivateKey::generate(Network::Regtest);
+        let signing_data = SigningData {
+            private_key: private_key.inner,
+            public_key: private_key.public_key(&secp).inner,
+            script: None,
+        };
+
+        // Output key used directly, without the BIP-86 tweak
+        let (internal_key, _) = signing_data.public_key.x_only_public_key();
+        let prevout = TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(internal_key.dangerous_assume_tweaked()),
+        };
+
+        let result = WitnessGenerator::new().generate_taproot_key_witness(
+            &taproot_spend(&prevout),
+            0,
+            &prevout,
+            &signing_data,
+        );
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,656 @@
+use crate::error::{StorageError, StorageResult};
+/// Storage backends
+///
+/// `WalletStorage` keeps its encrypted records in a key-value store behind
+/// the `StorageBackend` trait. Sled was the original store; SQLite is the
+/// format operations tooling can inspect, and the in-memory backend keeps
+/// tests off the disk. A storage directory holds either `wallets.db` (sled)
+/// or `wallets.sqlite`, and the backend is picked from what is on disk.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+/// Database directory of the sled backend, inside the storage directory
+pub const SLED_FILE: &str = "wallets.db";
+
+/// Database file of the SQLite backend, inside the storage directory
+pub const SQLITE_FILE: &str = "wallets.sqlite";
+
+/// Key-value operations wallet storage needs from a database
+pub trait StorageBackend: Send + Sync {
+    /// Value stored under `key`
+    fn get(&self, key: &[u8]) -> StorageResult<Option<Vec<u8>>>;
+
+    /// Store `value` under `key`, replacing any previous value
+    fn put(&self, key: &[u8], value: &[u8]) -> StorageResult<()>;
+
+    /// Remove the value under `key`, if any
+    fn remove(&self, key: &[u8]) -> StorageResult<()>;
+
+    /// Entries whose key starts with `prefix`, in key order
+    fn scan_prefix(&self, prefix: &[u8]) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>>;
+
+    /// Apply every write of `batch`, or none of them
+    fn transaction(&self, batch: Batch) -> StorageResult<()>;
+
+    /// Replace the whole contents with `batch`, leaving no trace of the
+    /// previous values on disk
+    fn replace_all(&mut self, batch: Batch) -> StorageResult<()>;
+
+    /// Wait until earlier writes are durable
+    fn flush(&self) -> StorageResult<()>;
+
+    /// Close the database, releasing its files so they can be moved
+    ///
+    /// Every later operation on a closed database fails.
+    fn close(&mut self) -> StorageResult<()>;
+
+    /// Bytes used on disk
+    fn size_on_disk(&self) -> StorageResult<u64>;
+
+    /// Whether a value is stored under `key`
+    fn contains(&self, key: &[u8]) -> StorageResult<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+}
+
+/// Writes applied together by `StorageBackend::transaction`
+#[derive(Debug, Clone, Default)]
+pub struct Batch {
+    writes: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl Batch {
+    /// Store `value` under `key`
+    pub fn insert(&mut self, key: impl Into<Vec<u8>>, value: impl Into<Vec<u8>>) {
+        self.writes.push((key.into(), Some(value.into())));
+    }
+
+    /// Remove the value under `key`
+    pub fn remove(&mut self, key: impl Into<Vec<u8>>) {
+        self.writes.push((key.into(), None));
+    }
+}
+
+/// Available storage backends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    /// Sled database directory
+    Sled,
+    /// SQLite database file
+    Sqlite,
+    /// Process memory, lost when the storage is dropped
+    Memory,
+}
+
+impl BackendKind {
+    /// Backend of an existing storage directory; sled when there is none yet
+    pub fn detect(storage_path: &Path) -> Self {
+        if storage_path.join(SQLITE_FILE).exists() {
+            BackendKind::Sqlite
+        } else {
+            BackendKind::Sled
+        }
+    }
+
+    /// Database path of the backend inside a storage directory
+    pub fn path(self, storage_path: &Path) -> Option<PathBuf> {
+        match self {
+            BackendKind::Sled => Some(storage_path.join(SLED_FILE)),
+            BackendKind::Sqlite => Some(storage_path.join(SQLITE_FILE)),
+            BackendKind::Memory => None,
+        }
+    }
+
+    /// Open the backend's database at `path` (ignored for memory)
+    pub fn open(self, path: &Path) -> StorageResult<Box<dyn StorageBackend>> {
+        Ok(match self {
+            BackendKind::Sled => Box::new(SledBackend::open(path)?),
+            BackendKind::Sqlite => Box::new(SqliteBackend::open(path)?),
+            BackendKind::Memory => Box::new(MemoryBackend::default()),
+        })
+    }
+}
+
+impl std::fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BackendKind::Sled => "sled",
+            BackendKind::Sqlite => "sqlite",
+            BackendKind::Memory => "memory",
+        })
+    }
+}
+
+/// Sled backend
+pub struct SledBackend {
+    db: Option<sled::Db>,
+    path: PathBuf,
+}
+
+impl SledBackend {
+    /// Open or create the sled database at `path`
+    ///
+    /// Finishes a `replace_all` interrupted by a crash first.
+    pub fn open(path: &Path) -> StorageResult<Self> {
+        finish_replacement(path)?;
+        Ok(Self {
+            db: Some(open_db(path)?),
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// The open database, or an error once it was closed
+    fn db(&self) -> StorageResult<&sled::Db> {
+        self.db
+            .as_ref()
+            .ok_or_else(|| StorageError::Database("Database is closed".to_string()))
+    }
+}
+
+impl Drop for SledBackend {
+    fn drop(&mut self) {
+        // A drop cannot report errors
+        let _ = self.close();
+    }
+}
+
+impl StorageBackend for SledBackend {
+    fn get(&self, key: &[u8]) -> StorageResult<Option<Vec<u8>>> {
+        let value = self.db()?.get(key).map_err(database)?;
+        Ok(value.map(|value| value.to_vec()))
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> StorageResult<()> {
+        self.db()?.insert(key, value).map_err(database)?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &[u8]) -> StorageResult<()> {
+        self.db()?.remove(key).map_err(database)?;
+        Ok(())
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.db()?
+            .scan_prefix(prefix)
+            .map(|entry| {
+                let (key, value) = entry.map_err(database)?;
+                Ok((key.to_vec(), value.to_vec()))
+            })
+            .collect()
+    }
+
+    fn transaction(&self, batch: Batch) -> StorageResult<()> {
+        self.db()?
+            .apply_batch(sled_batch(batch))
+            .map_err(|e| StorageError::AtomicUpdateFailed(e.to_string()))
+    }
+
+    /// Sled keeps overwritten values in its log, so the new contents are
+    /// written to a fresh database next to the old one, which is swapped in
+    /// by renaming; `open` finishes a swap interrupted by a crash.
+    fn replace_all(&mut self, batch: Batch) -> StorageResult<()> {
+        let (staging, retired) = replacement_paths(&self.path);
+        if staging.exists() {
+            std::fs::remove_dir_all(&staging)?;
+        }
+
+        let fresh = open_db(&staging)?;
+        fresh
+            .apply_batch(sled_batch(batch))
+            .map_err(|e| StorageError::AtomicUpdateFailed(e.to_string()))?;
+        close_db(fresh, &staging)?;
+
+        self.close()?;
+        std::fs::rename(&self.path, &retired)?;
+        std::fs::rename(&staging, &self.path)?;
+        self.db = Some(open_db(&self.path)?);
+        finish_replacement(&self.path)
+    }
+
+    fn flush(&self) -> StorageResult<()> {
+        self.db()?.flush().map_err(database)?;
+        Ok(())
+    }
+
+    fn close(&mut self) -> StorageResult<()> {
+        match self.db.take() {
+            Some(db) => close_db(db, &self.path),
+            None => Ok(()),
+        }
+    }
+
+    fn size_on_disk(&self) -> StorageResult<u64> {
+        self.db()?.size_on_disk().map_err(database)
+    }
+
+    fn contains(&self, key: &[u8]) -> StorageResult<bool> {
+        self.db()?.contains_key(key).map_err(database)
+    }
+}
+
+/// SQLite backend
+///
+/// Records live in one `records (key BLOB PRIMARY KEY, value BLOB)` table.
+/// `secure_delete` is on, so SQLite zeroes the space of overwritten values.
+pub struct SqliteBackend {
+    connection: Mutex<Option<rusqlite::Connection>>,
+    path: PathBuf,
+}
+
+impl SqliteBackend {
+    /// Open or create the SQLite database at `path`
+    pub fn open(path: &Path) -> StorageResult<Self> {
+        let connection = rusqlite::Connection::open(path).map_err(database)?;
+        connection
+            .execute_batch(
+                "PRAGMA secure_delete = ON;
+                 CREATE TABLE IF NOT EXISTS records (
+                     key BLOB PRIMARY KEY NOT NULL,
+                     value BLOB NOT NULL
+                 ) WITHOUT ROWID;",
+            )
+            .map_err(database)?;
+        Ok(Self {
+            connection: Mutex::new(Some(connection)),
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn connection(&self) -> StorageResult<OpenConnection<'_>> {
+        let guard = self
+            .connection
+            .lock()
+            .map_err(|_| StorageError::Database("SQLite connection poisoned".to_string()))?;
+        if guard.is_none() {
+            return Err(StorageError::Database("Database is closed".to_string()));
+        }
+        Ok(OpenConnection(guard))
+    }
+}
+
+/// Locked connection of a `SqliteBackend` that has not been closed
+struct OpenConnection<'a>(std::sync::MutexGuard<'a, Option<rusqlite::Connection>>);
+
+impl std::ops::Deref for OpenConnection<'_> {
+    type Target = rusqlite::Connection;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref().expect("checked when locked")
+    }
+}
+
+impl std::ops::DerefMut for OpenConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.as_mut().expect("checked when locked")
+    }
+}
+
+impl StorageBackend for SqliteBackend {
+    fn get(&self, key: &[u8]) -> StorageResult<Option<Vec<u8>>> {
+        use rusqlite::OptionalExtension;
+
+        self.connection()?
+            .query_row("SELECT value FROM records WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(database)
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> StorageResult<()> {
+        self.connection()?
+            .execute(
+                "INSERT OR REPLACE INTO records (key, value) VALUES (?1, ?2)",
+                [key, value],
+            )
+            .map_err(database)?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &[u8]) -> StorageResult<()> {
+        self.connection()?
+            .execute("DELETE FROM records WHERE key = ?1", [key])
+            .map_err(database)?;
+        Ok(())
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let connection = self.connection()?;
+        // Keys compare bytewise, so a prefix is a half-open key range
+        let (sql, end) = match prefix_end(prefix) {
+            Some(end) => (
+                "SELECT key, value FROM records WHERE key >= ?1 AND key < ?2 ORDER BY key",
+                end,
+            ),
+            None => (
+                "SELECT key, value FROM records WHERE key >= ?1 AND ?2 IS NOT NULL ORDER BY key",
+                Vec::new(),
+            ),
+        };
+        let mut statement = connection.prepare(sql).map_err(database)?;
+        let rows = statement
+            .query_map([prefix, end.as_slice()], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .map_err(database)?;
+        rows.map(|row| row.map_err(database)).collect()
+    }
+
+    fn transaction(&self, batch: Batch) -> StorageResult<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction().map_err(atomic)?;
+        apply_sql(&transaction, batch).map_err(atomic)?;
+        transaction.commit().map_err(atomic)
+    }
+
+    fn replace_all(&mut self, batch: Batch) -> StorageResult<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction().map_err(atomic)?;
+        transaction
+            .execute("DELETE FROM records", [])
+            .map_err(atomic)?;
+        apply_sql(&transaction, batch).map_err(atomic)?;
+        transaction.commit().map_err(atomic)?;
+        // Also drop the freed pages from the file
+        connection.execute_batch("VACUUM").map_err(database)
+    }
+
+    /// Commits are durable once they return
+    fn flush(&self) -> StorageResult<()> {
+        Ok(())
+    }
+
+    fn close(&mut self) -> StorageResult<()> {
+        let connection = self
+            .connection
+            .get_mut()
+            .map_err(|_| StorageError::Database("SQLite connection poisoned".to_string()))?
+            .take();
+        match connection {
+            Some(connection) => connection.close().map_err(|(_, e)| database(e)),
+            None => Ok(()),
+        }
+    }
+
+    fn size_on_disk(&self) -> StorageResult<u64> {
+        Ok(std::fs::metadata(&self.path)?.len())
+    }
+}
+
+/// In-memory backend, mostly for tests
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    entries: RwLock<BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl MemoryBackend {
+    fn entries(&self) -> StorageResult<std::sync::RwLockReadGuard<'_, BTreeMap<Vec<u8>, Vec<u8>>>> {
+        self.entries
+            .read()
+            .map_err(|_| StorageError::Database("Memory backend poisoned".to_string()))
+    }
+
+    fn entries_mut(
+        &self,
+    ) -> StorageResult<std::sync::RwLockWriteGuard<'_, BTreeMap<Vec<u8>, Vec<u8>>>> {
+        self.entries
+            .write()
+            .map_err(|_| StorageError::Database("Memory backend poisoned".to_string()))
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn get(&self, key: &[u8]) -> StorageResult<Option<Vec<u8>>> {
+        Ok(self.entries()?.get(key).cloned())
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> StorageResult<()> {
+        self.entries_mut()?.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self, key: &[u8]) -> StorageResult<()> {
+        self.entries_mut()?.remove(key);
+        Ok(())
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        Ok(self
+            .entries()?
+            .range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+
+    fn transaction(&self, batch: Batch) -> StorageResult<()> {
+        let mut entries = self.entries_mut()?;
+        for (key, value) in batch.writes {
+            match value {
+                Some(value) => entries.insert(key, value),
+                None => entries.remove(&key),
+            };
+        }
+        Ok(())
+    }
+
+    fn replace_all(&mut self, batch: Batch) -> StorageResult<()> {
+        self.entries_mut()?.clear();
+        self.transaction(batch)
+    }
+
+    fn flush(&self) -> StorageResult<()> {
+        Ok(())
+    }
+
+    /// There are no files to release, so the entries stay usable
+    fn close(&mut self) -> StorageResult<()> {
+        Ok(())
+    }
+
+    fn size_on_disk(&self) -> StorageResult<u64> {
+        Ok(0)
+    }
+}
+
+/// Smallest key above every key starting with `prefix`, if there is one
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+fn sled_batch(batch: Batch) -> sled::Batch {
+    let mut sled_batch = sled::Batch::default();
+    for (key, value) in batch.writes {
+        match value {
+            Some(value) => sled_batch.insert(key, value),
+            None => sled_batch.remove(key),
+        }
+    }
+    sled_batch
+}
+
+fn apply_sql(transaction: &rusqlite::Transaction<'_>, batch: Batch) -> rusqlite::Result<()> {
+    for (key, value) in batch.writes {
+        match value {
+            Some(value) => transaction.execute(
+                "INSERT OR REPLACE INTO records (key, value) VALUES (?1, ?2)",
+                [key, value],
+            )?,
+            None => transaction.execute("DELETE FROM records WHERE key = ?1", [key])?,
+        };
+    }
+    Ok(())
+}
+
+fn database(e: impl std::fmt::Display) -> StorageError {
+    StorageError::Database(e.to_string())
+}
+
+fn atomic(e: impl std::fmt::Display) -> StorageError {
+    StorageError::AtomicUpdateFailed(e.to_string())
+}
+
+/// Staging and retired paths used while replacing the database at `db_path`
+fn replacement_paths(db_path: &Path) -> (PathBuf, PathBuf) {
+    (
+        db_path.with_extension("db.staging"),
+        db_path.with_extension("db.retired"),
+    )
+}
+
+/// Finish or roll back an interrupted database replacement
+///
+/// Once the new database is in place the retired one is deleted; if the
+/// crash came between the two renames, the retired database is restored.
+/// A leftover staging database is rebuilt by the next replacement.
+fn finish_replacement(db_path: &Path) -> StorageResult<()> {
+    let (_, retired) = replacement_paths(db_path);
+    if !retired.exists() {
+        return Ok(());
+    }
+    if !db_path.exists() {
+        std::fs::rename(&retired, db_path)?;
+        return Ok(());
+    }
+    std::fs::remove_dir_all(&retired)?;
+    Ok(())
+}
+
+/// Open a sled database
+///
+/// Sled locks the database for the lifetime of its handle, so opening one
+/// that another handle still holds fails with `StorageError::Database`.
+fn open_db(path: &Path) -> StorageResult<sled::Db> {
+    sled::open(path).map_err(database)
+}
+
+/// Close a sled database and wait until sled has let go of its files
+///
+/// Sled finishes writes on background threads that keep the database
+/// locked for a moment after the last handle is dropped. Taking that lock
+/// blocks until they are done, so the database can be reopened, moved or
+/// removed as soon as this returns.
+fn close_db(db: sled::Db, path: &Path) -> StorageResult<()> {
+    db.flush().map_err(database)?;
+    drop(db);
+    let lock_file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path.join("db"))?;
+    lock_file.lock()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn exercise(backend: &mut dyn StorageBackend) {
+        backend.put(b"wallet:b", b"2").unwrap();
+        backend.put(b"wallet:a", b"1").unwrap();
+        backend.put(b"walletz", b"x").unwrap();
+        backend.put(&[0xff, 0xff], b"y").unwrap();
+        backend.put(b"wallet:a", b"one").unwrap();
+        assert_eq!(backend.get(b"wallet:a").unwrap(), Some(b"one".to_vec()));
+        assert!(backend.contains(b"wallet:b").unwrap());
+        assert_eq!(backend.get(b"missing").unwrap(), None);
+
+        let keys = |entries: Vec<(Vec<u8>, Vec<u8>)>| -> Vec<Vec<u8>> {
+            entries.into_iter().map(|(key, _)| key).collect()
+        };
+        assert_eq!(
+            keys(backend.scan_prefix(b"wallet:").unwrap()),
+            vec![b"wallet:a".to_vec(), b"wallet:b".to_vec()]
+        );
+        assert_eq!(
+            keys(backend.scan_prefix(&[0xff]).unwrap()),
+            vec![vec![0xff, 0xff]]
+        );
+        assert_eq!(backend.scan_prefix(b"").unwrap().len(), 4);
+
+        let mut batch = Batch::default();
+        batch.insert(b"wallet:c".as_slice(), b"3".as_slice());
+        batch.remove(b"wallet:b".as_slice());
+        backend.transaction(batch).unwrap();
+        backend.remove(b"walletz").unwrap();
+        backend.flush().unwrap();
+        assert_eq!(
+            keys(backend.scan_prefix(b"wallet").unwrap()),
+            vec![b"wallet:a".to_vec(), b"wallet:c".to_vec()]
+        );
+
+        let mut batch = Batch::default();
+        batch.insert(b"wallet:d".as_slice(), b"4".as_slice());
+        backend.replace_all(batch).unwrap();
+        assert_eq!(
+            backend.scan_prefix(b"").unwrap(),
+            vec![(b"wallet:d".to_vec(), b"4".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_sled_lock_conflict_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(SLED_FILE);
+        let held = SledBackend::open(&path).unwrap();
+        assert!(matches!(
+            SledBackend::open(&path),
+            Err(StorageError::Database(_))
+        ));
+        drop(held);
+
+        // A dropped handle releases the lock before the drop returns
+        for round in 0..20u8 {
+            let backend = SledBackend::open(&path).unwrap();
+            backend.put(b"round", &[round]).unwrap();
+        }
+        let backend = SledBackend::open(&path).unwrap();
+        assert_eq!(backend.get(b"round").unwrap(), Some(vec![19]));
+    }
+
+    #[test]
+    fn test_backends_behave_alike() {
+        exercise(&mut MemoryBackend::default());
+
+        let dir = TempDir::new().unwrap();
+        for kind in [BackendKind::Sled, BackendKind::Sqlite] {
+            let path = kind.path(dir.path()).unwrap();
+            exercise(kind.open(&path).unwrap().as_mut());
+            // Contents survive reopening
+            let mut reopened = kind.open(&path).unwrap();
+            assert_eq!(
+                reopened.get(b"wallet:d").unwrap(),
+                Some(b"4".to_vec()),
+                "{kind}"
+            );
+            assert!(reopened.size_on_disk().unwrap() > 0, "{kind}");
+
+            // A closed database can be moved at once but no longer used
+            reopened.close().unwrap();
+            assert!(reopened.get(b"wallet:d").is_err(), "{kind}");
+            let moved = dir.path().join(format!("moved-{kind}"));
+            std::fs::rename(&path, &moved).unwrap();
+            assert_eq!(
+                kind.open(&moved).unwrap().get(b"wallet:d").unwrap(),
+                Some(b"4".to_vec()),
+                "{kind}"
+            );
+            std::fs::rename(&moved, &path).unwrap();
+        }
+        assert_eq!(BackendKind::detect(dir.path()), BackendKind::Sqlite);
+        assert_eq!(
+            BackendKind::detect(&dir.path().join("empty")),
+            BackendKind::Sled
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use crate::crypto::{
+    decrypt_data, derive_key_from_password, encrypt_data, generate_salt, EncryptedData, KdfParams,
+    SecureKey, NONCE_SIZE,
+};
+/// Portable wallet backup files
+///
+/// A backup is a single self-describing file holding the complete wallet
+/// record, encrypted under a key derived with Argon2id from the wallet
+/// passphrase. Everything needed to decrypt it travels in the file, so it
+/// can be restored on any machine and under any wallet name.
+///
+/// Layout, integers little-endian:
+///
+/// | Size | Field |
+/// |------|-------|
+/// | 8 | Magic `ARMRYBAK` |
+/// | 2 | Format version |
+/// | 4 | Argon2id memory cost in KiB |
+/// | 4 | Argon2id time cost |
+/// | 4 | Argon2id parallelism |
+/// | 1 | Salt length |
+/// | n | Salt |
+/// | 12 | Nonce |
+/// | 8 | Ciphertext length |
+/// | m | ChaCha20Poly1305 ciphertext of the record JSON |
+/// | 32 | SHA-256 of all preceding bytes |
+///
+/// The fields from the magic to the salt are the associated data of the
+/// encryption, so they cannot be altered without failing decryption even
+/// when the checksum is recomputed.
+use crate::error::{StorageError, StorageResult};
+use crate::storage::wallet_storage::{parse_record, WalletData};
+use bitcoin::hashes::{sha256, Hash};
+use zeroize::Zeroize;
+
+/// Magic bytes opening every backup file
+pub const BACKUP_MAGIC: &[u8; 8] = b"ARMRYBAK";
+
+/// Backup format version written by this build
+pub const BACKUP_FORMAT_VERSION: u16 = 1;
+
+/// Largest Argon2id memory cost accepted from a backup file, in KiB
+///
+/// Keeps a hostile file from making the restore allocate without bound.
+const MAX_MEMORY_COST: u32 = 1024 * 1024;
+
+/// Largest Argon2id time cost accepted from a backup file
+///
+/// Well above what `tune_params` picks, but keeps a hostile file from
+/// stalling the restore.
+const MAX_TIME_COST: u32 = 64;
+
+/// Largest Argon2id parallelism accepted from a backup file
+const MAX_PARALLELISM: u32 = 64;
+
+/// Length of the integrity checksum
+const CHECKSUM_SIZE: usize = 32;
+
+/// Encrypted wallet backup
+#[derive(Debug, Clone)]
+pub struct WalletBackup {
+    /// Argon2id parameters of the backup key
+    pub kdf_params: KdfParams,
+    salt: Vec<u8>,
+    encrypted: EncryptedData,
+}
+
+impl WalletBackup {
+    /// Encrypt a wallet record under a key derived from `passphrase`
+    pub fn seal(
+        data: &WalletData,
+        passphrase: &str,
+        kdf_params: &KdfParams,
+    ) -> StorageResult<Self> {
+        let salt =
+            generate_salt().map_err(|e| StorageError::Database(format!("Salt failed: {e}")))?;
+        let key = backup_key(passphrase, &salt, kdf_params)?;
+        let header = header(kdf_params, &salt);
+
+        let mut plaintext = serde_json::to_vec(data)?;
+        let encrypted = encrypt_data(&key, &plaintext, Some(&header));
+        plaintext.zeroize();
+        Ok(Self {
+            kdf_params: kdf_params.clone(),
+            salt,
+            encrypted: encrypted
+                .map_err(|e| StorageError::Database(format!("Encryption failed: {e}")))?,
+        })
+    }
+
+    /// Decrypt the wallet record, upgrading it from older schema versions
+    ///
+    /// A wrong passphrase fails with `StorageError::DecryptionFailed`.
+    pub fn open(&self, passphrase: &str) -> StorageResult<WalletData> {
+        let key = backup_key(passphrase, &self.salt, &self.kdf_params)?;
+        let header = header(&self.kdf_params, &self.salt);
+        let mut plaintext = decrypt_data(&key, &self.encrypted, Some(&header))
+            .map_err(|_| StorageError::DecryptionFailed)?;
+        let record = parse_record(&plaintext);
+        plaintext.zeroize();
+        Ok(record?.0)
+    }
+
+    /// Encode the backup file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = header(&self.kdf_params, &self.salt);
+        bytes.extend_from_slice(&self.encrypted.nonce);
+        bytes.extend_from_slice(&(self.encrypted.ciphertext.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.encrypted.ciphertext);
+        let checksum = sha256::Hash::hash(&bytes);
+        bytes.extend_from_slice(checksum.as_byte_array());
+        bytes
+    }
+
+    /// Decode a backup file, checking its magic, version and checksum
+    pub fn from_bytes(bytes: &[u8]) -> StorageResult<Self> {
+        if bytes.len() < BACKUP_MAGIC.len() || &bytes[..BACKUP_MAGIC.len()] != BACKUP_MAGIC {
+            return Err(invalid("not an Armory wallet backup"));
+        }
+        let (body, checksum) = bytes
+            .split_at_checked(bytes.len().saturating_sub(CHECKSUM_SIZE))
+            .filter(|(body, _)| body.len() > BACKUP_MAGIC.len())
+            .ok_or_else(|| invalid("file is truncated"))?;
+        if sha256::Hash::hash(body).as_byte_array() != checksum {
+            return Err(invalid("checksum mismatch, the file is corrupted"));
+        }
+
+        let mut reader = Reader {
+            bytes: &body[BACKUP_MAGIC.len()..],
+        };
+        let version = u16::from_le_bytes(reader.array()?);
+        if version != BACKUP_FORMAT_VERSION {
+            return Err(invalid(&format!(
+                "format version {version} is not supported (expected {BACKUP_FORMAT_VERSION})"
+            )));
+        }
+        let kdf_params = KdfParams {
+            memory_cost: u32::from_le_bytes(reader.array()?),
+            time_cost: u32::from_le_bytes(reader.array()?),
+            parallelism: u32::from_le_bytes(reader.array()?),
+            output_length: 32,
+        };
+        if kdf_params.memory_cost > MAX_MEMORY_COST {
+            return Err(invalid("key derivation memory cost is too high"));
+        }
+        if kdf_params.time_cost > MAX_TIME_COST {
+            return Err(invalid("key derivation time cost is too high"));
+        }
+        if kdf_params.parallelism > MAX_PARALLELISM {
+            return Err(invalid("key derivation parallelism is too high"));
+        }
+        let [salt_len] = reader.array()?;
+        let salt = reader.take(salt_len as usize)?.to_vec();
+        let nonce = reader.take(NONCE_SIZE)?.to_vec();
+        let ciphertext_len = usize::try_from(u64::from_le_bytes(reader.array()?))
+            .map_err(|_| invalid("ciphertext length is out of range"))?;
+        let ciphertext = reader.take(ciphertext_len)?.to_vec();
+        if !reader.bytes.is_empty() {
+            return Err(invalid("unexpected trailing data"));
+        }
+
+        Ok(Self {
+            kdf_params,
+            salt,
+            encrypted: EncryptedData::new(nonce, ciphertext),
+        })
+    }
+}
+
+/// Cursor over the body of a backup file
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> StorageResult<&'a [u8]> {
+        let (taken, rest) = self
+            .bytes
+            .split_at_checked(len)
+            .ok_or_else(|| invalid("file is truncated"))?;
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> StorageResult<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("Slice of length N"))
+    }
+}
+
+/// Magic, version, KDF parameters and salt
+fn header(kdf_params: &KdfParams, salt: &[u8]) -> Vec<u8> {
+    let mut header = BACKUP_MAGIC.to_vec();
+    header.extend_from_slice(&BACKUP_FORMAT_VERSION.to_le_bytes());
+    header.extend_from_slice(&kdf_params.memory_cost.to_le_bytes());
+    header.extend_from_slice(&kdf_params.time_cost.to_le_bytes());
+    header.extend_from_slice(&kdf_params.parallelism.to_le_bytes());
+    header.push(salt.len() as u8);
+    header.extend_from_slice(salt);
+    header
+}
+
+/// Derive the backup key from a passphrase with Argon2id
+fn backup_key(passphrase: &str, salt: &[u8], params: &KdfParams) -> StorageResult<SecureKey> {
+    let mut key_bytes = derive_key_from_password(passphrase, salt, params)
+        .map_err(|e| invalid(&format!("key derivation failed: {e}")))?;
+    let key = SecureKey::from_bytes(&key_bytes);
+    key_bytes.zeroize();
+    key.map_err(|e| StorageError::Database(format!("Key derivation failed: {e}")))
+}
+
+/// Error for a malformed backup file
+fn invalid(reason: &str) -> StorageError {
+    StorageError::InvalidBackup(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{StorageConfig, WalletStorage};
+    use crate::wallet::descriptor_wallet::AddressType;
+    use crate::wallet::Wallet;
+    use crate::Network;
+    use tempfile::TempDir;
+
+    fn storage(dir: &TempDir) -> WalletStorage {
+        WalletStorage::new(StorageConfig {
+            storage_path: dir.path().to_path_buf(),
+            auto_backup: false,
+            backup_count: 1,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_backup_roundtrip_and_integrity() {
+        let dir = TempDir::new().unwrap();
+        let mut wallet = Wallet::create_with_passphrase(
+            "original".to_string(),
+            Network::Regtest,
+            "correct horse",
+            storage(&dir),
+        )
+        .unwrap();
+        let address = wallet.get_new_address(AddressType::Taproot).unwrap();
+        assert!(matches!(
+            wallet.backup("wrong"),
+            Err(crate::error::WalletError::InvalidPassphrase)
+        ));
+        let bytes = wallet.backup("correct horse").unwrap().to_bytes();
+        assert!(bytes.starts_with(BACKUP_MAGIC));
+
+        let backup = WalletBackup::from_bytes(&bytes).unwrap();
+        assert!(matches!(
+            backup.open("wrong"),
+            Err(StorageError::DecryptionFailed)
+        ));
+        let restored_dir = TempDir::new().unwrap();
+        let restored = Wallet::restore(
+            "restored".to_string(),
+            &backup,
+            "correct horse",
+            storage(&restored_dir),
+        )
+        .unwrap();
+        assert_eq!(restored.id, "restored");
+        assert!(restored.owns_address(&address).is_some());
+        assert_eq!(
+            restored.account_xpub(AddressType::Taproot, 0).unwrap(),
+            wallet.account_xpub(AddressType::Taproot, 0).unwrap()
+        );
+
+        // Any flipped bit fails the checksum
+        for index in [0, 10, bytes.len() / 2, bytes.len() - 1] {
+            let mut corrupted = bytes.clone();
+            corrupted[index] ^= 1;
+            assert!(matches!(
+                WalletBackup::from_bytes(&corrupted),
+                Err(StorageError::InvalidBackup(_))
+            ));
+        }
+        assert!(WalletBackup::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(WalletBackup::from_bytes(BACKUP_MAGIC).is_err());
+
+        // A header edited with the checksum recomputed fails decryption
+        let tamper = |offset: usize, field: &[u8]| {
+            let mut tampered = bytes[..bytes.len() - CHECKSUM_SIZE].to_vec();
+            tampered[offset..offset + field.len()].copy_from_slice(field);
+            let checksum = sha256::Hash::hash(&tampered);
+            tampered.extend_from_slice(checksum.as_byte_array());
+            tampered
+        };
+        assert!(matches!(
+            WalletBackup::from_bytes(&tamper(14, &[bytes[14] ^ 1]))
+                .unwrap()
+                .open("correct horse"),
+            Err(StorageError::DecryptionFailed)
+        ));
+
+        // Key derivation costs beyond the limits are refused before deriving
+        for (offset, limit) in [
+            (10, MAX_MEMORY_COST),
+            (14, MAX_TIME_COST),
+            (18, MAX_PARALLELISM),
+        ] {
+            assert!(WalletBackup::from_bytes(&tamper(offset, &limit.to_le_bytes())).is_ok());
+            assert!(matches!(
+                WalletBackup::from_bytes(&tamper(offset, &(limit + 1).to_le_bytes())),
+                Err(StorageError::InvalidBackup(_))
+            ));
+        }
+    }
+}
@@ -14,7 +14,7 @@
 /// - Crypto/KDF info (512 bytes): Encryption parameters
 /// - Key generator (237 bytes): Root key information
 use crate::error::{StorageError, StorageResult};
-use crate::storage::wallet_storage::{WalletData, WalletStorage};
+use crate::storage::wallet_storage::{WalletData, WalletStorage, WALLET_SCHEMA_VERSION};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
@@ -60,7 +60,7 @@ pub struct ImportResult {
 /// # Arguments
 /// * `file_path` - Path to the legacy .wallet file
 /// * `passphrase` - Optional passphrase for encrypted wallets
-/// * `storage` - Storage instance to save the converted wallet
+/// * `storage` - Unlocked storage instance to save the converted wallet
 ///
 /// # Returns
 /// Import result with statistics and any warnings
@@ -397,15 +397,30 @@ fn convert_to_modern_format(
             header.long_name.clone()
         },
         encrypted_seed,
+        kdf_salt: Vec::new(),
+        kdf_params: Default::default(),
+        network: None,
+        watch_only: false,
+        chain_indices: Vec::new(),
+        accounts: Vec::new(),
+        utxos: Vec::new(),
+        utxo_metadata: Vec::new(),
         descriptors,
         address_book: entries.addr_comments.clone(),
         tx_comments: entries.tx_comments.clone(),
+        transactions: Vec::new(),
+        tip_height: None,
+        keychains: Vec::new(),
+        labels: Vec::new(),
+        policy: None,
+        policy_spends: Vec::new(),
+        silent_payments: Vec::new(),
         created_at: header.create_date,
         modified_at: std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_secs(),
-        version: 1,
+        version: WALLET_SCHEMA_VERSION,
     })
 }
 
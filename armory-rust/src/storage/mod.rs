@@ -1,4 +1,7 @@
+pub mod backend;
+pub mod backup;
 pub mod legacy_import;
+pub mod snapshot;
 /// Storage layer module
 ///
 /// This module provides encrypted storage functionality to replace
@@ -6,5 +9,8 @@ pub mod legacy_import;
 pub mod wallet_storage;
 
 // Re-exports for convenience
+pub use backend::{BackendKind, StorageBackend};
+pub use backup::WalletBackup;
 pub use legacy_import::import_armory_wallet;
+pub use snapshot::{Snapshot, SnapshotKind};
 pub use wallet_storage::{StorageConfig, WalletStorage};
@@ -0,0 +1,153 @@
+use crate::error::{StorageError, StorageResult};
+/// Wallet snapshots
+///
+/// Every save with automatic backups enabled leaves a timestamped copy of
+/// the encrypted wallet record in `backups/`, and every schema migration a
+/// pre-migration copy. This module names those files and compares the
+/// records they hold, so a wallet can be rolled back to an earlier state
+/// after an accidental change or a bad migration.
+use crate::storage::wallet_storage::WalletData;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Record fields left out of diffs because every save changes them
+const IGNORED_FIELDS: &[&str] = &["modified_at"];
+
+/// How a snapshot was taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotKind {
+    /// Written on save when automatic backups are enabled
+    Automatic,
+    /// Written before the record was migrated from `version`
+    PreMigration { version: u32 },
+}
+
+/// Snapshot of a wallet record in the backup directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Wallet the snapshot belongs to
+    pub wallet_id: String,
+    /// Unix time the snapshot was taken
+    pub timestamp: u64,
+    /// How the snapshot was taken
+    pub kind: SnapshotKind,
+    /// Snapshot file
+    pub path: PathBuf,
+}
+
+impl Snapshot {
+    /// Read a snapshot's wallet id, time and kind from its file name
+    ///
+    /// Automatic snapshots are named `<id>_<timestamp>.backup`, pre-migration
+    /// ones `<id>_v<version>_<timestamp>.premigration`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        let (stem, premigration) = match file_name.strip_suffix(".backup") {
+            Some(stem) => (stem, false),
+            None => (file_name.strip_suffix(".premigration")?, true),
+        };
+        let (prefix, timestamp) = stem.rsplit_once('_')?;
+        let timestamp = timestamp.parse().ok()?;
+        let (wallet_id, kind) = if premigration {
+            let (wallet_id, version) = prefix.rsplit_once("_v")?;
+            let version = version.parse().ok()?;
+            (wallet_id, SnapshotKind::PreMigration { version })
+        } else {
+            (prefix, SnapshotKind::Automatic)
+        };
+
+        Some(Self {
+            wallet_id: wallet_id.to_string(),
+            timestamp,
+            kind,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+/// Difference in one field of two wallet records
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldDiff {
+    /// Record field
+    pub field: String,
+    /// Entries only in the older record
+    pub removed: Vec<String>,
+    /// Entries only in the newer record
+    pub added: Vec<String>,
+}
+
+/// Compare two records of a wallet field by field
+///
+/// List and map fields are compared entry by entry, other fields as a
+/// whole; entries are rendered as JSON. Fields that did not change are
+/// left out.
+pub fn diff(from: &WalletData, to: &WalletData) -> StorageResult<Vec<FieldDiff>> {
+    let (Value::Object(from), Value::Object(to)) =
+        (serde_json::to_value(from)?, serde_json::to_value(to)?)
+    else {
+        return Err(StorageError::WalletCorrupted);
+    };
+
+    let fields: BTreeSet<&String> = from.keys().chain(to.keys()).collect();
+    let mut diffs = Vec::new();
+    for field in fields {
+        let (old, new) = (
+            from.get(field).unwrap_or(&Value::Null),
+            to.get(field).unwrap_or(&Value::Null),
+        );
+        if old == new || IGNORED_FIELDS.contains(&field.as_str()) {
+            continue;
+        }
+        let (old, new) = (entries(old), entries(new));
+        diffs.push(FieldDiff {
+            field: field.clone(),
+            removed: old.iter().filter(|e| !new.contains(e)).cloned().collect(),
+            added: new.iter().filter(|e| !old.contains(e)).cloned().collect(),
+        });
+    }
+    Ok(diffs)
+}
+
+/// Entries of a record field, as JSON
+fn entries(value: &Value) -> Vec<String> {
+    match value {
+        Value::Null => Vec::new(),
+        Value::Array(items) => items.iter().map(Value::to_string).collect(),
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| format!("{key}: {value}"))
+            .collect(),
+        value => vec![value.to_string()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_file_names() {
+        let snapshot = Snapshot::from_path(Path::new("backups/my_wallet_1700000000.backup"));
+        assert_eq!(
+            snapshot.map(|s| (s.wallet_id, s.timestamp, s.kind)),
+            Some((
+                "my_wallet".to_string(),
+                1_700_000_000,
+                SnapshotKind::Automatic
+            ))
+        );
+        let snapshot = Snapshot::from_path(Path::new("my_wallet_v1_1700000000.premigration"));
+        assert_eq!(
+            snapshot.map(|s| (s.wallet_id, s.kind)),
+            Some((
+                "my_wallet".to_string(),
+                SnapshotKind::PreMigration { version: 1 }
+            ))
+        );
+        for name in ["my_wallet.backup", "my_wallet_x.backup", "wallet_1.json"] {
+            assert!(Snapshot::from_path(Path::new(name)).is_none(), "{name}");
+        }
+    }
+}
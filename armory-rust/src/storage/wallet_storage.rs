@@ -1,13 +1,57 @@
-use crate::crypto::{decrypt_data, encrypt_data, EncryptedData, SecureKey};
+use crate::crypto::{
+    decrypt_data, derive_key_from_password, encrypt_data, generate_salt, EncryptedData, KdfParams,
+    SecureKey,
+};
 /// Modern wallet storage implementation
 ///
 /// This module replaces the custom wallet file format with modern
-/// encrypted storage using ChaCha20Poly1305 and atomic updates.
+/// encrypted storage using ChaCha20Poly1305 and atomic updates. Records carry
+/// a schema version; older records are backed up and upgraded step by step
+/// when loaded, and records from newer builds are refused.
+///
+/// Every record is encrypted under a storage key derived with Argon2id from
+/// the wallet passphrase, with the record's database key as associated data
+/// so a record copied under another wallet id fails to decrypt. The salt and
+/// parameters of the storage key are kept unencrypted in the database.
 use crate::error::{StorageError, StorageResult};
+use crate::storage::backend::{BackendKind, Batch, StorageBackend};
+use crate::storage::snapshot::{Snapshot, SnapshotKind};
+use crate::wallet::{
+    descriptor_wallet::{Account, AddressType, Chain, UtxoMetadata, WalletTransaction},
+    labels::Label,
+    policy::{PolicySpend, SpendingPolicy},
+    silent_payments::SilentPaymentOutput,
+};
+use crate::Network;
+use bitcoin::{bip32::DerivationPath, OutPoint, ScriptBuf};
 use serde::{Deserialize, Serialize};
-use sled::Db;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 use std::path::PathBuf;
+use zeroize::Zeroize;
+
+/// Schema version of the wallet records written by this build
+pub const WALLET_SCHEMA_VERSION: u32 = 2;
+
+/// Database key of the storage key header
+const KEY_HEADER: &[u8] = b"storage:key";
+
+/// Step upgrading a stored wallet record to the next schema version
+struct Migration {
+    /// Version the step upgrades from
+    from: u32,
+    /// Rewrite the record in place
+    apply: fn(&mut Map<String, Value>) -> StorageResult<()>,
+}
+
+/// Schema registry: one migration per past version, in order
+///
+/// Version 1 covers every record written before versions were checked.
+/// Fields added since then with serde defaults need no migration.
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    apply: fold_legacy_indices,
+}];
 
 /// Configuration for wallet storage
 #[derive(Debug, Clone)]
@@ -39,73 +83,320 @@ pub struct WalletData {
     pub label: String,
     /// Encrypted seed or root key
     pub encrypted_seed: EncryptedData,
+    /// Salt used to derive the seed encryption key from the passphrase
+    #[serde(default)]
+    pub kdf_salt: Vec<u8>,
+    /// Argon2id parameters used to derive the seed encryption key
+    #[serde(default)]
+    pub kdf_params: KdfParams,
+    /// Bitcoin network (absent in records written before it was stored)
+    #[serde(default)]
+    pub network: Option<Network>,
+    /// Watch-only wallet: no encrypted seed, keys live in `descriptors`
+    #[serde(default)]
+    pub watch_only: bool,
+    /// Next unused address index per account, address type and chain
+    #[serde(default)]
+    pub chain_indices: Vec<ChainIndex>,
+    /// Wallet accounts and their labels
+    #[serde(default)]
+    pub accounts: Vec<Account>,
+    /// Known unspent outputs
+    #[serde(default)]
+    pub utxos: Vec<UtxoRecord>,
+    /// Coin control metadata (frozen flag, label, source) per outpoint
+    #[serde(default)]
+    pub utxo_metadata: Vec<UtxoMetadataRecord>,
     /// Wallet descriptors
     pub descriptors: Vec<String>,
     /// Address book entries
     pub address_book: HashMap<String, String>,
     /// Transaction comments
     pub tx_comments: HashMap<String, String>,
+    /// Transaction history
+    #[serde(default)]
+    pub transactions: Vec<WalletTransaction>,
+    /// Height of the best block seen
+    #[serde(default)]
+    pub tip_height: Option<u32>,
+    /// Descriptor keychains
+    #[serde(default)]
+    pub keychains: Vec<KeychainRecord>,
+    /// BIP-329 labels of public keys, inputs and xpubs
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    /// Spending policy enforced before signing
+    #[serde(default)]
+    pub policy: Option<SpendingPolicy>,
+    /// Recent spends counted against the daily limit
+    #[serde(default)]
+    pub policy_spends: Vec<PolicySpend>,
+    /// Silent payment outputs found by scanning, with their spend tweaks
+    #[serde(default)]
+    pub silent_payments: Vec<SilentPaymentOutput>,
     /// Creation timestamp
     pub created_at: u64,
     /// Last modified timestamp
     pub modified_at: u64,
-    /// Wallet version
+    /// Schema version of the record (`WALLET_SCHEMA_VERSION` when written)
     pub version: u32,
 }
 
+/// Persisted address index state of a single account chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainIndex {
+    /// Account index
+    pub account: u32,
+    /// Address type of the chain
+    pub address_type: AddressType,
+    /// Receive or change chain
+    pub chain: Chain,
+    /// Next address index to hand out
+    pub next: u32,
+    /// Indices that have received funds
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub used: Vec<u32>,
+}
+
+/// Persisted descriptor keychain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeychainRecord {
+    /// Keychain name
+    pub name: String,
+    /// Receive descriptor, followed by the change descriptor if any
+    pub descriptors: Vec<String>,
+    /// Next receive index to hand out
+    #[serde(default)]
+    pub next_receive: u32,
+    /// Next change index to hand out
+    #[serde(default)]
+    pub next_change: u32,
+}
+
+/// Persisted unspent output owned by the wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UtxoRecord {
+    /// Outpoint of the output
+    pub outpoint: OutPoint,
+    /// Value in satoshis
+    pub value: u64,
+    /// Output script
+    pub script_pubkey: ScriptBuf,
+    /// Derivation path of the receiving address
+    pub derivation_path: DerivationPath,
+    /// Confirmation height (None if unconfirmed)
+    pub block_height: Option<u32>,
+}
+
+/// Persisted coin control metadata of a single outpoint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UtxoMetadataRecord {
+    /// Outpoint the metadata belongs to
+    pub outpoint: OutPoint,
+    /// Frozen flag, label and source tag
+    #[serde(flatten)]
+    pub metadata: UtxoMetadata,
+}
+
+/// Salt and parameters of the storage key, stored unencrypted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyHeader {
+    /// Argon2id salt
+    salt: Vec<u8>,
+    /// Argon2id parameters
+    params: KdfParams,
+    /// Empty plaintext sealed under the key, to check a passphrase up front
+    check: EncryptedData,
+}
+
 /// Main wallet storage implementation
 pub struct WalletStorage {
-    db: Db,
+    backend: Box<dyn StorageBackend>,
+    backend_kind: BackendKind,
     config: StorageConfig,
     encryption_key: Option<SecureKey>,
 }
 
 impl WalletStorage {
     /// Create a new wallet storage instance
+    ///
+    /// Opens the backend found in the storage directory, or a new sled
+    /// database if there is none.
     pub fn new(config: StorageConfig) -> StorageResult<Self> {
+        let kind = BackendKind::detect(&config.storage_path);
+        Self::with_backend(config, kind)
+    }
+
+    /// Create a wallet storage instance on a given backend
+    ///
+    /// Fails if the storage directory already holds a database of another
+    /// backend; use `migrate_backend` to move it.
+    pub fn with_backend(config: StorageConfig, kind: BackendKind) -> StorageResult<Self> {
         // Ensure storage directory exists
         std::fs::create_dir_all(&config.storage_path)?;
 
-        // Open SLED database
-        let db_path = config.storage_path.join("wallets.db");
-        let db = sled::open(db_path).map_err(|e| StorageError::Database(e.to_string()))?;
+        let detected = BackendKind::detect(&config.storage_path);
+        let existing = detected
+            .path(&config.storage_path)
+            .is_some_and(|path| path.exists());
+        if kind != BackendKind::Memory && kind != detected && existing {
+            return Err(StorageError::Database(format!(
+                "Storage uses the {detected} backend; migrate it to use {kind}"
+            )));
+        }
 
+        let path = kind.path(&config.storage_path).unwrap_or_default();
         Ok(Self {
-            db,
+            backend: kind.open(&path)?,
+            backend_kind: kind,
             config,
             encryption_key: None,
         })
     }
 
-    /// Set encryption key for wallet data
-    pub fn set_encryption_key(&mut self, key: SecureKey) {
+    /// Backend the records are stored in
+    pub fn backend_kind(&self) -> BackendKind {
+        self.backend_kind
+    }
+
+    /// Copy every record to another backend and switch to it
+    ///
+    /// Records are copied as stored, so the storage need not be unlocked.
+    /// The copy is written next to the current database in one transaction
+    /// and read back before it is renamed into place; the old database is
+    /// then moved aside as `<file>.old-<timestamp>`. A migration that fails
+    /// or is interrupted leaves the storage on the old backend. Returns the
+    /// number of records copied.
+    pub fn migrate_backend(&mut self, to: BackendKind) -> StorageResult<usize> {
+        let storage_path = self.config.storage_path.clone();
+        let (Some(source), Some(target)) = (
+            self.backend_kind.path(&storage_path),
+            to.path(&storage_path),
+        ) else {
+            return Err(StorageError::Database(
+                "In-memory storage cannot be migrated".to_string(),
+            ));
+        };
+        if to == self.backend_kind {
+            return Err(StorageError::Database(format!(
+                "Storage already uses the {to} backend"
+            )));
+        }
+        if target.exists() {
+            return Err(StorageError::Database(format!(
+                "{} already exists",
+                target.display()
+            )));
+        }
+
+        let entries = self.backend.scan_prefix(b"")?;
+        let staging = with_suffix(&target, ".migrating");
+        remove_path(&staging)?;
+        {
+            let copy = to.open(&staging)?;
+            let mut batch = Batch::default();
+            for (key, value) in &entries {
+                batch.insert(key.as_slice(), value.as_slice());
+            }
+            copy.transaction(batch)?;
+            copy.flush()?;
+            if copy.scan_prefix(b"")? != entries {
+                return Err(StorageError::AtomicUpdateFailed(
+                    "Migrated records differ from the originals".to_string(),
+                ));
+            }
+        }
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let retired = with_suffix(&source, &format!(".old-{timestamp}"));
+        self.backend.close()?;
+        let switched = std::fs::rename(&staging, &target)
+            .and_then(|()| std::fs::rename(&source, &retired))
+            .map_err(StorageError::from)
+            .and_then(|()| to.open(&target));
+        match switched {
+            Ok(backend) => {
+                self.backend = backend;
+                self.backend_kind = to;
+                Ok(entries.len())
+            }
+            Err(e) => {
+                // Put the old database back and keep using it
+                if !source.exists() {
+                    std::fs::rename(&retired, &source)?;
+                }
+                self.backend = self.backend_kind.open(&source)?;
+                remove_path(&target)?;
+                remove_path(&staging)?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Derive the storage key from the wallet passphrase
+    ///
+    /// The passphrase is checked against the key header, so a wrong one fails
+    /// here with `StorageError::DecryptionFailed`. A new database gets a key
+    /// header with a fresh salt. A database written before storage
+    /// encryption was mandatory is encrypted on the spot: its records are
+    /// sealed into a fresh database that replaces the old one, and plaintext
+    /// backup files are sealed in place.
+    pub fn unlock(&mut self, passphrase: &str) -> StorageResult<()> {
+        let stored_header = self.backend.get(KEY_HEADER)?;
+        let key = match stored_header {
+            Some(header) => {
+                let header: KeyHeader = serde_json::from_slice(&header)?;
+                let key = derive_storage_key(passphrase, &header.salt, &header.params)?;
+                decrypt_data(&key, &header.check, Some(KEY_HEADER))
+                    .map_err(|_| StorageError::DecryptionFailed)?;
+                key
+            }
+            None => {
+                let (header, key) =
+                    new_key_header(passphrase, &KdfParams::for_wallet_encryption())?;
+                let mut batch = Batch::default();
+                let mut plaintext = false;
+                for (record_key, stored) in self.backend.scan_prefix(b"wallet:")? {
+                    let sealed = seal(&key, &record_key, &stored)?;
+                    batch.insert(record_key, sealed);
+                    plaintext = true;
+                }
+                batch.insert(KEY_HEADER, serde_json::to_vec(&header)?);
+                // Sealing in place could leave the plaintext readable on disk
+                if plaintext {
+                    self.backend.replace_all(batch)?;
+                } else {
+                    self.backend.transaction(batch)?;
+                }
+                self.backend.flush()?;
+                key
+            }
+        };
+
+        // Also covers backups left behind by an interrupted earlier unlock
+        self.seal_backups(&key)?;
         self.encryption_key = Some(key);
+        Ok(())
+    }
+
+    /// Whether the storage key has been derived
+    pub fn is_unlocked(&self) -> bool {
+        self.encryption_key.is_some()
     }
 
     /// Save wallet data to storage
     pub fn save_wallet_data(&self, data: &WalletData) -> StorageResult<()> {
-        // Serialize wallet data
-        let serialized = serde_json::to_vec(data)?;
-
-        // Encrypt if key is available
-        let final_data = if let Some(key) = &self.encryption_key {
-            let encrypted = encrypt_data(key, &serialized, None)
-                .map_err(|e| StorageError::Database(format!("Encryption failed: {e}")))?;
-            serde_json::to_vec(&encrypted)?
-        } else {
-            serialized
-        };
+        let key = format!("wallet:{}", data.id);
+        let final_data = seal(self.key()?, key.as_bytes(), &serde_json::to_vec(data)?)?;
 
         // Store in database with atomic update
-        let key = format!("wallet:{}", data.id);
-        self.db
-            .insert(key.as_bytes(), final_data)
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+        self.backend.put(key.as_bytes(), &final_data)?;
 
         // Flush to ensure data is written
-        self.db
-            .flush()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+        self.backend.flush()?;
 
         // Create backup if enabled
         if self.config.auto_backup {
@@ -115,35 +406,146 @@ impl WalletStorage {
         Ok(())
     }
 
+    /// Atomically replace a wallet record and re-encrypt every stored record
+    ///
+    /// A new storage key is derived from `passphrase` with a fresh salt and
+    /// `params`. All wallet records are re-sealed under it and written in a
+    /// single transaction together with `data` and the new key header, so a
+    /// crash leaves either the old or the new state on disk. Backup files
+    /// written earlier keep their old encryption.
+    pub fn rekey(
+        &mut self,
+        data: &WalletData,
+        passphrase: &str,
+        params: &KdfParams,
+    ) -> StorageResult<()> {
+        let (header, new_key) = new_key_header(passphrase, params)?;
+
+        let mut batch = Batch::default();
+        for wallet_id in self.list_wallets()? {
+            if wallet_id != data.id {
+                let record = self.load_wallet_data(&wallet_id)?;
+                let key = format!("wallet:{wallet_id}");
+                batch.insert(
+                    key.as_bytes(),
+                    seal(&new_key, key.as_bytes(), &serde_json::to_vec(&record)?)?,
+                );
+            }
+        }
+        let key = format!("wallet:{}", data.id);
+        batch.insert(
+            key.as_bytes(),
+            seal(&new_key, key.as_bytes(), &serde_json::to_vec(data)?)?,
+        );
+        batch.insert(KEY_HEADER, serde_json::to_vec(&header)?);
+
+        self.backend.transaction(batch)?;
+        self.backend.flush()?;
+
+        self.encryption_key = Some(new_key);
+        if self.config.auto_backup {
+            self.create_backup(&data.id)?;
+        }
+        Ok(())
+    }
+
     /// Load wallet data from storage
+    ///
+    /// The storage must be unlocked. Records of an older schema version are
+    /// copied, still encrypted, to a pre-migration backup, upgraded to
+    /// `WALLET_SCHEMA_VERSION` and stored again. Records of a newer version
+    /// are refused with `StorageError::UnsupportedVersion`.
     pub fn load_wallet_data(&self, wallet_id: &str) -> StorageResult<WalletData> {
+        let encryption_key = self.key()?;
         let key = format!("wallet:{wallet_id}");
         let stored_data = self
-            .db
-            .get(key.as_bytes())
-            .map_err(|e| StorageError::Database(e.to_string()))?
+            .backend
+            .get(key.as_bytes())?
             .ok_or_else(|| StorageError::Database("Wallet not found".to_string()))?;
+        let decrypted_data = open(encryption_key, key.as_bytes(), &stored_data)?;
 
-        // Decrypt if key is available
-        let decrypted_data = if let Some(key) = &self.encryption_key {
-            let encrypted: EncryptedData = serde_json::from_slice(&stored_data)?;
-            decrypt_data(key, &encrypted, None)
-                .map_err(|e| StorageError::Database(format!("Decryption failed: {e}")))?
-        } else {
-            stored_data.to_vec()
-        };
+        let (wallet_data, version) = parse_record(&decrypted_data)?;
+        if version == WALLET_SCHEMA_VERSION {
+            return Ok(wallet_data);
+        }
+        self.backup_before_migration(wallet_id, version, &stored_data)?;
 
-        // Deserialize wallet data
-        let wallet_data: WalletData = serde_json::from_slice(&decrypted_data)?;
+        // Store the upgraded record so the migration runs once
+        let sealed = seal(
+            encryption_key,
+            key.as_bytes(),
+            &serde_json::to_vec(&wallet_data)?,
+        )?;
+        self.backend.put(key.as_bytes(), &sealed)?;
+        self.backend.flush()?;
         Ok(wallet_data)
     }
 
+    /// Copy a stored record, as stored, before migrating it
+    ///
+    /// Pre-migration backups are kept out of the rotation of regular backups.
+    fn backup_before_migration(
+        &self,
+        wallet_id: &str,
+        version: u32,
+        stored: &[u8],
+    ) -> StorageResult<PathBuf> {
+        let backup_dir = self.config.storage_path.join("backups");
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let backup_file =
+            backup_dir.join(format!("{wallet_id}_v{version}_{timestamp}.premigration"));
+
+        std::fs::create_dir_all(&backup_dir)
+            .and_then(|_| std::fs::write(&backup_file, stored))
+            .map_err(|e| {
+                StorageError::BackupFailed(format!(
+                    "Pre-migration backup of '{wallet_id}' failed: {e}"
+                ))
+            })?;
+        Ok(backup_file)
+    }
+
+    /// Storage key, if the storage is unlocked
+    fn key(&self) -> StorageResult<&SecureKey> {
+        self.encryption_key.as_ref().ok_or(StorageError::Locked)
+    }
+
+    /// Seal backup files holding plaintext records
+    ///
+    /// Backups are written as stored, so databases from before storage
+    /// encryption was mandatory left plaintext copies behind.
+    fn seal_backups(&self, key: &SecureKey) -> StorageResult<()> {
+        let backup_dir = self.config.storage_path.join("backups");
+        if !backup_dir.exists() {
+            return Ok(());
+        }
+
+        for entry in std::fs::read_dir(&backup_dir)? {
+            let path = entry?.path();
+            let stored = std::fs::read(&path)?;
+            // Sealed records carry no wallet id in the clear
+            let Some(wallet_id) = serde_json::from_slice::<Value>(&stored)
+                .ok()
+                .and_then(|record| record.get("id")?.as_str().map(str::to_string))
+            else {
+                continue;
+            };
+            let sealed = seal(key, format!("wallet:{wallet_id}").as_bytes(), &stored)?;
+            std::fs::write(&path, sealed).map_err(|e| {
+                StorageError::BackupFailed(format!("Sealing backup {} failed: {e}", path.display()))
+            })?;
+        }
+        Ok(())
+    }
+
     /// List all wallet IDs
     pub fn list_wallets(&self) -> StorageResult<Vec<String>> {
         let mut wallet_ids = Vec::new();
 
-        for result in self.db.scan_prefix(b"wallet:") {
-            let (key, _) = result.map_err(|e| StorageError::Database(e.to_string()))?;
+        for (key, _) in self.backend.scan_prefix(b"wallet:")? {
             let key_str = String::from_utf8_lossy(&key);
             if let Some(wallet_id) = key_str.strip_prefix("wallet:") {
                 wallet_ids.push(wallet_id.to_string());
@@ -156,19 +558,29 @@ impl WalletStorage {
     /// Delete wallet data
     pub fn delete_wallet(&self, wallet_id: &str) -> StorageResult<()> {
         let key = format!("wallet:{wallet_id}");
-        self.db
-            .remove(key.as_bytes())
-            .map_err(|e| StorageError::Database(e.to_string()))?;
-
-        self.db
-            .flush()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+        self.backend.remove(key.as_bytes())?;
+        self.backend.flush()?;
 
         Ok(())
     }
 
-    /// Create a backup of wallet data
+    /// Snapshot the stored wallet record, then prune older snapshots
+    ///
+    /// The new snapshot is read back and verified before anything is pruned.
     fn create_backup(&self, wallet_id: &str) -> StorageResult<()> {
+        let Some(snapshot) = self.write_snapshot(wallet_id)? else {
+            return Ok(());
+        };
+        self.load_snapshot(&snapshot).map_err(|e| {
+            StorageError::BackupFailed(format!("Snapshot of '{wallet_id}' does not verify: {e}"))
+        })?;
+
+        // Cleanup old backups
+        self.cleanup_backups(wallet_id)
+    }
+
+    /// Copy the stored wallet record, as stored, to a timestamped snapshot
+    fn write_snapshot(&self, wallet_id: &str) -> StorageResult<Option<Snapshot>> {
         let backup_dir = self.config.storage_path.join("backups");
         std::fs::create_dir_all(&backup_dir)?;
 
@@ -181,68 +593,103 @@ impl WalletStorage {
 
         // Copy wallet data to backup file
         let key = format!("wallet:{wallet_id}");
-        if let Some(data) = self
-            .db
-            .get(key.as_bytes())
-            .map_err(|e| StorageError::Database(e.to_string()))?
-        {
-            std::fs::write(&backup_file, &data)?;
-        }
+        let Some(data) = self.backend.get(key.as_bytes())? else {
+            return Ok(None);
+        };
+        std::fs::write(&backup_file, &data)?;
 
-        // Cleanup old backups
-        self.cleanup_backups(wallet_id)?;
+        Ok(Some(Snapshot {
+            wallet_id: wallet_id.to_string(),
+            timestamp,
+            kind: SnapshotKind::Automatic,
+            path: backup_file,
+        }))
+    }
+
+    /// Prune automatic snapshots beyond `backup_count`
+    ///
+    /// Only snapshots that decrypt and parse count towards the retention, so
+    /// damaged ones never push out the last good ones. Everything older than
+    /// the newest `backup_count` good snapshots is deleted.
+    fn cleanup_backups(&self, wallet_id: &str) -> StorageResult<()> {
+        let mut verified = 0;
+        for snapshot in self.list_snapshots(wallet_id)? {
+            if snapshot.kind != SnapshotKind::Automatic {
+                continue;
+            }
+            if verified >= self.config.backup_count {
+                let _ = std::fs::remove_file(&snapshot.path);
+            } else if self.load_snapshot(&snapshot).is_ok() {
+                verified += 1;
+            }
+        }
 
         Ok(())
     }
 
-    /// Clean up old backup files
-    fn cleanup_backups(&self, wallet_id: &str) -> StorageResult<()> {
+    /// Snapshots of a wallet, newest first
+    ///
+    /// Covers automatic snapshots and the pre-migration copies of the record.
+    pub fn list_snapshots(&self, wallet_id: &str) -> StorageResult<Vec<Snapshot>> {
         let backup_dir = self.config.storage_path.join("backups");
         if !backup_dir.exists() {
-            return Ok(());
+            return Ok(Vec::new());
         }
 
-        // Find all backup files for this wallet
-        let mut backups = Vec::new();
+        let mut snapshots = Vec::new();
         for entry in std::fs::read_dir(&backup_dir)? {
-            let entry = entry?;
-            let file_name = entry.file_name();
-            let file_name_str = file_name.to_string_lossy();
-
-            if file_name_str.starts_with(wallet_id) && file_name_str.ends_with(".backup") {
-                if let Ok(metadata) = entry.metadata() {
-                    if let Ok(modified) = metadata.modified() {
-                        backups.push((entry.path(), modified));
-                    }
+            if let Some(snapshot) = Snapshot::from_path(&entry?.path()) {
+                if snapshot.wallet_id == wallet_id {
+                    snapshots.push(snapshot);
                 }
             }
         }
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.timestamp));
+        Ok(snapshots)
+    }
 
-        // Sort by modification time (newest first)
-        backups.sort_by(|a, b| b.1.cmp(&a.1));
+    /// Decrypt and verify a snapshot, upgrading it from older schema versions
+    ///
+    /// Snapshots sealed under an earlier passphrase fail to decrypt like
+    /// damaged ones, with `StorageError::DecryptionFailed`.
+    pub fn load_snapshot(&self, snapshot: &Snapshot) -> StorageResult<WalletData> {
+        let stored = std::fs::read(&snapshot.path)?;
+        self.open_snapshot(snapshot, &stored)
+    }
 
-        // Remove excess backups
-        for (path, _) in backups.into_iter().skip(self.config.backup_count as usize) {
-            let _ = std::fs::remove_file(path);
-        }
+    /// Roll a wallet back to a snapshot
+    ///
+    /// The snapshot is verified first, and the current record is snapshotted
+    /// as stored before it is replaced, even if it no longer decrypts, so a
+    /// rollback can be undone the same way. The snapshot is stored as it was
+    /// taken: a pre-migration snapshot is migrated again when next loaded.
+    pub fn rollback(&self, snapshot: &Snapshot) -> StorageResult<WalletData> {
+        let stored = std::fs::read(&snapshot.path)?;
+        let wallet_data = self.open_snapshot(snapshot, &stored)?;
 
-        Ok(())
+        self.write_snapshot(&snapshot.wallet_id)?;
+        let key = format!("wallet:{}", snapshot.wallet_id);
+        self.backend.put(key.as_bytes(), &stored)?;
+        self.backend.flush()?;
+        Ok(wallet_data)
+    }
+
+    /// Decrypt and parse the contents of a snapshot file
+    fn open_snapshot(&self, snapshot: &Snapshot, stored: &[u8]) -> StorageResult<WalletData> {
+        let key = format!("wallet:{}", snapshot.wallet_id);
+        let decrypted = open(self.key()?, key.as_bytes(), stored)?;
+        Ok(parse_record(&decrypted)?.0)
     }
 
     /// Check if wallet exists
     pub fn wallet_exists(&self, wallet_id: &str) -> StorageResult<bool> {
         let key = format!("wallet:{wallet_id}");
-        self.db
-            .contains_key(key.as_bytes())
-            .map_err(|e| StorageError::Database(e.to_string()))
+        self.backend.contains(key.as_bytes())
     }
 
     /// Get storage statistics
     pub fn get_stats(&self) -> StorageResult<StorageStats> {
-        let size_on_disk = self
-            .db
-            .size_on_disk()
-            .map_err(|e| StorageError::Database(e.to_string()))?;
+        let size_on_disk = self.backend.size_on_disk()?;
 
         let wallet_count = self.list_wallets()?.len();
 
@@ -262,6 +709,143 @@ pub struct StorageStats {
     pub database_path: PathBuf,
 }
 
+/// Encrypt a serialized record, bound to its database key
+fn seal(key: &SecureKey, record_key: &[u8], plaintext: &[u8]) -> StorageResult<Vec<u8>> {
+    let encrypted = encrypt_data(key, plaintext, Some(record_key))
+        .map_err(|e| StorageError::Database(format!("Encryption failed: {e}")))?;
+    Ok(serde_json::to_vec(&encrypted)?)
+}
+
+/// Decrypt a stored record, checking that it was sealed under `record_key`
+fn open(key: &SecureKey, record_key: &[u8], stored: &[u8]) -> StorageResult<Vec<u8>> {
+    let encrypted: EncryptedData =
+        serde_json::from_slice(stored).map_err(|_| StorageError::DecryptionFailed)?;
+    decrypt_data(key, &encrypted, Some(record_key)).map_err(|_| StorageError::DecryptionFailed)
+}
+
+/// Derive a storage key from a passphrase with Argon2id
+fn derive_storage_key(
+    passphrase: &str,
+    salt: &[u8],
+    params: &KdfParams,
+) -> StorageResult<SecureKey> {
+    let mut key_bytes = derive_key_from_password(passphrase, salt, params)
+        .map_err(|e| StorageError::Database(format!("Key derivation failed: {e}")))?;
+    let key = SecureKey::from_bytes(&key_bytes);
+    key_bytes.zeroize();
+    key.map_err(|e| StorageError::Database(format!("Key derivation failed: {e}")))
+}
+
+/// Derive a storage key with a fresh salt, and the header to find it again
+fn new_key_header(passphrase: &str, params: &KdfParams) -> StorageResult<(KeyHeader, SecureKey)> {
+    let salt = generate_salt().map_err(|e| StorageError::Database(format!("Salt failed: {e}")))?;
+    let key = derive_storage_key(passphrase, &salt, params)?;
+    let check = encrypt_data(&key, &[], Some(KEY_HEADER))
+        .map_err(|e| StorageError::Database(format!("Encryption failed: {e}")))?;
+    let header = KeyHeader {
+        salt,
+        params: params.clone(),
+        check,
+    };
+    Ok((header, key))
+}
+
+/// Parse a decrypted wallet record, upgrading it from older schema versions
+///
+/// Returns the record together with the version it was written with.
+/// Records of a newer version are refused.
+pub(crate) fn parse_record(plaintext: &[u8]) -> StorageResult<(WalletData, u32)> {
+    let mut record: Value = serde_json::from_slice(plaintext)?;
+    let version = record_version(&record)?;
+    if version > WALLET_SCHEMA_VERSION {
+        return Err(StorageError::UnsupportedVersion {
+            found: version,
+            supported: WALLET_SCHEMA_VERSION,
+        });
+    }
+    migrate(&mut record, version)?;
+    Ok((serde_json::from_value(record)?, version))
+}
+
+/// Schema version of a stored record
+fn record_version(record: &Value) -> StorageResult<u32> {
+    record
+        .get("version")
+        .and_then(Value::as_u64)
+        .and_then(|version| u32::try_from(version).ok())
+        .filter(|version| *version > 0)
+        .ok_or(StorageError::WalletCorrupted)
+}
+
+/// Run the migrations from `version` up to `WALLET_SCHEMA_VERSION`
+fn migrate(record: &mut Value, mut version: u32) -> StorageResult<()> {
+    let fields = record
+        .as_object_mut()
+        .ok_or(StorageError::WalletCorrupted)?;
+    while version < WALLET_SCHEMA_VERSION {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|migration| migration.from == version)
+            .ok_or_else(|| {
+                StorageError::Database(format!("No migration from schema version {version}"))
+            })?;
+        (migration.apply)(fields)?;
+        version += 1;
+        fields.insert("version".to_string(), Value::from(version));
+    }
+    Ok(())
+}
+
+/// Version 1 to 2: move the account 0 receive indices of records written
+/// before accounts existed into `chain_indices`
+fn fold_legacy_indices(record: &mut Map<String, Value>) -> StorageResult<()> {
+    let Some(legacy) = record.remove("next_indices") else {
+        return Ok(());
+    };
+    let has_chain_indices = record
+        .get("chain_indices")
+        .and_then(Value::as_array)
+        .is_some_and(|indices| !indices.is_empty());
+    if has_chain_indices {
+        return Ok(());
+    }
+
+    let legacy: HashMap<AddressType, u32> = serde_json::from_value(legacy)?;
+    let mut chain_indices: Vec<ChainIndex> = legacy
+        .into_iter()
+        .map(|(address_type, next)| ChainIndex {
+            account: 0,
+            address_type,
+            chain: Chain::External,
+            next,
+            used: Vec::new(),
+        })
+        .collect();
+    chain_indices.sort_by_key(|entry| entry.address_type);
+    record.insert(
+        "chain_indices".to_string(),
+        serde_json::to_value(chain_indices)?,
+    );
+    Ok(())
+}
+
+/// `path` with `suffix` appended to its file name
+fn with_suffix(path: &std::path::Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Remove a database file or directory, if present
+fn remove_path(path: &std::path::Path) -> StorageResult<()> {
+    if path.is_dir() {
+        std::fs::remove_dir_all(path)?;
+    } else if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -269,13 +853,36 @@ mod tests {
 
     fn create_test_storage() -> (WalletStorage, TempDir) {
         let temp_dir = TempDir::new().unwrap();
-        let config = StorageConfig {
+        let mut storage = open_test_storage(&temp_dir, false);
+        storage.unlock("").unwrap();
+        (storage, temp_dir)
+    }
+
+    fn open_test_storage(temp_dir: &TempDir, auto_backup: bool) -> WalletStorage {
+        WalletStorage::new(StorageConfig {
             storage_path: temp_dir.path().to_path_buf(),
-            auto_backup: false,
+            auto_backup,
             backup_count: 3,
-        };
-        let storage = WalletStorage::new(config).unwrap();
-        (storage, temp_dir)
+        })
+        .unwrap()
+    }
+
+    /// Files under a directory containing `needle`
+    fn files_containing(dir: &std::path::Path, needle: &[u8]) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                found.extend(files_containing(&path, needle));
+            } else if std::fs::read(&path)
+                .unwrap()
+                .windows(needle.len())
+                .any(|window| window == needle)
+            {
+                found.push(path);
+            }
+        }
+        found
     }
 
     fn create_test_wallet_data() -> WalletData {
@@ -289,12 +896,27 @@ mod tests {
             id: "test_wallet_123".to_string(),
             label: "Test Wallet".to_string(),
             encrypted_seed,
+            kdf_salt: vec![0u8; 32],
+            kdf_params: KdfParams::for_fast_operations(),
+            network: Some(Network::Regtest),
+            watch_only: false,
+            chain_indices: Vec::new(),
+            accounts: Vec::new(),
+            utxos: Vec::new(),
+            utxo_metadata: Vec::new(),
             descriptors: vec!["wpkh(xpub123...)".to_string(), "tr(xpub456...)".to_string()],
             address_book: HashMap::new(),
             tx_comments: HashMap::new(),
+            transactions: Vec::new(),
+            tip_height: None,
+            keychains: Vec::new(),
+            labels: Vec::new(),
+            policy: None,
+            policy_spends: Vec::new(),
+            silent_payments: Vec::new(),
             created_at: 1640995200, // 2022-01-01
             modified_at: 1640995200,
-            version: 1,
+            version: WALLET_SCHEMA_VERSION,
         }
     }
 
@@ -341,4 +963,417 @@ mod tests {
         storage.delete_wallet(&wallet_data.id).unwrap();
         assert!(!storage.wallet_exists(&wallet_data.id).unwrap());
     }
+    #[test]
+    fn test_records_are_encrypted_at_rest() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = open_test_storage(&temp_dir, true);
+        let wallet_data = create_test_wallet_data();
+        assert!(matches!(
+            storage.save_wallet_data(&wallet_data),
+            Err(StorageError::Locked)
+        ));
+
+        storage.unlock("correct horse").unwrap();
+        storage.save_wallet_data(&wallet_data).unwrap();
+        drop(storage);
+        assert!(files_containing(temp_dir.path(), b"xpub123").is_empty());
+
+        let mut reopened = open_test_storage(&temp_dir, false);
+        assert!(matches!(
+            reopened.load_wallet_data(&wallet_data.id),
+            Err(StorageError::Locked)
+        ));
+        assert!(matches!(
+            reopened.unlock("wrong"),
+            Err(StorageError::DecryptionFailed)
+        ));
+        reopened.unlock("correct horse").unwrap();
+        assert_eq!(
+            reopened
+                .load_wallet_data(&wallet_data.id)
+                .unwrap()
+                .descriptors,
+            wallet_data.descriptors
+        );
+
+        // A record copied under another wallet id does not decrypt
+        let stored = reopened
+            .backend
+            .get(format!("wallet:{}", wallet_data.id).as_bytes())
+            .unwrap()
+            .unwrap();
+        reopened.backend.put(b"wallet:swapped", &stored).unwrap();
+        assert!(matches!(
+            reopened.load_wallet_data("swapped"),
+            Err(StorageError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_encrypts_plaintext_databases() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = open_test_storage(&temp_dir, false);
+        let wallet_data = create_test_wallet_data();
+        let plaintext = serde_json::to_vec(&wallet_data).unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+        std::fs::create_dir_all(&backup_dir).unwrap();
+        std::fs::write(backup_dir.join("test_wallet_123_1.backup"), &plaintext).unwrap();
+        storage
+            .backend
+            .put(format!("wallet:{}", wallet_data.id).as_bytes(), &plaintext)
+            .unwrap();
+        storage.backend.flush().unwrap();
+
+        storage.unlock("correct horse").unwrap();
+        assert!(storage.backend.contains(KEY_HEADER).unwrap());
+        assert_eq!(
+            storage.load_wallet_data(&wallet_data.id).unwrap().label,
+            wallet_data.label
+        );
+        drop(storage);
+        assert!(files_containing(temp_dir.path(), b"xpub123").is_empty());
+
+        // Backups were sealed, not dropped
+        let backup = std::fs::read(backup_dir.join("test_wallet_123_1.backup")).unwrap();
+        let reopened = {
+            let mut storage = open_test_storage(&temp_dir, false);
+            storage.unlock("correct horse").unwrap();
+            storage
+        };
+        let opened = open(
+            reopened.key().unwrap(),
+            format!("wallet:{}", wallet_data.id).as_bytes(),
+            &backup,
+        );
+        assert_eq!(
+            serde_json::from_slice::<WalletData>(&opened.unwrap())
+                .unwrap()
+                .id,
+            wallet_data.id
+        );
+    }
+
+    #[test]
+    fn test_rekey_rotates_encrypted_records() {
+        let (mut storage, temp_dir) = create_test_storage();
+
+        let wallet_data = create_test_wallet_data();
+        let mut other = create_test_wallet_data();
+        other.id = "other_wallet".to_string();
+        storage.save_wallet_data(&wallet_data).unwrap();
+        storage.save_wallet_data(&other).unwrap();
+
+        let mut updated = wallet_data.clone();
+        updated.label = "Rekeyed".to_string();
+        storage
+            .rekey(&updated, "rotated", &KdfParams::for_fast_operations())
+            .unwrap();
+        assert_eq!(
+            storage.load_wallet_data(&updated.id).unwrap().label,
+            "Rekeyed"
+        );
+        assert_eq!(storage.load_wallet_data(&other.id).unwrap().id, other.id);
+        drop(storage);
+
+        // Records sealed under the old key are gone
+        let mut reopened = open_test_storage(&temp_dir, false);
+        assert!(matches!(
+            reopened.unlock(""),
+            Err(StorageError::DecryptionFailed)
+        ));
+        reopened.unlock("rotated").unwrap();
+        assert_eq!(reopened.load_wallet_data(&other.id).unwrap().id, other.id);
+    }
+
+    #[test]
+    fn test_snapshots_roll_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = open_test_storage(&temp_dir, true);
+        storage.unlock("").unwrap();
+        let mut wallet_data = create_test_wallet_data();
+        wallet_data
+            .address_book
+            .insert("bcrt1qexample".to_string(), "Exchange".to_string());
+        storage.save_wallet_data(&wallet_data).unwrap();
+
+        // Move the first snapshot back in time, then wipe the labels
+        let snapshot = storage.list_snapshots(&wallet_data.id).unwrap().remove(0);
+        let earlier = temp_dir
+            .path()
+            .join("backups")
+            .join(format!("{}_1000.backup", wallet_data.id));
+        std::fs::rename(&snapshot.path, &earlier).unwrap();
+        let original = wallet_data.clone();
+        wallet_data.address_book.clear();
+        storage.save_wallet_data(&wallet_data).unwrap();
+
+        let snapshots = storage.list_snapshots(&wallet_data.id).unwrap();
+        assert_eq!(snapshots.len(), 2);
+        let earlier = snapshots.last().unwrap();
+        assert_eq!(earlier.timestamp, 1000);
+        let diffs = crate::storage::snapshot::diff(
+            &storage.load_snapshot(earlier).unwrap(),
+            &storage.load_wallet_data(&wallet_data.id).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].field, "address_book");
+        assert_eq!(diffs[0].removed, vec![r#"bcrt1qexample: "Exchange""#]);
+        assert!(diffs[0].added.is_empty());
+
+        storage.rollback(earlier).unwrap();
+        assert_eq!(
+            storage
+                .load_wallet_data(&wallet_data.id)
+                .unwrap()
+                .address_book,
+            original.address_book
+        );
+    }
+
+    #[test]
+    fn test_pruning_keeps_verified_snapshots() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = open_test_storage(&temp_dir, true);
+        storage.unlock("").unwrap();
+        let wallet_data = create_test_wallet_data();
+        storage.save_wallet_data(&wallet_data).unwrap();
+
+        let backup_dir = temp_dir.path().join("backups");
+        let current = storage.list_snapshots(&wallet_data.id).unwrap().remove(0);
+        let sealed = std::fs::read(&current.path).unwrap();
+        for timestamp in 1000..1003 {
+            let file = format!("{}_{timestamp}.backup", wallet_data.id);
+            std::fs::write(backup_dir.join(file), &sealed).unwrap();
+        }
+        let damaged = backup_dir.join(format!("{}_1003.backup", wallet_data.id));
+        std::fs::write(&damaged, b"damaged").unwrap();
+        // Another wallet's snapshots are left alone
+        let other = backup_dir.join("test_wallet_1234_1000.backup");
+        std::fs::write(&other, &sealed).unwrap();
+
+        storage.save_wallet_data(&wallet_data).unwrap();
+        let timestamps: Vec<u64> = storage
+            .list_snapshots(&wallet_data.id)
+            .unwrap()
+            .iter()
+            .map(|snapshot| snapshot.timestamp)
+            .collect();
+        // Three good snapshots are kept; the damaged one does not count
+        assert_eq!(timestamps, vec![current.timestamp, 1003, 1002, 1001]);
+        assert!(matches!(
+            storage.load_snapshot(&Snapshot::from_path(&damaged).unwrap()),
+            Err(StorageError::DecryptionFailed)
+        ));
+        assert!(other.exists());
+    }
+
+    /// Records as written by past schema versions
+    const FIXTURES: &[(&str, u32, &str)] = &[
+        (
+            "baseline",
+            1,
+            include_str!("fixtures/wallet_v1_baseline.json"),
+        ),
+        (
+            "indices",
+            1,
+            include_str!("fixtures/wallet_v1_indices.json"),
+        ),
+    ];
+
+    #[test]
+    fn test_migrates_between_backends() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = open_test_storage(&temp_dir, false);
+        storage.unlock("correct horse").unwrap();
+        let wallet_data = create_test_wallet_data();
+        storage.save_wallet_data(&wallet_data).unwrap();
+        assert_eq!(storage.backend_kind(), BackendKind::Sled);
+        assert!(storage.migrate_backend(BackendKind::Sled).is_err());
+
+        // A failed rename leaves the storage on the old database
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let blockers: Vec<PathBuf> = (now..now + 60)
+            .map(|timestamp| temp_dir.path().join(format!("wallets.db.old-{timestamp}")))
+            .collect();
+        for blocker in &blockers {
+            std::fs::create_dir(blocker).unwrap();
+            std::fs::write(blocker.join("occupied"), b"").unwrap();
+        }
+        assert!(storage.migrate_backend(BackendKind::Sqlite).is_err());
+        assert_eq!(storage.backend_kind(), BackendKind::Sled);
+        assert_eq!(
+            storage.load_wallet_data(&wallet_data.id).unwrap().id,
+            wallet_data.id
+        );
+        assert!(!temp_dir.path().join("wallets.sqlite").exists());
+        assert!(!temp_dir.path().join("wallets.sqlite.migrating").exists());
+        for blocker in &blockers {
+            std::fs::remove_dir_all(blocker).unwrap();
+        }
+
+        // Only the wallet record and the key header are stored
+        assert_eq!(storage.migrate_backend(BackendKind::Sqlite).unwrap(), 2);
+        assert_eq!(
+            storage.load_wallet_data(&wallet_data.id).unwrap().id,
+            wallet_data.id
+        );
+        drop(storage);
+        assert!(!temp_dir.path().join("wallets.db").exists());
+
+        // Reopening picks the SQLite database, still sealed under the passphrase
+        let mut storage = open_test_storage(&temp_dir, false);
+        assert_eq!(storage.backend_kind(), BackendKind::Sqlite);
+        assert!(matches!(
+            storage.unlock("wrong"),
+            Err(StorageError::DecryptionFailed)
+        ));
+        storage.unlock("correct horse").unwrap();
+        assert_eq!(
+            storage.list_wallets().unwrap(),
+            vec![wallet_data.id.clone()]
+        );
+        assert!(WalletStorage::with_backend(storage.config.clone(), BackendKind::Sled).is_err());
+
+        storage.migrate_backend(BackendKind::Sled).unwrap();
+        assert_eq!(
+            storage.load_wallet_data(&wallet_data.id).unwrap().id,
+            wallet_data.id
+        );
+    }
+
+    #[test]
+    fn test_memory_backend() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = StorageConfig {
+            storage_path: temp_dir.path().to_path_buf(),
+            auto_backup: false,
+            backup_count: 1,
+        };
+        let mut storage = WalletStorage::with_backend(config.clone(), BackendKind::Memory).unwrap();
+        storage.unlock("").unwrap();
+        let wallet_data = create_test_wallet_data();
+        storage.save_wallet_data(&wallet_data).unwrap();
+        assert!(storage.wallet_exists(&wallet_data.id).unwrap());
+        assert!(storage.migrate_backend(BackendKind::Sqlite).is_err());
+        drop(storage);
+
+        let storage = WalletStorage::with_backend(config, BackendKind::Memory).unwrap();
+        assert!(storage.list_wallets().unwrap().is_empty());
+        assert!(!temp_dir.path().join("wallets.db").exists());
+    }
+
+    fn premigration_backups(storage: &WalletStorage) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(storage.config.storage_path.join("backups")) else {
+            return Vec::new();
+        };
+        entries
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".premigration"))
+            .collect()
+    }
+
+    #[test]
+    fn test_migration_registry_is_complete() {
+        let versions: Vec<u32> = MIGRATIONS.iter().map(|migration| migration.from).collect();
+        assert_eq!(versions, (1..WALLET_SCHEMA_VERSION).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_migrates_past_versions() {
+        for (id, version, fixture) in FIXTURES {
+            let (storage, _temp_dir) = create_test_storage();
+            let key = format!("wallet:{id}");
+            let sealed = seal(storage.key().unwrap(), key.as_bytes(), fixture.as_bytes()).unwrap();
+            storage.backend.put(key.as_bytes(), &sealed).unwrap();
+
+            let data = storage.load_wallet_data(id).unwrap();
+            assert_eq!(data.id, *id);
+            assert_eq!(data.version, WALLET_SCHEMA_VERSION);
+            let backups = premigration_backups(&storage);
+            assert_eq!(backups.len(), 1, "{id}: one pre-migration backup expected");
+            assert!(backups[0].starts_with(&format!("{id}_v{version}_")));
+            let backup = std::fs::read(
+                storage
+                    .config
+                    .storage_path
+                    .join("backups")
+                    .join(&backups[0]),
+            );
+            assert_eq!(backup.unwrap(), sealed);
+
+            // The upgraded record was stored, so loading again does not migrate
+            let stored = storage.backend.get(key.as_bytes()).unwrap().unwrap();
+            let stored: Value = serde_json::from_slice(
+                &open(storage.key().unwrap(), key.as_bytes(), &stored).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(stored["version"], WALLET_SCHEMA_VERSION);
+            assert!(stored.get("next_indices").is_none());
+            storage.load_wallet_data(id).unwrap();
+            assert_eq!(premigration_backups(&storage).len(), 1);
+        }
+
+        // Account 0 receive indices of version 1 records become chain indices
+        let (storage, _temp_dir) = create_test_storage();
+        let sealed = seal(
+            storage.key().unwrap(),
+            b"wallet:indices",
+            FIXTURES[1].2.as_bytes(),
+        )
+        .unwrap();
+        storage.backend.put(b"wallet:indices", &sealed).unwrap();
+        let data = storage.load_wallet_data("indices").unwrap();
+        assert_eq!(data.network, Some(Network::Regtest));
+        assert_eq!(
+            data.chain_indices,
+            vec![
+                ChainIndex {
+                    account: 0,
+                    address_type: AddressType::NativeSegwit,
+                    chain: Chain::External,
+                    next: 5,
+                    used: Vec::new(),
+                },
+                ChainIndex {
+                    account: 0,
+                    address_type: AddressType::Taproot,
+                    chain: Chain::External,
+                    next: 2,
+                    used: Vec::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_refuses_future_versions() {
+        let (storage, _temp_dir) = create_test_storage();
+        let mut future = create_test_wallet_data();
+        future.version = WALLET_SCHEMA_VERSION + 1;
+        storage.save_wallet_data(&future).unwrap();
+        let stored = storage
+            .backend
+            .get(format!("wallet:{}", future.id).as_bytes())
+            .unwrap();
+
+        assert!(matches!(
+            storage.load_wallet_data(&future.id),
+            Err(StorageError::UnsupportedVersion { found, supported })
+                if found == WALLET_SCHEMA_VERSION + 1 && supported == WALLET_SCHEMA_VERSION
+        ));
+        // Nothing was rewritten or backed up
+        assert_eq!(
+            storage
+                .backend
+                .get(format!("wallet:{}", future.id).as_bytes())
+                .unwrap(),
+            stored
+        );
+        assert!(premigration_backups(&storage).is_empty());
+    }
 }
@@ -10,9 +10,18 @@
 use crate::error::{TransactionError, TransactionResult};
 use crate::transaction::psbt::PsbtV2;
 use crate::wallet::descriptor_wallet::{AddressType, Utxo, Wallet};
-use bitcoin::{absolute::LockTime, Address, Amount, FeeRate, TxOut};
+use crate::wallet::silent_payments::{self, SilentPaymentAddress};
+use bitcoin::{
+    absolute::LockTime, secp256k1::Secp256k1, Address, Amount, FeeRate, OutPoint, PublicKey,
+    ScriptBuf, TxOut,
+};
+use miniscript::descriptor::{DefiniteDescriptorKey, Descriptor};
+use miniscript::psbt::{PsbtInputExt, PsbtOutputExt};
 use std::sync::{Arc, RwLock};
 
+/// Weight of an input without its scriptSig and witness: outpoint, script length and sequence
+pub const INPUT_BASE_WEIGHT: u64 = 4 * (36 + 1 + 4);
+
 /// Fee estimation strategy
 #[derive(Debug, Clone, Copy)]
 pub enum FeeStrategy {
@@ -79,6 +88,8 @@ pub struct TransactionBuilder {
     psbt: PsbtV2,
     /// Transaction outputs (recipient, amount)
     outputs: Vec<(Address, Amount)>,
+    /// Silent payment outputs (recipient, amount), derived once inputs are known
+    silent_payments: Vec<(SilentPaymentAddress, Amount)>,
     /// Selected UTXOs for inputs
     selected_utxos: Vec<Utxo>,
     /// Builder configuration
@@ -87,8 +98,12 @@ pub struct TransactionBuilder {
     estimated_fee: Option<Amount>,
     /// Change output if needed
     change_output: Option<(Address, Amount)>,
+    /// Address receiving the change instead of a fresh native segwit change address
+    change_address: Option<Address>,
     /// Explicit locktime
     locktime: Option<LockTime>,
+    /// Inputs chosen by the user, bypassing coin selection
+    pinned_inputs: Vec<OutPoint>,
 }
 
 impl TransactionBuilder {
@@ -98,11 +113,14 @@ impl TransactionBuilder {
             wallet,
             psbt: PsbtV2::new()?,
             outputs: Vec::new(),
+            silent_payments: Vec::new(),
             selected_utxos: Vec::new(),
             config: BuilderConfig::default(),
             estimated_fee: None,
             change_output: None,
+            change_address: None,
             locktime: None,
+            pinned_inputs: Vec::new(),
         })
     }
 
@@ -115,11 +133,14 @@ impl TransactionBuilder {
             wallet,
             psbt: PsbtV2::new()?,
             outputs: Vec::new(),
+            silent_payments: Vec::new(),
             selected_utxos: Vec::new(),
             config,
             estimated_fee: None,
             change_output: None,
+            change_address: None,
             locktime: None,
+            pinned_inputs: Vec::new(),
         })
     }
 
@@ -139,6 +160,37 @@ impl TransactionBuilder {
         Ok(self)
     }
 
+    /// Add a payment to a silent payment (BIP-352) address
+    ///
+    /// The output script depends on the inputs, so it is derived when the
+    /// PSBT is built. This needs the private keys of the inputs: the wallet
+    /// must be unlocked and cannot be watch-only.
+    pub fn add_silent_payment(
+        &mut self,
+        address: SilentPaymentAddress,
+        amount: Amount,
+    ) -> TransactionResult<&mut Self> {
+        if amount == Amount::ZERO {
+            return Err(TransactionError::InvalidAmount(
+                "Cannot send zero amount".to_string(),
+            ));
+        }
+
+        let network = self
+            .wallet
+            .read()
+            .map_err(|_| TransactionError::InvalidInput("Failed to read wallet".to_string()))?
+            .network;
+        if !address.is_valid_for_network(network) {
+            return Err(TransactionError::InvalidInput(format!(
+                "Silent payment address {address} is not for {network:?}"
+            )));
+        }
+
+        self.silent_payments.push((address, amount));
+        Ok(self)
+    }
+
     /// Set fee strategy
     pub fn fee_strategy(&mut self, strategy: FeeStrategy) -> &mut Self {
         self.config.fee_strategy = strategy;
@@ -163,6 +215,24 @@ impl TransactionBuilder {
         self
     }
 
+    /// Spend exactly these outpoints instead of running coin selection
+    ///
+    /// Pinned UTXOs must belong to the wallet and must not be frozen; the
+    /// confirmation requirement does not apply to them.
+    pub fn pin_inputs(&mut self, outpoints: Vec<OutPoint>) -> &mut Self {
+        self.pinned_inputs = outpoints;
+        self
+    }
+
+    /// Send the change to this address
+    ///
+    /// Without recipients and with pinned inputs, everything but the fee goes
+    /// to this address, which merges the inputs into one output.
+    pub fn change_address(&mut self, address: Address) -> &mut Self {
+        self.change_address = Some(address);
+        self
+    }
+
     /// Estimate transaction fee based on current configuration
     pub fn estimate_fee(&mut self) -> TransactionResult<Amount> {
         // First, determine fee rate
@@ -186,7 +256,9 @@ impl TransactionBuilder {
         let total_output = self
             .outputs
             .iter()
-            .try_fold(Amount::ZERO, |acc, (_, amount)| acc.checked_add(*amount))
+            .map(|(_, amount)| *amount)
+            .chain(self.silent_payments.iter().map(|(_, amount)| *amount))
+            .try_fold(Amount::ZERO, |acc, amount| acc.checked_add(amount))
             .ok_or_else(|| TransactionError::InvalidAmount("Output amount overflow".to_string()))?;
 
         // Estimate fee if not already done
@@ -196,26 +268,45 @@ impl TransactionBuilder {
 
         let estimated_fee = self.estimated_fee.unwrap();
 
-        // Get available UTXOs from wallet (estimate current height for now)
-        let current_height = 800_000; // TODO: Get actual current block height
-        let available_utxos = {
-            let wallet = self
-                .wallet
-                .read()
-                .map_err(|_| TransactionError::InvalidInput("Failed to read wallet".to_string()))?;
-            let utxos = wallet.spendable_utxos(self.config.min_confirmations, current_height);
-            utxos.into_iter().cloned().collect::<Vec<Utxo>>()
-        };
+        let change_amount = if self.pinned_inputs.is_empty() {
+            // Get available UTXOs from wallet (estimate current height for now)
+            let current_height = 800_000; // TODO: Get actual current block height
+            let available_utxos = {
+                let wallet = self.wallet.read().map_err(|_| {
+                    TransactionError::InvalidInput("Failed to read wallet".to_string())
+                })?;
+                let utxos = wallet.spendable_utxos(self.config.min_confirmations, current_height);
+                utxos.into_iter().cloned().collect::<Vec<Utxo>>()
+            };
 
-        // Select UTXOs based on strategy
-        let (selected_utxos, change_amount) =
-            self.perform_coin_selection(&available_utxos, total_output, estimated_fee)?;
+            // Select UTXOs based on strategy
+            let (selected_utxos, change_amount) =
+                self.perform_coin_selection(&available_utxos, total_output, estimated_fee)?;
 
-        self.selected_utxos = selected_utxos;
+            self.selected_utxos = selected_utxos;
+            change_amount
+        } else {
+            self.selected_utxos = self.pinned_utxos()?;
+
+            // Re-estimate now that the input count is known
+            let total_needed = total_output
+                .checked_add(self.estimate_fee()?)
+                .ok_or_else(|| TransactionError::InvalidAmount("Fee overflow".to_string()))?;
+            let total_input = self.total_input_value();
+            if total_input < total_needed {
+                return Err(TransactionError::InsufficientFunds {
+                    available: total_input.to_sat(),
+                    required: total_needed.to_sat(),
+                });
+            }
+            total_input - total_needed
+        };
 
         // Create change output if needed
         if change_amount > Amount::ZERO {
-            let change_address = {
+            let change_address = if let Some(address) = &self.change_address {
+                address.clone()
+            } else {
                 let mut wallet = self.wallet.write().map_err(|_| {
                     TransactionError::InvalidInput("Failed to write wallet".to_string())
                 })?;
@@ -241,6 +332,12 @@ impl TransactionBuilder {
         // Clear existing PSBT
         self.psbt = PsbtV2::new()?;
 
+        // Silent payment input keys erase the wallet's keys once its unlock has expired
+        let mut wallet = self
+            .wallet
+            .write()
+            .map_err(|_| TransactionError::InvalidInput("Failed to write wallet".to_string()))?;
+
         // Add inputs from selected UTXOs
         for utxo in &self.selected_utxos {
             self.psbt
@@ -253,6 +350,32 @@ impl TransactionBuilder {
                 script_pubkey: utxo.script_pubkey.clone(),
             };
             self.psbt.set_witness_utxo(input_index, txout)?;
+
+            // Silent payment outputs are spent with a tweaked key the wallet
+            // signs for directly; they have no descriptor
+            if wallet.silent_payment_output(&utxo.script_pubkey).is_some() {
+                continue;
+            }
+
+            // Key origins and scripts from the spending descriptor let an
+            // offline signer find its keys for this input
+            let descriptor = spending_descriptor(&wallet, &utxo.script_pubkey)?;
+            let mut update = bitcoin::psbt::Input::default();
+            update
+                .update_with_descriptor_unchecked(&descriptor)
+                .map_err(|e| TransactionError::InvalidInput(format!("Invalid descriptor: {e}")))?;
+
+            let input = &mut self.psbt.inputs[input_index];
+            input.redeem_script = update.redeem_script;
+            input.witness_script = update.witness_script;
+            input.bip32_derivation = update
+                .bip32_derivation
+                .into_iter()
+                .map(|(key, origin)| (PublicKey::new(key), origin))
+                .collect();
+            input.tap_internal_key = update.tap_internal_key;
+            input.tap_key_origins = update.tap_key_origins.into_iter().collect();
+            input.tap_scripts = update.tap_scripts.into_iter().collect();
         }
 
         // Add recipient outputs
@@ -260,11 +383,69 @@ impl TransactionBuilder {
             self.psbt.add_output(*amount, address.script_pubkey())?;
         }
 
+        // Silent payment outputs are derived from the keys of all inputs
+        if !self.silent_payments.is_empty() {
+            let outpoints: Vec<OutPoint> = self.selected_utxos.iter().map(Utxo::outpoint).collect();
+            let input_keys = self
+                .selected_utxos
+                .iter()
+                .filter_map(|utxo| {
+                    wallet
+                        .silent_payment_input_key(&utxo.script_pubkey)
+                        .transpose()
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| TransactionError::InvalidInput(format!("Silent payment: {e}")))?;
+            let recipients: Vec<SilentPaymentAddress> = self
+                .silent_payments
+                .iter()
+                .map(|(address, _)| *address)
+                .collect();
+            let scripts = silent_payments::derive_outputs(
+                &Secp256k1::new(),
+                &outpoints,
+                &input_keys,
+                &recipients,
+            )
+            .map_err(|e| TransactionError::InvalidInput(format!("Silent payment: {e}")))?;
+            for (script, (_, amount)) in scripts.into_iter().zip(&self.silent_payments) {
+                self.psbt.add_output(*amount, script)?;
+            }
+        }
+
         // Add change output if needed
         if let Some((change_address, change_amount)) = &self.change_output {
             self.psbt
                 .add_output(*change_amount, change_address.script_pubkey())?;
+
+            // Mark the change output as ours for the signer
+            if wallet.is_mine(&change_address.script_pubkey()) {
+                let descriptor = spending_descriptor(&wallet, &change_address.script_pubkey())?;
+                let mut update = bitcoin::psbt::Output::default();
+                update
+                    .update_with_descriptor_unchecked(&descriptor)
+                    .map_err(|e| {
+                        TransactionError::InvalidInput(format!("Invalid descriptor: {e}"))
+                    })?;
+
+                let output = self
+                    .psbt
+                    .outputs
+                    .last_mut()
+                    .expect("Change output just added");
+                output.redeem_script = update.redeem_script;
+                output.witness_script = update.witness_script;
+                output.bip32_derivation = update
+                    .bip32_derivation
+                    .into_iter()
+                    .map(|(key, origin)| (PublicKey::new(key), origin))
+                    .collect();
+                output.tap_internal_key = update.tap_internal_key;
+                output.tap_tree = update.tap_tree;
+                output.tap_key_origins = update.tap_key_origins.into_iter().collect();
+            }
         }
+        drop(wallet);
 
         // Set locktime
         if let Some(locktime) = self.locktime {
@@ -281,6 +462,11 @@ impl TransactionBuilder {
         Ok(self.psbt.clone())
     }
 
+    /// Get UTXOs selected as inputs
+    pub fn selected_utxos(&self) -> &[Utxo] {
+        &self.selected_utxos
+    }
+
     /// Get total input value
     pub fn total_input_value(&self) -> Amount {
         self.selected_utxos
@@ -293,7 +479,9 @@ impl TransactionBuilder {
         let recipient_total = self
             .outputs
             .iter()
-            .fold(Amount::ZERO, |acc, (_, amount)| acc + *amount);
+            .map(|(_, amount)| *amount)
+            .chain(self.silent_payments.iter().map(|(_, amount)| *amount))
+            .fold(Amount::ZERO, |acc, amount| acc + amount);
 
         let change_amount = self
             .change_output
@@ -346,20 +534,44 @@ impl TransactionBuilder {
         let mut size = 10; // version (4) + input count (1) + output count (1) + locktime (4)
 
         // Input sizes (estimate based on script type)
-        let input_count = self.selected_utxos.len().max(1); // At least 1 for estimation
-        for _ in 0..input_count {
-            // Conservative estimate: 148 bytes per input (P2WPKH)
+        if self.selected_utxos.is_empty() {
+            // Conservative estimate for one input: 148 bytes (P2PKH)
             size += 148;
+        } else {
+            let wallet = self
+                .wallet
+                .read()
+                .map_err(|_| TransactionError::InvalidInput("Failed to read wallet".to_string()))?;
+            for utxo in &self.selected_utxos {
+                size += match wallet.satisfaction_weight(&utxo.script_pubkey) {
+                    // Outpoint, script length and sequence, plus the satisfaction
+                    Ok(Some(weight)) => (INPUT_BASE_WEIGHT + weight).div_ceil(4) as usize,
+                    _ => 148,
+                };
+            }
         }
 
-        // Output sizes
-        let output_count = self.outputs.len() + if self.change_output.is_some() { 1 } else { 0 };
-        for _ in 0..output_count.max(1) {
-            // Conservative estimate: 34 bytes per output (P2WPKH)
-            size += 34;
+        // Output sizes: value (8) + script length (1) + script
+        let mut output_sizes: Vec<usize> = self
+            .outputs
+            .iter()
+            .map(|(address, _)| 9 + address.script_pubkey().len())
+            .collect();
+        output_sizes.extend(self.silent_payments.iter().map(|_| 9 + 34)); // P2TR
+        if let Some(address) = self
+            .change_output
+            .as_ref()
+            .map(|(address, _)| address)
+            .or(self.change_address.as_ref())
+        {
+            output_sizes.push(9 + address.script_pubkey().len());
+        }
+        if output_sizes.is_empty() {
+            // Conservative estimate: 34 bytes per output (P2PKH)
+            output_sizes.push(34);
         }
 
-        Ok(size)
+        Ok(size + output_sizes.iter().sum::<usize>())
     }
 
     /// Perform coin selection based on strategy
@@ -376,20 +588,20 @@ impl TransactionBuilder {
         // Sort based on strategy
         match self.config.coin_selection {
             CoinSelectionStrategy::LargestFirst => {
-                sorted_utxos.sort_by(|a, b| b.value.cmp(&a.value));
+                sorted_utxos.sort_by_key(|utxo| std::cmp::Reverse(utxo.value));
             }
             CoinSelectionStrategy::SmallestFirst => {
-                sorted_utxos.sort_by(|a, b| a.value.cmp(&b.value));
+                sorted_utxos.sort_by_key(|utxo| utxo.value);
             }
             CoinSelectionStrategy::BranchAndBound => {
                 // For now, use largest first as approximation
                 // TODO: Implement proper branch and bound algorithm
-                sorted_utxos.sort_by(|a, b| b.value.cmp(&a.value));
+                sorted_utxos.sort_by_key(|utxo| std::cmp::Reverse(utxo.value));
             }
             CoinSelectionStrategy::Random => {
                 // For now, use largest first as approximation
                 // TODO: Implement proper random selection
-                sorted_utxos.sort_by(|a, b| b.value.cmp(&a.value));
+                sorted_utxos.sort_by_key(|utxo| std::cmp::Reverse(utxo.value));
             }
         }
 
@@ -424,6 +636,30 @@ impl TransactionBuilder {
         Ok((selected, change))
     }
 
+    /// Resolve pinned outpoints to wallet UTXOs
+    fn pinned_utxos(&self) -> TransactionResult<Vec<Utxo>> {
+        let wallet = self
+            .wallet
+            .read()
+            .map_err(|_| TransactionError::InvalidInput("Failed to read wallet".to_string()))?;
+
+        self.pinned_inputs
+            .iter()
+            .map(|outpoint| {
+                if wallet.is_frozen(outpoint) {
+                    return Err(TransactionError::CoinSelection(format!(
+                        "UTXO {outpoint} is frozen"
+                    )));
+                }
+                wallet.utxo(outpoint).cloned().ok_or_else(|| {
+                    TransactionError::CoinSelection(format!(
+                        "UTXO {outpoint} does not belong to the wallet"
+                    ))
+                })
+            })
+            .collect()
+    }
+
     /// Get sequence number (enables RBF if configured)
     fn get_sequence_number(&self) -> Option<u32> {
         if self.config.enable_rbf {
@@ -475,3 +711,16 @@ impl FeeEstimator {
         weight.div_ceil(4)
     }
 }
+
+/// Look up the descriptor spending a wallet script
+fn spending_descriptor(
+    wallet: &Wallet,
+    script: &ScriptBuf,
+) -> TransactionResult<Descriptor<DefiniteDescriptorKey>> {
+    wallet
+        .spending_descriptor(script)
+        .map_err(|e| {
+            TransactionError::InvalidInput(format!("Unknown descriptor for {script}: {e}"))
+        })?
+        .ok_or_else(|| TransactionError::InvalidInput(format!("Script {script} is not ours")))
+}
@@ -0,0 +1,306 @@
+use crate::error::{TransactionError, TransactionResult};
+/// UTXO consolidation planning
+///
+/// Merging many small UTXOs into one while fees are low saves fees later,
+/// when the coins would otherwise be spent one input at a time at higher
+/// rates. The planner only merges UTXOs the wallet already keeps together:
+/// those of the same account and script kind (address type, descriptor
+/// keychain or silent payments) carrying the same label and source. Each
+/// group is merged into a fresh change address of its own kind, so a
+/// consolidation never links coins across those boundaries.
+use crate::transaction::builder::{FeeStrategy, TransactionBuilder, INPUT_BASE_WEIGHT};
+use crate::transaction::psbt::PsbtV2;
+use crate::wallet::descriptor_wallet::{AddressType, Chain, Utxo, Wallet};
+use bitcoin::{Amount, FeeRate, OutPoint};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+/// Consolidation parameters
+#[derive(Debug, Clone)]
+pub struct ConsolidationConfig {
+    /// Highest fee rate a consolidation may pay
+    pub max_fee_rate: FeeRate,
+    /// Fee rate expected when the coins would otherwise be spent
+    pub future_fee_rate: FeeRate,
+    /// Fewest UTXOs worth merging in one transaction
+    pub min_inputs: usize,
+    /// Most UTXOs merged in one transaction
+    pub max_inputs: usize,
+    /// Only UTXOs up to this value are merged
+    pub max_utxo_value: Option<Amount>,
+    /// Minimum confirmations of merged UTXOs
+    pub min_confirmations: u32,
+}
+
+impl Default for ConsolidationConfig {
+    fn default() -> Self {
+        Self {
+            max_fee_rate: FeeRate::from_sat_per_vb(5).expect("Valid fee rate"),
+            future_fee_rate: FeeRate::from_sat_per_vb(20).expect("Valid fee rate"),
+            min_inputs: 3,
+            max_inputs: 100,
+            max_utxo_value: None,
+            min_confirmations: 1,
+        }
+    }
+}
+
+/// Kind of script paid by a group of UTXOs
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ScriptKind {
+    /// One of the built-in single-key address types
+    AddressType(AddressType),
+    /// A descriptor keychain
+    Keychain(String),
+    /// Silent payment outputs
+    SilentPayment,
+}
+
+/// Boundary a consolidation must not cross
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConsolidationBoundary {
+    /// Wallet account (None for descriptor keychains)
+    pub account: Option<u32>,
+    /// Kind of script the UTXOs pay
+    pub kind: ScriptKind,
+    /// UTXO label, or the address book label of the receiving address
+    pub label: Option<String>,
+    /// Where the coins came from
+    pub source: Option<String>,
+}
+
+/// Proposed consolidation transaction
+#[derive(Debug, Clone)]
+pub struct ConsolidationGroup {
+    /// Boundary shared by the merged UTXOs
+    pub boundary: ConsolidationBoundary,
+    /// UTXOs to merge, smallest first
+    pub utxos: Vec<Utxo>,
+    /// Estimated virtual size of the consolidation
+    pub vsize: u64,
+    /// Estimated fee at the planned fee rate
+    pub fee: Amount,
+    /// Fees saved at the future fee rate by spending one merged output
+    /// instead of every UTXO, net of the consolidation fee
+    pub savings: Amount,
+}
+
+impl ConsolidationGroup {
+    /// Total value of the merged UTXOs
+    pub fn total(&self) -> Amount {
+        Amount::from_sat(self.utxos.iter().map(|utxo| utxo.value).sum())
+    }
+
+    /// Outpoints of the merged UTXOs
+    pub fn outpoints(&self) -> Vec<OutPoint> {
+        self.utxos.iter().map(Utxo::outpoint).collect()
+    }
+}
+
+/// Consolidations worth making at a fee rate
+#[derive(Debug, Clone)]
+pub struct ConsolidationPlan {
+    /// Fee rate the consolidations pay
+    pub fee_rate: FeeRate,
+    /// Proposed transactions, largest savings first
+    pub groups: Vec<ConsolidationGroup>,
+}
+
+impl ConsolidationPlan {
+    /// Total fee of all proposed transactions
+    pub fn fee(&self) -> Amount {
+        self.groups.iter().map(|group| group.fee).sum()
+    }
+
+    /// Total estimated savings of all proposed transactions
+    pub fn savings(&self) -> Amount {
+        self.groups.iter().map(|group| group.savings).sum()
+    }
+}
+
+/// Plans and builds UTXO consolidations for a wallet
+pub struct ConsolidationPlanner {
+    wallet: Arc<RwLock<Wallet>>,
+    config: ConsolidationConfig,
+}
+
+impl ConsolidationPlanner {
+    /// Create a planner for a wallet
+    pub fn new(wallet: Arc<RwLock<Wallet>>, config: ConsolidationConfig) -> Self {
+        Self { wallet, config }
+    }
+
+    /// Propose consolidations paying `fee_rate`
+    ///
+    /// Fails if the fee rate is above the configured ceiling. UTXOs that are
+    /// frozen, unconfirmed, above the value limit or worth less than the fee
+    /// to spend them are left alone; groups with fewer than `min_inputs`
+    /// UTXOs or without positive savings are not proposed.
+    pub fn plan(
+        &self,
+        fee_rate: FeeRate,
+        current_height: u32,
+    ) -> TransactionResult<ConsolidationPlan> {
+        self.check_fee_rate(fee_rate)?;
+
+        let wallet = self
+            .wallet
+            .read()
+            .map_err(|_| TransactionError::InvalidInput("Failed to read wallet".to_string()))?;
+
+        // UTXOs with the virtual size of the input spending them
+        let mut candidates: BTreeMap<ConsolidationBoundary, Vec<(Utxo, u64)>> = BTreeMap::new();
+        for utxo in wallet.spendable_utxos(self.config.min_confirmations, current_height) {
+            if self
+                .config
+                .max_utxo_value
+                .is_some_and(|max| utxo.value > max.to_sat())
+            {
+                continue;
+            }
+            let Some(boundary) = boundary(&wallet, utxo) else {
+                continue;
+            };
+            let Ok(Some(weight)) = wallet.satisfaction_weight(&utxo.script_pubkey) else {
+                continue;
+            };
+            let input_vsize = (INPUT_BASE_WEIGHT + weight).div_ceil(4);
+            // Merging coins worth less than their spend cost only loses value
+            if fee(fee_rate, input_vsize) >= Amount::from_sat(utxo.value) {
+                continue;
+            }
+            candidates
+                .entry(boundary)
+                .or_default()
+                .push((utxo.clone(), input_vsize));
+        }
+
+        let mut groups = Vec::new();
+        for (boundary, mut utxos) in candidates {
+            utxos.sort_by_key(|(utxo, _)| utxo.value);
+            utxos.truncate(self.config.max_inputs);
+            if utxos.len() < self.config.min_inputs.max(2) {
+                continue;
+            }
+
+            // The merged output pays a script of the same kind, so it costs
+            // as much to spend as the largest of the merged inputs
+            let merged_input = utxos.iter().map(|(_, vsize)| *vsize).max().unwrap_or(0);
+            let output_vsize = 9 + utxos[0].0.script_pubkey.len() as u64;
+            let inputs_vsize: u64 = utxos.iter().map(|(_, vsize)| vsize).sum();
+            let vsize = 10 + inputs_vsize + output_vsize;
+
+            let consolidation_fee = fee(fee_rate, vsize);
+            let future = self.config.future_fee_rate;
+            let savings = fee(future, inputs_vsize)
+                .checked_sub(fee(future, merged_input))
+                .and_then(|saved| saved.checked_sub(consolidation_fee))
+                .unwrap_or(Amount::ZERO);
+            if savings == Amount::ZERO {
+                continue;
+            }
+
+            groups.push(ConsolidationGroup {
+                boundary,
+                utxos: utxos.into_iter().map(|(utxo, _)| utxo).collect(),
+                vsize,
+                fee: consolidation_fee,
+                savings,
+            });
+        }
+        groups.sort_by_key(|group| std::cmp::Reverse(group.savings));
+
+        Ok(ConsolidationPlan { fee_rate, groups })
+    }
+
+    /// Build the unsigned PSBT merging a proposed group
+    ///
+    /// The inputs pay into the next change address of the group's account
+    /// and script kind; silent payment outputs are merged into a Taproot
+    /// change address of their account. Fails while the change chain holds
+    /// a full gap of unused addresses, as change is never reused.
+    pub fn build_psbt(
+        &self,
+        group: &ConsolidationGroup,
+        fee_rate: FeeRate,
+    ) -> TransactionResult<PsbtV2> {
+        self.check_fee_rate(fee_rate)?;
+
+        let destination = {
+            let mut wallet = self.wallet.write().map_err(|_| {
+                TransactionError::InvalidInput("Failed to write wallet".to_string())
+            })?;
+            let account = group.boundary.account.unwrap_or(0);
+            match &group.boundary.kind {
+                ScriptKind::AddressType(address_type) => {
+                    wallet.get_account_change_address(account, *address_type)
+                }
+                ScriptKind::Keychain(name) => {
+                    // Receive-only keychains consolidate into a receive address
+                    let change = wallet
+                        .keychain(name)
+                        .is_some_and(|keychain| keychain.descriptor(Chain::Internal).is_some());
+                    wallet.keychain_address(name, change)
+                }
+                ScriptKind::SilentPayment => {
+                    wallet.get_account_change_address(account, AddressType::Taproot)
+                }
+            }
+            .map_err(|e| {
+                TransactionError::InvalidInput(format!("Failed to get consolidation address: {e}"))
+            })?
+        };
+
+        let mut builder = TransactionBuilder::new(self.wallet.clone())?;
+        builder
+            .fee_strategy(FeeStrategy::FeeRate(fee_rate))
+            .change_address(destination)
+            .pin_inputs(group.outpoints());
+        builder.build_psbt()
+    }
+}
+
+impl ConsolidationPlanner {
+    fn check_fee_rate(&self, fee_rate: FeeRate) -> TransactionResult<()> {
+        if fee_rate > self.config.max_fee_rate {
+            return Err(TransactionError::FeeEstimation(format!(
+                "Fee rate of {} sat/vB is above the consolidation ceiling of {} sat/vB",
+                fee_rate.to_sat_per_vb_ceil(),
+                self.config.max_fee_rate.to_sat_per_vb_ceil()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Consolidation boundary of a wallet UTXO, if the wallet can spend it
+fn boundary(wallet: &Wallet, utxo: &Utxo) -> Option<ConsolidationBoundary> {
+    let (account, kind) = if let Some(output) = wallet.silent_payment_output(&utxo.script_pubkey) {
+        (Some(output.account), ScriptKind::SilentPayment)
+    } else if let Some((name, _, _)) = wallet.keychain_position(&utxo.script_pubkey) {
+        (None, ScriptKind::Keychain(name.to_string()))
+    } else {
+        let ((account, address_type, _), _) = wallet.chain_position(&utxo.derivation_path)?;
+        (Some(account), ScriptKind::AddressType(address_type))
+    };
+
+    let metadata = wallet.utxo_metadata(&utxo.outpoint());
+    let label = metadata
+        .and_then(|metadata| metadata.label.clone())
+        .or_else(|| {
+            wallet
+                .address_label(&utxo.address.to_string())
+                .map(str::to_string)
+        });
+    Some(ConsolidationBoundary {
+        account,
+        kind,
+        label,
+        source: metadata.and_then(|metadata| metadata.source.clone()),
+    })
+}
+
+/// Fee for a virtual size, saturating on overflow
+fn fee(fee_rate: FeeRate, vsize: u64) -> Amount {
+    fee_rate.fee_vb(vsize).unwrap_or(Amount::MAX_MONEY)
+}
@@ -2,6 +2,7 @@
 ///
 /// This module handles PSBT v2 transaction creation and processing.
 pub mod builder;
+pub mod consolidation;
 pub mod psbt;
 
 #[cfg(test)]
@@ -9,4 +10,5 @@ mod tests;
 
 // Re-exports for convenience
 pub use builder::TransactionBuilder;
+pub use consolidation::{ConsolidationConfig, ConsolidationPlanner};
 pub use psbt::PsbtV2;
@@ -9,8 +9,8 @@ use bitcoin::{
     bip32::{DerivationPath, Fingerprint},
     ecdsa::Signature as EcdsaSignature,
     taproot::{ControlBlock, LeafVersion, Signature as TaprootSignature, TapLeafHash, TapTree},
-    Amount, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
-    XOnlyPublicKey,
+    Amount, OutPoint, Psbt, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
+    Witness, XOnlyPublicKey,
 };
 use psbt::PsbtSighashType;
 use std::collections::HashMap;
@@ -65,6 +65,8 @@ pub struct PsbtV2Input {
     pub tap_scripts: HashMap<ControlBlock, (ScriptBuf, LeafVersion)>,
     /// Taproot BIP32 derivations
     pub tap_key_origins: HashMap<XOnlyPublicKey, (Vec<TapLeafHash>, (Fingerprint, DerivationPath))>,
+    /// Taproot internal key
+    pub tap_internal_key: Option<XOnlyPublicKey>,
     /// Required time locktime
     pub required_time_locktime: Option<u32>,
     /// Required height locktime
@@ -156,6 +158,7 @@ impl PsbtV2 {
             tap_script_sigs: HashMap::new(),
             tap_scripts: HashMap::new(),
             tap_key_origins: HashMap::new(),
+            tap_internal_key: None,
             required_time_locktime: None,
             required_height_locktime: None,
             previous_txid,
@@ -325,6 +328,80 @@ impl PsbtV2 {
             output: outputs,
         })
     }
+
+    /// Convert to a version 0 PSBT (BIP-174) for exchange with external signers
+    ///
+    /// Unsigned transaction data, UTXOs, scripts, key origins and any
+    /// signatures are carried over; finalized inputs keep their final scripts.
+    pub fn to_psbt(&self) -> TransactionResult<Psbt> {
+        let unsigned_tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: LockTime::from_consensus(self.fallback_locktime.unwrap_or(0)),
+            input: self
+                .inputs
+                .iter()
+                .map(|input| TxIn {
+                    previous_output: OutPoint {
+                        txid: input.previous_txid,
+                        vout: input.previous_output_index,
+                    },
+                    script_sig: ScriptBuf::new(),
+                    sequence: Sequence(input.sequence.unwrap_or(0xffffffff)),
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: self
+                .outputs
+                .iter()
+                .map(|output| TxOut {
+                    value: output.amount,
+                    script_pubkey: output.script.clone(),
+                })
+                .collect(),
+        };
+
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).map_err(|e| {
+            TransactionError::InvalidPsbt(format!("Invalid unsigned transaction: {e}"))
+        })?;
+
+        for (target, input) in psbt.inputs.iter_mut().zip(&self.inputs) {
+            target.witness_utxo = input.witness_utxo.clone();
+            target.non_witness_utxo = input.non_witness_utxo.clone();
+            target.partial_sigs = input.partial_sigs.clone().into_iter().collect();
+            target.sighash_type = input
+                .sighash_type
+                .map(|sighash| bitcoin::psbt::PsbtSighashType::from_u32(sighash.to_u32()));
+            target.redeem_script = input.redeem_script.clone();
+            target.witness_script = input.witness_script.clone();
+            target.bip32_derivation = input
+                .bip32_derivation
+                .iter()
+                .map(|(key, origin)| (key.inner, origin.clone()))
+                .collect();
+            target.final_script_sig = input.final_script_sig.clone();
+            target.final_script_witness = input.final_script_witness.clone();
+            target.tap_key_sig = input.tap_key_sig;
+            target.tap_script_sigs = input.tap_script_sigs.clone().into_iter().collect();
+            target.tap_scripts = input.tap_scripts.clone().into_iter().collect();
+            target.tap_key_origins = input.tap_key_origins.clone().into_iter().collect();
+            target.tap_internal_key = input.tap_internal_key;
+        }
+
+        for (target, output) in psbt.outputs.iter_mut().zip(&self.outputs) {
+            target.redeem_script = output.redeem_script.clone();
+            target.witness_script = output.witness_script.clone();
+            target.bip32_derivation = output
+                .bip32_derivation
+                .iter()
+                .map(|(key, origin)| (key.inner, origin.clone()))
+                .collect();
+            target.tap_internal_key = output.tap_internal_key;
+            target.tap_tree = output.tap_tree.clone();
+            target.tap_key_origins = output.tap_key_origins.clone().into_iter().collect();
+        }
+
+        Ok(psbt)
+    }
 }
 
 impl Default for PsbtV2 {
@@ -497,3 +497,511 @@ mod integration_tests {
         assert!(true);
     }
 }
+
+#[cfg(test)]
+mod watch_only_tests {
+    use super::*;
+    use crate::wallet::descriptor_wallet::{AddressType, Utxo};
+    use bitcoin::Psbt;
+    use tempfile::tempdir;
+    use test_utils::*;
+
+    fn storage_at(path: &std::path::Path) -> WalletStorage {
+        WalletStorage::new(StorageConfig {
+            storage_path: path.to_path_buf(),
+            auto_backup: false,
+            backup_count: 0,
+        })
+        .expect("Failed to create storage")
+    }
+
+    #[test]
+    fn test_watch_only_builds_unsigned_psbt_with_key_origins() {
+        let signer_dir = tempdir().expect("Failed to create temp dir");
+        let mut signer = Wallet::create_new(
+            "signer".to_string(),
+            crate::Network::Regtest,
+            storage_at(signer_dir.path()),
+        )
+        .expect("Failed to create signer wallet");
+
+        let watcher_dir = tempdir().expect("Failed to create temp dir");
+        let mut watcher = Wallet::watch_only(
+            "watcher".to_string(),
+            crate::Network::Regtest,
+            signer.account_xpubs(),
+            storage_at(watcher_dir.path()),
+        )
+        .expect("Failed to create watch-only wallet");
+
+        // Fund a receive address of the watch-only wallet
+        let address = watcher
+            .get_new_address(AddressType::NativeSegwit)
+            .expect("Failed to get address");
+        let path = watcher.owns_address(&address).expect("Address not owned");
+        watcher.add_utxo(Utxo {
+            txid: create_test_outpoint().txid,
+            vout: 0,
+            value: 100_000,
+            script_pubkey: address.script_pubkey(),
+            address: address.clone(),
+            derivation_path: path.clone(),
+            block_height: Some(700_000),
+        });
+
+        let mut builder = TransactionBuilder::new(Arc::new(RwLock::new(watcher)))
+            .expect("Failed to create transaction builder");
+        let recipient = Address::from_str("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+            .expect("Invalid address")
+            .assume_checked();
+        builder
+            .add_recipient(recipient, Amount::from_sat(50_000))
+            .expect("Failed to add recipient");
+        let psbt = builder.build_psbt().expect("Failed to build PSBT");
+
+        // The input carries the origin the signer needs to find its key
+        let (fingerprint, public_key) = signer.key_origin(&path).expect("Unknown key");
+        let input = &psbt.inputs[0];
+        assert_eq!(
+            input.bip32_derivation.get(&public_key),
+            Some(&(fingerprint, path.clone()))
+        );
+        assert_eq!(
+            signer
+                .get_private_key(&path)
+                .expect("Signer has the key")
+                .public_key(),
+            public_key.inner
+        );
+
+        // Change is marked as belonging to the same wallet
+        assert_eq!(psbt.outputs.len(), 2);
+        assert_eq!(psbt.outputs[1].bip32_derivation.len(), 1);
+
+        // The BIP-174 form survives serialization for transfer to the signer
+        let exported = psbt.to_psbt().expect("Failed to convert PSBT");
+        let decoded = Psbt::deserialize(&exported.serialize()).expect("Failed to decode PSBT");
+        assert_eq!(decoded.unsigned_tx.input.len(), 1);
+        assert_eq!(
+            decoded.inputs[0].bip32_derivation.get(&public_key.inner),
+            Some(&(fingerprint, path))
+        );
+        assert_eq!(
+            decoded.inputs[0]
+                .witness_utxo
+                .as_ref()
+                .unwrap()
+                .value
+                .to_sat(),
+            100_000
+        );
+    }
+}
+
+#[cfg(test)]
+mod coin_control_tests {
+    use super::*;
+    use crate::error::TransactionError;
+    use crate::wallet::descriptor_wallet::{AddressType, Utxo};
+
+    /// Wallet holding three confirmed UTXOs of 100k, 200k and 300k sats
+    fn funded_wallet() -> (Wallet, Vec<OutPoint>) {
+        let mut wallet = test_utils::create_test_wallet().expect("Failed to create wallet");
+        let mut outpoints = Vec::new();
+        for value in [100_000u64, 200_000, 300_000] {
+            let address = wallet
+                .get_new_address(AddressType::NativeSegwit)
+                .expect("Failed to get address");
+            let derivation_path = wallet.owns_address(&address).expect("Address not owned");
+            let txid = Txid::from_str(&format!("{value:064x}")).expect("Valid txid");
+            wallet.add_utxo(Utxo {
+                txid,
+                vout: 0,
+                value,
+                script_pubkey: address.script_pubkey(),
+                address,
+                derivation_path,
+                block_height: Some(700_000),
+            });
+            outpoints.push(OutPoint::new(txid, 0));
+        }
+        (wallet, outpoints)
+    }
+
+    fn recipient() -> Address {
+        Address::from_str("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+            .expect("Invalid address")
+            .assume_checked()
+    }
+
+    #[test]
+    fn test_frozen_utxos_are_not_selected() {
+        let (mut wallet, outpoints) = funded_wallet();
+        wallet.freeze_utxo(&outpoints[2]).expect("Failed to freeze");
+
+        let mut builder = TransactionBuilder::new(Arc::new(RwLock::new(wallet)))
+            .expect("Failed to create transaction builder");
+        builder.coin_selection(CoinSelectionStrategy::LargestFirst);
+        builder
+            .add_recipient(recipient(), Amount::from_sat(150_000))
+            .expect("Failed to add recipient");
+        builder.select_utxos().expect("Coin selection failed");
+
+        // The 300k coin would be picked first if it were not frozen
+        let selected: Vec<OutPoint> = builder
+            .selected_utxos()
+            .iter()
+            .map(Utxo::outpoint)
+            .collect();
+        assert_eq!(selected, vec![outpoints[1]]);
+    }
+
+    #[test]
+    fn test_frozen_funds_cause_insufficient_funds() {
+        let (mut wallet, outpoints) = funded_wallet();
+        wallet.freeze_utxo(&outpoints[2]).expect("Failed to freeze");
+
+        let mut builder = TransactionBuilder::new(Arc::new(RwLock::new(wallet)))
+            .expect("Failed to create transaction builder");
+        builder
+            .add_recipient(recipient(), Amount::from_sat(350_000))
+            .expect("Failed to add recipient");
+        assert!(matches!(
+            builder.select_utxos(),
+            Err(TransactionError::InsufficientFunds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_pinned_inputs_replace_coin_selection() {
+        let (wallet, outpoints) = funded_wallet();
+
+        let mut builder = TransactionBuilder::new(Arc::new(RwLock::new(wallet)))
+            .expect("Failed to create transaction builder");
+        builder
+            .add_recipient(recipient(), Amount::from_sat(50_000))
+            .expect("Failed to add recipient");
+        builder.pin_inputs(vec![outpoints[0], outpoints[1]]);
+        let psbt = builder.build_psbt().expect("Failed to build PSBT");
+
+        let spent: Vec<OutPoint> = psbt
+            .inputs
+            .iter()
+            .map(|input| OutPoint::new(input.previous_txid, input.previous_output_index))
+            .collect();
+        assert_eq!(spent, vec![outpoints[0], outpoints[1]]);
+        assert_eq!(builder.total_input_value(), Amount::from_sat(300_000));
+        assert_eq!(
+            builder.change_amount().expect("Change expected")
+                + builder.fee().expect("Fee expected")
+                + Amount::from_sat(50_000),
+            Amount::from_sat(300_000)
+        );
+    }
+
+    #[test]
+    fn test_pinned_inputs_are_validated() {
+        let (mut wallet, outpoints) = funded_wallet();
+        wallet.freeze_utxo(&outpoints[0]).expect("Failed to freeze");
+        let wallet = Arc::new(RwLock::new(wallet));
+
+        for (pinned, amount) in [
+            (vec![outpoints[0]], 50_000),
+            (vec![test_utils::create_test_outpoint()], 50_000),
+        ] {
+            let mut builder =
+                TransactionBuilder::new(wallet.clone()).expect("Failed to create builder");
+            builder
+                .add_recipient(recipient(), Amount::from_sat(amount))
+                .expect("Failed to add recipient");
+            builder.pin_inputs(pinned);
+            assert!(matches!(
+                builder.select_utxos(),
+                Err(TransactionError::CoinSelection(_))
+            ));
+        }
+
+        // Pinned inputs must cover the payment on their own
+        let mut builder = TransactionBuilder::new(wallet).expect("Failed to create builder");
+        builder
+            .add_recipient(recipient(), Amount::from_sat(250_000))
+            .expect("Failed to add recipient");
+        builder.pin_inputs(vec![outpoints[1]]);
+        assert!(matches!(
+            builder.select_utxos(),
+            Err(TransactionError::InsufficientFunds { .. })
+        ));
+    }
+}
+
+#[cfg(test)]
+mod silent_payment_tests {
+    use super::*;
+    use crate::wallet::descriptor_wallet::{AddressType, Utxo};
+    use miniscript::psbt::PsbtExt;
+
+    fn fund(wallet: &mut Wallet, address_type: AddressType, value: u64) -> OutPoint {
+        let address = wallet
+            .get_new_address(address_type)
+            .expect("Failed to get address");
+        let derivation_path = wallet.owns_address(&address).expect("Address not owned");
+        let txid = Txid::from_str(&format!("{value:064x}")).expect("Valid txid");
+        wallet.add_utxo(Utxo {
+            txid,
+            vout: 1,
+            value,
+            script_pubkey: address.script_pubkey(),
+            address,
+            derivation_path,
+            block_height: Some(700_000),
+        });
+        OutPoint::new(txid, 1)
+    }
+
+    /// Sign, finalize and extract a PSBT built by a wallet's transaction builder
+    fn sign(wallet: &Arc<RwLock<Wallet>>, psbt: &PsbtV2) -> Transaction {
+        let mut psbt = psbt.to_psbt().expect("Failed to convert PSBT");
+        let signed = wallet
+            .write()
+            .unwrap()
+            .sign_psbt(&mut psbt)
+            .expect("Failed to sign");
+        assert_eq!(signed, psbt.inputs.len());
+        // Finalizing checks every signature against the spent output
+        psbt.finalize_mut(&Secp256k1::new())
+            .expect("Failed to finalize");
+        psbt.extract_tx_unchecked_fee_rate()
+    }
+
+    #[test]
+    fn test_silent_payment_send_scan_and_spend() {
+        let mut sender = test_utils::create_test_wallet().expect("Failed to create wallet");
+        let inputs = vec![
+            fund(&mut sender, AddressType::NativeSegwit, 100_000),
+            fund(&mut sender, AddressType::Taproot, 200_000),
+        ];
+        let sender = Arc::new(RwLock::new(sender));
+
+        let mut receiver = test_utils::create_test_wallet().expect("Failed to create wallet");
+        let address = receiver
+            .silent_payment_address(0, None)
+            .expect("Failed to derive address");
+        assert!(address.to_string().starts_with("sprt1q"));
+
+        // The sender derives the output from its input keys
+        let mut builder =
+            TransactionBuilder::new(sender.clone()).expect("Failed to create builder");
+        builder
+            .add_silent_payment(address, Amount::from_sat(150_000))
+            .expect("Failed to add silent payment");
+        builder.pin_inputs(inputs);
+        let psbt = builder.build_psbt().expect("Failed to build PSBT");
+        let payment = sign(&sender, &psbt);
+        assert!(payment.output[0].script_pubkey.is_p2tr());
+
+        // The receiver finds it with its scan key
+        let prevouts: Vec<TxOut> = psbt
+            .inputs
+            .iter()
+            .map(|input| input.witness_utxo.clone().expect("Missing spent output"))
+            .collect();
+        let scanner = receiver
+            .silent_payment_scanner(0)
+            .expect("Failed to create scanner");
+        let found = scanner
+            .scan_transaction(&payment, &prevouts)
+            .expect("Failed to scan");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].outpoint, OutPoint::new(payment.compute_txid(), 0));
+        assert_eq!(found[0].label, None);
+        assert_eq!(receiver.add_silent_payment_outputs(&found).unwrap(), 1);
+        assert_eq!(receiver.balance(), 150_000);
+        assert!(receiver.is_mine(&payment.output[0].script_pubkey));
+
+        // The sender's wallet does not see the payment as its own
+        let outsider = sender
+            .write()
+            .unwrap()
+            .silent_payment_scanner(0)
+            .expect("Failed to create scanner");
+        assert!(outsider
+            .scan_transaction(&payment, &prevouts)
+            .unwrap()
+            .is_empty());
+
+        // The receiver spends the output with its tweaked spend key
+        let receiver = Arc::new(RwLock::new(receiver));
+        let mut builder =
+            TransactionBuilder::new(receiver.clone()).expect("Failed to create builder");
+        builder
+            .add_recipient(
+                Address::from_str("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+                    .expect("Invalid address")
+                    .assume_checked(),
+                Amount::from_sat(50_000),
+            )
+            .expect("Failed to add recipient");
+        builder.pin_inputs(vec![found[0].outpoint]);
+        let spend = sign(
+            &receiver,
+            &builder.build_psbt().expect("Failed to build PSBT"),
+        );
+        assert_eq!(spend.input[0].witness.len(), 1);
+        assert_eq!(spend.input[0].witness.to_vec()[0].len(), 64);
+    }
+}
+
+#[cfg(test)]
+mod consolidation_tests {
+    use super::*;
+    use crate::error::TransactionError;
+    use crate::transaction::consolidation::{
+        ConsolidationConfig, ConsolidationPlanner, ScriptKind,
+    };
+    use crate::wallet::descriptor_wallet::{AddressType, Utxo};
+    use bitcoin::FeeRate;
+
+    fn fund(wallet: &mut Wallet, address_type: AddressType, value: u64) -> OutPoint {
+        let address = wallet
+            .get_new_address(address_type)
+            .expect("Failed to get address");
+        let derivation_path = wallet.owns_address(&address).expect("Address not owned");
+        let txid = Txid::from_str(&format!("{value:064x}")).expect("Valid txid");
+        wallet.add_utxo(Utxo {
+            txid,
+            vout: 0,
+            value,
+            script_pubkey: address.script_pubkey(),
+            address,
+            derivation_path,
+            block_height: Some(700_000),
+        });
+        OutPoint::new(txid, 0)
+    }
+
+    fn sat_per_vb(rate: u64) -> FeeRate {
+        FeeRate::from_sat_per_vb(rate).expect("Valid fee rate")
+    }
+
+    /// Wallet with small UTXOs across address types, labels and sources
+    fn dusty_wallet() -> Arc<RwLock<Wallet>> {
+        let mut wallet = test_utils::create_test_wallet().expect("Failed to create wallet");
+        for value in [10_000, 11_000, 12_000, 13_000] {
+            fund(&mut wallet, AddressType::NativeSegwit, value);
+        }
+        for value in [20_000, 21_000, 22_000] {
+            let outpoint = fund(&mut wallet, AddressType::NativeSegwit, value);
+            wallet
+                .set_utxo_source(&outpoint, Some("exchange".to_string()))
+                .unwrap();
+        }
+        for value in [30_000, 31_000, 32_000] {
+            fund(&mut wallet, AddressType::Taproot, value);
+        }
+        fund(&mut wallet, AddressType::Legacy, 5_000);
+        // Worth less than its spend cost at 5 sat/vB
+        fund(&mut wallet, AddressType::NativeSegwit, 300);
+        let frozen = fund(&mut wallet, AddressType::NativeSegwit, 14_000);
+        wallet.freeze_utxo(&frozen).unwrap();
+        Arc::new(RwLock::new(wallet))
+    }
+
+    #[test]
+    fn test_plan_respects_boundaries() {
+        let planner = ConsolidationPlanner::new(dusty_wallet(), ConsolidationConfig::default());
+        let plan = planner
+            .plan(sat_per_vb(5), 800_000)
+            .expect("Failed to plan");
+
+        assert_eq!(plan.groups.len(), 3);
+        let mut totals: Vec<(ScriptKind, Option<String>, u64)> = plan
+            .groups
+            .iter()
+            .map(|group| {
+                (
+                    group.boundary.kind.clone(),
+                    group.boundary.source.clone(),
+                    group.total().to_sat(),
+                )
+            })
+            .collect();
+        totals.sort();
+        assert_eq!(
+            totals,
+            vec![
+                (
+                    ScriptKind::AddressType(AddressType::NativeSegwit),
+                    None,
+                    46_000
+                ),
+                (
+                    ScriptKind::AddressType(AddressType::NativeSegwit),
+                    Some("exchange".to_string()),
+                    63_000
+                ),
+                (ScriptKind::AddressType(AddressType::Taproot), None, 93_000),
+            ]
+        );
+        for group in &plan.groups {
+            assert!(group.savings > Amount::ZERO);
+            assert_eq!(group.fee, sat_per_vb(5).fee_vb(group.vsize).unwrap());
+        }
+        assert!(plan.groups[0].savings >= plan.groups[2].savings);
+        assert_eq!(
+            plan.savings(),
+            plan.groups.iter().map(|group| group.savings).sum()
+        );
+    }
+
+    #[test]
+    fn test_plan_requires_savings_and_fee_ceiling() {
+        let wallet = dusty_wallet();
+        let planner = ConsolidationPlanner::new(wallet.clone(), ConsolidationConfig::default());
+        assert!(matches!(
+            planner.plan(sat_per_vb(6), 800_000),
+            Err(TransactionError::FeeEstimation(_))
+        ));
+
+        // Consolidating at the future fee rate never pays off
+        let planner = ConsolidationPlanner::new(
+            wallet,
+            ConsolidationConfig {
+                max_fee_rate: sat_per_vb(20),
+                ..Default::default()
+            },
+        );
+        assert!(planner
+            .plan(sat_per_vb(20), 800_000)
+            .unwrap()
+            .groups
+            .is_empty());
+    }
+
+    #[test]
+    fn test_consolidation_psbt_merges_group() {
+        let wallet = dusty_wallet();
+        let planner = ConsolidationPlanner::new(wallet.clone(), ConsolidationConfig::default());
+        let plan = planner
+            .plan(sat_per_vb(2), 800_000)
+            .expect("Failed to plan");
+        let group = plan
+            .groups
+            .iter()
+            .find(|group| group.boundary.kind == ScriptKind::AddressType(AddressType::Taproot))
+            .expect("Taproot group expected");
+
+        let psbt = planner
+            .build_psbt(group, sat_per_vb(2))
+            .expect("Failed to build PSBT");
+        let spent: Vec<OutPoint> = psbt
+            .inputs
+            .iter()
+            .map(|input| OutPoint::new(input.previous_txid, input.previous_output_index))
+            .collect();
+        assert_eq!(spent, group.outpoints());
+        assert_eq!(psbt.outputs.len(), 1);
+        assert!(psbt.outputs[0].script.is_p2tr());
+        assert!(wallet.read().unwrap().is_mine(&psbt.outputs[0].script));
+        assert_eq!(psbt.outputs[0].amount, group.total() - group.fee);
+    }
+}
@@ -1,4 +1,8 @@
-use crate::crypto::{encrypt_data, generate_random_bytes, signatures::PrivateKey, SecureKey};
+use crate::crypto::{
+    decrypt_data, derive_key_from_password, encrypt_data, generate_random_bytes, generate_salt,
+    signatures::PrivateKey, tune_params, EncryptedData, KdfParams, SecureKey,
+    DEFAULT_ARGON2_MEMORY_COST, DEFAULT_KDF_TARGET_MS,
+};
 /// Modern descriptor-based wallet implementation
 ///
 /// This module implements a Bitcoin wallet using output descriptors for address generation,
@@ -10,24 +14,54 @@ use crate::crypto::{encrypt_data, generate_random_bytes, signatures::PrivateKey,
 /// - Modern derivation paths (BIP-44/49/84 for legacy/segwit/native segwit)
 /// - Taproot support with BIP-341 descriptors
 /// - Integration with encrypted storage and legacy import
-use crate::error::{WalletError, WalletResult};
+use crate::error::{
+    CryptoError, PolicyError, StorageError, TransactionError, WalletError, WalletResult,
+};
 use crate::storage::{
-    wallet_storage::{StorageConfig, WalletData},
-    WalletStorage,
+    wallet_storage::{
+        ChainIndex, KeychainRecord, StorageConfig, UtxoMetadataRecord, UtxoRecord, WalletData,
+        WALLET_SCHEMA_VERSION,
+    },
+    WalletBackup, WalletStorage,
+};
+use crate::wallet::{
+    discovery::DEFAULT_GAP_LIMIT,
+    events::{self, WalletEvent, DEFAULT_CONFIRMATION_TARGET},
+    history::{HistoryFilter, HistoryInput, HistoryOutput},
+    keychain::Keychain,
+    labels::{self, Label, LabelType},
+    message::{self, MessageFormat},
+    policy::{self, PolicySpend, SpendSummary, SpendingPolicy},
+    privacy::{self, PrivacyReport},
+    silent_payments::{self, SilentPaymentAddress, SilentPaymentOutput, SilentPaymentScanner},
+    watch_only::AccountXpub,
 };
 use crate::Network;
 
+use bip39::Mnemonic;
 use bitcoin::{
-    bip32::{ChildNumber, DerivationPath, Xpriv, Xpub},
-    key::TweakedPublicKey,
-    secp256k1::{All, Secp256k1},
-    Address, CompressedPublicKey, PublicKey, ScriptBuf, Txid,
+    bip32::{ChainCode, ChildNumber, DerivationPath, Fingerprint, Xpriv, Xpub},
+    hashes::Hash,
+    key::{Keypair, TapTweak},
+    psbt::{Psbt, SigningKeys},
+    secp256k1::{All, Message, Secp256k1, SecretKey},
+    sighash::{Prevouts, SighashCache, TapSighashType},
+    Address, CompressedPublicKey, NetworkKind, OutPoint, PublicKey, Script, ScriptBuf, Transaction,
+    Txid,
+};
+use miniscript::descriptor::{
+    DefiniteDescriptorKey, Descriptor, DescriptorPublicKey, DescriptorType,
 };
-use std::collections::{BTreeMap, HashMap};
+use miniscript::psbt::PsbtExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use zeroize::Zeroize;
 
 /// Supported address types for descriptor generation
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum AddressType {
     /// Legacy Pay-to-Public-Key-Hash (P2PKH) - 1...
     Legacy,
@@ -59,6 +93,58 @@ impl AddressType {
     }
 }
 
+/// Address chain within a BIP-44 account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Chain {
+    /// Receiving addresses handed out to payers (chain 0)
+    External,
+    /// Change addresses for the wallet's own outputs (chain 1)
+    Internal,
+}
+
+impl Chain {
+    /// BIP-44 chain index (0 = external, 1 = internal)
+    pub fn index(self) -> u32 {
+        match self {
+            Chain::External => 0,
+            Chain::Internal => 1,
+        }
+    }
+
+    /// Chain for a BIP-44 `change` flag
+    pub fn from_change(change: bool) -> Self {
+        if change {
+            Chain::Internal
+        } else {
+            Chain::External
+        }
+    }
+}
+
+/// BIP-44 account within a wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Account {
+    /// Hardened account index
+    pub index: u32,
+    /// Human-readable label
+    pub label: String,
+}
+
+impl Account {
+    /// Create an account with the default label
+    pub fn new(index: u32) -> Self {
+        let label = if index == 0 {
+            "Default".to_string()
+        } else {
+            format!("Account {index}")
+        };
+        Self { index, label }
+    }
+}
+
+/// Key of a single address chain: (account, address type, chain)
+pub type ChainKey = (u32, AddressType, Chain);
+
 /// Extended private key with metadata for HD wallet operations
 #[derive(Debug, Clone)]
 pub struct ExtendedPrivateKey {
@@ -95,6 +181,19 @@ impl ExtendedPrivateKey {
         Ok(Self { xpriv, secp })
     }
 
+    /// Create a master key from a BIP-39 mnemonic and optional passphrase ("25th word")
+    pub fn from_mnemonic(
+        mnemonic: &Mnemonic,
+        passphrase: &str,
+        network: Network,
+    ) -> WalletResult<Self> {
+        let mut seed = mnemonic.to_seed(passphrase);
+        let xpriv = Xpriv::new_master(bitcoin::Network::from(network), &seed);
+        seed.zeroize();
+
+        Ok(Self::new(xpriv.map_err(|_| WalletError::KeyGeneration)?))
+    }
+
     /// Derive a child key at the given path
     pub fn derive_path(&self, path: &DerivationPath) -> WalletResult<Self> {
         let derived = self
@@ -122,6 +221,14 @@ impl ExtendedPrivateKey {
     }
 }
 
+impl Drop for ExtendedPrivateKey {
+    fn drop(&mut self) {
+        // Overwrite the secret key and chain code before the memory is released
+        self.xpriv.private_key.non_secure_erase();
+        self.xpriv.chain_code = ChainCode::from([0u8; 32]);
+    }
+}
+
 /// UTXO (Unspent Transaction Output) information
 #[derive(Debug, Clone)]
 pub struct Utxo {
@@ -135,14 +242,36 @@ pub struct Utxo {
     pub script_pubkey: ScriptBuf,
     /// Address that owns this UTXO
     pub address: Address,
-    /// Derivation path for this address
+    /// Derivation path for this address (`<chain>/<index>` below the
+    /// keychain descriptor for descriptor keychain outputs)
     pub derivation_path: DerivationPath,
     /// Block height when confirmed (None if unconfirmed)
     pub block_height: Option<u32>,
 }
 
+impl Utxo {
+    /// Outpoint spent by an input using this UTXO
+    pub fn outpoint(&self) -> OutPoint {
+        OutPoint::new(self.txid, self.vout)
+    }
+}
+
+/// Coin control metadata attached to an outpoint
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UtxoMetadata {
+    /// Excluded from automatic coin selection
+    #[serde(default)]
+    pub frozen: bool,
+    /// Free-form user label
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Where the coins came from (e.g. "exchange", "mining", "coinjoin")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
 /// Wallet transaction history entry
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalletTransaction {
     /// Transaction ID
     pub txid: Txid,
@@ -154,6 +283,57 @@ pub struct WalletTransaction {
     pub timestamp: u64,
     /// Human-readable description
     pub label: Option<String>,
+    /// Inputs with the values and ownership of the outputs they spend
+    #[serde(default)]
+    pub inputs: Vec<HistoryInput>,
+    /// Outputs and whether they pay the wallet
+    #[serde(default)]
+    pub outputs: Vec<HistoryOutput>,
+}
+
+/// Request of Bitcoin Core's `importdescriptors` RPC
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DescriptorImport {
+    /// Checksummed descriptor
+    pub desc: String,
+    /// Unix time from which Core rescans for the descriptor's outputs
+    pub timestamp: u64,
+    /// Whether Core hands out addresses of this descriptor for its output type
+    pub active: bool,
+    /// Whether the descriptor derives change addresses
+    pub internal: bool,
+    /// First and last index to import
+    pub range: [u32; 2],
+}
+
+/// Key material held by a wallet
+enum WalletKeys {
+    /// Full wallet holding the master private key
+    Private {
+        /// Master extended private key
+        master_key: ExtendedPrivateKey,
+        /// Key protecting the persisted master key, derived from the passphrase
+        seed_key: SecureKey,
+        /// Salt used to derive `seed_key`
+        kdf_salt: Vec<u8>,
+        /// KDF parameters used to derive `seed_key`
+        kdf_params: KdfParams,
+    },
+    /// Watch-only wallet holding account-level extended public keys
+    WatchOnly(Vec<AccountXpub>),
+    /// Full wallet whose private keys were erased until it is unlocked again
+    Locked {
+        /// Master key sealed under the passphrase-derived key
+        encrypted_seed: EncryptedData,
+        /// Salt used to derive the key sealing the master key
+        kdf_salt: Vec<u8>,
+        /// KDF parameters used to derive the key sealing the master key
+        kdf_params: KdfParams,
+        /// Master key fingerprint
+        fingerprint: Fingerprint,
+        /// Account keys of every account, for deriving addresses while locked
+        accounts: Vec<AccountXpub>,
+    },
 }
 
 /// Main descriptor-based wallet implementation
@@ -164,8 +344,12 @@ pub struct Wallet {
     pub label: String,
     /// Bitcoin network
     pub network: Network,
-    /// Master extended private key
-    master_key: ExtendedPrivateKey,
+    /// Private, locked or watch-only key material
+    keys: WalletKeys,
+    /// When an unlocked wallet locks itself again (None: stays unlocked)
+    unlocked_until: Option<Instant>,
+    /// Secp256k1 context for public key derivation
+    secp: Secp256k1<All>,
     /// Cached derived keys by path
     derived_keys: HashMap<DerivationPath, ExtendedPrivateKey>,
     /// Generated addresses by path
@@ -174,8 +358,41 @@ pub struct Wallet {
     utxos: HashMap<(Txid, u32), Utxo>,
     /// Transaction history
     transactions: BTreeMap<Txid, WalletTransaction>,
-    /// Next unused address indices for each address type
-    next_indices: HashMap<AddressType, u32>,
+    /// Accounts by index
+    accounts: BTreeMap<u32, Account>,
+    /// Next address index to hand out per account, address type and chain
+    next_indices: HashMap<ChainKey, u32>,
+    /// Indices known to have received funds, per chain
+    used_indices: HashMap<ChainKey, BTreeSet<u32>>,
+    /// Descriptor keychains by name
+    keychains: BTreeMap<String, Keychain>,
+    /// Next address index to hand out per keychain chain
+    keychain_indices: HashMap<(String, Chain), u32>,
+    /// Keychain position of every keychain script handed out or within the
+    /// gap limit lookahead
+    keychain_scripts: HashMap<ScriptBuf, (String, Chain, u32)>,
+    /// End of the cached script range per keychain chain
+    keychain_lookahead: HashMap<(String, Chain), u32>,
+    /// Coin control metadata per outpoint
+    utxo_metadata: HashMap<OutPoint, UtxoMetadata>,
+    /// Address book entries (address -> label)
+    address_book: HashMap<String, String>,
+    /// Transaction comments (txid -> comment)
+    tx_comments: HashMap<String, String>,
+    /// BIP-329 labels of public keys, inputs and xpubs
+    labels: BTreeMap<(LabelType, String), String>,
+    /// Spending policy enforced before signing
+    policy: Option<SpendingPolicy>,
+    /// Recent spends counted against the daily limit
+    policy_spends: Vec<PolicySpend>,
+    /// Silent payment outputs by script
+    silent_payments: HashMap<ScriptBuf, SilentPaymentOutput>,
+    /// Height of the best block seen, if known
+    tip_height: Option<u32>,
+    /// Confirmation depth at which `WalletEvent::Confirmed` is emitted
+    confirmation_target: u32,
+    /// Event channel for subscribers
+    events: broadcast::Sender<WalletEvent>,
     /// Creation timestamp
     created_at: u64,
     /// Last modified timestamp
@@ -185,9 +402,71 @@ pub struct Wallet {
 }
 
 impl Wallet {
-    /// Create a new wallet with a random master key
+    /// Create a new wallet with a random master key and no passphrase
+    ///
+    /// The master key is still stored in a passphrase envelope, keyed from
+    /// the empty passphrase. Use `create_with_passphrase` to protect it.
     pub fn create_new(id: String, network: Network, storage: WalletStorage) -> WalletResult<Self> {
+        Self::create_with_passphrase(id, network, "", storage)
+    }
+
+    /// Create a new wallet with a random master key protected by a passphrase
+    pub fn create_with_passphrase(
+        id: String,
+        network: Network,
+        passphrase: &str,
+        storage: WalletStorage,
+    ) -> WalletResult<Self> {
         let master_key = ExtendedPrivateKey::generate_master(network)?;
+        Self::from_master_key(id, network, master_key, passphrase, storage)
+    }
+
+    /// Generate a new BIP-39 mnemonic with the given number of words (12-24)
+    pub fn generate_mnemonic(word_count: usize) -> WalletResult<Mnemonic> {
+        if !matches!(word_count, 12 | 15 | 18 | 21 | 24) {
+            return Err(WalletError::InvalidInput(format!(
+                "Mnemonic must have 12, 15, 18, 21 or 24 words, got {word_count}"
+            )));
+        }
+
+        // 32 bits of entropy per 3 words
+        let mut entropy =
+            generate_random_bytes(word_count / 3 * 4).map_err(|_| WalletError::KeyGeneration)?;
+        let mnemonic = Mnemonic::from_entropy(&entropy);
+        entropy.zeroize();
+        mnemonic.map_err(|_| WalletError::KeyGeneration)
+    }
+
+    /// Create or restore a wallet from a BIP-39 mnemonic
+    ///
+    /// `mnemonic_passphrase` is the optional BIP-39 passphrase that is mixed
+    /// into the seed; `passphrase` protects the stored master key.
+    pub fn from_mnemonic(
+        id: String,
+        network: Network,
+        mnemonic: &Mnemonic,
+        mnemonic_passphrase: &str,
+        passphrase: &str,
+        storage: WalletStorage,
+    ) -> WalletResult<Self> {
+        let master_key = ExtendedPrivateKey::from_mnemonic(mnemonic, mnemonic_passphrase, network)?;
+        Self::from_master_key(id, network, master_key, passphrase, storage)
+    }
+
+    /// Build a fresh wallet around an existing master key
+    ///
+    /// The storage is unlocked with the same passphrase.
+    fn from_master_key(
+        id: String,
+        network: Network,
+        master_key: ExtendedPrivateKey,
+        passphrase: &str,
+        mut storage: WalletStorage,
+    ) -> WalletResult<Self> {
+        unlock_storage(&mut storage, passphrase)?;
+        let kdf_params = KdfParams::for_wallet_encryption();
+        let kdf_salt = generate_salt().map_err(WalletError::Crypto)?;
+        let seed_key = derive_seed_key(passphrase, &kdf_salt, &kdf_params)?;
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
@@ -197,35 +476,166 @@ impl Wallet {
             label: id.clone(),
             id,
             network,
-            master_key,
+            keys: WalletKeys::Private {
+                master_key,
+                seed_key,
+                kdf_salt,
+                kdf_params,
+            },
+            unlocked_until: None,
+            secp: Secp256k1::new(),
             derived_keys: HashMap::new(),
             addresses: HashMap::new(),
             utxos: HashMap::new(),
             transactions: BTreeMap::new(),
+            accounts: BTreeMap::from([(0, Account::new(0))]),
             next_indices: HashMap::new(),
+            used_indices: HashMap::new(),
+            keychains: BTreeMap::new(),
+            keychain_indices: HashMap::new(),
+            keychain_scripts: HashMap::new(),
+            keychain_lookahead: HashMap::new(),
+            utxo_metadata: HashMap::new(),
+            address_book: HashMap::new(),
+            tx_comments: HashMap::new(),
+            labels: BTreeMap::new(),
+            policy: None,
+            policy_spends: Vec::new(),
+            silent_payments: HashMap::new(),
+            tip_height: None,
+            confirmation_target: DEFAULT_CONFIRMATION_TARGET,
+            events: events::channel(),
             created_at: now,
             modified_at: now,
             storage,
         };
 
-        // Initialize next indices for all address types
-        wallet.next_indices.insert(AddressType::Legacy, 0);
-        wallet.next_indices.insert(AddressType::NestedSegwit, 0);
-        wallet.next_indices.insert(AddressType::NativeSegwit, 0);
-        wallet.next_indices.insert(AddressType::Taproot, 0);
-
         // Generate initial receiving addresses
-        wallet.generate_initial_addresses()?;
+        wallet.generate_initial_addresses(0)?;
+
+        Ok(wallet)
+    }
+
+    /// Create a watch-only wallet from account-level extended public keys
+    ///
+    /// The wallet can generate addresses, track UTXOs and build unsigned
+    /// PSBTs, but holds no private keys; signing happens elsewhere. Unlock
+    /// the storage first to encrypt the wallet under a passphrase; a locked
+    /// storage is unlocked with the empty passphrase.
+    pub fn watch_only(
+        id: String,
+        network: Network,
+        accounts: Vec<AccountXpub>,
+        mut storage: WalletStorage,
+    ) -> WalletResult<Self> {
+        if !storage.is_unlocked() {
+            unlock_storage(&mut storage, "")?;
+        }
+        let mut unique: Vec<AccountXpub> = Vec::new();
+        for account in accounts {
+            if unique.contains(&account) {
+                continue;
+            }
+            if account.xpub.network != NetworkKind::from(bitcoin::Network::from(network)) {
+                return Err(WalletError::InvalidInput(format!(
+                    "Extended public key {} is for a different network",
+                    account.xpub
+                )));
+            }
+            if unique.iter().any(|other| {
+                (other.address_type, other.account()) == (account.address_type, account.account())
+                    || other.origin == account.origin
+            }) {
+                return Err(WalletError::InvalidInput(format!(
+                    "Conflicting watch-only keys for {:?} account {}",
+                    account.address_type,
+                    account.account()
+                )));
+            }
+            unique.push(account);
+        }
+
+        if unique.is_empty() {
+            return Err(WalletError::InvalidInput(
+                "A watch-only wallet needs at least one account key".to_string(),
+            ));
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let mut wallet = Self {
+            label: id.clone(),
+            id,
+            network,
+            keys: WalletKeys::WatchOnly(unique),
+            unlocked_until: None,
+            secp: Secp256k1::new(),
+            derived_keys: HashMap::new(),
+            addresses: HashMap::new(),
+            utxos: HashMap::new(),
+            transactions: BTreeMap::new(),
+            accounts: BTreeMap::new(),
+            next_indices: HashMap::new(),
+            used_indices: HashMap::new(),
+            keychains: BTreeMap::new(),
+            keychain_indices: HashMap::new(),
+            keychain_scripts: HashMap::new(),
+            keychain_lookahead: HashMap::new(),
+            utxo_metadata: HashMap::new(),
+            address_book: HashMap::new(),
+            tx_comments: HashMap::new(),
+            labels: BTreeMap::new(),
+            policy: None,
+            policy_spends: Vec::new(),
+            silent_payments: HashMap::new(),
+            tip_height: None,
+            confirmation_target: DEFAULT_CONFIRMATION_TARGET,
+            events: events::channel(),
+            created_at: now,
+            modified_at: now,
+            storage,
+        };
+
+        // Register every watched account and hand out its first receiving address
+        let watched: Vec<u32> = wallet.watched_accounts();
+        for account in watched {
+            wallet.accounts.insert(account, Account::new(account));
+            wallet.generate_initial_addresses(account)?;
+        }
 
         Ok(wallet)
     }
 
-    /// Load existing wallet from storage
-    pub fn load(id: String, storage: WalletStorage) -> WalletResult<Self> {
+    /// Create a watch-only wallet from account descriptors
+    ///
+    /// Receive and change descriptors of the same account may both be given.
+    pub fn from_descriptors(
+        id: String,
+        network: Network,
+        descriptors: &[String],
+        storage: WalletStorage,
+    ) -> WalletResult<Self> {
+        let accounts = descriptors
+            .iter()
+            .map(|descriptor| AccountXpub::from_descriptor(descriptor))
+            .collect::<WalletResult<Vec<_>>>()?;
+        Self::watch_only(id, network, accounts, storage)
+    }
+
+    /// Load existing wallet from storage, decrypting the storage and the
+    /// master key with the passphrase
+    ///
+    /// Storage written before encryption was mandatory is encrypted under
+    /// the passphrase on the way (see `WalletStorage::unlock`).
+    pub fn load(id: String, passphrase: &str, mut storage: WalletStorage) -> WalletResult<Self> {
+        unlock_storage(&mut storage, passphrase)?;
         let wallet_data = storage
             .load_wallet_data(&id)
             .map_err(WalletError::Storage)?;
-        Self::from_wallet_data(wallet_data, storage)
+        Self::from_wallet_data(wallet_data, passphrase, storage)
     }
 
     /// Save wallet to storage
@@ -237,44 +647,557 @@ impl Wallet {
         Ok(())
     }
 
-    /// Generate a new receiving address of the specified type
-    pub fn get_new_address(&mut self, address_type: AddressType) -> WalletResult<Address> {
-        let index = self.next_indices.get(&address_type).copied().unwrap_or(0);
-        let path = address_type.derivation_path(0, 0, index); // account=0, change=0 (receiving)
+    /// Write the complete wallet state to an encrypted backup
+    ///
+    /// The backup is encrypted under `passphrase`, which must be the wallet
+    /// passphrase: the restored wallet's master key is still sealed under it.
+    pub fn backup(&self, passphrase: &str) -> WalletResult<WalletBackup> {
+        match &self.keys {
+            WalletKeys::Private { .. } => self.verify_passphrase(passphrase)?,
+            WalletKeys::Locked {
+                encrypted_seed,
+                kdf_salt,
+                kdf_params,
+                ..
+            } => {
+                let seed_key = derive_seed_key(passphrase, kdf_salt, kdf_params)?;
+                decrypt_data(&seed_key, encrypted_seed, None)
+                    .map_err(|_| WalletError::InvalidPassphrase)?
+                    .zeroize();
+            }
+            WalletKeys::WatchOnly(_) => {}
+        }
+        WalletBackup::seal(
+            &self.to_wallet_data()?,
+            passphrase,
+            &KdfParams::for_wallet_encryption(),
+        )
+        .map_err(WalletError::Storage)
+    }
 
-        let address = self.generate_address(&path, address_type)?;
+    /// Restore a wallet from a backup under `id` and save it
+    ///
+    /// The backup may come from another machine and carry another wallet id;
+    /// the storage is unlocked with the passphrase that opens the backup.
+    pub fn restore(
+        id: String,
+        backup: &WalletBackup,
+        passphrase: &str,
+        mut storage: WalletStorage,
+    ) -> WalletResult<Self> {
+        if storage.wallet_exists(&id)? {
+            return Err(WalletError::InvalidInput(format!(
+                "Wallet '{id}' already exists"
+            )));
+        }
+        let mut wallet_data = backup.open(passphrase).map_err(|e| match e {
+            StorageError::DecryptionFailed => WalletError::InvalidPassphrase,
+            e => WalletError::Storage(e),
+        })?;
+        wallet_data.id = id;
 
-        // Increment the next index for this address type
-        self.next_indices.insert(address_type, index + 1);
+        unlock_storage(&mut storage, passphrase)?;
+        let wallet = Self::from_wallet_data(wallet_data, passphrase, storage)?;
+        wallet.save()?;
+        Ok(wallet)
+    }
 
-        Ok(address)
+    /// Change the wallet passphrase
+    ///
+    /// The master key is re-encrypted under the new passphrase with a fresh
+    /// salt and KDF parameters tuned for this machine, and the wallet is saved.
+    pub fn change_passphrase(
+        &mut self,
+        old_passphrase: &str,
+        new_passphrase: &str,
+    ) -> WalletResult<()> {
+        let kdf_params = tune_params(DEFAULT_KDF_TARGET_MS, DEFAULT_ARGON2_MEMORY_COST / 1024)
+            .map_err(WalletError::Crypto)?;
+        self.rekey(old_passphrase, new_passphrase, kdf_params)
+    }
+
+    /// Re-encrypt the master key and rotate the storage encryption key
+    ///
+    /// The old passphrase is checked before anything changes. The storage
+    /// key is derived again from the new passphrase with `kdf_params`, and
+    /// the wallet record and every other record in the storage are
+    /// rewritten in one atomic update; on failure the wallet keeps its
+    /// previous keys and the stored state is unchanged.
+    pub fn rekey(
+        &mut self,
+        old_passphrase: &str,
+        new_passphrase: &str,
+        kdf_params: KdfParams,
+    ) -> WalletResult<()> {
+        self.lock_if_expired()?;
+        let master_key = self.master_key()?.clone();
+        self.verify_passphrase(old_passphrase)?;
+
+        let kdf_salt = generate_salt().map_err(WalletError::Crypto)?;
+        let seed_key = derive_seed_key(new_passphrase, &kdf_salt, &kdf_params)?;
+        let keys = WalletKeys::Private {
+            master_key,
+            seed_key,
+            kdf_salt,
+            kdf_params: kdf_params.clone(),
+        };
+
+        let previous = std::mem::replace(&mut self.keys, keys);
+        let result = self.to_wallet_data().and_then(|data| {
+            self.storage
+                .rekey(&data, new_passphrase, &kdf_params)
+                .map_err(WalletError::Storage)
+        });
+        if result.is_err() {
+            self.keys = previous;
+        }
+        result
+    }
+
+    /// Whether the wallet's private keys are unavailable until it is unlocked
+    pub fn is_locked(&self) -> bool {
+        match self.keys {
+            WalletKeys::Private { .. } => self.lock_expired(),
+            WalletKeys::Locked { .. } => true,
+            WalletKeys::WatchOnly(_) => false,
+        }
+    }
+
+    /// Erase the private keys, keeping the public data needed for addresses
+    ///
+    /// Addresses of existing accounts can still be generated while locked;
+    /// signing, creating accounts and changing the passphrase fail with
+    /// `WalletError::Locked`. Locking a locked or watch-only wallet does nothing.
+    pub fn lock(&mut self) -> WalletResult<()> {
+        let WalletKeys::Private { master_key, .. } = &self.keys else {
+            return Ok(());
+        };
+        let fingerprint = master_key.xpriv.fingerprint(&self.secp);
+        let accounts = self.account_xpubs();
+        let (encrypted_seed, kdf_salt, kdf_params) = self.sealed_keys()?;
+
+        // Dropping the private keys zeroizes them
+        self.keys = WalletKeys::Locked {
+            encrypted_seed,
+            kdf_salt,
+            kdf_params,
+            fingerprint,
+            accounts,
+        };
+        self.derived_keys.clear();
+        self.unlocked_until = None;
+        Ok(())
+    }
+
+    /// Unlock the private keys with the passphrase
+    ///
+    /// With a timeout the wallet locks itself again once it has passed:
+    /// every private key operation erases the keys and fails from then on.
+    /// Unlocking an unlocked wallet checks the passphrase and replaces the
+    /// timeout.
+    pub fn unlock(&mut self, passphrase: &str, timeout: Option<Duration>) -> WalletResult<()> {
+        match &self.keys {
+            WalletKeys::WatchOnly(_) => return Err(WalletError::WatchOnly),
+            WalletKeys::Private { .. } => self.verify_passphrase(passphrase)?,
+            WalletKeys::Locked {
+                encrypted_seed,
+                kdf_salt,
+                kdf_params,
+                fingerprint,
+                ..
+            } => {
+                let seed_key = derive_seed_key(passphrase, kdf_salt, kdf_params)?;
+                let mut master_key_bytes = decrypt_data(&seed_key, encrypted_seed, None)
+                    .map_err(|_| WalletError::InvalidPassphrase)?;
+                let xpriv = Xpriv::decode(&master_key_bytes);
+                master_key_bytes.zeroize();
+                let master_key = ExtendedPrivateKey::new(
+                    xpriv.map_err(|_| WalletError::Storage(StorageError::WalletCorrupted))?,
+                );
+                if master_key.xpriv.fingerprint(&self.secp) != *fingerprint {
+                    return Err(WalletError::Storage(StorageError::WalletCorrupted));
+                }
+
+                self.keys = WalletKeys::Private {
+                    master_key,
+                    seed_key,
+                    kdf_salt: kdf_salt.clone(),
+                    kdf_params: kdf_params.clone(),
+                };
+            }
+        }
+        self.unlocked_until = timeout.map(|timeout| Instant::now() + timeout);
+        Ok(())
+    }
+
+    /// Lock the wallet if its unlock timeout has passed
+    ///
+    /// Key accessors call this before touching the keys. Long-running
+    /// services holding an idle unlocked wallet call it on a timer so expired
+    /// keys do not stay in memory until the next private key operation.
+    /// Returns whether the wallet was locked.
+    pub fn lock_if_expired(&mut self) -> WalletResult<bool> {
+        if matches!(self.keys, WalletKeys::Private { .. }) && self.lock_expired() {
+            self.lock()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Whether the unlock timeout has passed
+    fn lock_expired(&self) -> bool {
+        self.unlocked_until
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Master private key, if the wallet holds it and is unlocked
+    ///
+    /// Callers run `lock_if_expired` first so expired keys are erased rather
+    /// than only refused.
+    fn master_key(&self) -> WalletResult<&ExtendedPrivateKey> {
+        match &self.keys {
+            WalletKeys::Private { master_key, .. } if !self.lock_expired() => Ok(master_key),
+            WalletKeys::WatchOnly(_) => Err(WalletError::WatchOnly),
+            _ => Err(WalletError::Locked),
+        }
+    }
+
+    /// Check a passphrase against the key currently sealing the master key
+    fn verify_passphrase(&self, passphrase: &str) -> WalletResult<()> {
+        let WalletKeys::Private {
+            seed_key,
+            kdf_salt,
+            kdf_params,
+            ..
+        } = &self.keys
+        else {
+            return Err(WalletError::Locked);
+        };
+        let candidate = derive_seed_key(passphrase, kdf_salt, kdf_params)?;
+        let probe = encrypt_data(seed_key, &[0u8; 32], None).map_err(WalletError::Crypto)?;
+        decrypt_data(&candidate, &probe, None).map_err(|_| WalletError::InvalidPassphrase)?;
+        Ok(())
+    }
+
+    /// Generate a new receiving address of the specified type in account 0
+    pub fn get_new_address(&mut self, address_type: AddressType) -> WalletResult<Address> {
+        self.get_new_account_address(0, address_type)
+    }
+
+    /// Generate a new receiving address of the specified type in an account
+    pub fn get_new_account_address(
+        &mut self,
+        account: u32,
+        address_type: AddressType,
+    ) -> WalletResult<Address> {
+        self.require_account(account)?;
+        self.reveal_address((account, address_type, Chain::External))
     }
 
-    /// Generate a change address of the specified type
+    /// Generate a change address of the specified type in account 0
     pub fn get_change_address(&mut self, address_type: AddressType) -> WalletResult<Address> {
-        let index = self.next_indices.get(&address_type).copied().unwrap_or(0);
-        let path = address_type.derivation_path(0, 1, index); // account=0, change=1 (change)
+        self.get_account_change_address(0, address_type)
+    }
+
+    /// Generate a change address of the specified type in an account
+    ///
+    /// Every call hands out a fresh index so concurrent transactions never
+    /// share change. Once the unused tail of the change chain reaches the
+    /// discovery gap limit, further change could not be recovered from the
+    /// seed, and an unused address may already be the change of a broadcast
+    /// transaction the wallet has not seen yet, so this fails with
+    /// `WalletError::ChangeGapExhausted` until a sync records the change.
+    pub fn get_account_change_address(
+        &mut self,
+        account: u32,
+        address_type: AddressType,
+    ) -> WalletResult<Address> {
+        self.require_account(account)?;
+        if self.unused_gap(account, address_type, true) >= DEFAULT_GAP_LIMIT {
+            return Err(WalletError::ChangeGapExhausted(DEFAULT_GAP_LIMIT));
+        }
+        self.reveal_address((account, address_type, Chain::Internal))
+    }
+
+    /// First handed-out address of a chain that has not received funds yet
+    ///
+    /// A new address is revealed when every handed-out address is used.
+    pub fn next_unused_address(
+        &mut self,
+        account: u32,
+        address_type: AddressType,
+        change: bool,
+    ) -> WalletResult<Address> {
+        self.require_account(account)?;
+        let key = (account, address_type, Chain::from_change(change));
+        let next = self.next_indices.get(&key).copied().unwrap_or(0);
+
+        match (0..next).find(|index| !self.is_index_used(key, *index)) {
+            Some(index) => {
+                let path = self.chain_path(address_type, account, key.2.index(), index)?;
+                self.generate_address(&path, address_type)
+            }
+            None => self.reveal_address(key),
+        }
+    }
+
+    /// Number of handed-out addresses after the last used one on a chain
+    pub fn unused_gap(&self, account: u32, address_type: AddressType, change: bool) -> u32 {
+        let key = (account, address_type, Chain::from_change(change));
+        let next = self.next_indices.get(&key).copied().unwrap_or(0);
+        let first_after_used = self
+            .used_indices
+            .get(&key)
+            .and_then(|used| used.last())
+            .map_or(0, |last| last + 1);
+        next.saturating_sub(first_after_used)
+    }
+
+    /// Whether the address at `index` on a chain has received funds
+    pub fn is_index_used(&self, chain: ChainKey, index: u32) -> bool {
+        self.used_indices
+            .get(&chain)
+            .is_some_and(|used| used.contains(&index))
+    }
+
+    /// Hand out the next index of a chain
+    fn reveal_address(&mut self, chain: ChainKey) -> WalletResult<Address> {
+        let (account, address_type, chain_kind) = chain;
+        let index = self.next_indices.get(&chain).copied().unwrap_or(0);
+        let path = self.chain_path(address_type, account, chain_kind.index(), index)?;
 
         let address = self.generate_address(&path, address_type)?;
 
+        // Increment the next index for this chain
+        self.next_indices.insert(chain, index + 1);
+
         Ok(address)
     }
 
-    /// Get all addresses for a specific type and purpose
+    /// Get all addresses for a specific type and purpose in account 0
     pub fn get_addresses(&self, address_type: AddressType, change: bool) -> Vec<Address> {
-        let change_index = if change { 1 } else { 0 };
-        let max_index = self.next_indices.get(&address_type).copied().unwrap_or(0);
+        self.get_account_addresses(0, address_type, change)
+    }
+
+    /// Get all addresses handed out for a specific type and purpose in an account
+    pub fn get_account_addresses(
+        &self,
+        account: u32,
+        address_type: AddressType,
+        change: bool,
+    ) -> Vec<Address> {
+        let chain = Chain::from_change(change);
+        let max_index = self
+            .next_indices
+            .get(&(account, address_type, chain))
+            .copied()
+            .unwrap_or(0);
 
         (0..max_index)
             .filter_map(|i| {
-                let path = address_type.derivation_path(0, change_index, i);
+                let path = self
+                    .chain_path(address_type, account, chain.index(), i)
+                    .ok()?;
                 self.addresses.get(&path).cloned()
             })
             .collect()
     }
 
+    /// All accounts of this wallet, ordered by index
+    pub fn accounts(&self) -> Vec<&Account> {
+        self.accounts.values().collect()
+    }
+
+    /// Look up an account by index
+    pub fn account(&self, index: u32) -> Option<&Account> {
+        self.accounts.get(&index)
+    }
+
+    /// Index after the highest existing account
+    pub fn next_account_index(&self) -> u32 {
+        self.accounts.keys().next_back().map_or(0, |last| last + 1)
+    }
+
+    /// Whether any address of an account has received funds
+    pub fn account_has_history(&self, account: u32) -> bool {
+        self.used_indices
+            .iter()
+            .any(|((index, _, _), used)| *index == account && !used.is_empty())
+    }
+
+    /// Create a new account and hand out its first receiving addresses
+    ///
+    /// BIP-44 recovery, here and in other wallets, stops at the first account
+    /// without history, so an account is only created once the one before it
+    /// has received funds; otherwise this fails with `WalletError::UnusedAccount`.
+    pub fn create_account(&mut self, index: u32, label: Option<String>) -> WalletResult<&Account> {
+        if self.accounts.contains_key(&index) {
+            return Err(WalletError::InvalidInput(format!(
+                "Account {index} already exists"
+            )));
+        }
+        if !self.watched_accounts().contains(&index) && self.is_watch_only() {
+            return Err(WalletError::InvalidInput(format!(
+                "Watch-only wallet has no keys for account {index}"
+            )));
+        }
+        // New account keys are derived from the master private key
+        self.lock_if_expired()?;
+        if self.is_locked() {
+            return Err(WalletError::Locked);
+        }
+        // Hardened derivation limits the account index to 31 bits
+        ChildNumber::from_hardened_idx(index)
+            .map_err(|_| WalletError::InvalidInput(format!("Invalid account index {index}")))?;
+        if let Some(previous) = index.checked_sub(1) {
+            if !self.account_has_history(previous) {
+                return Err(WalletError::UnusedAccount(previous));
+            }
+        }
+
+        let mut account = Account::new(index);
+        if let Some(label) = label {
+            account.label = label;
+        }
+        self.accounts.insert(index, account);
+        self.generate_initial_addresses(index)?;
+
+        Ok(&self.accounts[&index])
+    }
+
+    /// Change the label of an existing account
+    pub fn set_account_label(&mut self, index: u32, label: String) -> WalletResult<()> {
+        self.accounts
+            .get_mut(&index)
+            .ok_or_else(|| WalletError::InvalidInput(format!("Account {index} does not exist")))?
+            .label = label;
+        Ok(())
+    }
+
+    /// Register an account found on chain, keeping any existing label
+    pub(crate) fn ensure_account(&mut self, index: u32) {
+        self.accounts
+            .entry(index)
+            .or_insert_with(|| Account::new(index));
+    }
+
+    fn require_account(&self, account: u32) -> WalletResult<()> {
+        if self.accounts.contains_key(&account) {
+            Ok(())
+        } else {
+            Err(WalletError::InvalidInput(format!(
+                "Account {account} does not exist"
+            )))
+        }
+    }
+
+    /// Account indices a watch-only wallet holds keys for
+    fn watched_accounts(&self) -> Vec<u32> {
+        match &self.keys {
+            WalletKeys::Private { .. } | WalletKeys::Locked { .. } => Vec::new(),
+            WalletKeys::WatchOnly(keys) => {
+                let mut accounts: Vec<u32> = keys.iter().map(AccountXpub::account).collect();
+                accounts.sort_unstable();
+                accounts.dedup();
+                accounts
+            }
+        }
+    }
+
+    /// Account a full derivation path belongs to
+    pub fn account_of_path(&self, path: &DerivationPath) -> Option<u32> {
+        match &self.keys {
+            WalletKeys::Private { .. } | WalletKeys::Locked { .. } => match path.as_ref().get(2) {
+                Some(ChildNumber::Hardened { index }) => Some(*index),
+                _ => None,
+            },
+            WalletKeys::WatchOnly(keys) => keys
+                .iter()
+                .find(|key| path.as_ref().starts_with(key.origin.as_ref()))
+                .map(AccountXpub::account),
+        }
+    }
+
+    /// UTXOs owned by a single account
+    pub fn account_utxos(&self, account: u32) -> Vec<&Utxo> {
+        self.utxos
+            .values()
+            .filter(|utxo| self.account_of_path(&utxo.derivation_path) == Some(account))
+            .collect()
+    }
+
+    /// Total balance of a single account in satoshis
+    pub fn account_balance(&self, account: u32) -> u64 {
+        self.account_utxos(account)
+            .iter()
+            .map(|utxo| utxo.value)
+            .sum()
+    }
+
+    /// Receive and change descriptors of every address type in an account
+    pub fn account_descriptors(&self, account: u32) -> Vec<String> {
+        self.account_xpubs()
+            .iter()
+            .filter(|key| key.account() == account)
+            .flat_map(|key| [key.descriptor(false), key.descriptor(true)])
+            .collect()
+    }
+
+    /// Requests importing the wallet's descriptors into Bitcoin Core
+    ///
+    /// Serialized as a JSON array this is the argument of `bitcoin-cli
+    /// importdescriptors` for a wallet without private keys. Receive and
+    /// change chains are separate descriptors. The lowest account of each
+    /// address type becomes Core's active descriptor; other accounts and
+    /// keychains are imported inactive. Ranges cover the addresses handed
+    /// out plus the gap limit. Keychains are included unless an account is
+    /// selected.
+    pub fn descriptor_imports(&self, account: Option<u32>) -> Vec<DescriptorImport> {
+        let mut keys = self.account_xpubs();
+        keys.retain(|key| account.is_none_or(|index| key.account() == index));
+        keys.sort_by_key(|key| (key.address_type, key.account()));
+
+        let range = |next: u32| [0, next + DEFAULT_GAP_LIMIT - 1];
+        let mut imports = Vec::new();
+        let mut active_types = BTreeSet::new();
+        for key in &keys {
+            let active = active_types.insert(key.address_type);
+            for chain in [Chain::External, Chain::Internal] {
+                let next = self
+                    .next_indices
+                    .get(&(key.account(), key.address_type, chain))
+                    .copied()
+                    .unwrap_or(0);
+                imports.push(DescriptorImport {
+                    desc: key.descriptor(chain == Chain::Internal),
+                    timestamp: self.created_at,
+                    active,
+                    internal: chain == Chain::Internal,
+                    range: range(next),
+                });
+            }
+        }
+
+        if account.is_none() {
+            for keychain in self.keychains.values() {
+                for chain in [Chain::External, Chain::Internal] {
+                    if let Some(descriptor) = keychain.descriptor(chain) {
+                        imports.push(DescriptorImport {
+                            desc: descriptor.to_string(),
+                            timestamp: self.created_at,
+                            active: false,
+                            internal: chain == Chain::Internal,
+                            range: range(self.keychain_next(&keychain.name, chain)),
+                        });
+                    }
+                }
+            }
+        }
+        imports
+    }
+
     /// Generate an address for a specific derivation path
-    fn generate_address(
+    pub(crate) fn generate_address(
         &mut self,
         path: &DerivationPath,
         address_type: AddressType,
@@ -283,18 +1206,23 @@ impl Wallet {
         if let Some(address) = self.addresses.get(path) {
             return Ok(address.clone());
         }
+        self.lock_if_expired()?;
 
         // Derive the key for this path
-        let derived_key = if let Some(key) = self.derived_keys.get(path) {
-            key.clone()
-        } else {
-            let key = self.master_key.derive_path(path)?;
-            self.derived_keys.insert(path.clone(), key.clone());
-            key.clone()
+        let public_key = match &self.keys {
+            WalletKeys::Private { master_key, .. } => {
+                let derived_key = if let Some(key) = self.derived_keys.get(path) {
+                    key.clone()
+                } else {
+                    let key = master_key.derive_path(path)?;
+                    self.derived_keys.insert(path.clone(), key.clone());
+                    key
+                };
+                derived_key.public_key()
+            }
+            WalletKeys::WatchOnly(_) | WalletKeys::Locked { .. } => self.public_key(path)?,
         };
 
-        let public_key = derived_key.public_key();
-
         let bitcoin_network = match self.network {
             Network::Bitcoin => bitcoin::Network::Bitcoin,
             Network::Testnet => bitcoin::Network::Testnet,
@@ -316,11 +1244,13 @@ impl Wallet {
                 bitcoin_network,
             ),
             AddressType::Taproot => {
-                // For BIP-86 key-only spending path
-                let tweaked_key = TweakedPublicKey::dangerous_assume_tweaked(
+                // BIP-86: key-path only output, internal key tweaked with an empty script tree
+                Address::p2tr(
+                    &self.secp,
                     public_key.inner.x_only_public_key().0,
-                );
-                Address::p2tr_tweaked(tweaked_key, bitcoin_network)
+                    None,
+                    bitcoin_network,
+                )
             }
         };
 
@@ -328,31 +1258,920 @@ impl Wallet {
         Ok(address)
     }
 
-    /// Generate initial addresses for all types
-    fn generate_initial_addresses(&mut self) -> WalletResult<()> {
-        // Generate first receiving address for each type
+    /// Record that an address received funds, so it is never handed out again
+    pub(crate) fn mark_index_used(&mut self, chain: ChainKey, index: u32) {
+        self.used_indices.entry(chain).or_default().insert(index);
+        let next = self.next_indices.entry(chain).or_insert(0);
+        *next = (*next).max(index + 1);
+    }
+
+    /// Chain and index of a wallet derivation path
+    pub(crate) fn chain_position(&self, path: &DerivationPath) -> Option<(ChainKey, u32)> {
+        let (address_type, account, suffix) = match &self.keys {
+            WalletKeys::Private { .. } | WalletKeys::Locked { .. } => {
+                let (purpose, account) = match path.as_ref() {
+                    [ChildNumber::Hardened { index: purpose }, _, ChildNumber::Hardened { index: account }, ..] => {
+                        (*purpose, *account)
+                    }
+                    _ => return None,
+                };
+                let address_type = [
+                    AddressType::Legacy,
+                    AddressType::NestedSegwit,
+                    AddressType::NativeSegwit,
+                    AddressType::Taproot,
+                ]
+                .into_iter()
+                .find(|t| {
+                    t.derivation_path(0, 0, 0)[0] == ChildNumber::Hardened { index: purpose }
+                })?;
+                (address_type, account, &path.as_ref()[3..])
+            }
+            WalletKeys::WatchOnly(keys) => {
+                let key = keys.iter().find(|key| {
+                    path.len() == key.origin.len() + 2
+                        && path.as_ref().starts_with(key.origin.as_ref())
+                })?;
+                (
+                    key.address_type,
+                    key.account(),
+                    &path.as_ref()[key.origin.len()..],
+                )
+            }
+        };
+
+        match suffix {
+            [ChildNumber::Normal { index: change }, ChildNumber::Normal { index }]
+                if *change <= 1 =>
+            {
+                Some((
+                    (account, address_type, Chain::from_change(*change == 1)),
+                    *index,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Generate initial addresses of an account for all types
+    fn generate_initial_addresses(&mut self, account: u32) -> WalletResult<()> {
+        // Generate first receiving address for each type the wallet has keys for
         for &addr_type in &[
             AddressType::Legacy,
             AddressType::NestedSegwit,
             AddressType::NativeSegwit,
             AddressType::Taproot,
         ] {
-            self.get_new_address(addr_type)?;
+            if self.has_account(addr_type, account) {
+                self.get_new_account_address(account, addr_type)?;
+            }
         }
         Ok(())
     }
 
-    /// Get the total balance in satoshis
-    pub fn balance(&self) -> u64 {
-        self.utxos.values().map(|utxo| utxo.value).sum()
+    /// Whether the wallet can derive addresses for the given account
+    pub fn has_account(&self, address_type: AddressType, account: u32) -> bool {
+        match &self.keys {
+            WalletKeys::Private { .. } => true,
+            WalletKeys::WatchOnly(accounts) | WalletKeys::Locked { accounts, .. } => accounts
+                .iter()
+                .any(|key| key.address_type == address_type && key.account() == account),
+        }
     }
 
-    /// Get confirmed balance (UTXOs with block height)
-    pub fn confirmed_balance(&self) -> u64 {
-        self.utxos
-            .values()
-            .filter(|utxo| utxo.block_height.is_some())
-            .map(|utxo| utxo.value)
+    /// Full derivation path of an address on the given account chain
+    pub(crate) fn chain_path(
+        &self,
+        address_type: AddressType,
+        account: u32,
+        change: u32,
+        index: u32,
+    ) -> WalletResult<DerivationPath> {
+        match &self.keys {
+            WalletKeys::Private { .. } | WalletKeys::Locked { .. } => {
+                Ok(address_type.derivation_path(account, change, index))
+            }
+            WalletKeys::WatchOnly(accounts) => accounts
+                .iter()
+                .find(|key| key.address_type == address_type && key.account() == account)
+                .ok_or_else(|| {
+                    WalletError::InvalidInput(format!(
+                        "Watch-only wallet has no {address_type:?} key for account {account}"
+                    ))
+                })?
+                .chain_path(change, index),
+        }
+    }
+
+    /// Whether this wallet only holds public keys
+    pub fn is_watch_only(&self) -> bool {
+        matches!(self.keys, WalletKeys::WatchOnly(_))
+    }
+
+    /// Public key at a full derivation path
+    pub fn public_key(&self, path: &DerivationPath) -> WalletResult<PublicKey> {
+        match &self.keys {
+            WalletKeys::Private { master_key, .. } => match self.derived_keys.get(path) {
+                Some(key) => Ok(key.public_key()),
+                None => Ok(master_key.derive_path(path)?.public_key()),
+            },
+            WalletKeys::WatchOnly(accounts) | WalletKeys::Locked { accounts, .. } => accounts
+                .iter()
+                .find_map(|key| key.derive_public_key(&self.secp, path))
+                .unwrap_or(Err(WalletError::KeyDerivation)),
+        }
+    }
+
+    /// Master key fingerprint and public key for a full derivation path
+    ///
+    /// This is the BIP-32 key origin an external signer needs to find the key.
+    pub fn key_origin(&self, path: &DerivationPath) -> WalletResult<(Fingerprint, PublicKey)> {
+        let fingerprint = match &self.keys {
+            WalletKeys::Private { master_key, .. } => master_key.xpriv.fingerprint(&self.secp),
+            WalletKeys::Locked { fingerprint, .. } => *fingerprint,
+            WalletKeys::WatchOnly(accounts) => accounts
+                .iter()
+                .find(|key| path.as_ref().starts_with(key.origin.as_ref()))
+                .map(|key| key.fingerprint)
+                .ok_or(WalletError::KeyDerivation)?,
+        };
+        Ok((fingerprint, self.public_key(path)?))
+    }
+
+    /// Account-level extended public key, for exporting to a watch-only wallet
+    pub fn account_xpub(
+        &self,
+        address_type: AddressType,
+        account: u32,
+    ) -> WalletResult<AccountXpub> {
+        match &self.keys {
+            WalletKeys::Private { master_key, .. } => {
+                // m/purpose'/coin'/account' is the parent of the chain path
+                let origin: DerivationPath = address_type.derivation_path(account, 0, 0)[..3]
+                    .to_vec()
+                    .into();
+                let account_key = master_key.derive_path(&origin)?;
+                Ok(AccountXpub::new(
+                    address_type,
+                    account_key.extended_public_key(),
+                    master_key.xpriv.fingerprint(&self.secp),
+                    origin,
+                ))
+            }
+            WalletKeys::WatchOnly(accounts) => accounts
+                .iter()
+                .find(|key| key.address_type == address_type && key.account() == account)
+                .cloned()
+                .ok_or_else(|| {
+                    WalletError::InvalidInput(format!(
+                        "Watch-only wallet has no {address_type:?} key for account {account}"
+                    ))
+                }),
+            // Keys of accounts created before locking are kept
+            WalletKeys::Locked { accounts, .. } => accounts
+                .iter()
+                .find(|key| key.address_type == address_type && key.account() == account)
+                .cloned()
+                .ok_or(WalletError::Locked),
+        }
+    }
+
+    /// Account keys of every address type in every account this wallet can export
+    pub fn account_xpubs(&self) -> Vec<AccountXpub> {
+        match &self.keys {
+            WalletKeys::Private { .. } => self
+                .accounts
+                .keys()
+                .flat_map(|&account| {
+                    [
+                        AddressType::Legacy,
+                        AddressType::NestedSegwit,
+                        AddressType::NativeSegwit,
+                        AddressType::Taproot,
+                    ]
+                    .map(|address_type| self.account_xpub(address_type, account))
+                })
+                .filter_map(Result::ok)
+                .collect(),
+            WalletKeys::WatchOnly(accounts) | WalletKeys::Locked { accounts, .. } => {
+                accounts.clone()
+            }
+        }
+    }
+
+    /// Add a descriptor keychain
+    ///
+    /// `descriptor` is a multipath receive/change descriptor, or the receive
+    /// descriptor with `change` as the optional change descriptor. Extended
+    /// keys must belong to the wallet's network.
+    pub fn add_keychain(
+        &mut self,
+        name: &str,
+        descriptor: &str,
+        change: Option<&str>,
+    ) -> WalletResult<&Keychain> {
+        if self.keychains.contains_key(name) {
+            return Err(WalletError::InvalidInput(format!(
+                "Keychain '{name}' already exists"
+            )));
+        }
+
+        let keychain = Keychain::new(name, descriptor, change)?;
+        if !keychain.matches_network(NetworkKind::from(bitcoin::Network::from(self.network))) {
+            return Err(WalletError::InvalidInput(format!(
+                "Keychain '{name}' has keys for another network"
+            )));
+        }
+
+        self.keychains.insert(name.to_string(), keychain);
+        self.extend_keychain_lookahead(name);
+        Ok(&self.keychains[name])
+    }
+
+    /// Descriptor keychain by name
+    pub fn keychain(&self, name: &str) -> Option<&Keychain> {
+        self.keychains.get(name)
+    }
+
+    /// All descriptor keychains, ordered by name
+    pub fn keychains(&self) -> Vec<&Keychain> {
+        self.keychains.values().collect()
+    }
+
+    /// Hand out the next receive (`change = false`) or change address of a keychain
+    pub fn keychain_address(&mut self, name: &str, change: bool) -> WalletResult<Address> {
+        let key = (name.to_string(), Chain::from_change(change));
+        let index = self.keychain_indices.get(&key).copied().unwrap_or(0);
+        let address = self.cache_keychain_address(name, key.1, index)?;
+        self.keychain_indices.insert(key, index + 1);
+        self.extend_keychain_lookahead(name);
+        Ok(address)
+    }
+
+    /// Addresses handed out so far on a keychain chain
+    pub fn keychain_addresses(&self, name: &str, change: bool) -> WalletResult<Vec<Address>> {
+        let keychain = self.require_keychain(name)?;
+        let chain = Chain::from_change(change);
+        let next = self
+            .keychain_indices
+            .get(&(name.to_string(), chain))
+            .copied()
+            .unwrap_or(0);
+        (0..next)
+            .map(|index| keychain.address(chain, index, bitcoin::Network::from(self.network)))
+            .collect()
+    }
+
+    /// Keychain name, chain and index of a keychain script
+    ///
+    /// Covers the addresses handed out plus `DEFAULT_GAP_LIMIT` addresses
+    /// beyond them on each chain, so payments a cosigner requested at higher
+    /// indices are found too.
+    pub fn keychain_position(&self, script: &Script) -> Option<(&str, Chain, u32)> {
+        self.keychain_scripts
+            .get(script)
+            .map(|(name, chain, index)| (name.as_str(), *chain, *index))
+    }
+
+    /// Whether a script pays this wallet, through an address type or a keychain
+    pub fn is_mine(&self, script: &Script) -> bool {
+        self.owned_output(script).is_some()
+    }
+
+    /// Concrete descriptor of an owned script, carrying the key origins a signer needs
+    ///
+    /// Built-in address types resolve to their single-key account descriptor.
+    pub fn spending_descriptor(
+        &self,
+        script: &Script,
+    ) -> WalletResult<Option<Descriptor<DefiniteDescriptorKey>>> {
+        if let Some((name, chain, index)) = self.keychain_position(script) {
+            return self.keychains[name].derive(chain, index).map(Some);
+        }
+
+        let position = Address::from_script(script, bitcoin::Network::from(self.network))
+            .ok()
+            .and_then(|address| self.owns_address(&address))
+            .and_then(|path| self.chain_position(&path));
+        let Some(((account, address_type, chain), index)) = position else {
+            return Ok(None);
+        };
+
+        self.account_xpub(address_type, account)?
+            .ranged_descriptor(chain == Chain::Internal)
+            .at_derivation_index(index)
+            .map(Some)
+            .map_err(|_| WalletError::KeyDerivation)
+    }
+
+    /// Maximum weight of the scriptSig and witness spending a wallet script
+    ///
+    /// `None` for scripts that do not belong to the wallet.
+    pub fn satisfaction_weight(&self, script: &Script) -> WalletResult<Option<u64>> {
+        if self.silent_payments.contains_key(script) {
+            // Key path spend: item count, length and a 64-byte signature
+            return Ok(Some(66));
+        }
+        Ok(self.spending_descriptor(script)?.map(|descriptor| {
+            descriptor
+                .max_weight_to_satisfy()
+                .map(|weight| weight.to_wu())
+                .unwrap_or(0)
+        }))
+    }
+
+    /// Sign every PSBT input that spends a wallet script
+    ///
+    /// Inputs are first updated from their spending descriptors (key origins,
+    /// witness and redeem scripts, Taproot data), then signed with each key
+    /// of the descriptor that derives from this wallet's master key. Keys of
+    /// cosigners are left for them. Returns the number of inputs signed.
+    ///
+    /// The wallet's spending policy, if any, is checked first, and the spend
+    /// is counted against the daily limit once signed.
+    pub fn sign_psbt(&mut self, psbt: &mut Psbt) -> WalletResult<usize> {
+        self.sign_with_policy(psbt, false)
+    }
+
+    /// Sign a PSBT whose spend was approved, lifting the policy's approval threshold
+    ///
+    /// Every other policy rule still applies.
+    pub fn sign_approved_psbt(&mut self, psbt: &mut Psbt) -> WalletResult<usize> {
+        self.sign_with_policy(psbt, true)
+    }
+
+    /// Spending policy enforced before signing, if any
+    pub fn policy(&self) -> Option<&SpendingPolicy> {
+        self.policy.as_ref()
+    }
+
+    /// Set or clear the spending policy
+    pub fn set_policy(&mut self, policy: Option<SpendingPolicy>) -> WalletResult<()> {
+        let policy = match policy {
+            Some(mut policy) => {
+                policy.validate(bitcoin::Network::from(self.network))?;
+                Some(policy)
+            }
+            None => None,
+        };
+        self.policy = policy;
+        Ok(())
+    }
+
+    /// Summarize what a PSBT spends from this wallet
+    ///
+    /// Every input must carry its previous output so the fee is known. The
+    /// values of wallet inputs are taken from the wallet's UTXO set, which
+    /// must hold them. Other inputs are taken from the PSBT; legacy inputs
+    /// must carry the full previous transaction, as their signatures do not
+    /// commit to the value. Inputs whose previous output does not match
+    /// fail with `PolicyError::InputMismatch`.
+    pub fn spend_summary(&self, psbt: &Psbt) -> WalletResult<SpendSummary> {
+        let mut input_total = 0u64;
+        let mut owned_input_total = 0u64;
+        let mut satisfaction_weight = 0u64;
+        for (index, input) in psbt.inputs.iter().enumerate() {
+            let outpoint = psbt.unsigned_tx.input[index].previous_output;
+            let previous_output = self.input_value(index, outpoint, input)?;
+            let value = previous_output.value.to_sat();
+
+            input_total += value;
+            if let Some(weight) = self.satisfaction_weight(&previous_output.script_pubkey)? {
+                owned_input_total += value;
+                satisfaction_weight += weight;
+            }
+        }
+
+        let fingerprints: BTreeSet<Fingerprint> = self
+            .account_xpubs()
+            .iter()
+            .map(|account| account.fingerprint)
+            .collect();
+        let mut payments = Vec::new();
+        let mut change = 0u64;
+        let mut unknown_change = Vec::new();
+        for (index, output) in psbt.unsigned_tx.output.iter().enumerate() {
+            let value = output.value.to_sat();
+            if self.is_mine(&output.script_pubkey) {
+                change += value;
+                continue;
+            }
+            payments.push((output.script_pubkey.clone(), value));
+
+            let metadata = &psbt.outputs[index];
+            let claims_wallet_key = metadata
+                .bip32_derivation
+                .values()
+                .map(|(fingerprint, _)| fingerprint)
+                .chain(
+                    metadata
+                        .tap_key_origins
+                        .values()
+                        .map(|(_, (fingerprint, _))| fingerprint),
+                )
+                .any(|fingerprint| fingerprints.contains(fingerprint));
+            if claims_wallet_key {
+                unknown_change.push(index);
+            }
+        }
+
+        let output_total: u64 = psbt
+            .unsigned_tx
+            .output
+            .iter()
+            .map(|output| output.value.to_sat())
+            .sum();
+        let fee = input_total
+            .checked_sub(output_total)
+            .ok_or_else(|| TransactionError::InvalidPsbt("Outputs exceed inputs".to_string()))?;
+        let weight = psbt.unsigned_tx.weight().to_wu() + satisfaction_weight;
+
+        Ok(SpendSummary {
+            txid: psbt.unsigned_tx.compute_txid(),
+            spent: owned_input_total.saturating_sub(change),
+            payments,
+            change,
+            fee,
+            vsize: weight.div_ceil(4),
+            unknown_change,
+        })
+    }
+
+    /// Previous output of a PSBT input, checked against the wallet's records
+    fn input_value(
+        &self,
+        index: usize,
+        outpoint: OutPoint,
+        input: &bitcoin::psbt::Input,
+    ) -> WalletResult<bitcoin::TxOut> {
+        let mismatch = |reason: &str| PolicyError::InputMismatch {
+            index,
+            reason: reason.to_string(),
+        };
+
+        let previous = match &input.non_witness_utxo {
+            Some(previous) if previous.compute_txid() != outpoint.txid => {
+                return Err(mismatch("previous transaction has another txid").into());
+            }
+            Some(previous) => Some(
+                previous
+                    .output
+                    .get(outpoint.vout as usize)
+                    .ok_or_else(|| mismatch("previous transaction has no such output"))?,
+            ),
+            None => None,
+        };
+        let claimed = match (&input.witness_utxo, previous) {
+            (Some(witness), Some(previous)) if witness != previous => {
+                return Err(mismatch("witness and previous transaction disagree").into());
+            }
+            (Some(utxo), _) | (None, Some(utxo)) => utxo.clone(),
+            (None, None) => return Err(PolicyError::UnknownInputValue { index }.into()),
+        };
+
+        if let Some(utxo) = self.utxo(&outpoint) {
+            if utxo.value != claimed.value.to_sat() || utxo.script_pubkey != claimed.script_pubkey {
+                return Err(mismatch("value or script differs from the wallet's UTXO").into());
+            }
+            return Ok(claimed);
+        }
+        if self.is_mine(&claimed.script_pubkey) {
+            return Err(mismatch("not in the wallet's UTXO set").into());
+        }
+
+        let segwit = claimed.script_pubkey.is_witness_program()
+            || input
+                .redeem_script
+                .as_ref()
+                .is_some_and(|script| script.is_witness_program());
+        if !segwit && previous.is_none() {
+            return Err(mismatch("legacy input without its previous transaction").into());
+        }
+        Ok(claimed)
+    }
+
+    /// Check a PSBT against the spending policy without signing it
+    pub fn check_policy(&self, psbt: &Psbt, approved: bool) -> WalletResult<SpendSummary> {
+        let summary = self.spend_summary(psbt)?;
+        if let Some(policy) = &self.policy {
+            let spent_today =
+                policy::spent_within_day(&self.policy_spends, unix_time(), &summary.txid);
+            policy.evaluate(
+                &summary,
+                spent_today,
+                approved,
+                bitcoin::Network::from(self.network),
+            )?;
+        }
+        Ok(summary)
+    }
+
+    fn sign_with_policy(&mut self, psbt: &mut Psbt, approved: bool) -> WalletResult<usize> {
+        self.lock_if_expired()?;
+        self.master_key()?;
+        let summary = match self.policy {
+            Some(_) => Some(self.check_policy(psbt, approved)?),
+            None => None,
+        };
+
+        let signed = self.sign_inputs(psbt)?;
+        if let Some(summary) = summary.filter(|summary| signed > 0 && summary.spent > 0) {
+            let now = unix_time();
+            self.policy_spends.retain(|spend| {
+                spend.txid != summary.txid && spend.timestamp + policy::DAILY_WINDOW_SECS > now
+            });
+            self.policy_spends.push(PolicySpend {
+                txid: summary.txid,
+                amount: summary.spent,
+                timestamp: now,
+            });
+        }
+        Ok(signed)
+    }
+
+    fn sign_inputs(&self, psbt: &mut Psbt) -> WalletResult<usize> {
+        let master_key = self.master_key()?;
+
+        let mut owned = BTreeSet::new();
+        let mut silent_inputs = Vec::new();
+        for index in 0..psbt.inputs.len() {
+            let input = &psbt.inputs[index];
+            let vout = psbt.unsigned_tx.input[index].previous_output.vout as usize;
+            let script = match (&input.witness_utxo, &input.non_witness_utxo) {
+                (Some(utxo), _) => utxo.script_pubkey.clone(),
+                (None, Some(previous)) => match previous.output.get(vout) {
+                    Some(output) => output.script_pubkey.clone(),
+                    None => continue,
+                },
+                (None, None) => continue,
+            };
+
+            if let Some(output) = self.silent_payments.get(&script) {
+                silent_inputs.push((index, output));
+            } else if let Some(descriptor) = self.spending_descriptor(&script)? {
+                psbt.update_input_with_descriptor(index, &descriptor)
+                    .map_err(|e| TransactionError::SigningFailed(format!("Input {index}: {e}")))?;
+                owned.insert(index);
+            }
+        }
+
+        let signed = match psbt.sign(&master_key.xpriv, &self.secp) {
+            Ok(signed) => signed,
+            Err((signed, errors)) => {
+                // Inputs of other wallets may lack the data needed to sign; ours may not
+                if let Some((index, error)) = errors.iter().find(|(i, _)| owned.contains(*i)) {
+                    return Err(
+                        TransactionError::SigningFailed(format!("Input {index}: {error}")).into(),
+                    );
+                }
+                signed
+            }
+        };
+
+        let signed = signed
+            .values()
+            .filter(|keys| match keys {
+                SigningKeys::Ecdsa(keys) => !keys.is_empty(),
+                SigningKeys::Schnorr(keys) => !keys.is_empty(),
+            })
+            .count();
+        Ok(signed + self.sign_silent_payment_inputs(psbt, master_key, &silent_inputs)?)
+    }
+
+    /// Sign silent payment inputs with their tweaked spend keys
+    ///
+    /// The output key is the tweaked spend key itself, so these inputs are
+    /// signed without the BIP-341 tweak applied by the PSBT signer.
+    fn sign_silent_payment_inputs(
+        &self,
+        psbt: &mut Psbt,
+        master_key: &ExtendedPrivateKey,
+        inputs: &[(usize, &SilentPaymentOutput)],
+    ) -> WalletResult<usize> {
+        if inputs.is_empty() {
+            return Ok(0);
+        }
+
+        // Taproot signatures commit to every spent output
+        let prevouts = psbt
+            .inputs
+            .iter()
+            .zip(&psbt.unsigned_tx.input)
+            .enumerate()
+            .map(|(index, (input, txin))| {
+                match (&input.witness_utxo, &input.non_witness_utxo) {
+                    (Some(utxo), _) => Some(utxo.clone()),
+                    (None, Some(previous)) => previous
+                        .output
+                        .get(txin.previous_output.vout as usize)
+                        .cloned(),
+                    (None, None) => None,
+                }
+                .ok_or_else(|| {
+                    TransactionError::SigningFailed(format!("Input {index}: missing spent output"))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut cache = SighashCache::new(&psbt.unsigned_tx);
+        for &(index, output) in inputs {
+            let sighash_type = psbt.inputs[index]
+                .sighash_type
+                .map(|sighash_type| sighash_type.taproot_hash_ty())
+                .transpose()
+                .map_err(|e| TransactionError::SigningFailed(format!("Input {index}: {e}")))?
+                .unwrap_or(TapSighashType::Default);
+            let sighash = cache
+                .taproot_key_spend_signature_hash(index, &Prevouts::All(&prevouts), sighash_type)
+                .map_err(|e| TransactionError::SigningFailed(format!("Input {index}: {e}")))?;
+
+            let spend_key = master_key.derive_path(&silent_payments::key_path(
+                self.network,
+                output.account,
+                false,
+            ))?;
+            let keypair = Keypair::from_secret_key(
+                &self.secp,
+                &output.spending_key(&spend_key.xpriv.private_key)?,
+            );
+            let signature = self
+                .secp
+                .sign_schnorr(&Message::from_digest(sighash.to_byte_array()), &keypair);
+            psbt.inputs[index].tap_key_sig = Some(bitcoin::taproot::Signature {
+                signature,
+                sighash_type,
+            });
+        }
+        Ok(inputs.len())
+    }
+
+    /// Sign a message with the key of a wallet address
+    ///
+    /// Legacy (BIP-137) signatures need a single-key P2PKH, P2SH-P2WPKH or
+    /// P2WPKH address of the wallet. BIP-322 signatures are produced by
+    /// signing the virtual `to_sign` transaction like any other PSBT, so they
+    /// work for Taproot and keychain addresses as long as the wallet holds
+    /// every key needed to satisfy the script.
+    pub fn sign_message(
+        &mut self,
+        address: &Address,
+        message: &str,
+        format: MessageFormat,
+    ) -> WalletResult<String> {
+        self.lock_if_expired()?;
+        let master_key = self.master_key()?;
+        let script = address.script_pubkey();
+        if !self.is_mine(&script) {
+            return Err(WalletError::InvalidInput(format!(
+                "Address {address} does not belong to this wallet"
+            )));
+        }
+
+        match format {
+            MessageFormat::Legacy => {
+                let path = self.owns_address(address).ok_or_else(|| {
+                    WalletError::InvalidInput(format!(
+                        "BIP-137 needs a single-key wallet address; use BIP-322 for {address}"
+                    ))
+                })?;
+                let key = master_key.derive_path(&path)?;
+                message::sign_legacy(&self.secp, &key.xpriv.private_key, address, message)
+            }
+            MessageFormat::Simple | MessageFormat::Full => {
+                if format == MessageFormat::Simple && !script.is_witness_program() {
+                    return Err(WalletError::InvalidInput(format!(
+                        "{address} needs a scriptSig; use the full BIP-322 format"
+                    )));
+                }
+                // The virtual transaction moves no funds, so no spending policy applies
+                let mut psbt = message::to_sign_psbt(address, message)?;
+                self.sign_inputs(&mut psbt)?;
+                psbt.finalize_mut(&self.secp).map_err(|errors| {
+                    let reasons: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                    TransactionError::SigningFailed(format!(
+                        "Cannot complete message signature: {}",
+                        reasons.join("; ")
+                    ))
+                })?;
+                message::encode_bip322(&psbt.extract_tx_unchecked_fee_rate(), format)
+            }
+        }
+    }
+
+    /// Scanner for silent payments (BIP-352) to an account
+    ///
+    /// The scan and spend keys derive from the master key, so the wallet
+    /// must hold its private keys and be unlocked.
+    pub fn silent_payment_scanner(&mut self, account: u32) -> WalletResult<SilentPaymentScanner> {
+        self.lock_if_expired()?;
+        let master_key = self.master_key()?;
+        self.require_account(account)?;
+        let scan_key =
+            master_key.derive_path(&silent_payments::key_path(self.network, account, true))?;
+        let spend_key =
+            master_key.derive_path(&silent_payments::key_path(self.network, account, false))?;
+        SilentPaymentScanner::new(
+            account,
+            scan_key.xpriv.private_key,
+            spend_key.xpriv.private_key.public_key(&self.secp),
+        )
+    }
+
+    /// Silent payment address of an account, or one of its labelled variants
+    pub fn silent_payment_address(
+        &mut self,
+        account: u32,
+        label: Option<u32>,
+    ) -> WalletResult<SilentPaymentAddress> {
+        self.silent_payment_scanner(account)?
+            .address(self.network, label)
+    }
+
+    /// Add outputs found by a silent payment scanner to the wallet
+    ///
+    /// The outputs become UTXOs that are signed for with the spend key plus
+    /// the output's tweak. Returns the number of outputs that were new.
+    pub fn add_silent_payment_outputs(
+        &mut self,
+        outputs: &[SilentPaymentOutput],
+    ) -> WalletResult<usize> {
+        let network = bitcoin::Network::from(self.network);
+        let utxos = outputs
+            .iter()
+            .map(|output| {
+                self.require_account(output.account)?;
+                let script_pubkey = output.txout.script_pubkey.clone();
+                if !script_pubkey.is_p2tr() {
+                    return Err(WalletError::InvalidInput(format!(
+                        "Silent payment output {} is not a Taproot output",
+                        output.outpoint
+                    )));
+                }
+                let address = Address::from_script(&script_pubkey, network)
+                    .map_err(|e| WalletError::InvalidInput(e.to_string()))?;
+                Ok(Utxo {
+                    txid: output.outpoint.txid,
+                    vout: output.outpoint.vout,
+                    value: output.txout.value.to_sat(),
+                    script_pubkey,
+                    address,
+                    derivation_path: silent_payments::key_path(self.network, output.account, false),
+                    block_height: output.block_height,
+                })
+            })
+            .collect::<WalletResult<Vec<_>>>()?;
+
+        let before = self.balances();
+        let mut added = 0;
+        for (output, utxo) in outputs.iter().zip(utxos) {
+            added += usize::from(self.utxo(&output.outpoint).is_none());
+            self.silent_payments
+                .insert(utxo.script_pubkey.clone(), output.clone());
+            self.insert_utxo(utxo);
+        }
+        self.notify_balance(before);
+        Ok(added)
+    }
+
+    /// Silent payment output paying a script, if the wallet found one
+    pub fn silent_payment_output(&self, script: &Script) -> Option<&SilentPaymentOutput> {
+        self.silent_payments.get(script)
+    }
+
+    /// Private key an input spending a wallet script contributes to silent payment outputs
+    ///
+    /// Returns the key and whether it spends a Taproot output, or `None` for
+    /// script types that do not take part in the shared secret.
+    pub(crate) fn silent_payment_input_key(
+        &mut self,
+        script: &Script,
+    ) -> WalletResult<Option<(SecretKey, bool)>> {
+        self.lock_if_expired()?;
+        let master_key = self.master_key()?;
+        if let Some(output) = self.silent_payments.get(script) {
+            let spend_key = master_key.derive_path(&silent_payments::key_path(
+                self.network,
+                output.account,
+                false,
+            ))?;
+            return Ok(Some((
+                output.spending_key(&spend_key.xpriv.private_key)?,
+                true,
+            )));
+        }
+
+        if let Some((name, chain, index)) = self.keychain_position(script) {
+            // Single-key script types would contribute a key the wallet cannot pick out
+            return match self.keychains[name].derive(chain, index)?.desc_type() {
+                DescriptorType::Pkh
+                | DescriptorType::Wpkh
+                | DescriptorType::ShWpkh
+                | DescriptorType::Tr => Err(WalletError::InvalidInput(format!(
+                    "Inputs of keychain '{name}' cannot fund silent payments"
+                ))),
+                _ => Ok(None),
+            };
+        }
+
+        let path = Address::from_script(script, bitcoin::Network::from(self.network))
+            .ok()
+            .and_then(|address| self.owns_address(&address))
+            .ok_or_else(|| {
+                WalletError::InvalidInput(format!("Script {script} does not belong to this wallet"))
+            })?;
+        let key = master_key.derive_path(&path)?.xpriv.private_key;
+        if script.is_p2tr() {
+            // BIP-86 outputs commit to the key tweaked with an empty script tree
+            let keypair = Keypair::from_secret_key(&self.secp, &key).tap_tweak(&self.secp, None);
+            return Ok(Some((keypair.to_keypair().secret_key(), true)));
+        }
+        Ok(Some((key, false)))
+    }
+
+    fn keychain_next(&self, name: &str, chain: Chain) -> u32 {
+        self.keychain_indices
+            .get(&(name.to_string(), chain))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn require_keychain(&self, name: &str) -> WalletResult<&Keychain> {
+        self.keychains
+            .get(name)
+            .ok_or_else(|| WalletError::InvalidInput(format!("Unknown keychain '{name}'")))
+    }
+
+    /// Cache the scripts of a keychain up to the gap limit past the last
+    /// address handed out or used on each chain
+    ///
+    /// Indices that cannot be derived end the range early.
+    fn extend_keychain_lookahead(&mut self, name: &str) {
+        for chain in [Chain::External, Chain::Internal] {
+            let Some(keychain) = self.keychains.get(name) else {
+                return;
+            };
+            if keychain.descriptor(chain).is_none() {
+                continue;
+            }
+            let key = (name.to_string(), chain);
+            let end = self.keychain_next(name, chain) + DEFAULT_GAP_LIMIT;
+            let start = self.keychain_lookahead.get(&key).copied().unwrap_or(0);
+            for index in start..end {
+                if self.cache_keychain_address(name, chain, index).is_err() {
+                    break;
+                }
+                self.keychain_lookahead.insert(key.clone(), index + 1);
+            }
+        }
+    }
+
+    /// Derive a keychain address and remember its script for ownership lookups
+    fn cache_keychain_address(
+        &mut self,
+        name: &str,
+        chain: Chain,
+        index: u32,
+    ) -> WalletResult<Address> {
+        let address = self.require_keychain(name)?.address(
+            chain,
+            index,
+            bitcoin::Network::from(self.network),
+        )?;
+        self.keychain_scripts
+            .insert(address.script_pubkey(), (name.to_string(), chain, index));
+        Ok(address)
+    }
+
+    /// Derivation path and address of an output paying this wallet
+    fn owned_output(&self, script: &Script) -> Option<(DerivationPath, Address)> {
+        let address = Address::from_script(script, bitcoin::Network::from(self.network)).ok()?;
+        if let Some(output) = self.silent_payments.get(script) {
+            return Some((
+                silent_payments::key_path(self.network, output.account, false),
+                address,
+            ));
+        }
+        if let Some((_, chain, index)) = self.keychain_position(script) {
+            let path = DerivationPath::from(vec![
+                ChildNumber::from_normal_idx(chain.index()).ok()?,
+                ChildNumber::from_normal_idx(index).ok()?,
+            ]);
+            return Some((path, address));
+        }
+        Some((self.owns_address(&address)?, address))
+    }
+
+    /// Get the total balance in satoshis
+    pub fn balance(&self) -> u64 {
+        self.utxos.values().map(|utxo| utxo.value).sum()
+    }
+
+    /// Get confirmed balance (UTXOs with block height)
+    pub fn confirmed_balance(&self) -> u64 {
+        self.utxos
+            .values()
+            .filter(|utxo| utxo.block_height.is_some())
+            .map(|utxo| utxo.value)
             .sum()
     }
 
@@ -371,9 +2190,12 @@ impl Wallet {
     }
 
     /// Get UTXOs suitable for spending (with minimum confirmations)
+    ///
+    /// Frozen UTXOs are never returned.
     pub fn spendable_utxos(&self, min_confirmations: u32, current_height: u32) -> Vec<&Utxo> {
         self.utxos
             .values()
+            .filter(|utxo| !self.is_frozen(&utxo.outpoint()))
             .filter(|utxo| {
                 if let Some(height) = utxo.block_height {
                     current_height.saturating_sub(height) >= min_confirmations
@@ -385,111 +2207,1012 @@ impl Wallet {
     }
 
     /// Add a UTXO to the wallet
+    ///
+    /// The receiving address is marked as used on its chain.
     pub fn add_utxo(&mut self, utxo: Utxo) {
-        self.utxos.insert((utxo.txid, utxo.vout), utxo);
+        let before = self.balances();
+        self.insert_utxo(utxo);
+        self.notify_balance(before);
     }
 
     /// Remove a spent UTXO
     pub fn remove_utxo(&mut self, txid: Txid, vout: u32) -> Option<Utxo> {
-        self.utxos.remove(&(txid, vout))
+        let before = self.balances();
+        let removed = self.take_utxo(OutPoint::new(txid, vout), None);
+        self.notify_balance(before);
+        removed
     }
 
-    /// Add a transaction to history
-    pub fn add_transaction(&mut self, transaction: WalletTransaction) {
-        self.transactions.insert(transaction.txid, transaction);
+    fn insert_utxo(&mut self, utxo: Utxo) {
+        if let Some((name, chain, index)) = self.keychain_position(&utxo.script_pubkey) {
+            let name = name.to_string();
+            let next = self
+                .keychain_indices
+                .entry((name.clone(), chain))
+                .or_default();
+            *next = (*next).max(index + 1);
+            self.extend_keychain_lookahead(&name);
+        } else if let Some((chain, index)) = self.chain_position(&utxo.derivation_path) {
+            self.mark_index_used(chain, index);
+            self.addresses
+                .entry(utxo.derivation_path.clone())
+                .or_insert_with(|| utxo.address.clone());
+        }
+
+        // A payment is announced once, however often its outpoint is added
+        let outpoint = utxo.outpoint();
+        let event = (!self.outpoint_seen(&outpoint)).then(|| WalletEvent::Received {
+            outpoint,
+            value: utxo.value,
+            address: utxo.address.clone(),
+            block_height: utxo.block_height,
+        });
+        self.utxos.insert((utxo.txid, utxo.vout), utxo);
+        if let Some(event) = event {
+            self.emit(event);
+        }
     }
 
-    /// Get transaction history
-    pub fn transactions(&self) -> Vec<&WalletTransaction> {
-        self.transactions.values().collect()
+    /// Whether the wallet has held an outpoint: as a UTXO, as an owned output
+    /// of a recorded transaction, or as the input of one
+    fn outpoint_seen(&self, outpoint: &OutPoint) -> bool {
+        self.utxo(outpoint).is_some()
+            || self
+                .transactions
+                .get(&outpoint.txid)
+                .and_then(|transaction| transaction.outputs.get(outpoint.vout as usize))
+                .is_some_and(|output| output.owned)
+            || self.spending_transaction(outpoint).is_some()
     }
 
-    /// Get a private key for a specific derivation path
-    pub fn get_private_key(&self, path: &DerivationPath) -> WalletResult<PrivateKey> {
-        if let Some(derived_key) = self.derived_keys.get(path) {
-            Ok(derived_key.private_key())
-        } else {
-            let derived_key = self.master_key.derive_path(path)?;
-            Ok(derived_key.private_key())
-        }
+    fn take_utxo(&mut self, outpoint: OutPoint, spending_txid: Option<Txid>) -> Option<Utxo> {
+        let removed = self.utxos.remove(&(outpoint.txid, outpoint.vout))?;
+        self.emit(WalletEvent::Spent {
+            outpoint,
+            value: removed.value,
+            spending_txid,
+        });
+        Some(removed)
     }
 
-    /// Check if an address belongs to this wallet
-    pub fn owns_address(&self, address: &Address) -> Option<DerivationPath> {
-        self.addresses
-            .iter()
-            .find(|(_, addr)| *addr == address)
-            .map(|(path, _)| path.clone())
+    /// Look up a UTXO by outpoint
+    pub fn utxo(&self, outpoint: &OutPoint) -> Option<&Utxo> {
+        self.utxos.get(&(outpoint.txid, outpoint.vout))
     }
 
-    /// Get wallet creation timestamp
-    pub fn created_at(&self) -> u64 {
-        self.created_at
+    /// Coin control metadata of an outpoint, if any was set
+    pub fn utxo_metadata(&self, outpoint: &OutPoint) -> Option<&UtxoMetadata> {
+        self.utxo_metadata.get(outpoint)
     }
 
-    /// Get wallet last modified timestamp
-    pub fn modified_at(&self) -> u64 {
-        self.modified_at
+    /// Whether an outpoint is excluded from coin selection
+    pub fn is_frozen(&self, outpoint: &OutPoint) -> bool {
+        self.utxo_metadata
+            .get(outpoint)
+            .is_some_and(|metadata| metadata.frozen)
     }
 
-    /// Convert wallet to WalletData for storage
-    fn to_wallet_data(&self) -> WalletResult<WalletData> {
-        // Generate a temporary key for encryption (in production, this would be user-provided)
-        let encryption_key = SecureKey::generate().map_err(|_| WalletError::KeyGeneration)?;
-
-        // Serialize master key for encryption
-        let master_key_bytes = self.master_key.xpriv.encode();
-        let encrypted_seed =
-            encrypt_data(&encryption_key, &master_key_bytes, None).map_err(|_| {
-                WalletError::Crypto(crate::error::CryptoError::Encryption(
-                    "Failed to encrypt master key".to_string(),
-                ))
-            })?;
+    /// Exclude a UTXO from automatic coin selection
+    pub fn freeze_utxo(&mut self, outpoint: &OutPoint) -> WalletResult<()> {
+        self.update_utxo_metadata(outpoint, |metadata| metadata.frozen = true)
+    }
 
-        // Generate descriptors from addresses (simplified for now)
-        let descriptors = vec![
-            format!("wpkh({})", self.master_key.extended_public_key()),
-            format!("tr({})", self.master_key.extended_public_key()),
-        ];
+    /// Make a frozen UTXO available to coin selection again
+    pub fn unfreeze_utxo(&mut self, outpoint: &OutPoint) -> WalletResult<()> {
+        self.update_utxo_metadata(outpoint, |metadata| metadata.frozen = false)
+    }
 
-        Ok(WalletData {
-            id: self.id.clone(),
-            label: self.label.clone(),
-            encrypted_seed,
-            descriptors,
-            address_book: std::collections::HashMap::new(),
-            tx_comments: std::collections::HashMap::new(),
-            created_at: self.created_at,
-            modified_at: self.modified_at,
-            version: 1,
-        })
+    /// Set or clear the label of a UTXO
+    pub fn set_utxo_label(
+        &mut self,
+        outpoint: &OutPoint,
+        label: Option<String>,
+    ) -> WalletResult<()> {
+        self.update_utxo_metadata(outpoint, |metadata| metadata.label = label)
     }
 
-    /// Create wallet from WalletData
-    fn from_wallet_data(data: WalletData, storage: WalletStorage) -> WalletResult<Self> {
-        // For now, return an error since we need proper decryption handling
-        // In production, this would decrypt the seed and reconstruct the wallet
-        Err(WalletError::NotImplemented(
-            "Wallet loading from storage not yet implemented".to_string(),
-        ))
+    /// Set or clear the source tag of a UTXO
+    pub fn set_utxo_source(
+        &mut self,
+        outpoint: &OutPoint,
+        source: Option<String>,
+    ) -> WalletResult<()> {
+        self.update_utxo_metadata(outpoint, |metadata| metadata.source = source)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::storage::WalletStorage;
-    use tempfile::tempdir;
+    /// Apply a change to the metadata of a known UTXO
+    fn update_utxo_metadata(
+        &mut self,
+        outpoint: &OutPoint,
+        update: impl FnOnce(&mut UtxoMetadata),
+    ) -> WalletResult<()> {
+        if self.utxo(outpoint).is_none() {
+            return Err(WalletError::InvalidInput(format!(
+                "Unknown UTXO {outpoint}"
+            )));
+        }
 
-    fn create_test_wallet() -> Wallet {
-        let temp_dir = tempdir().unwrap();
-        let config = StorageConfig {
-            storage_path: temp_dir.path().to_path_buf(),
-            auto_backup: true,
-            backup_count: 3,
+        let metadata = self.utxo_metadata.entry(*outpoint).or_default();
+        update(metadata);
+        if *metadata == UtxoMetadata::default() {
+            self.utxo_metadata.remove(outpoint);
+        }
+        Ok(())
+    }
+
+    /// Label of an address from the address book
+    pub fn address_label(&self, address: &str) -> Option<&str> {
+        self.address_book.get(address).map(String::as_str)
+    }
+
+    /// Set or clear the address book label of an address
+    pub fn set_address_label(&mut self, address: &str, label: Option<String>) {
+        match label {
+            Some(label) => self.address_book.insert(address.to_string(), label),
+            None => self.address_book.remove(address),
         };
-        let storage = WalletStorage::new(config).unwrap();
-        Wallet::create_new("test_wallet".to_string(), Network::Regtest, storage).unwrap()
+    }
+
+    /// Comment attached to a transaction
+    pub fn transaction_label(&self, txid: &Txid) -> Option<&str> {
+        self.tx_comments.get(&txid.to_string()).map(String::as_str)
+    }
+
+    /// Set or clear the comment of a transaction
+    pub fn set_transaction_label(&mut self, txid: &Txid, label: Option<String>) {
+        if let Some(transaction) = self.transactions.get_mut(txid) {
+            transaction.label = label.clone();
+        }
+        match label {
+            Some(label) => self.tx_comments.insert(txid.to_string(), label),
+            None => self.tx_comments.remove(&txid.to_string()),
+        };
+    }
+
+    /// Export every label of this wallet in BIP-329 form
+    ///
+    /// Owned addresses and outputs carry the key origin of their account.
+    pub fn export_labels(&self) -> Vec<Label> {
+        let accounts = self.account_xpubs();
+        let origin_of = |path: &DerivationPath| {
+            accounts
+                .iter()
+                .find(|account| {
+                    path.len() == account.origin.len() + 2
+                        && path.as_ref().starts_with(account.origin.as_ref())
+                        && self.chain_position(path).map(|(chain, _)| chain.1)
+                            == Some(account.address_type)
+                })
+                .map(labels::origin_descriptor)
+        };
+        let mut exported = Vec::new();
+
+        let mut tx_comments: Vec<_> = self.tx_comments.iter().collect();
+        tx_comments.sort();
+        for (txid, comment) in tx_comments {
+            exported.push(Label::new(LabelType::Tx, txid.clone(), comment.clone()));
+        }
+
+        let mut address_book: Vec<_> = self.address_book.iter().collect();
+        address_book.sort();
+        for (address, label) in address_book {
+            let origin = self
+                .addresses
+                .iter()
+                .find(|(_, owned)| owned.to_string() == *address)
+                .and_then(|(path, _)| origin_of(path));
+            exported.push(Label {
+                origin,
+                ..Label::new(LabelType::Addr, address.clone(), label.clone())
+            });
+        }
+
+        for ((label_type, reference), label) in &self.labels {
+            exported.push(Label::new(*label_type, reference.clone(), label.clone()));
+        }
+
+        let mut outputs: Vec<_> = self.utxo_metadata.iter().collect();
+        outputs.sort_by_key(|(outpoint, _)| **outpoint);
+        for (outpoint, metadata) in outputs {
+            if metadata.label.is_none() && !metadata.frozen {
+                continue;
+            }
+            exported.push(Label {
+                label_type: LabelType::Output,
+                reference: outpoint.to_string(),
+                label: metadata.label.clone(),
+                origin: self
+                    .utxo(outpoint)
+                    .and_then(|utxo| origin_of(&utxo.derivation_path)),
+                spendable: Some(!metadata.frozen),
+            });
+        }
+
+        exported
+    }
+
+    /// Import BIP-329 labels, overwriting existing labels of the same objects
+    ///
+    /// All records are validated before any is applied. Returns the number
+    /// of records imported.
+    pub fn import_labels(&mut self, labels: Vec<Label>) -> WalletResult<usize> {
+        let invalid = |label: &Label| {
+            WalletError::InvalidInput(format!(
+                "Invalid {:?} label reference '{}'",
+                label.label_type, label.reference
+            ))
+        };
+        for label in &labels {
+            let valid = match label.label_type {
+                LabelType::Tx => Txid::from_str(&label.reference).is_ok(),
+                LabelType::Addr => label
+                    .reference
+                    .parse::<Address<bitcoin::address::NetworkUnchecked>>()
+                    .is_ok(),
+                LabelType::Pubkey => PublicKey::from_str(&label.reference).is_ok(),
+                LabelType::Input | LabelType::Output => {
+                    OutPoint::from_str(&label.reference).is_ok()
+                }
+                LabelType::Xpub => Xpub::from_str(&label.reference).is_ok(),
+            };
+            if !valid {
+                return Err(invalid(label));
+            }
+        }
+
+        let count = labels.len();
+        for label in labels {
+            match label.label_type {
+                LabelType::Tx => {
+                    let txid = Txid::from_str(&label.reference).map_err(|_| invalid(&label))?;
+                    if label.label.is_some() {
+                        self.set_transaction_label(&txid, label.label);
+                    }
+                }
+                LabelType::Addr => {
+                    if label.label.is_some() {
+                        self.set_address_label(&label.reference, label.label);
+                    }
+                }
+                LabelType::Output => {
+                    let outpoint =
+                        OutPoint::from_str(&label.reference).map_err(|_| invalid(&label))?;
+                    // Spent outputs keep their labels for history, so no UTXO is required
+                    let metadata = self.utxo_metadata.entry(outpoint).or_default();
+                    if label.label.is_some() {
+                        metadata.label = label.label;
+                    }
+                    if let Some(spendable) = label.spendable {
+                        metadata.frozen = !spendable;
+                    }
+                    if *metadata == UtxoMetadata::default() {
+                        self.utxo_metadata.remove(&outpoint);
+                    }
+                }
+                LabelType::Pubkey | LabelType::Input | LabelType::Xpub => {
+                    if let Some(text) = label.label {
+                        self.labels
+                            .insert((label.label_type, label.reference), text);
+                    }
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Add a transaction to history
+    pub fn add_transaction(&mut self, transaction: WalletTransaction) {
+        let previous_depth = self.transaction_depth(&transaction.txid);
+        let (txid, block_height) = (transaction.txid, transaction.block_height);
+        self.transactions.insert(txid, transaction);
+        self.notify_depth(txid, block_height, previous_depth);
+    }
+
+    /// Move a history entry and its unspent outputs to another block height
+    ///
+    /// Everything else the entry records is kept. Unknown txids are ignored.
+    pub(crate) fn set_transaction_height(&mut self, txid: &Txid, block_height: Option<u32>) {
+        let before = self.balances();
+        let previous_depth = self.transaction_depth(txid);
+        let Some(entry) = self.transactions.get_mut(txid) else {
+            return;
+        };
+        entry.block_height = block_height;
+        for vout in 0..entry.outputs.len() as u32 {
+            if let Some(utxo) = self.utxos.get_mut(&(*txid, vout)) {
+                utxo.block_height = block_height;
+            }
+        }
+        self.notify_balance(before);
+        self.notify_depth(*txid, block_height, previous_depth);
+    }
+
+    /// Get transaction history
+    pub fn transactions(&self) -> Vec<&WalletTransaction> {
+        self.transactions.values().collect()
+    }
+
+    /// Get a single history entry
+    pub fn transaction(&self, txid: &Txid) -> Option<&WalletTransaction> {
+        self.transactions.get(txid)
+    }
+
+    /// Record a transaction spending from or paying to this wallet
+    ///
+    /// Inputs are matched against the UTXO set and earlier history to learn
+    /// their values; outputs paying wallet addresses become UTXOs, unless a
+    /// recorded transaction already spends them, and spent wallet outputs
+    /// leave the UTXO set. Recording the same transaction again (e.g. once it
+    /// confirms) only updates its confirmation height: its inputs and outputs
+    /// stay as first recorded, and outputs spent since stay spent.
+    pub fn record_transaction(
+        &mut self,
+        transaction: &Transaction,
+        block_height: Option<u32>,
+        timestamp: u64,
+    ) -> WalletResult<&WalletTransaction> {
+        let txid = transaction.compute_txid();
+        let network = bitcoin::Network::from(self.network);
+        let before = self.balances();
+        let previous_depth = self.transaction_depth(&txid);
+
+        if self.transactions.contains_key(&txid) {
+            self.set_transaction_height(&txid, block_height);
+            return Ok(&self.transactions[&txid]);
+        }
+
+        let inputs: Vec<HistoryInput> = transaction
+            .input
+            .iter()
+            .map(|input| {
+                let previous_output = input.previous_output;
+                let (value, owned) = match self.utxo(&previous_output) {
+                    Some(utxo) => (Some(utxo.value), true),
+                    None => self
+                        .transactions
+                        .get(&previous_output.txid)
+                        .and_then(|previous| previous.outputs.get(previous_output.vout as usize))
+                        .map_or((None, false), |output| (Some(output.value), output.owned)),
+                };
+                HistoryInput {
+                    previous_output,
+                    value,
+                    owned,
+                }
+            })
+            .collect();
+
+        let mut received = Vec::new();
+        let outputs: Vec<HistoryOutput> = transaction
+            .output
+            .iter()
+            .enumerate()
+            .map(|(vout, output)| {
+                let owner = self.owned_output(&output.script_pubkey);
+                let owned = owner.is_some();
+                let outpoint = OutPoint::new(txid, vout as u32);
+                if let Some((derivation_path, address)) =
+                    owner.filter(|_| self.spending_transaction(&outpoint).is_none())
+                {
+                    received.push(Utxo {
+                        txid,
+                        vout: vout as u32,
+                        value: output.value.to_sat(),
+                        script_pubkey: output.script_pubkey.clone(),
+                        address,
+                        derivation_path,
+                        block_height,
+                    });
+                }
+                HistoryOutput {
+                    value: output.value.to_sat(),
+                    script_pubkey: output.script_pubkey.clone(),
+                    owned,
+                }
+            })
+            .collect();
+
+        if !outputs.iter().any(|output| output.owned) && !inputs.iter().any(|input| input.owned) {
+            return Err(WalletError::InvalidInput(format!(
+                "Transaction {txid} does not involve this wallet"
+            )));
+        }
+
+        let spent: u64 = inputs
+            .iter()
+            .filter(|input| input.owned)
+            .filter_map(|input| input.value)
+            .sum();
+        let value_delta = outputs
+            .iter()
+            .filter(|output| output.owned)
+            .map(|output| output.value as i64)
+            .sum::<i64>()
+            - spent as i64;
+
+        for input in inputs.iter().filter(|input| input.owned) {
+            self.take_utxo(input.previous_output, Some(txid));
+        }
+        for utxo in received {
+            self.insert_utxo(utxo);
+        }
+
+        let entry = WalletTransaction {
+            txid,
+            value_delta,
+            block_height,
+            timestamp,
+            label: self.tx_comments.get(&txid.to_string()).cloned(),
+            inputs,
+            outputs,
+        };
+        self.transactions.insert(txid, entry);
+        self.notify_balance(before);
+        self.notify_depth(txid, block_height, previous_depth);
+        Ok(&self.transactions[&txid])
+    }
+
+    /// Recorded transaction spending an outpoint, if any
+    pub fn spending_transaction(&self, outpoint: &OutPoint) -> Option<Txid> {
+        self.transactions
+            .values()
+            .find(|transaction| {
+                transaction
+                    .inputs
+                    .iter()
+                    .any(|input| input.previous_output == *outpoint)
+            })
+            .map(|transaction| transaction.txid)
+    }
+
+    /// Transactions matching a filter, oldest first
+    pub fn history(&self, filter: &HistoryFilter) -> Vec<&WalletTransaction> {
+        let mut history: Vec<_> = self
+            .transactions
+            .values()
+            .filter(|transaction| filter.matches(transaction))
+            .collect();
+        history.sort_by_key(|transaction| (transaction.timestamp, transaction.block_height));
+        history
+    }
+
+    /// Privacy analysis of the recorded transactions and UTXOs
+    pub fn privacy_report(&self) -> PrivacyReport {
+        privacy::analyze(self)
+    }
+
+    /// Subscribe to wallet events
+    ///
+    /// Only events emitted after subscribing are delivered.
+    pub fn subscribe(&self) -> broadcast::Receiver<WalletEvent> {
+        self.events.subscribe()
+    }
+
+    /// Height of the best block seen, if known
+    pub fn tip_height(&self) -> Option<u32> {
+        self.tip_height
+    }
+
+    /// Set the confirmation depth at which `WalletEvent::Confirmed` is emitted
+    pub fn set_confirmation_target(&mut self, depth: u32) {
+        self.confirmation_target = depth.max(1);
+    }
+
+    /// Confirmations of a wallet transaction at the current tip
+    pub fn confirmations(&self, txid: &Txid) -> u32 {
+        self.transaction_depth(txid)
+    }
+
+    /// Advance the chain tip
+    ///
+    /// Emits `Confirmed` for transactions that reach the confirmation target.
+    /// A tip below the previous one is treated as a reorg and rolls back
+    /// everything confirmed above it.
+    pub fn set_tip_height(&mut self, height: u32) {
+        if self.tip_height.is_some_and(|tip| height < tip) {
+            self.rollback_to(height);
+            return;
+        }
+
+        let previous: Vec<(Txid, Option<u32>, u32)> = self
+            .transactions
+            .values()
+            .filter(|transaction| transaction.block_height.is_some())
+            .map(|transaction| {
+                let depth = self.depth(transaction.block_height);
+                (transaction.txid, transaction.block_height, depth)
+            })
+            .collect();
+
+        self.tip_height = Some(height);
+        for (txid, block_height, previous_depth) in previous {
+            self.notify_depth(txid, block_height, previous_depth);
+        }
+    }
+
+    /// Roll back a reorg: transactions and UTXOs confirmed above `height` become unconfirmed
+    ///
+    /// Emits `Reorged` for every affected transaction and `BalanceChanged`
+    /// when the confirmed balance drops.
+    pub fn rollback_to(&mut self, height: u32) {
+        let before = self.balances();
+        let mut reorged = BTreeMap::new();
+
+        for transaction in self.transactions.values_mut() {
+            if let Some(previous_height) = transaction.block_height.filter(|h| *h > height) {
+                transaction.block_height = None;
+                reorged.insert(transaction.txid, previous_height);
+            }
+        }
+        for utxo in self.utxos.values_mut() {
+            if let Some(previous_height) = utxo.block_height.filter(|h| *h > height) {
+                utxo.block_height = None;
+                reorged.insert(utxo.txid, previous_height);
+            }
+        }
+
+        self.tip_height = Some(height);
+        for (txid, previous_height) in reorged {
+            self.emit(WalletEvent::Reorged {
+                txid,
+                previous_height,
+            });
+        }
+        self.notify_balance(before);
+    }
+
+    /// Confirmations at the current tip of something confirmed at `block_height`
+    ///
+    /// With no known tip a confirmed transaction counts as one confirmation.
+    fn depth(&self, block_height: Option<u32>) -> u32 {
+        match (block_height, self.tip_height) {
+            (None, _) => 0,
+            (Some(height), Some(tip)) if tip >= height => tip - height + 1,
+            (Some(_), _) => 1,
+        }
+    }
+
+    fn transaction_depth(&self, txid: &Txid) -> u32 {
+        self.transactions
+            .get(txid)
+            .map_or(0, |transaction| self.depth(transaction.block_height))
+    }
+
+    /// Emit `Confirmed` if a transaction just crossed the confirmation target
+    fn notify_depth(&self, txid: Txid, block_height: Option<u32>, previous_depth: u32) {
+        let depth = self.depth(block_height);
+        if let Some(block_height) = block_height {
+            if previous_depth < self.confirmation_target && depth >= self.confirmation_target {
+                self.emit(WalletEvent::Confirmed {
+                    txid,
+                    block_height,
+                    confirmations: depth,
+                });
+            }
+        }
+    }
+
+    /// Total and confirmed balance
+    fn balances(&self) -> (u64, u64) {
+        (self.balance(), self.confirmed_balance())
+    }
+
+    /// Emit `BalanceChanged` if the balances differ from `before`
+    fn notify_balance(&self, before: (u64, u64)) {
+        let (total, confirmed) = self.balances();
+        if (total, confirmed) != before {
+            self.emit(WalletEvent::BalanceChanged {
+                previous: before.0,
+                total,
+                confirmed,
+            });
+        }
+    }
+
+    fn emit(&self, event: WalletEvent) {
+        // Sending only fails when nobody is subscribed
+        let _ = self.events.send(event);
+    }
+
+    /// Get a private key for a specific derivation path
+    pub fn get_private_key(&mut self, path: &DerivationPath) -> WalletResult<PrivateKey> {
+        self.lock_if_expired()?;
+        let master_key = self.master_key()?;
+
+        if let Some(derived_key) = self.derived_keys.get(path) {
+            Ok(derived_key.private_key())
+        } else {
+            let derived_key = master_key.derive_path(path)?;
+            Ok(derived_key.private_key())
+        }
+    }
+
+    /// Check if an address belongs to this wallet
+    pub fn owns_address(&self, address: &Address) -> Option<DerivationPath> {
+        self.addresses
+            .iter()
+            .find(|(_, addr)| *addr == address)
+            .map(|(path, _)| path.clone())
+    }
+
+    /// Get wallet creation timestamp
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    /// Get wallet last modified timestamp
+    pub fn modified_at(&self) -> u64 {
+        self.modified_at
+    }
+
+    /// Sealed master key with its KDF salt and parameters
+    fn sealed_keys(&self) -> WalletResult<(EncryptedData, Vec<u8>, KdfParams)> {
+        Ok(match &self.keys {
+            WalletKeys::Private {
+                master_key,
+                seed_key,
+                kdf_salt,
+                kdf_params,
+            } => {
+                // Serialize master key and seal it under the passphrase-derived key
+                let mut master_key_bytes = master_key.xpriv.encode();
+                let encrypted_seed = encrypt_data(seed_key, &master_key_bytes, None);
+                master_key_bytes.zeroize();
+                let encrypted_seed = encrypted_seed.map_err(|_| {
+                    WalletError::Crypto(CryptoError::Encryption(
+                        "Failed to encrypt master key".to_string(),
+                    ))
+                })?;
+
+                (encrypted_seed, kdf_salt.clone(), kdf_params.clone())
+            }
+            // Watch-only wallets are fully described by their account descriptors
+            WalletKeys::WatchOnly(_) => (
+                EncryptedData::new(Vec::new(), Vec::new()),
+                Vec::new(),
+                KdfParams::default(),
+            ),
+            WalletKeys::Locked {
+                encrypted_seed,
+                kdf_salt,
+                kdf_params,
+                ..
+            } => (encrypted_seed.clone(), kdf_salt.clone(), kdf_params.clone()),
+        })
+    }
+
+    /// Convert wallet to WalletData for storage
+    fn to_wallet_data(&self) -> WalletResult<WalletData> {
+        let (encrypted_seed, kdf_salt, kdf_params) = self.sealed_keys()?;
+        // One multipath descriptor with key origin and checksum per account and address type
+        let descriptors = self
+            .account_xpubs()
+            .iter()
+            .map(AccountXpub::multipath_descriptor)
+            .collect();
+
+        Ok(WalletData {
+            id: self.id.clone(),
+            label: self.label.clone(),
+            encrypted_seed,
+            kdf_salt,
+            kdf_params,
+            network: Some(self.network),
+            watch_only: self.is_watch_only(),
+            chain_indices: {
+                let mut chain_indices: Vec<ChainIndex> = self
+                    .next_indices
+                    .iter()
+                    .map(|(&key, &next)| ChainIndex {
+                        account: key.0,
+                        address_type: key.1,
+                        chain: key.2,
+                        next,
+                        used: self
+                            .used_indices
+                            .get(&key)
+                            .map(|used| used.iter().copied().collect())
+                            .unwrap_or_default(),
+                    })
+                    .collect();
+                chain_indices.sort_by_key(|entry| (entry.account, entry.address_type, entry.chain));
+                chain_indices
+            },
+            accounts: self.accounts.values().cloned().collect(),
+            utxos: {
+                let mut utxos: Vec<UtxoRecord> = self
+                    .utxos
+                    .values()
+                    .map(|utxo| UtxoRecord {
+                        outpoint: utxo.outpoint(),
+                        value: utxo.value,
+                        script_pubkey: utxo.script_pubkey.clone(),
+                        derivation_path: utxo.derivation_path.clone(),
+                        block_height: utxo.block_height,
+                    })
+                    .collect();
+                utxos.sort_by_key(|utxo| utxo.outpoint);
+                utxos
+            },
+            utxo_metadata: {
+                let mut records: Vec<UtxoMetadataRecord> = self
+                    .utxo_metadata
+                    .iter()
+                    .map(|(outpoint, metadata)| UtxoMetadataRecord {
+                        outpoint: *outpoint,
+                        metadata: metadata.clone(),
+                    })
+                    .collect();
+                records.sort_by_key(|record| record.outpoint);
+                records
+            },
+            descriptors,
+            address_book: self.address_book.clone(),
+            tx_comments: self.tx_comments.clone(),
+            transactions: self.transactions.values().cloned().collect(),
+            tip_height: self.tip_height,
+            keychains: self
+                .keychains
+                .values()
+                .map(|keychain| KeychainRecord {
+                    name: keychain.name.clone(),
+                    descriptors: keychain.descriptors(),
+                    next_receive: self.keychain_next(&keychain.name, Chain::External),
+                    next_change: self.keychain_next(&keychain.name, Chain::Internal),
+                })
+                .collect(),
+            labels: self
+                .labels
+                .iter()
+                .map(|((label_type, reference), label)| {
+                    Label::new(*label_type, reference.clone(), label.clone())
+                })
+                .collect(),
+            policy: self.policy.clone(),
+            policy_spends: self.policy_spends.clone(),
+            silent_payments: {
+                let mut outputs: Vec<SilentPaymentOutput> =
+                    self.silent_payments.values().cloned().collect();
+                outputs.sort_by_key(|output| output.outpoint);
+                outputs
+            },
+            created_at: self.created_at,
+            modified_at: self.modified_at,
+            version: WALLET_SCHEMA_VERSION,
+        })
+    }
+
+    /// Create wallet from WalletData
+    fn from_wallet_data(
+        data: WalletData,
+        passphrase: &str,
+        storage: WalletStorage,
+    ) -> WalletResult<Self> {
+        let (keys, network_kind) = if data.watch_only {
+            let accounts = data
+                .descriptors
+                .iter()
+                .map(|descriptor| AccountXpub::from_descriptor(descriptor))
+                .collect::<WalletResult<Vec<_>>>()
+                .map_err(|_| WalletError::Storage(StorageError::WalletCorrupted))?;
+            let network_kind = accounts
+                .first()
+                .map(|account| account.xpub.network)
+                .ok_or(WalletError::Storage(StorageError::WalletCorrupted))?;
+            (WalletKeys::WatchOnly(accounts), network_kind)
+        } else {
+            // Records written before the passphrase envelope existed cannot be decrypted
+            if data.kdf_salt.is_empty() {
+                return Err(WalletError::Storage(StorageError::WalletCorrupted));
+            }
+            // Keys are rederived from the master key, but descriptors must still check out
+            for descriptor in &data.descriptors {
+                Descriptor::<DescriptorPublicKey>::from_str(descriptor)
+                    .map_err(|_| WalletError::Storage(StorageError::WalletCorrupted))?;
+            }
+
+            let seed_key = derive_seed_key(passphrase, &data.kdf_salt, &data.kdf_params)?;
+            let mut master_key_bytes = decrypt_data(&seed_key, &data.encrypted_seed, None)
+                .map_err(|_| WalletError::InvalidPassphrase)?;
+            let xpriv = Xpriv::decode(&master_key_bytes);
+            master_key_bytes.zeroize();
+            let xpriv = xpriv.map_err(|_| WalletError::Storage(StorageError::WalletCorrupted))?;
+
+            let keys = WalletKeys::Private {
+                master_key: ExtendedPrivateKey::new(xpriv),
+                seed_key,
+                kdf_salt: data.kdf_salt,
+                kdf_params: data.kdf_params,
+            };
+            (keys, xpriv.network)
+        };
+
+        // Older records only carry the key's network kind
+        let network = data.network.unwrap_or(match network_kind {
+            NetworkKind::Main => Network::Bitcoin,
+            NetworkKind::Test => Network::Testnet,
+        });
+
+        let mut wallet = Self {
+            id: data.id,
+            label: data.label,
+            network,
+            keys,
+            unlocked_until: None,
+            secp: Secp256k1::new(),
+            derived_keys: HashMap::new(),
+            addresses: HashMap::new(),
+            utxos: HashMap::new(),
+            transactions: data
+                .transactions
+                .into_iter()
+                .map(|mut transaction| {
+                    // Comments are the source of truth for transaction labels
+                    transaction.label =
+                        data.tx_comments.get(&transaction.txid.to_string()).cloned();
+                    (transaction.txid, transaction)
+                })
+                .collect(),
+            accounts: data
+                .accounts
+                .into_iter()
+                .map(|account| (account.index, account))
+                .collect(),
+            next_indices: data
+                .chain_indices
+                .iter()
+                .map(|entry| ((entry.account, entry.address_type, entry.chain), entry.next))
+                .collect(),
+            used_indices: data
+                .chain_indices
+                .iter()
+                .filter(|entry| !entry.used.is_empty())
+                .map(|entry| {
+                    let key = (entry.account, entry.address_type, entry.chain);
+                    (key, entry.used.iter().copied().collect())
+                })
+                .collect(),
+            keychains: BTreeMap::new(),
+            keychain_indices: HashMap::new(),
+            keychain_scripts: HashMap::new(),
+            keychain_lookahead: HashMap::new(),
+            utxo_metadata: data
+                .utxo_metadata
+                .into_iter()
+                .map(|record| (record.outpoint, record.metadata))
+                .collect(),
+            address_book: data.address_book,
+            tx_comments: data.tx_comments,
+            labels: data
+                .labels
+                .into_iter()
+                .filter_map(|label| Some(((label.label_type, label.reference), label.label?)))
+                .collect(),
+            policy: data.policy,
+            policy_spends: data.policy_spends,
+            silent_payments: data
+                .silent_payments
+                .into_iter()
+                .map(|output| (output.txout.script_pubkey.clone(), output))
+                .collect(),
+            tip_height: data.tip_height,
+            confirmation_target: DEFAULT_CONFIRMATION_TARGET,
+            events: events::channel(),
+            created_at: data.created_at,
+            modified_at: data.modified_at,
+            storage,
+        };
+
+        wallet.ensure_account(0);
+        for account in wallet.watched_accounts() {
+            wallet.ensure_account(account);
+        }
+
+        for record in data.utxos {
+            let address =
+                Address::from_script(&record.script_pubkey, bitcoin::Network::from(network))
+                    .map_err(|_| WalletError::Storage(StorageError::WalletCorrupted))?;
+            wallet.utxos.insert(
+                (record.outpoint.txid, record.outpoint.vout),
+                Utxo {
+                    txid: record.outpoint.txid,
+                    vout: record.outpoint.vout,
+                    value: record.value,
+                    script_pubkey: record.script_pubkey,
+                    address,
+                    derivation_path: record.derivation_path,
+                    block_height: record.block_height,
+                },
+            );
+        }
+
+        for record in data.keychains {
+            let (descriptor, change) = match record.descriptors.as_slice() {
+                [receive] => (receive, None),
+                [receive, change] => (receive, Some(change.as_str())),
+                _ => return Err(WalletError::Storage(StorageError::WalletCorrupted)),
+            };
+            wallet
+                .add_keychain(&record.name, descriptor, change)
+                .map_err(|_| WalletError::Storage(StorageError::WalletCorrupted))?;
+            for (chain, next) in [
+                (Chain::External, record.next_receive),
+                (Chain::Internal, record.next_change),
+            ] {
+                wallet
+                    .keychain_indices
+                    .insert((record.name.clone(), chain), next);
+            }
+            wallet.extend_keychain_lookahead(&record.name);
+        }
+
+        // Rebuild the address cache for every index handed out so far
+        let handed_out: Vec<(ChainKey, u32)> = wallet
+            .next_indices
+            .iter()
+            .map(|(key, next)| (*key, *next))
+            .collect();
+        for ((account, address_type, chain), next) in handed_out {
+            for index in 0..next {
+                let path = wallet.chain_path(address_type, account, chain.index(), index)?;
+                wallet.generate_address(&path, address_type)?;
+            }
+        }
+
+        Ok(wallet)
+    }
+}
+
+/// Derive the key that protects the persisted master key
+fn derive_seed_key(passphrase: &str, salt: &[u8], params: &KdfParams) -> WalletResult<SecureKey> {
+    let mut key_bytes =
+        derive_key_from_password(passphrase, salt, params).map_err(WalletError::Crypto)?;
+    let key = SecureKey::from_bytes(&key_bytes);
+    key_bytes.zeroize();
+    key.map_err(WalletError::Crypto)
+}
+
+/// Unlock a wallet's storage, reporting a wrong passphrase as such
+fn unlock_storage(storage: &mut WalletStorage, passphrase: &str) -> WalletResult<()> {
+    storage.unlock(passphrase).map_err(|e| match e {
+        StorageError::DecryptionFailed => WalletError::InvalidPassphrase,
+        e => WalletError::Storage(e),
+    })
+}
+
+/// Current Unix time in seconds
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::WalletStorage;
+    use tempfile::tempdir;
+
+    fn create_test_wallet() -> Wallet {
+        let temp_dir = tempdir().unwrap();
+        let config = StorageConfig {
+            storage_path: temp_dir.path().to_path_buf(),
+            auto_backup: true,
+            backup_count: 3,
+        };
+        let storage = WalletStorage::new(config).unwrap();
+        Wallet::create_new("test_wallet".to_string(), Network::Regtest, storage).unwrap()
+    }
+
+    fn test_storage(path: &std::path::Path) -> WalletStorage {
+        let config = StorageConfig {
+            storage_path: path.to_path_buf(),
+            auto_backup: false,
+            backup_count: 3,
+        };
+        WalletStorage::new(config).unwrap()
     }
 
     #[test]
@@ -598,4 +3321,1550 @@ mod tests {
         // Original wallet should not own the other wallet's address
         assert!(wallet.owns_address(&other_address).is_none());
     }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = Wallet::create_with_passphrase(
+            "roundtrip".to_string(),
+            Network::Regtest,
+            "correct horse",
+            test_storage(temp_dir.path()),
+        )
+        .unwrap();
+
+        let extra_address = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        let xpub = wallet.account_xpub(AddressType::NativeSegwit, 0).unwrap();
+        wallet.save().unwrap();
+        drop(wallet);
+
+        let loaded = Wallet::load(
+            "roundtrip".to_string(),
+            "correct horse",
+            test_storage(temp_dir.path()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            loaded.account_xpub(AddressType::NativeSegwit, 0).unwrap(),
+            xpub
+        );
+        assert_eq!(loaded.network, Network::Regtest);
+        assert_eq!(
+            loaded.next_indices[&(0, AddressType::NativeSegwit, Chain::External)],
+            2
+        );
+        assert!(loaded.owns_address(&extra_address).is_some());
+        assert_eq!(
+            loaded.get_addresses(AddressType::NativeSegwit, false).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_load_with_wrong_passphrase_fails() {
+        let temp_dir = tempdir().unwrap();
+        let wallet = Wallet::create_with_passphrase(
+            "locked".to_string(),
+            Network::Regtest,
+            "correct horse",
+            test_storage(temp_dir.path()),
+        )
+        .unwrap();
+        wallet.save().unwrap();
+        drop(wallet);
+
+        let result = Wallet::load(
+            "locked".to_string(),
+            "battery staple",
+            test_storage(temp_dir.path()),
+        );
+        assert!(matches!(result, Err(WalletError::InvalidPassphrase)));
+    }
+
+    #[test]
+    fn test_rekey_changes_passphrase() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = Wallet::create_with_passphrase(
+            "rekeyed".to_string(),
+            Network::Regtest,
+            "correct horse",
+            test_storage(temp_dir.path()),
+        )
+        .unwrap();
+        wallet.save().unwrap();
+        let xpub = wallet.account_xpub(AddressType::NativeSegwit, 0).unwrap();
+
+        let params = KdfParams::for_fast_operations();
+        assert!(matches!(
+            wallet.rekey("wrong", "battery staple", params.clone()),
+            Err(WalletError::InvalidPassphrase)
+        ));
+        wallet
+            .rekey("correct horse", "battery staple", params.clone())
+            .unwrap();
+        // The previous passphrase no longer matches the in-memory keys either
+        assert!(wallet
+            .rekey("correct horse", "other", params.clone())
+            .is_err());
+        drop(wallet);
+
+        // The storage key follows the passphrase
+        let mut storage = test_storage(temp_dir.path());
+        assert!(storage.unlock("correct horse").is_err());
+        storage.unlock("battery staple").unwrap();
+        assert_eq!(
+            storage.load_wallet_data("rekeyed").unwrap().kdf_params,
+            params
+        );
+        let result = Wallet::load("rekeyed".to_string(), "correct horse", storage);
+        assert!(matches!(result, Err(WalletError::InvalidPassphrase)));
+
+        let loaded = Wallet::load(
+            "rekeyed".to_string(),
+            "battery staple",
+            test_storage(temp_dir.path()),
+        )
+        .unwrap();
+        assert_eq!(
+            loaded.account_xpub(AddressType::NativeSegwit, 0).unwrap(),
+            xpub
+        );
+    }
+
+    const ABANDON_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_mnemonic_master_key_vector() {
+        // BIP-39 reference vector with passphrase "TREZOR"
+        let mnemonic = Mnemonic::parse(ABANDON_MNEMONIC).unwrap();
+        let master =
+            ExtendedPrivateKey::from_mnemonic(&mnemonic, "TREZOR", Network::Bitcoin).unwrap();
+        assert_eq!(
+            master.xpriv.to_string(),
+            "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF"
+        );
+    }
+
+    #[test]
+    fn test_mnemonic_restore_matches_reference_addresses() {
+        let temp_dir = tempdir().unwrap();
+        let mnemonic = Mnemonic::parse(ABANDON_MNEMONIC).unwrap();
+        let mut wallet = Wallet::from_mnemonic(
+            "restored".to_string(),
+            Network::Bitcoin,
+            &mnemonic,
+            "",
+            "",
+            test_storage(temp_dir.path()),
+        )
+        .unwrap();
+
+        // First receive addresses from the BIP-44/49/84 reference vectors
+        let legacy = wallet.get_addresses(AddressType::Legacy, false);
+        let nested = wallet.get_addresses(AddressType::NestedSegwit, false);
+        let native = wallet.get_addresses(AddressType::NativeSegwit, false);
+        assert_eq!(legacy[0].to_string(), "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA");
+        assert_eq!(nested[0].to_string(), "37VucYSaXLCAsxYyAPfbSi9eh4iEcbShgf");
+        assert_eq!(
+            native[0].to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+
+        // A BIP-39 passphrase yields an entirely different wallet
+        let other_dir = tempdir().unwrap();
+        let mut with_passphrase = Wallet::from_mnemonic(
+            "restored".to_string(),
+            Network::Bitcoin,
+            &mnemonic,
+            "TREZOR",
+            "",
+            test_storage(other_dir.path()),
+        )
+        .unwrap();
+        assert_ne!(
+            with_passphrase
+                .get_new_address(AddressType::NativeSegwit)
+                .unwrap(),
+            wallet.get_new_address(AddressType::NativeSegwit).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_lock_and_unlock() {
+        let temp_dir = tempdir().unwrap();
+        let reference_dir = tempdir().unwrap();
+        let mnemonic = Mnemonic::parse(ABANDON_MNEMONIC).unwrap();
+        let restore = |path: &std::path::Path| {
+            Wallet::from_mnemonic(
+                "lockable".to_string(),
+                Network::Bitcoin,
+                &mnemonic,
+                "",
+                "correct horse",
+                test_storage(path),
+            )
+            .unwrap()
+        };
+        let mut wallet = restore(temp_dir.path());
+        let mut reference = restore(reference_dir.path());
+        let path = wallet
+            .owns_address(&wallet.get_addresses(AddressType::NativeSegwit, false)[0])
+            .unwrap();
+
+        // Locked wallets keep deriving addresses from the account keys
+        wallet.lock().unwrap();
+        assert!(wallet.is_locked());
+        assert!(wallet.derived_keys.is_empty());
+        assert_eq!(
+            wallet.get_new_address(AddressType::NativeSegwit).unwrap(),
+            reference
+                .get_new_address(AddressType::NativeSegwit)
+                .unwrap()
+        );
+        assert!(matches!(
+            wallet.get_private_key(&path),
+            Err(WalletError::Locked)
+        ));
+        assert!(matches!(
+            wallet.create_account(1, None),
+            Err(WalletError::Locked)
+        ));
+        let address = wallet.get_addresses(AddressType::NativeSegwit, false)[0].clone();
+        assert!(matches!(
+            wallet.sign_message(&address, "locked", MessageFormat::Legacy),
+            Err(WalletError::Locked)
+        ));
+
+        // The sealed master key is still saved while locked
+        wallet.save().unwrap();
+        assert!(matches!(
+            wallet.unlock("battery staple", None),
+            Err(WalletError::InvalidPassphrase)
+        ));
+
+        // An elapsed timeout locks the wallet again
+        wallet
+            .unlock("correct horse", Some(Duration::ZERO))
+            .unwrap();
+        assert!(wallet.is_locked());
+        assert!(wallet.lock_if_expired().unwrap());
+
+        // Key accessors that find the timeout passed erase the keys themselves
+        wallet
+            .unlock("correct horse", Some(Duration::ZERO))
+            .unwrap();
+        assert!(matches!(wallet.keys, WalletKeys::Private { .. }));
+        assert!(matches!(
+            wallet.get_private_key(&path),
+            Err(WalletError::Locked)
+        ));
+        assert!(matches!(wallet.keys, WalletKeys::Locked { .. }));
+        assert!(!wallet.lock_if_expired().unwrap());
+        wallet
+            .unlock("correct horse", Some(Duration::ZERO))
+            .unwrap();
+        assert!(matches!(
+            wallet.sign_message(&address, "expired", MessageFormat::Legacy),
+            Err(WalletError::Locked)
+        ));
+        assert!(matches!(wallet.keys, WalletKeys::Locked { .. }));
+
+        wallet
+            .unlock("correct horse", Some(Duration::from_secs(3600)))
+            .unwrap();
+        assert!(!wallet.is_locked());
+        assert!(!wallet.lock_if_expired().unwrap());
+        assert_eq!(
+            wallet.get_private_key(&path).unwrap().public_key(),
+            reference.get_private_key(&path).unwrap().public_key()
+        );
+        wallet.mark_index_used((0, AddressType::NativeSegwit, Chain::External), 0);
+        wallet.create_account(1, None).unwrap();
+        drop(wallet);
+
+        let loaded = Wallet::load(
+            "lockable".to_string(),
+            "correct horse",
+            test_storage(temp_dir.path()),
+        )
+        .unwrap();
+        assert!(!loaded.is_locked());
+    }
+
+    #[test]
+    fn test_taproot_addresses_match_bip86_vectors() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = abandon_wallet(temp_dir.path(), Network::Bitcoin);
+
+        // BIP-86 reference vectors: m/86'/0'/0'/0/0, m/86'/0'/0'/0/1 and m/86'/0'/0'/1/0
+        wallet.get_new_address(AddressType::Taproot).unwrap();
+        let receive = wallet.get_addresses(AddressType::Taproot, false);
+        assert_eq!(
+            receive[0].to_string(),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+        assert_eq!(
+            receive[1].to_string(),
+            "bc1p4qhjn9zdvkux4e44uhx8tc55attvtyu358kutcqkudyccelu0was9fqzwh"
+        );
+        assert_eq!(
+            wallet
+                .get_change_address(AddressType::Taproot)
+                .unwrap()
+                .to_string(),
+            "bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7"
+        );
+
+        // Output key of the first address, tweaked from internal key cc8a4bc6...
+        assert_eq!(
+            receive[0].script_pubkey().to_hex_string(),
+            "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+        );
+    }
+
+    #[test]
+    fn test_generate_mnemonic_word_counts() {
+        assert_eq!(Wallet::generate_mnemonic(12).unwrap().word_count(), 12);
+        assert_eq!(Wallet::generate_mnemonic(24).unwrap().word_count(), 24);
+        assert!(Wallet::generate_mnemonic(13).is_err());
+        assert_ne!(
+            Wallet::generate_mnemonic(12).unwrap(),
+            Wallet::generate_mnemonic(12).unwrap()
+        );
+    }
+
+    fn abandon_wallet(path: &std::path::Path, network: Network) -> Wallet {
+        let mnemonic = Mnemonic::parse(ABANDON_MNEMONIC).unwrap();
+        Wallet::from_mnemonic(
+            "signer".to_string(),
+            network,
+            &mnemonic,
+            "",
+            "",
+            test_storage(path),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_account_xpub_matches_reference_vector() {
+        let temp_dir = tempdir().unwrap();
+        let wallet = abandon_wallet(temp_dir.path(), Network::Bitcoin);
+
+        // BIP-84 reference account key (xpub version bytes)
+        let account = wallet.account_xpub(AddressType::NativeSegwit, 0).unwrap();
+        assert_eq!(
+            account.xpub.to_string(),
+            "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V"
+        );
+        assert_eq!(account.fingerprint.to_string(), "73c5da0a");
+        assert_eq!(account.origin.to_string(), "84'/0'/0'");
+        assert_eq!(wallet.account_xpubs().len(), 4);
+    }
+
+    #[test]
+    fn test_descriptors_carry_origins_and_checksums() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = abandon_wallet(temp_dir.path(), Network::Bitcoin);
+        let address = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+
+        let mut data = wallet.to_wallet_data().unwrap();
+        assert_eq!(data.descriptors.len(), 4);
+        assert!(data.descriptors[2].starts_with(&format!(
+            "wpkh([73c5da0a/84'/0'/0']{}/<0;1>/*)#",
+            wallet
+                .account_xpub(AddressType::NativeSegwit, 0)
+                .unwrap()
+                .xpub
+        )));
+        for (descriptor, account) in data.descriptors.iter().zip(wallet.account_xpubs()) {
+            assert_eq!(AccountXpub::from_descriptor(descriptor).unwrap(), account);
+        }
+
+        // Core import requests: one active receive/change pair per address type
+        let imports = wallet.descriptor_imports(None);
+        assert_eq!(imports.len(), 8);
+        assert!(imports
+            .iter()
+            .all(|import| import.active && import.range[0] == 0));
+        let receive = imports
+            .iter()
+            .find(|import| import.desc.starts_with("wpkh(") && !import.internal)
+            .unwrap();
+        assert!(receive.desc.contains("/0/*)#"));
+        let parsed = Descriptor::<DescriptorPublicKey>::from_str(&receive.desc).unwrap();
+        let index = wallet
+            .chain_position(&wallet.owns_address(&address).unwrap())
+            .unwrap()
+            .1;
+        assert!(receive.range[1] >= index + DEFAULT_GAP_LIMIT - 1);
+        assert_eq!(
+            parsed
+                .at_derivation_index(index)
+                .unwrap()
+                .address(bitcoin::Network::Bitcoin)
+                .unwrap(),
+            address
+        );
+
+        // A descriptor that fails its checksum marks the record as corrupted
+        let last = data.descriptors[0].pop().unwrap();
+        data.descriptors[0].push(if last == 'q' { 'p' } else { 'q' });
+        let other_dir = tempdir().unwrap();
+        assert!(matches!(
+            Wallet::from_wallet_data(data, "", test_storage(other_dir.path())),
+            Err(WalletError::Storage(StorageError::WalletCorrupted))
+        ));
+    }
+
+    #[test]
+    fn test_watch_only_matches_private_wallet() {
+        let signer_dir = tempdir().unwrap();
+        let mut signer = abandon_wallet(signer_dir.path(), Network::Regtest);
+        let descriptors: Vec<String> = signer
+            .account_xpubs()
+            .iter()
+            .map(|account| account.descriptor(false))
+            .collect();
+
+        let watcher_dir = tempdir().unwrap();
+        let mut watcher = Wallet::from_descriptors(
+            "watcher".to_string(),
+            Network::Regtest,
+            &descriptors,
+            test_storage(watcher_dir.path()),
+        )
+        .unwrap();
+        assert!(watcher.is_watch_only());
+        assert!(!signer.is_watch_only());
+
+        for address_type in [
+            AddressType::Legacy,
+            AddressType::NestedSegwit,
+            AddressType::NativeSegwit,
+            AddressType::Taproot,
+        ] {
+            assert_eq!(
+                watcher.get_new_address(address_type).unwrap(),
+                signer.get_new_address(address_type).unwrap()
+            );
+            assert_eq!(
+                watcher.get_change_address(address_type).unwrap(),
+                signer.get_change_address(address_type).unwrap()
+            );
+        }
+
+        // Same key origins, but no private keys on the watch-only side
+        let path = AddressType::NativeSegwit.derivation_path(0, 0, 1);
+        assert_eq!(
+            watcher.key_origin(&path).unwrap(),
+            signer.key_origin(&path).unwrap()
+        );
+        assert!(signer.get_private_key(&path).is_ok());
+        assert!(matches!(
+            watcher.get_private_key(&path),
+            Err(WalletError::WatchOnly)
+        ));
+    }
+
+    #[test]
+    fn test_watch_only_save_and_load() {
+        let signer_dir = tempdir().unwrap();
+        let signer = abandon_wallet(signer_dir.path(), Network::Regtest);
+        let account = signer.account_xpub(AddressType::NativeSegwit, 0).unwrap();
+
+        let watcher_dir = tempdir().unwrap();
+        let mut watcher = Wallet::watch_only(
+            "watcher".to_string(),
+            Network::Regtest,
+            vec![account.clone()],
+            test_storage(watcher_dir.path()),
+        )
+        .unwrap();
+        let address = watcher.get_new_address(AddressType::NativeSegwit).unwrap();
+        assert!(watcher.get_new_address(AddressType::Taproot).is_err());
+        watcher.save().unwrap();
+        drop(watcher);
+
+        // Created on a locked storage, the wallet is keyed from the empty passphrase
+        let result = Wallet::load(
+            "watcher".to_string(),
+            "other",
+            test_storage(watcher_dir.path()),
+        );
+        assert!(matches!(result, Err(WalletError::InvalidPassphrase)));
+        let loaded =
+            Wallet::load("watcher".to_string(), "", test_storage(watcher_dir.path())).unwrap();
+        assert!(loaded.is_watch_only());
+        assert_eq!(loaded.network, Network::Regtest);
+        assert_eq!(loaded.account_xpubs(), vec![account]);
+        assert!(loaded.owns_address(&address).is_some());
+    }
+
+    #[test]
+    fn test_watch_only_rejects_wrong_network() {
+        let signer_dir = tempdir().unwrap();
+        let signer = abandon_wallet(signer_dir.path(), Network::Bitcoin);
+
+        let watcher_dir = tempdir().unwrap();
+        let result = Wallet::watch_only(
+            "watcher".to_string(),
+            Network::Regtest,
+            signer.account_xpubs(),
+            test_storage(watcher_dir.path()),
+        );
+        assert!(matches!(result, Err(WalletError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_accounts_have_separate_chains() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = abandon_wallet(temp_dir.path(), Network::Bitcoin);
+        assert_eq!(wallet.accounts().len(), 1);
+        assert_eq!(wallet.next_account_index(), 1);
+
+        // Unknown accounts must be created first
+        assert!(wallet
+            .get_new_account_address(1, AddressType::NativeSegwit)
+            .is_err());
+
+        // Account 1 waits until account 0 has history
+        assert!(matches!(
+            wallet.create_account(1, None),
+            Err(WalletError::UnusedAccount(0))
+        ));
+        assert!(!wallet.account_has_history(0));
+        wallet.mark_index_used((0, AddressType::NativeSegwit, Chain::External), 0);
+        assert!(wallet.account_has_history(0));
+        wallet
+            .create_account(1, Some("Payroll".to_string()))
+            .unwrap();
+        assert!(wallet.create_account(1, None).is_err());
+
+        // First address of account 1 follows m/84'/0'/1'/0/0 and has its own index
+        let first = wallet.get_account_addresses(1, AddressType::NativeSegwit, false);
+        assert_eq!(
+            wallet.owns_address(&first[0]),
+            Some(AddressType::NativeSegwit.derivation_path(1, 0, 0))
+        );
+        assert_ne!(
+            first[0].to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        wallet
+            .get_new_account_address(1, AddressType::NativeSegwit)
+            .unwrap();
+        assert_eq!(
+            wallet
+                .get_account_addresses(1, AddressType::NativeSegwit, false)
+                .len(),
+            2
+        );
+        assert_eq!(
+            wallet.get_addresses(AddressType::NativeSegwit, false).len(),
+            1
+        );
+
+        // Per-account export
+        let account_key = wallet.account_xpub(AddressType::NativeSegwit, 1).unwrap();
+        assert_eq!(account_key.origin.to_string(), "84'/0'/1'");
+        assert_eq!(wallet.account_descriptors(1).len(), 8);
+        assert_eq!(wallet.account_xpubs().len(), 8);
+
+        wallet
+            .set_account_label(1, "Operations".to_string())
+            .unwrap();
+        assert_eq!(wallet.account(1).unwrap().label, "Operations");
+        assert!(wallet.set_account_label(7, "Missing".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_account_balances() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = create_test_wallet_at(temp_dir.path());
+        wallet.mark_index_used((0, AddressType::NativeSegwit, Chain::External), 0);
+        wallet.create_account(1, None).unwrap();
+
+        for (account, value) in [(0, 10_000), (1, 25_000), (1, 5_000)] {
+            let address = wallet
+                .get_new_account_address(account, AddressType::NativeSegwit)
+                .unwrap();
+            let derivation_path = wallet.owns_address(&address).unwrap();
+            wallet.add_utxo(Utxo {
+                txid: Txid::from_str(&format!("{value:064x}")).unwrap(),
+                vout: 0,
+                value,
+                script_pubkey: address.script_pubkey(),
+                address,
+                derivation_path,
+                block_height: Some(100),
+            });
+        }
+
+        assert_eq!(wallet.balance(), 40_000);
+        assert_eq!(wallet.account_balance(0), 10_000);
+        assert_eq!(wallet.account_balance(1), 30_000);
+        assert_eq!(wallet.account_utxos(1).len(), 2);
+        assert_eq!(wallet.account_balance(2), 0);
+    }
+
+    #[test]
+    fn test_accounts_persist() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = create_test_wallet_at(temp_dir.path());
+        wallet.mark_index_used((0, AddressType::NativeSegwit, Chain::External), 0);
+        wallet
+            .create_account(1, Some("Savings".to_string()))
+            .unwrap();
+        let address = wallet
+            .get_new_account_address(1, AddressType::Taproot)
+            .unwrap();
+        wallet.save().unwrap();
+        drop(wallet);
+
+        let loaded =
+            Wallet::load("accounts".to_string(), "", test_storage(temp_dir.path())).unwrap();
+        assert_eq!(
+            loaded
+                .accounts()
+                .iter()
+                .map(|account| (account.index, account.label.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(0, "Default"), (1, "Savings")]
+        );
+        assert_eq!(
+            loaded.owns_address(&address),
+            Some(AddressType::Taproot.derivation_path(1, 0, 1))
+        );
+        assert!(loaded.account_has_history(0));
+        assert_eq!(loaded.next_account_index(), 2);
+    }
+
+    #[test]
+    fn test_change_addresses_are_never_reused() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = create_test_wallet_at(temp_dir.path());
+
+        let first = wallet
+            .get_change_address(AddressType::NativeSegwit)
+            .unwrap();
+        let second = wallet
+            .get_change_address(AddressType::NativeSegwit)
+            .unwrap();
+        assert_ne!(first, second);
+        assert_eq!(
+            wallet.owns_address(&second),
+            Some(AddressType::NativeSegwit.derivation_path(0, 1, 1))
+        );
+        assert_eq!(
+            wallet.get_addresses(AddressType::NativeSegwit, true).len(),
+            2
+        );
+
+        // Change never advances the receive chain
+        assert_eq!(
+            wallet.get_addresses(AddressType::NativeSegwit, false).len(),
+            1
+        );
+        assert_eq!(wallet.unused_gap(0, AddressType::NativeSegwit, true), 2);
+    }
+
+    #[test]
+    fn test_change_chain_respects_gap_limit() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = create_test_wallet_at(temp_dir.path());
+
+        let change: Vec<_> = (0..DEFAULT_GAP_LIMIT)
+            .map(|_| wallet.get_change_address(AddressType::Taproot).unwrap())
+            .collect();
+
+        // A full gap of unused change is refused rather than reused
+        assert!(matches!(
+            wallet.get_change_address(AddressType::Taproot),
+            Err(WalletError::ChangeGapExhausted(DEFAULT_GAP_LIMIT))
+        ));
+        assert_eq!(
+            wallet.get_addresses(AddressType::Taproot, true).len(),
+            DEFAULT_GAP_LIMIT as usize
+        );
+
+        // Funds arriving on the chain make room for a fresh address again
+        fund(&mut wallet, &change[0], 1_000);
+        let fresh = wallet.get_change_address(AddressType::Taproot).unwrap();
+        assert_eq!(
+            wallet.owns_address(&fresh),
+            Some(AddressType::Taproot.derivation_path(0, 1, DEFAULT_GAP_LIMIT))
+        );
+    }
+
+    #[test]
+    fn test_next_unused_address_skips_funded_addresses() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = create_test_wallet_at(temp_dir.path());
+
+        let first = wallet
+            .next_unused_address(0, AddressType::NativeSegwit, false)
+            .unwrap();
+        assert_eq!(
+            wallet.get_addresses(AddressType::NativeSegwit, false),
+            std::slice::from_ref(&first)
+        );
+        assert_eq!(
+            wallet
+                .next_unused_address(0, AddressType::NativeSegwit, false)
+                .unwrap(),
+            first
+        );
+
+        fund(&mut wallet, &first, 5_000);
+        assert!(wallet.is_index_used((0, AddressType::NativeSegwit, Chain::External), 0));
+        let second = wallet
+            .next_unused_address(0, AddressType::NativeSegwit, false)
+            .unwrap();
+        assert_ne!(second, first);
+        assert_eq!(wallet.unused_gap(0, AddressType::NativeSegwit, false), 1);
+    }
+
+    #[test]
+    fn test_used_indices_persist() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = create_test_wallet_at(temp_dir.path());
+        let change = wallet.get_change_address(AddressType::Legacy).unwrap();
+        fund(&mut wallet, &change, 2_000);
+        wallet.get_change_address(AddressType::Legacy).unwrap();
+        wallet.save().unwrap();
+        drop(wallet);
+
+        let mut loaded =
+            Wallet::load("accounts".to_string(), "", test_storage(temp_dir.path())).unwrap();
+        assert!(loaded.is_index_used((0, AddressType::Legacy, Chain::Internal), 0));
+        assert_eq!(loaded.unused_gap(0, AddressType::Legacy, true), 1);
+        assert_eq!(
+            loaded
+                .next_unused_address(0, AddressType::Legacy, true)
+                .unwrap(),
+            loaded.get_addresses(AddressType::Legacy, true)[1]
+        );
+    }
+
+    #[test]
+    fn test_utxo_metadata_and_freezing() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = create_test_wallet_at(temp_dir.path());
+        let address = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        fund(&mut wallet, &address, 7_000);
+        let outpoint = wallet.utxos()[0].outpoint();
+
+        wallet.freeze_utxo(&outpoint).unwrap();
+        wallet
+            .set_utxo_label(&outpoint, Some("Cold storage".to_string()))
+            .unwrap();
+        wallet
+            .set_utxo_source(&outpoint, Some("exchange".to_string()))
+            .unwrap();
+        assert!(wallet.is_frozen(&outpoint));
+        assert!(wallet.spendable_utxos(0, 200).is_empty());
+        assert_eq!(wallet.balance(), 7_000);
+
+        // Metadata can only be attached to known outputs
+        let unknown = OutPoint::new(outpoint.txid, outpoint.vout + 1);
+        assert!(wallet.freeze_utxo(&unknown).is_err());
+
+        wallet.save().unwrap();
+        drop(wallet);
+
+        let mut loaded =
+            Wallet::load("accounts".to_string(), "", test_storage(temp_dir.path())).unwrap();
+        assert_eq!(loaded.utxo(&outpoint).unwrap().address, address);
+        assert_eq!(
+            loaded.utxo_metadata(&outpoint),
+            Some(&UtxoMetadata {
+                frozen: true,
+                label: Some("Cold storage".to_string()),
+                source: Some("exchange".to_string()),
+            })
+        );
+
+        loaded.unfreeze_utxo(&outpoint).unwrap();
+        assert_eq!(loaded.spendable_utxos(0, 200).len(), 1);
+    }
+
+    #[test]
+    fn test_bip329_labels_roundtrip() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = abandon_wallet(temp_dir.path(), Network::Bitcoin);
+        let address = wallet.get_addresses(AddressType::NativeSegwit, false)[0].clone();
+        fund(&mut wallet, &address, 50_000);
+        let utxo = wallet.utxos()[0].clone();
+
+        wallet.set_address_label(&address.to_string(), Some("Donations".to_string()));
+        wallet.set_transaction_label(&utxo.txid, Some("First deposit".to_string()));
+        wallet
+            .set_utxo_label(&utxo.outpoint(), Some("Do not spend".to_string()))
+            .unwrap();
+        wallet.freeze_utxo(&utxo.outpoint()).unwrap();
+        wallet
+            .import_labels(vec![Label::new(
+                LabelType::Pubkey,
+                "0283409659355b6d1cc3c32decd5d561abaac86c37a353b52895a5e6c196d6f448",
+                "Cosigner",
+            )])
+            .unwrap();
+
+        let exported = wallet.export_labels();
+        assert_eq!(exported.len(), 4);
+        let addr = exported
+            .iter()
+            .find(|label| label.label_type == LabelType::Addr)
+            .unwrap();
+        assert_eq!(addr.origin.as_deref(), Some("wpkh([73c5da0a/84'/0'/0'])"));
+        let output = exported
+            .iter()
+            .find(|label| label.label_type == LabelType::Output)
+            .unwrap();
+        assert_eq!(output.reference, utxo.outpoint().to_string());
+        assert_eq!(output.spendable, Some(false));
+
+        // A restored copy of the wallet ends up with identical labels
+        let other_dir = tempdir().unwrap();
+        let mut restored = abandon_wallet(other_dir.path(), Network::Bitcoin);
+        restored.add_utxo(utxo.clone());
+        assert_eq!(restored.import_labels(exported.clone()).unwrap(), 4);
+        assert!(restored.is_frozen(&utxo.outpoint()));
+        assert_eq!(
+            restored.transaction_label(&utxo.txid),
+            Some("First deposit")
+        );
+        restored.save().unwrap();
+        drop(restored);
+
+        let loaded =
+            Wallet::load("signer".to_string(), "", test_storage(other_dir.path())).unwrap();
+        assert_eq!(loaded.export_labels(), exported);
+    }
+
+    #[test]
+    fn test_bip329_import_is_atomic() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = create_test_wallet_at(temp_dir.path());
+        let txid = "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd";
+
+        let result = wallet.import_labels(vec![
+            Label::new(LabelType::Tx, txid, "Transaction"),
+            Label::new(LabelType::Output, "not-an-outpoint", "Output"),
+        ]);
+        assert!(result.is_err());
+        assert!(wallet.export_labels().is_empty());
+
+        // Labels of spent outputs are kept even though the UTXO is unknown
+        let outpoint = format!("{txid}:1");
+        wallet
+            .import_labels(vec![Label {
+                spendable: Some(false),
+                ..Label::new(LabelType::Output, outpoint.clone(), "Spent")
+            }])
+            .unwrap();
+        assert_eq!(
+            wallet
+                .utxo_metadata(&OutPoint::from_str(&outpoint).unwrap())
+                .unwrap()
+                .label
+                .as_deref(),
+            Some("Spent")
+        );
+    }
+
+    #[test]
+    fn test_transaction_history() {
+        use crate::wallet::history::TransactionKind;
+        use bitcoin::hashes::Hash;
+
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = create_test_wallet_at(temp_dir.path());
+        let first = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        let second = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        let change = wallet
+            .get_change_address(AddressType::NativeSegwit)
+            .unwrap();
+        let foreign = Address::p2wsh(&ScriptBuf::new(), bitcoin::Network::Regtest);
+
+        // Receive two payments from outside
+        let deposit = spend(
+            vec![OutPoint::new(Txid::all_zeros(), 7)],
+            vec![(&first, 60_000), (&second, 40_000), (&foreign, 5_000)],
+        );
+        let deposit_id = deposit.compute_txid();
+        let recorded = wallet
+            .record_transaction(&deposit, Some(100), 1_700_000_000)
+            .unwrap();
+        assert_eq!(recorded.kind(), TransactionKind::Receive);
+        assert_eq!(recorded.value_delta, 100_000);
+        assert_eq!(recorded.fee(), None);
+        assert_eq!(wallet.balance(), 100_000);
+        wallet.set_transaction_label(&deposit_id, Some("Salary".to_string()));
+
+        // Pay someone, with change back to the wallet
+        let payment = spend(
+            vec![OutPoint::new(deposit_id, 0)],
+            vec![(&foreign, 25_000), (&change, 34_000)],
+        );
+        let recorded = wallet
+            .record_transaction(&payment, Some(110), 1_700_100_000)
+            .unwrap();
+        assert_eq!(recorded.kind(), TransactionKind::Send);
+        assert_eq!(recorded.value_delta, -26_000);
+        assert_eq!(recorded.fee(), Some(1_000));
+        assert!(wallet.utxo(&OutPoint::new(deposit_id, 0)).is_none());
+
+        // Merge the remaining coins into one
+        let merged = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        let consolidation = spend(
+            vec![
+                OutPoint::new(deposit_id, 1),
+                OutPoint::new(payment.compute_txid(), 1),
+            ],
+            vec![(&merged, 73_500)],
+        );
+        let recorded = wallet
+            .record_transaction(&consolidation, None, 1_700_200_000)
+            .unwrap();
+        assert_eq!(recorded.kind(), TransactionKind::Consolidation);
+        assert_eq!(recorded.fee(), Some(500));
+        assert_eq!(wallet.balance(), 73_500);
+        assert_eq!(wallet.utxos().len(), 1);
+
+        let unrelated = spend(vec![OutPoint::null()], vec![(&foreign, 1_000)]);
+        assert!(wallet.record_transaction(&unrelated, None, 0).is_err());
+
+        wallet.save().unwrap();
+        drop(wallet);
+        let loaded =
+            Wallet::load("accounts".to_string(), "", test_storage(temp_dir.path())).unwrap();
+
+        let all = loaded.history(&HistoryFilter::default());
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].txid, deposit_id);
+        assert_eq!(all[0].label.as_deref(), Some("Salary"));
+        assert_eq!(all[2].fee(), Some(500));
+
+        let sends = loaded.history(&HistoryFilter {
+            min_amount: Some(10_000),
+            to: Some(1_700_150_000),
+            ..HistoryFilter::default()
+        });
+        assert_eq!(sends.len(), 2);
+        let labelled = loaded.history(&HistoryFilter {
+            label: Some("salary".to_string()),
+            ..HistoryFilter::default()
+        });
+        assert_eq!(labelled.len(), 1);
+        assert_eq!(labelled[0].txid, deposit_id);
+    }
+
+    #[test]
+    fn test_rerecording_keeps_spent_outputs_spent() {
+        use bitcoin::hashes::Hash;
+
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = create_test_wallet_at(temp_dir.path());
+        let address = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        let foreign = Address::p2wsh(&ScriptBuf::new(), bitcoin::Network::Regtest);
+
+        let deposit = spend(
+            vec![OutPoint::new(Txid::all_zeros(), 3)],
+            vec![(&address, 50_000)],
+        );
+        let deposit_id = deposit.compute_txid();
+        wallet.record_transaction(&deposit, None, 1_000).unwrap();
+        let payment = spend(vec![OutPoint::new(deposit_id, 0)], vec![(&foreign, 49_000)]);
+        wallet.record_transaction(&payment, None, 2_000).unwrap();
+        assert_eq!(wallet.balance(), 0);
+
+        // The deposit confirms after it was spent
+        let recorded = wallet
+            .record_transaction(&deposit, Some(120), 3_000)
+            .unwrap();
+        assert_eq!(recorded.block_height, Some(120));
+        assert_eq!(recorded.timestamp, 1_000);
+        assert_eq!(recorded.value_delta, 50_000);
+        assert_eq!(wallet.balance(), 0);
+        assert!(wallet.utxo(&OutPoint::new(deposit_id, 0)).is_none());
+
+        // So does the payment, which keeps its owned input
+        let recorded = wallet
+            .record_transaction(&payment, Some(121), 3_000)
+            .unwrap();
+        assert!(recorded.inputs[0].owned);
+        assert_eq!(recorded.value_delta, -50_000);
+        assert_eq!(recorded.fee(), Some(1_000));
+        assert_eq!(wallet.balance(), 0);
+
+        // A parent recorded after its spender does not become spendable
+        let address_two = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        let third = spend(
+            vec![OutPoint::new(Txid::all_zeros(), 5)],
+            vec![(&address_two, 10_000), (&foreign, 1_000)],
+        );
+        let third_id = third.compute_txid();
+        let spender = spend(
+            vec![OutPoint::new(third_id, 0), OutPoint::new(deposit_id, 0)],
+            vec![(&foreign, 58_000)],
+        );
+        wallet.record_transaction(&spender, None, 5_000).unwrap();
+        let recorded = wallet.record_transaction(&third, None, 4_500).unwrap();
+        assert_eq!(recorded.value_delta, 10_000);
+        assert!(wallet.utxo(&OutPoint::new(third_id, 0)).is_none());
+        assert_eq!(wallet.balance(), 0);
+    }
+
+    #[test]
+    fn test_received_is_emitted_once() {
+        use crate::wallet::events::WalletEvent;
+
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = create_test_wallet_at(temp_dir.path());
+        let address = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        let foreign = Address::p2wsh(&ScriptBuf::new(), bitcoin::Network::Regtest);
+        let mut events = wallet.subscribe();
+
+        let deposit = spend(vec![OutPoint::null()], vec![(&address, 50_000)]);
+        let deposit_id = deposit.compute_txid();
+        wallet.record_transaction(&deposit, None, 0).unwrap();
+        wallet.record_transaction(&deposit, None, 0).unwrap();
+        let payment = spend(vec![OutPoint::new(deposit_id, 0)], vec![(&foreign, 49_000)]);
+        wallet.record_transaction(&payment, None, 0).unwrap();
+        wallet.record_transaction(&deposit, Some(101), 0).unwrap();
+
+        // A sync adding the spent output back does not announce it again
+        let utxo = Utxo {
+            txid: deposit_id,
+            vout: 0,
+            value: 50_000,
+            script_pubkey: address.script_pubkey(),
+            address: address.clone(),
+            derivation_path: wallet.owns_address(&address).unwrap(),
+            block_height: Some(101),
+        };
+        wallet.add_utxo(utxo);
+
+        let received = std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|event| matches!(event, WalletEvent::Received { .. }))
+            .count();
+        assert_eq!(received, 1);
+    }
+
+    #[test]
+    fn test_wallet_events() {
+        use crate::wallet::events::WalletEvent;
+
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = create_test_wallet_at(temp_dir.path());
+        let address = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        let change = wallet
+            .get_change_address(AddressType::NativeSegwit)
+            .unwrap();
+        let foreign = Address::p2wsh(&ScriptBuf::new(), bitcoin::Network::Regtest);
+        wallet.set_confirmation_target(3);
+        wallet.set_tip_height(100);
+        let mut events = wallet.subscribe();
+
+        // Incoming payment, still in the mempool
+        let deposit = spend(vec![OutPoint::null()], vec![(&address, 50_000)]);
+        let deposit_id = deposit.compute_txid();
+        wallet.record_transaction(&deposit, None, 0).unwrap();
+        assert_eq!(
+            events.try_recv().unwrap(),
+            WalletEvent::Received {
+                outpoint: OutPoint::new(deposit_id, 0),
+                value: 50_000,
+                address: address.clone(),
+                block_height: None,
+            }
+        );
+        assert_eq!(
+            events.try_recv().unwrap(),
+            WalletEvent::BalanceChanged {
+                previous: 0,
+                total: 50_000,
+                confirmed: 0,
+            }
+        );
+
+        // Mined at 101, target depth reached at 103
+        wallet.record_transaction(&deposit, Some(101), 0).unwrap();
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            WalletEvent::BalanceChanged {
+                confirmed: 50_000,
+                ..
+            }
+        ));
+        wallet.set_tip_height(102);
+        assert!(events.try_recv().is_err());
+        wallet.set_tip_height(103);
+        assert_eq!(
+            events.try_recv().unwrap(),
+            WalletEvent::Confirmed {
+                txid: deposit_id,
+                block_height: 101,
+                confirmations: 3,
+            }
+        );
+        assert_eq!(wallet.confirmations(&deposit_id), 3);
+        wallet.set_tip_height(104);
+        assert!(events.try_recv().is_err());
+
+        // Reorg back to 100
+        wallet.rollback_to(100);
+        assert_eq!(
+            events.try_recv().unwrap(),
+            WalletEvent::Reorged {
+                txid: deposit_id,
+                previous_height: 101,
+            }
+        );
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            WalletEvent::BalanceChanged {
+                previous: 50_000,
+                total: 50_000,
+                confirmed: 0,
+            }
+        ));
+        assert_eq!(wallet.confirmations(&deposit_id), 0);
+
+        // Spend it
+        let payment = spend(
+            vec![OutPoint::new(deposit_id, 0)],
+            vec![(&foreign, 30_000), (&change, 19_000)],
+        );
+        wallet.record_transaction(&payment, None, 0).unwrap();
+        assert_eq!(
+            events.try_recv().unwrap(),
+            WalletEvent::Spent {
+                outpoint: OutPoint::new(deposit_id, 0),
+                value: 50_000,
+                spending_txid: Some(payment.compute_txid()),
+            }
+        );
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            WalletEvent::Received { value: 19_000, .. }
+        ));
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            WalletEvent::BalanceChanged {
+                previous: 50_000,
+                total: 19_000,
+                ..
+            }
+        ));
+        assert!(events.try_recv().is_err());
+
+        // The tip survives a reload
+        wallet.save().unwrap();
+        drop(wallet);
+        let loaded =
+            Wallet::load("accounts".to_string(), "", test_storage(temp_dir.path())).unwrap();
+        assert_eq!(loaded.tip_height(), Some(100));
+    }
+
+    #[test]
+    fn test_sign_message() {
+        let mut wallet = create_test_wallet();
+        let legacy = wallet.get_new_address(AddressType::Legacy).unwrap();
+        let nested = wallet.get_new_address(AddressType::NestedSegwit).unwrap();
+        let native = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        let taproot = wallet.get_new_address(AddressType::Taproot).unwrap();
+        let message = "I control this address";
+
+        let cases = [
+            (&legacy, MessageFormat::Legacy),
+            (&legacy, MessageFormat::Full),
+            (&nested, MessageFormat::Legacy),
+            (&nested, MessageFormat::Full),
+            (&native, MessageFormat::Legacy),
+            (&native, MessageFormat::Simple),
+            (&native, MessageFormat::Full),
+            (&taproot, MessageFormat::Simple),
+            (&taproot, MessageFormat::Full),
+        ];
+        for (address, format) in cases {
+            let signature = wallet.sign_message(address, message, format).unwrap();
+            assert!(
+                message::verify_message(address, message, &signature).unwrap(),
+                "{format:?} signature for {address}"
+            );
+            assert!(!message::verify_message(address, "Something else", &signature).unwrap());
+            if address != &native {
+                assert!(!message::verify_message(&native, message, &signature).unwrap());
+            }
+        }
+
+        assert!(wallet
+            .sign_message(&taproot, message, MessageFormat::Legacy)
+            .is_err());
+        assert!(wallet
+            .sign_message(&legacy, message, MessageFormat::Simple)
+            .is_err());
+        let foreign = Address::p2wsh(&ScriptBuf::new(), bitcoin::Network::Regtest);
+        assert!(wallet
+            .sign_message(&foreign, message, MessageFormat::Simple)
+            .is_err());
+    }
+
+    #[test]
+    fn test_spending_policy() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = create_test_wallet_at(temp_dir.path());
+        let receive = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        let change = wallet
+            .get_change_address(AddressType::NativeSegwit)
+            .unwrap();
+        let foreign = Address::p2wsh(&ScriptBuf::new(), bitcoin::Network::Regtest);
+
+        let deposit = spend(vec![OutPoint::null()], vec![(&receive, 100_000)]);
+        wallet.record_transaction(&deposit, Some(1), 0).unwrap();
+        let payment = spend(
+            vec![OutPoint::new(deposit.compute_txid(), 0)],
+            vec![(&foreign, 30_000), (&change, 69_000)],
+        );
+        let unsigned = {
+            let mut psbt = Psbt::from_unsigned_tx(payment.clone()).unwrap();
+            psbt.inputs[0].witness_utxo = Some(deposit.output[0].clone());
+            psbt
+        };
+
+        let summary = wallet.spend_summary(&unsigned).unwrap();
+        assert_eq!(summary.spent, 31_000);
+        assert_eq!(summary.paid(), 30_000);
+        assert_eq!(summary.fee, 1_000);
+        assert!(summary.fee_rate() > 1.0 && summary.fee_rate() < 10.0);
+
+        wallet
+            .set_policy(Some(SpendingPolicy {
+                max_per_transaction: Some(40_000),
+                daily_limit: Some(50_000),
+                approval_threshold: Some(25_000),
+                forbid_unknown_change: true,
+                ..Default::default()
+            }))
+            .unwrap();
+        let mut psbt = unsigned.clone();
+        assert!(matches!(
+            wallet.sign_psbt(&mut psbt),
+            Err(WalletError::Policy(PolicyError::ApprovalRequired {
+                amount: 31_000,
+                threshold: 25_000
+            }))
+        ));
+        assert_eq!(wallet.sign_approved_psbt(&mut psbt).unwrap(), 1);
+        // Signing the same transaction again does not count twice
+        assert_eq!(wallet.sign_approved_psbt(&mut unsigned.clone()).unwrap(), 1);
+
+        // An output claiming a wallet key that does not pay the wallet
+        let path = wallet.owns_address(&change).unwrap();
+        let (fingerprint, key) = wallet.key_origin(&path).unwrap();
+        let mut tampered = unsigned.clone();
+        tampered.outputs[0]
+            .bip32_derivation
+            .insert(key.inner, (fingerprint, path));
+        assert!(matches!(
+            wallet.check_policy(&tampered, true),
+            Err(WalletError::Policy(PolicyError::UnknownChange { index: 0 }))
+        ));
+
+        // 31k already spent today; another 21k breaks the 50k daily limit
+        wallet.record_transaction(&payment, None, 0).unwrap();
+        let second = spend(
+            vec![OutPoint::new(payment.compute_txid(), 1)],
+            vec![(&foreign, 20_000), (&change, 48_000)],
+        );
+        let mut second = Psbt::from_unsigned_tx(second).unwrap();
+        second.inputs[0].witness_utxo = Some(payment.output[1].clone());
+        assert!(matches!(
+            wallet.check_policy(&second, true),
+            Err(WalletError::Policy(PolicyError::DailyLimit {
+                amount: 21_000,
+                spent: 31_000,
+                limit: 50_000
+            }))
+        ));
+
+        // Input values the wallet can check are never taken on trust
+        let mut understated = second.clone();
+        understated.inputs[0].witness_utxo.as_mut().unwrap().value =
+            bitcoin::Amount::from_sat(1_000);
+        assert!(matches!(
+            wallet.check_policy(&understated, true),
+            Err(WalletError::Policy(PolicyError::InputMismatch {
+                index: 0,
+                ..
+            }))
+        ));
+        let mut wrong_parent = second.clone();
+        wrong_parent.inputs[0].non_witness_utxo = Some(deposit.clone());
+        assert!(matches!(
+            wallet.check_policy(&wrong_parent, true),
+            Err(WalletError::Policy(PolicyError::InputMismatch {
+                index: 0,
+                ..
+            }))
+        ));
+        let mut unrecorded = unsigned.clone();
+        unrecorded.unsigned_tx.input[0].previous_output.vout = 1;
+        assert!(matches!(
+            wallet.spend_summary(&unrecorded),
+            Err(WalletError::Policy(PolicyError::InputMismatch {
+                index: 0,
+                ..
+            }))
+        ));
+
+        // A foreign legacy input needs its previous transaction
+        let legacy = Address::p2pkh(
+            bitcoin::PublicKey::new(key.inner),
+            bitcoin::Network::Regtest,
+        );
+        let parent = spend(vec![OutPoint::null()], vec![(&legacy, 10_000)]);
+        let mut joint = second.clone();
+        joint.unsigned_tx.input.push(bitcoin::TxIn {
+            previous_output: OutPoint::new(parent.compute_txid(), 0),
+            ..Default::default()
+        });
+        joint.inputs.push(bitcoin::psbt::Input {
+            witness_utxo: Some(parent.output[0].clone()),
+            ..Default::default()
+        });
+        assert!(matches!(
+            wallet.spend_summary(&joint),
+            Err(WalletError::Policy(PolicyError::InputMismatch {
+                index: 1,
+                ..
+            }))
+        ));
+        joint.inputs[1].non_witness_utxo = Some(parent);
+        assert_eq!(wallet.spend_summary(&joint).unwrap().fee, 11_000);
+
+        let mut inputless = unsigned.clone();
+        inputless.inputs[0].witness_utxo = None;
+        assert!(matches!(
+            wallet.spend_summary(&inputless),
+            Err(WalletError::Policy(PolicyError::UnknownInputValue {
+                index: 0
+            }))
+        ));
+        assert!(wallet
+            .set_policy(Some(SpendingPolicy {
+                allowed_destinations: vec!["bogus".to_string()],
+                ..Default::default()
+            }))
+            .is_err());
+
+        // Policy and recent spends survive a reload
+        wallet.save().unwrap();
+        drop(wallet);
+        let mut loaded =
+            Wallet::load("accounts".to_string(), "", test_storage(temp_dir.path())).unwrap();
+        assert_eq!(loaded.policy().unwrap().daily_limit, Some(50_000));
+        assert!(loaded.sign_approved_psbt(&mut second).is_err());
+        loaded.set_policy(None).unwrap();
+        assert_eq!(loaded.sign_psbt(&mut second).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_descriptor_keychains_sign_and_persist() {
+        use crate::transaction::builder::{FeeStrategy, TransactionBuilder};
+        use bitcoin::{Amount, FeeRate};
+        use std::sync::{Arc, RwLock};
+
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = abandon_wallet(temp_dir.path(), Network::Regtest);
+        let key = |path: &str| {
+            let WalletKeys::Private { master_key, .. } = &wallet.keys else {
+                unreachable!()
+            };
+            let path = DerivationPath::from_str(path).unwrap();
+            format!(
+                "[{}/{}]{}",
+                master_key.xpriv.fingerprint(&wallet.secp),
+                path.to_string().trim_start_matches("m/"),
+                master_key.derive_path(&path).unwrap().extended_public_key()
+            )
+        };
+
+        // 2-of-2 where the wallet holds both keys, and a Taproot tree
+        let multisig = format!(
+            "wsh(sortedmulti(2,{}/<0;1>/*,{}/<0;1>/*))",
+            key("m/48'/1'/0'/2'"),
+            key("m/48'/1'/1'/2'")
+        );
+        let taproot = format!(
+            "tr({}/0/*,pk({}/0/*))",
+            key("m/86'/1'/5'"),
+            key("m/86'/1'/6'")
+        );
+        wallet.add_keychain("vault", &multisig, None).unwrap();
+        wallet.add_keychain("tree", &taproot, None).unwrap();
+        assert!(wallet.add_keychain("vault", &multisig, None).is_err());
+        assert!(wallet.keychain_address("tree", true).is_err());
+
+        let vault = wallet.keychain_address("vault", false).unwrap();
+        let tree = wallet.keychain_address("tree", false).unwrap();
+        assert!(vault.script_pubkey().is_p2wsh());
+        assert!(tree.script_pubkey().is_p2tr());
+        assert!(wallet.is_mine(&vault.script_pubkey()));
+        assert_eq!(
+            wallet.keychain_position(&tree.script_pubkey()),
+            Some(("tree", Chain::External, 0))
+        );
+
+        let deposit = spend(
+            vec![OutPoint::null()],
+            vec![(&vault, 80_000), (&tree, 20_000)],
+        );
+        wallet.record_transaction(&deposit, Some(1), 0).unwrap();
+        assert_eq!(wallet.balance(), 100_000);
+
+        // Sign both descriptor inputs of a hand-built PSBT and finalize it
+        let foreign = Address::p2wsh(&ScriptBuf::new(), bitcoin::Network::Regtest);
+        let payment = spend(
+            vec![
+                OutPoint::new(deposit.compute_txid(), 0),
+                OutPoint::new(deposit.compute_txid(), 1),
+            ],
+            vec![(&foreign, 99_000)],
+        );
+        let mut psbt = Psbt::from_unsigned_tx(payment).unwrap();
+        psbt.inputs[0].witness_utxo = Some(deposit.output[0].clone());
+        psbt.inputs[1].witness_utxo = Some(deposit.output[1].clone());
+        assert_eq!(wallet.sign_psbt(&mut psbt).unwrap(), 2);
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 2);
+        assert!(psbt.inputs[1].tap_key_sig.is_some());
+        psbt.finalize_mut(&wallet.secp).unwrap();
+        assert_eq!(
+            psbt.inputs[0].final_script_witness.as_ref().unwrap().len(),
+            4
+        );
+
+        // The builder fills witness scripts from the keychain descriptor
+        let change = wallet.keychain_address("vault", true).unwrap();
+        wallet.save().unwrap();
+        let wallet = Arc::new(RwLock::new(wallet));
+        let mut builder = TransactionBuilder::new(wallet.clone()).unwrap();
+        builder
+            .add_recipient(foreign.clone(), Amount::from_sat(30_000))
+            .unwrap();
+        builder.fee_strategy(FeeStrategy::FeeRate(FeeRate::from_sat_per_vb(2).unwrap()));
+        builder.pin_inputs(vec![OutPoint::new(deposit.compute_txid(), 0)]);
+        let built = builder.build_psbt().unwrap();
+        assert!(built.inputs[0].witness_script.is_some());
+        assert_eq!(built.inputs[0].bip32_derivation.len(), 2);
+        let mut psbt = built.to_psbt().unwrap();
+        assert_eq!(wallet.write().unwrap().sign_psbt(&mut psbt).unwrap(), 1);
+        drop(builder);
+        drop(wallet);
+
+        let loaded = Wallet::load("signer".to_string(), "", test_storage(temp_dir.path())).unwrap();
+        assert_eq!(loaded.keychains().len(), 2);
+        assert_eq!(
+            loaded.keychain_addresses("vault", false).unwrap(),
+            vec![vault]
+        );
+        assert_eq!(
+            loaded.keychain_addresses("vault", true).unwrap(),
+            vec![change]
+        );
+        assert!(loaded.is_mine(&tree.script_pubkey()));
+    }
+
+    #[test]
+    fn test_keychain_lookahead_finds_cosigner_addresses() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = abandon_wallet(temp_dir.path(), Network::Regtest);
+        let WalletKeys::Private { master_key, .. } = &wallet.keys else {
+            unreachable!()
+        };
+        let path = DerivationPath::from_str("m/84'/1'/7'").unwrap();
+        let descriptor = format!(
+            "wpkh([{}/84'/1'/7']{}/<0;1>/*)",
+            master_key.xpriv.fingerprint(&wallet.secp),
+            master_key.derive_path(&path).unwrap().extended_public_key()
+        );
+        wallet.add_keychain("shared", &descriptor, None).unwrap();
+
+        // A cosigner handed out receive index 15; nothing was handed out here
+        let keychain = wallet.keychain("shared").unwrap().clone();
+        let address = |index| {
+            keychain
+                .address(Chain::External, index, bitcoin::Network::Regtest)
+                .unwrap()
+        };
+        let cosigner = address(15);
+        assert_eq!(
+            wallet.keychain_position(&cosigner.script_pubkey()),
+            Some(("shared", Chain::External, 15))
+        );
+        assert!(!wallet.is_mine(&address(DEFAULT_GAP_LIMIT).script_pubkey()));
+
+        let deposit = spend(vec![OutPoint::null()], vec![(&cosigner, 30_000)]);
+        wallet.record_transaction(&deposit, Some(1), 0).unwrap();
+        assert_eq!(wallet.balance(), 30_000);
+
+        // The lookahead moves past the used index and survives a reload
+        let beyond = address(15 + DEFAULT_GAP_LIMIT);
+        assert!(wallet.is_mine(&beyond.script_pubkey()));
+        assert!(!wallet.is_mine(&address(16 + DEFAULT_GAP_LIMIT).script_pubkey()));
+        wallet.save().unwrap();
+        drop(wallet);
+        let loaded = Wallet::load("signer".to_string(), "", test_storage(temp_dir.path())).unwrap();
+        assert!(loaded.is_mine(&beyond.script_pubkey()));
+        assert_eq!(
+            loaded.keychain_addresses("shared", false).unwrap().len(),
+            16
+        );
+    }
+
+    fn spend(inputs: Vec<OutPoint>, outputs: Vec<(&Address, u64)>) -> Transaction {
+        use bitcoin::{absolute::LockTime, transaction::Version, Amount, TxIn, TxOut};
+
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: inputs
+                .into_iter()
+                .map(|previous_output| TxIn {
+                    previous_output,
+                    ..TxIn::default()
+                })
+                .collect(),
+            output: outputs
+                .into_iter()
+                .map(|(address, value)| TxOut {
+                    value: Amount::from_sat(value),
+                    script_pubkey: address.script_pubkey(),
+                })
+                .collect(),
+        }
+    }
+
+    fn fund(wallet: &mut Wallet, address: &Address, value: u64) {
+        let derivation_path = wallet.owns_address(address).unwrap();
+        let txid =
+            Txid::from_str(&format!("{:064x}", derivation_path.len() as u64 * value)).unwrap();
+        wallet.add_utxo(Utxo {
+            txid,
+            vout: derivation_path[4].into(),
+            value,
+            script_pubkey: address.script_pubkey(),
+            address: address.clone(),
+            derivation_path,
+            block_height: Some(100),
+        });
+    }
+
+    fn create_test_wallet_at(path: &std::path::Path) -> Wallet {
+        Wallet::create_new("accounts".to_string(), Network::Regtest, test_storage(path)).unwrap()
+    }
 }
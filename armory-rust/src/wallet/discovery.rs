@@ -0,0 +1,596 @@
+use crate::error::{NetworkError, NetworkResult, WalletResult};
+/// Gap-limit address discovery for restored wallets
+///
+/// A wallet restored from seed only knows about its first address of each
+/// type. This module walks the BIP-44 style receive and change chains of
+/// every purpose (44/49/84/86) and account against a chain backend, stopping
+/// a chain after `gap_limit` consecutive unused addresses and stopping the
+/// account scan at the first account without any history.
+use crate::network::rpc::{AddressDelta, RpcClient, UtxoInfo};
+use crate::wallet::descriptor_wallet::{AddressType, Chain, Utxo, Wallet, WalletTransaction};
+
+use bitcoin::{Address, Txid};
+use std::collections::BTreeMap;
+use std::future::Future;
+
+/// Default number of consecutive unused addresses before a chain is abandoned (BIP-44)
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+/// Default upper bound on the number of accounts scanned per address type
+pub const DEFAULT_ACCOUNT_LIMIT: u32 = 100;
+
+/// Source of per-address chain data used by discovery
+///
+/// An address with no balance changes is treated as unused.
+pub trait ChainBackend {
+    /// Unspent outputs currently paying to the address
+    fn address_utxos(
+        &mut self,
+        address: &Address,
+    ) -> impl Future<Output = NetworkResult<Vec<UtxoInfo>>> + Send;
+
+    /// Every balance change (received or spent output) involving the address
+    fn address_deltas(
+        &mut self,
+        address: &Address,
+    ) -> impl Future<Output = NetworkResult<Vec<AddressDelta>>> + Send;
+}
+
+impl ChainBackend for RpcClient {
+    fn address_utxos(
+        &mut self,
+        address: &Address,
+    ) -> impl Future<Output = NetworkResult<Vec<UtxoInfo>>> + Send {
+        self.get_address_utxos(address)
+    }
+
+    fn address_deltas(
+        &mut self,
+        address: &Address,
+    ) -> impl Future<Output = NetworkResult<Vec<AddressDelta>>> + Send {
+        self.get_address_deltas(address)
+    }
+}
+
+/// Discovery configuration
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    /// Consecutive unused addresses after which a chain is considered exhausted
+    pub gap_limit: u32,
+    /// Address types (purposes) to scan
+    pub address_types: Vec<AddressType>,
+    /// Maximum number of accounts scanned per address type
+    pub account_limit: u32,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            gap_limit: DEFAULT_GAP_LIMIT,
+            address_types: vec![
+                AddressType::Legacy,
+                AddressType::NestedSegwit,
+                AddressType::NativeSegwit,
+                AddressType::Taproot,
+            ],
+            account_limit: DEFAULT_ACCOUNT_LIMIT,
+        }
+    }
+}
+
+impl DiscoveryConfig {
+    /// Set the gap limit
+    pub fn with_gap_limit(mut self, gap_limit: u32) -> Self {
+        self.gap_limit = gap_limit;
+        self
+    }
+
+    /// Restrict discovery to the given address types
+    pub fn with_address_types(mut self, address_types: Vec<AddressType>) -> Self {
+        self.address_types = address_types;
+        self
+    }
+}
+
+/// Highest used index found on one chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainUsage {
+    /// Address type (purpose) of the chain
+    pub address_type: AddressType,
+    /// BIP-44 account index
+    pub account: u32,
+    /// Whether this is the change (internal) chain
+    pub change: bool,
+    /// Highest address index with history
+    pub last_used: u32,
+}
+
+/// Summary of a discovery run
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryReport {
+    /// Chains that had at least one used address
+    pub used_chains: Vec<ChainUsage>,
+    /// Total number of addresses queried
+    pub addresses_scanned: u32,
+    /// Number of unspent outputs added to the wallet
+    pub utxos_found: usize,
+    /// Number of transactions added to the wallet history
+    pub transactions_found: usize,
+}
+
+/// Gap-limit discovery runner
+pub struct AddressDiscovery {
+    config: DiscoveryConfig,
+}
+
+impl AddressDiscovery {
+    /// Create a discovery runner with the given configuration
+    pub fn new(config: DiscoveryConfig) -> Self {
+        Self { config }
+    }
+
+    /// Scan the wallet's chains and record everything found
+    ///
+    /// Accounts with history are registered, every chain's next index is
+    /// advanced past its last used address, and unspent outputs and
+    /// transactions are added to the wallet.
+    pub async fn run<B: ChainBackend>(
+        &self,
+        wallet: &mut Wallet,
+        backend: &mut B,
+    ) -> WalletResult<DiscoveryReport> {
+        let mut report = DiscoveryReport::default();
+        let mut transactions: BTreeMap<Txid, WalletTransaction> = BTreeMap::new();
+
+        for &address_type in &self.config.address_types {
+            for account in 0..self.config.account_limit {
+                // Watch-only wallets only know the accounts they were given
+                if !wallet.has_account(address_type, account) {
+                    continue;
+                }
+                let mut account_used = false;
+
+                for change in [false, true] {
+                    let last_used = self
+                        .scan_chain(
+                            wallet,
+                            backend,
+                            address_type,
+                            account,
+                            change,
+                            &mut report,
+                            &mut transactions,
+                        )
+                        .await?;
+
+                    if let Some(last_used) = last_used {
+                        account_used = true;
+                        wallet.ensure_account(account);
+                        report.used_chains.push(ChainUsage {
+                            address_type,
+                            account,
+                            change,
+                            last_used,
+                        });
+                    }
+                }
+
+                // BIP-44: stop at the first account without any history
+                if !account_used {
+                    break;
+                }
+            }
+        }
+
+        report.transactions_found = transactions.len();
+        for transaction in transactions.into_values() {
+            // Recorded entries know their inputs, outputs and label; only the height is news
+            if wallet.transaction(&transaction.txid).is_some() {
+                wallet.set_transaction_height(&transaction.txid, transaction.block_height);
+            } else {
+                wallet.add_transaction(transaction);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Scan a single chain, returning the highest used index
+    #[allow(clippy::too_many_arguments)]
+    async fn scan_chain<B: ChainBackend>(
+        &self,
+        wallet: &mut Wallet,
+        backend: &mut B,
+        address_type: AddressType,
+        account: u32,
+        change: bool,
+        report: &mut DiscoveryReport,
+        transactions: &mut BTreeMap<Txid, WalletTransaction>,
+    ) -> WalletResult<Option<u32>> {
+        let mut last_used = None;
+        let mut unused_run = 0;
+        let mut index = 0;
+
+        while unused_run < self.config.gap_limit {
+            let path = wallet.chain_path(address_type, account, u32::from(change), index)?;
+            let address = wallet.generate_address(&path, address_type)?;
+            report.addresses_scanned += 1;
+
+            let deltas = backend.address_deltas(&address).await?;
+            if deltas.is_empty() {
+                unused_run += 1;
+            } else {
+                unused_run = 0;
+                last_used = Some(index);
+                wallet.mark_index_used((account, address_type, Chain::from_change(change)), index);
+
+                for delta in deltas {
+                    let txid = parse_txid(&delta.txid)?;
+                    let entry = transactions.entry(txid).or_insert(WalletTransaction {
+                        txid,
+                        value_delta: 0,
+                        block_height: confirmed_height(delta.height),
+                        // Not reported by the address index
+                        timestamp: 0,
+                        label: None,
+                        inputs: Vec::new(),
+                        outputs: Vec::new(),
+                    });
+                    entry.value_delta += delta.satoshis;
+                }
+
+                for info in backend.address_utxos(&address).await? {
+                    wallet.add_utxo(Utxo {
+                        txid: parse_txid(&info.txid)?,
+                        vout: info.output_index,
+                        value: info.satoshis,
+                        script_pubkey: address.script_pubkey(),
+                        address: address.clone(),
+                        derivation_path: path.clone(),
+                        block_height: confirmed_height(info.height),
+                    });
+                    report.utxos_found += 1;
+                }
+            }
+
+            index += 1;
+        }
+
+        Ok(last_used)
+    }
+}
+
+/// The address index reports mempool entries at height 0
+fn confirmed_height(height: u32) -> Option<u32> {
+    (height > 0).then_some(height)
+}
+
+fn parse_txid(txid: &str) -> WalletResult<Txid> {
+    txid.parse()
+        .map_err(|e| NetworkError::InvalidResponse(format!("Invalid transaction ID: {e}")).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{wallet_storage::StorageConfig, WalletStorage};
+    use crate::Network;
+    use bip39::Mnemonic;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+    use tempfile::tempdir;
+
+    const ABANDON_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    /// In-memory backend keyed by address string
+    #[derive(Default)]
+    struct MockBackend {
+        utxos: HashMap<String, Vec<(String, u32, u64, u32)>>,
+        deltas: HashMap<String, Vec<(String, u32, i64, u32)>>,
+        queries: u32,
+    }
+
+    impl MockBackend {
+        fn receive(&mut self, address: &Address, tx: u64, value: u64, height: u32) {
+            let txid = format!("{tx:064x}");
+            self.utxos.entry(address.to_string()).or_default().push((
+                txid.clone(),
+                0,
+                value,
+                height,
+            ));
+            self.deltas.entry(address.to_string()).or_default().push((
+                txid,
+                0,
+                value as i64,
+                height,
+            ));
+        }
+
+        fn receive_and_spend(&mut self, address: &Address, tx: u64, spend_tx: u64, value: u64) {
+            let deltas = self.deltas.entry(address.to_string()).or_default();
+            deltas.push((format!("{tx:064x}"), 0, value as i64, 100));
+            deltas.push((format!("{spend_tx:064x}"), 0, -(value as i64), 101));
+        }
+    }
+
+    impl ChainBackend for MockBackend {
+        async fn address_utxos(&mut self, address: &Address) -> NetworkResult<Vec<UtxoInfo>> {
+            Ok(self
+                .utxos
+                .get(&address.to_string())
+                .into_iter()
+                .flatten()
+                .map(|(txid, output_index, satoshis, height)| UtxoInfo {
+                    address: address.to_string(),
+                    txid: txid.clone(),
+                    output_index: *output_index,
+                    script: address.script_pubkey().to_hex_string(),
+                    satoshis: *satoshis,
+                    height: *height,
+                })
+                .collect())
+        }
+
+        async fn address_deltas(&mut self, address: &Address) -> NetworkResult<Vec<AddressDelta>> {
+            self.queries += 1;
+            Ok(self
+                .deltas
+                .get(&address.to_string())
+                .into_iter()
+                .flatten()
+                .map(|(txid, index, satoshis, height)| AddressDelta {
+                    address: address.to_string(),
+                    txid: txid.clone(),
+                    index: *index,
+                    satoshis: *satoshis,
+                    height: *height,
+                })
+                .collect())
+        }
+    }
+
+    fn restored_wallet(path: &std::path::Path) -> Wallet {
+        let storage = WalletStorage::new(StorageConfig {
+            storage_path: path.to_path_buf(),
+            auto_backup: false,
+            backup_count: 3,
+        })
+        .unwrap();
+        let mnemonic = Mnemonic::from_str(ABANDON_MNEMONIC).unwrap();
+        Wallet::from_mnemonic(
+            "restored".to_string(),
+            Network::Regtest,
+            &mnemonic,
+            "",
+            "",
+            storage,
+        )
+        .unwrap()
+    }
+
+    fn address_at(
+        wallet: &mut Wallet,
+        address_type: AddressType,
+        account: u32,
+        change: u32,
+        index: u32,
+    ) -> Address {
+        let path = address_type.derivation_path(account, change, index);
+        wallet.generate_address(&path, address_type).unwrap()
+    }
+
+    fn native_segwit_only() -> DiscoveryConfig {
+        DiscoveryConfig::default().with_address_types(vec![AddressType::NativeSegwit])
+    }
+
+    #[tokio::test]
+    async fn test_discovers_used_receive_addresses_within_gap() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = restored_wallet(temp_dir.path());
+        let mut backend = MockBackend::default();
+
+        let first = address_at(&mut wallet, AddressType::NativeSegwit, 0, 0, 0);
+        let later = address_at(&mut wallet, AddressType::NativeSegwit, 0, 0, 15);
+        backend.receive(&first, 1, 50_000, 100);
+        backend.receive(&later, 2, 25_000, 0);
+
+        let report = AddressDiscovery::new(native_segwit_only())
+            .run(&mut wallet, &mut backend)
+            .await
+            .unwrap();
+
+        assert_eq!(report.utxos_found, 2);
+        assert_eq!(report.transactions_found, 2);
+        // Receive indices 0-15 plus a gap on both chains, then an empty account 1
+        assert_eq!(report.addresses_scanned, 16 + 20 + 20 + 40);
+        assert_eq!(wallet.balance(), 75_000);
+        assert_eq!(wallet.unconfirmed_balance(), 25_000);
+        assert_eq!(
+            wallet.get_addresses(AddressType::NativeSegwit, false).len(),
+            16
+        );
+        assert_eq!(
+            wallet.owns_address(&later),
+            Some(AddressType::NativeSegwit.derivation_path(0, 0, 15))
+        );
+
+        // The next fresh address comes after the last used one
+        let next = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        assert_eq!(
+            next,
+            address_at(&mut wallet, AddressType::NativeSegwit, 0, 0, 16)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stops_after_gap_limit() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = restored_wallet(temp_dir.path());
+        let mut backend = MockBackend::default();
+
+        let first = address_at(&mut wallet, AddressType::NativeSegwit, 0, 0, 0);
+        let beyond_gap = address_at(&mut wallet, AddressType::NativeSegwit, 0, 0, 25);
+        backend.receive(&first, 1, 10_000, 100);
+        backend.receive(&beyond_gap, 2, 20_000, 100);
+
+        AddressDiscovery::new(native_segwit_only())
+            .run(&mut wallet, &mut backend)
+            .await
+            .unwrap();
+        assert_eq!(wallet.balance(), 10_000);
+
+        // A wider gap limit reaches the distant address
+        let report = AddressDiscovery::new(native_segwit_only().with_gap_limit(30))
+            .run(&mut wallet, &mut backend)
+            .await
+            .unwrap();
+        assert_eq!(wallet.balance(), 30_000);
+        assert!(report.used_chains.contains(&ChainUsage {
+            address_type: AddressType::NativeSegwit,
+            account: 0,
+            change: false,
+            last_used: 25,
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_discovers_change_chain_and_later_accounts() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = restored_wallet(temp_dir.path());
+        let mut backend = MockBackend::default();
+
+        let change = address_at(&mut wallet, AddressType::Taproot, 0, 1, 3);
+        let second_account = address_at(&mut wallet, AddressType::Taproot, 1, 0, 0);
+        backend.receive(&change, 1, 7_000, 100);
+        backend.receive(&second_account, 2, 3_000, 100);
+
+        let report = AddressDiscovery::new(DiscoveryConfig::default())
+            .run(&mut wallet, &mut backend)
+            .await
+            .unwrap();
+
+        assert_eq!(wallet.balance(), 10_000);
+        assert_eq!(wallet.account_balance(1), 3_000);
+        assert_eq!(wallet.accounts().len(), 2);
+        assert_eq!(
+            wallet
+                .get_account_addresses(0, AddressType::Taproot, true)
+                .len(),
+            4
+        );
+        assert_eq!(
+            report.used_chains,
+            vec![
+                ChainUsage {
+                    address_type: AddressType::Taproot,
+                    account: 0,
+                    change: true,
+                    last_used: 3,
+                },
+                ChainUsage {
+                    address_type: AddressType::Taproot,
+                    account: 1,
+                    change: false,
+                    last_used: 0,
+                },
+            ]
+        );
+        // Empty account 0 for the other types, then Taproot accounts 0, 1 and an empty 2
+        assert_eq!(
+            report.addresses_scanned,
+            3 * 40 + (20 + 24) + (21 + 20) + 40
+        );
+        assert_eq!(backend.queries, report.addresses_scanned);
+    }
+
+    #[tokio::test]
+    async fn test_spent_history_counts_as_used() {
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = restored_wallet(temp_dir.path());
+        let mut backend = MockBackend::default();
+
+        let spent = address_at(&mut wallet, AddressType::NativeSegwit, 0, 0, 4);
+        backend.receive_and_spend(&spent, 1, 2, 40_000);
+
+        let report = AddressDiscovery::new(native_segwit_only())
+            .run(&mut wallet, &mut backend)
+            .await
+            .unwrap();
+
+        assert_eq!(wallet.balance(), 0);
+        assert_eq!(report.utxos_found, 0);
+        let deltas: Vec<i64> = wallet
+            .transactions()
+            .iter()
+            .map(|tx| tx.value_delta)
+            .collect();
+        assert_eq!(deltas, vec![40_000, -40_000]);
+        assert_eq!(
+            wallet.get_addresses(AddressType::NativeSegwit, false).len(),
+            5
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rediscovery_keeps_recorded_transactions() {
+        use bitcoin::{
+            absolute::LockTime, transaction::Version, Amount, OutPoint, ScriptBuf, Sequence,
+            Transaction, TxIn, TxOut, Witness,
+        };
+
+        let temp_dir = tempdir().unwrap();
+        let mut wallet = restored_wallet(temp_dir.path());
+        let mut backend = MockBackend::default();
+
+        let address = address_at(&mut wallet, AddressType::NativeSegwit, 0, 0, 0);
+        let payment = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_str(&format!("{:064x}", 9)).unwrap(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(60_000),
+                script_pubkey: address.script_pubkey(),
+            }],
+        };
+        let txid = payment.compute_txid();
+        wallet
+            .record_transaction(&payment, None, 1_700_000_000)
+            .unwrap();
+        wallet.set_transaction_label(&txid, Some("salary".to_string()));
+        let recorded = wallet.transaction(&txid).unwrap().clone();
+
+        backend.utxos.entry(address.to_string()).or_default().push((
+            txid.to_string(),
+            0,
+            60_000,
+            100,
+        ));
+        backend
+            .deltas
+            .entry(address.to_string())
+            .or_default()
+            .push((txid.to_string(), 0, 60_000, 100));
+        AddressDiscovery::new(native_segwit_only())
+            .run(&mut wallet, &mut backend)
+            .await
+            .unwrap();
+
+        // Only the confirmation is taken from the backend
+        let mut expected = recorded;
+        expected.block_height = Some(100);
+        let entry = wallet.transaction(&txid).unwrap();
+        assert_eq!(
+            serde_json::to_value(entry).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
+        assert_eq!(entry.outputs.len(), 1);
+        assert_eq!(entry.label.as_deref(), Some("salary"));
+        assert_eq!(wallet.balance(), 60_000);
+    }
+}
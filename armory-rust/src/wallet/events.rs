@@ -0,0 +1,71 @@
+use bitcoin::{Address, OutPoint, Txid};
+/// Wallet event notifications
+///
+/// State changes of a wallet (coins received or spent, transactions reaching
+/// the confirmation target, reorg rollbacks and balance changes) are
+/// published on a tokio broadcast channel so embedding applications can react
+/// without polling. Every subscriber sees every event sent after it
+/// subscribed; a subscriber that falls more than `EVENT_CHANNEL_CAPACITY`
+/// events behind gets `RecvError::Lagged` and loses the oldest ones.
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before the oldest are dropped
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Confirmation depth at which `WalletEvent::Confirmed` is emitted by default
+pub const DEFAULT_CONFIRMATION_TARGET: u32 = 6;
+
+/// Typed wallet state change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalletEvent {
+    /// An output paying the wallet was added
+    Received {
+        /// Outpoint of the new output
+        outpoint: OutPoint,
+        /// Value in satoshis
+        value: u64,
+        /// Receiving wallet address
+        address: Address,
+        /// Confirmation height (None if unconfirmed)
+        block_height: Option<u32>,
+    },
+    /// A transaction reached the wallet's confirmation target
+    Confirmed {
+        /// Transaction ID
+        txid: Txid,
+        /// Height of the confirming block
+        block_height: u32,
+        /// Confirmations at the current tip
+        confirmations: u32,
+    },
+    /// A wallet output was spent
+    Spent {
+        /// Outpoint of the spent output
+        outpoint: OutPoint,
+        /// Value in satoshis
+        value: u64,
+        /// Spending transaction, when known
+        spending_txid: Option<Txid>,
+    },
+    /// A reorg returned a confirmed transaction to the mempool
+    Reorged {
+        /// Transaction ID
+        txid: Txid,
+        /// Height it was confirmed at before the reorg
+        previous_height: u32,
+    },
+    /// Total or confirmed balance changed
+    BalanceChanged {
+        /// Total balance before the change
+        previous: u64,
+        /// Total balance after the change
+        total: u64,
+        /// Confirmed balance after the change
+        confirmed: u64,
+    },
+}
+
+/// Create the sending side of a wallet event channel
+pub(crate) fn channel() -> broadcast::Sender<WalletEvent> {
+    broadcast::channel(EVENT_CHANNEL_CAPACITY).0
+}
@@ -0,0 +1,398 @@
+use crate::error::{WalletError, WalletResult};
+/// Transaction history
+///
+/// Every transaction recorded by the wallet keeps its inputs and outputs
+/// together with which of them belong to the wallet. From those the history
+/// engine classifies the transaction (receive, send, self-transfer or
+/// consolidation), works out the fee the wallet paid, and exports the result
+/// as CSV or JSON for bookkeeping.
+use crate::wallet::descriptor_wallet::WalletTransaction;
+use bitcoin::{OutPoint, ScriptBuf, Txid};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// How a transaction moved funds relative to the wallet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionKind {
+    /// Funds received from someone else
+    Receive,
+    /// Funds paid to someone else
+    Send,
+    /// Wallet funds moved to wallet addresses
+    SelfTransfer,
+    /// Several wallet coins merged into fewer wallet outputs
+    Consolidation,
+}
+
+impl fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TransactionKind::Receive => "receive",
+            TransactionKind::Send => "send",
+            TransactionKind::SelfTransfer => "self_transfer",
+            TransactionKind::Consolidation => "consolidation",
+        })
+    }
+}
+
+/// Input of a recorded transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryInput {
+    /// Outpoint spent by the input
+    pub previous_output: OutPoint,
+    /// Value of the spent output, when known
+    pub value: Option<u64>,
+    /// Spent output belonged to the wallet
+    pub owned: bool,
+}
+
+/// Output of a recorded transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryOutput {
+    /// Value in satoshis
+    pub value: u64,
+    /// Output script
+    pub script_pubkey: ScriptBuf,
+    /// Output pays a wallet address
+    pub owned: bool,
+}
+
+impl WalletTransaction {
+    /// Classify the transaction from its recorded inputs and outputs
+    ///
+    /// Entries without input/output detail (e.g. from address discovery)
+    /// fall back to the sign of `value_delta`.
+    pub fn kind(&self) -> TransactionKind {
+        if self.inputs.is_empty() && self.outputs.is_empty() {
+            return if self.value_delta < 0 {
+                TransactionKind::Send
+            } else {
+                TransactionKind::Receive
+            };
+        }
+
+        if !self.inputs.iter().any(|input| input.owned) {
+            TransactionKind::Receive
+        } else if !self.outputs.iter().all(|output| output.owned) {
+            TransactionKind::Send
+        } else if self.inputs.len() > self.outputs.len() {
+            TransactionKind::Consolidation
+        } else {
+            TransactionKind::SelfTransfer
+        }
+    }
+
+    /// Fee paid by the wallet, known only when it funded every input
+    pub fn fee(&self) -> Option<u64> {
+        if self.inputs.is_empty() || !self.inputs.iter().all(|input| input.owned) {
+            return None;
+        }
+
+        let spent = self
+            .inputs
+            .iter()
+            .map(|input| input.value)
+            .sum::<Option<u64>>()?;
+        let created: u64 = self.outputs.iter().map(|output| output.value).sum();
+        spent.checked_sub(created)
+    }
+}
+
+/// Criteria for selecting history entries
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    /// Earliest timestamp (inclusive)
+    pub from: Option<u64>,
+    /// Latest timestamp (inclusive)
+    pub to: Option<u64>,
+    /// Smallest absolute amount in satoshis
+    pub min_amount: Option<u64>,
+    /// Largest absolute amount in satoshis
+    pub max_amount: Option<u64>,
+    /// Case-insensitive text the label must contain
+    pub label: Option<String>,
+    /// Only transactions of this kind
+    pub kind: Option<TransactionKind>,
+}
+
+impl HistoryFilter {
+    /// Whether a transaction satisfies every criterion
+    pub fn matches(&self, transaction: &WalletTransaction) -> bool {
+        let amount = transaction.value_delta.unsigned_abs();
+        let label_matches = match &self.label {
+            Some(needle) => transaction
+                .label
+                .as_deref()
+                .is_some_and(|label| label.to_lowercase().contains(&needle.to_lowercase())),
+            None => true,
+        };
+
+        self.from.is_none_or(|from| transaction.timestamp >= from)
+            && self.to.is_none_or(|to| transaction.timestamp <= to)
+            && self.min_amount.is_none_or(|min| amount >= min)
+            && self.max_amount.is_none_or(|max| amount <= max)
+            && self.kind.is_none_or(|kind| transaction.kind() == kind)
+            && label_matches
+    }
+}
+
+/// Flattened history row used for CSV and JSON export
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistoryEntry {
+    /// Transaction ID
+    pub txid: Txid,
+    /// UTC date and time (empty when the timestamp is unknown)
+    pub date: String,
+    /// Unix timestamp
+    pub timestamp: u64,
+    /// Confirmation height (None if unconfirmed)
+    pub block_height: Option<u32>,
+    /// Transaction classification
+    pub kind: TransactionKind,
+    /// Net value change for the wallet in satoshis
+    pub amount: i64,
+    /// Fee paid by the wallet in satoshis
+    pub fee: Option<u64>,
+    /// Transaction label
+    pub label: Option<String>,
+}
+
+impl From<&WalletTransaction> for HistoryEntry {
+    fn from(transaction: &WalletTransaction) -> Self {
+        Self {
+            txid: transaction.txid,
+            date: format_timestamp(transaction.timestamp),
+            timestamp: transaction.timestamp,
+            block_height: transaction.block_height,
+            kind: transaction.kind(),
+            amount: transaction.value_delta,
+            fee: transaction.fee(),
+            label: transaction.label.clone(),
+        }
+    }
+}
+
+/// Render history entries as CSV with a header row
+pub fn to_csv(entries: &[HistoryEntry]) -> String {
+    let mut output = String::from("txid,date,timestamp,block_height,kind,amount,fee,label\n");
+    for entry in entries {
+        let fields = [
+            entry.txid.to_string(),
+            entry.date.clone(),
+            entry.timestamp.to_string(),
+            entry
+                .block_height
+                .map(|h| h.to_string())
+                .unwrap_or_default(),
+            entry.kind.to_string(),
+            entry.amount.to_string(),
+            entry.fee.map(|fee| fee.to_string()).unwrap_or_default(),
+            csv_field(entry.label.as_deref().unwrap_or_default()),
+        ];
+        output.push_str(&fields.join(","));
+        output.push('\n');
+    }
+    output
+}
+
+/// Render history entries as a pretty-printed JSON array
+pub fn to_json(entries: &[HistoryEntry]) -> WalletResult<String> {
+    serde_json::to_string_pretty(entries)
+        .map_err(|e| WalletError::InvalidInput(format!("Failed to encode history: {e}")))
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Format a Unix timestamp as an ISO-8601 UTC date and time
+///
+/// A zero timestamp means "unknown" and formats as an empty string.
+pub fn format_timestamp(timestamp: u64) -> String {
+    if timestamp == 0 {
+        return String::new();
+    }
+
+    let (year, month, day) = civil_from_days(timestamp / SECONDS_PER_DAY);
+    let seconds = timestamp % SECONDS_PER_DAY;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Parse a `YYYY-MM-DD` date as the Unix timestamp of its first second (UTC)
+pub fn parse_date(date: &str) -> WalletResult<u64> {
+    let invalid =
+        || WalletError::InvalidInput(format!("Invalid date (expected YYYY-MM-DD): {date}"));
+
+    let mut parts = date.trim().splitn(3, '-');
+    let mut next = || parts.next().and_then(|part| part.parse::<u64>().ok());
+    let (year, month, day) = (
+        next().ok_or_else(invalid)?,
+        next().ok_or_else(invalid)?,
+        next().ok_or_else(invalid)?,
+    );
+
+    if year < 1970 || !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return Err(invalid());
+    }
+
+    Ok(days_from_civil(year, month, day) * SECONDS_PER_DAY)
+}
+
+/// Last second (UTC) of the day a `YYYY-MM-DD` date names
+pub fn parse_date_end(date: &str) -> WalletResult<u64> {
+    Ok(parse_date(date)? + SECONDS_PER_DAY - 1)
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Proleptic Gregorian calendar conversions (Howard Hinnant's algorithms),
+// restricted to dates from the Unix epoch onwards
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+
+    fn transaction(inputs: Vec<HistoryInput>, outputs: Vec<HistoryOutput>) -> WalletTransaction {
+        WalletTransaction {
+            txid: Txid::all_zeros(),
+            value_delta: 0,
+            block_height: None,
+            timestamp: 0,
+            label: None,
+            inputs,
+            outputs,
+        }
+    }
+
+    fn input(value: u64, owned: bool) -> HistoryInput {
+        HistoryInput {
+            previous_output: OutPoint::null(),
+            value: owned.then_some(value),
+            owned,
+        }
+    }
+
+    fn output(value: u64, owned: bool) -> HistoryOutput {
+        HistoryOutput {
+            value,
+            script_pubkey: ScriptBuf::new(),
+            owned,
+        }
+    }
+
+    #[test]
+    fn test_classification_and_fee() {
+        let receive = transaction(vec![input(0, false)], vec![output(5_000, true)]);
+        assert_eq!(receive.kind(), TransactionKind::Receive);
+        assert_eq!(receive.fee(), None);
+
+        let send = transaction(
+            vec![input(10_000, true)],
+            vec![output(6_000, false), output(3_000, true)],
+        );
+        assert_eq!(send.kind(), TransactionKind::Send);
+        assert_eq!(send.fee(), Some(1_000));
+
+        let self_transfer = transaction(vec![input(10_000, true)], vec![output(9_500, true)]);
+        assert_eq!(self_transfer.kind(), TransactionKind::SelfTransfer);
+        assert_eq!(self_transfer.fee(), Some(500));
+
+        let consolidation = transaction(
+            vec![input(4_000, true), input(6_000, true)],
+            vec![output(9_800, true)],
+        );
+        assert_eq!(consolidation.kind(), TransactionKind::Consolidation);
+        assert_eq!(consolidation.fee(), Some(200));
+
+        // Someone else contributed an input: the fee is not ours to report
+        let payjoin = transaction(
+            vec![input(10_000, true), input(7_000, false)],
+            vec![output(12_000, false), output(4_000, true)],
+        );
+        assert_eq!(payjoin.kind(), TransactionKind::Send);
+        assert_eq!(payjoin.fee(), None);
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(parse_date("1970-01-01").unwrap(), 0);
+        assert_eq!(parse_date("2009-01-03").unwrap(), 1_230_940_800);
+        assert_eq!(parse_date("2024-02-29").unwrap(), 1_709_164_800);
+        assert_eq!(parse_date_end("2024-02-29").unwrap(), 1_709_251_199);
+        assert!(parse_date("2023-02-29").is_err());
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("yesterday").is_err());
+
+        assert_eq!(format_timestamp(0), "");
+        assert_eq!(format_timestamp(1_231_006_505), "2009-01-03T18:15:05Z");
+        assert_eq!(format_timestamp(1_709_251_199), "2024-02-29T23:59:59Z");
+    }
+
+    #[test]
+    fn test_csv_escaping() {
+        let mut send = transaction(vec![input(10_000, true)], vec![output(9_000, false)]);
+        send.value_delta = -10_000;
+        send.timestamp = 1_231_006_505;
+        send.block_height = Some(170);
+        send.label = Some("Rent, \"March\"".to_string());
+
+        let csv = to_csv(&[HistoryEntry::from(&send)]);
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("txid,date,timestamp,block_height,kind,amount,fee,label")
+        );
+        assert_eq!(
+            lines.next().unwrap(),
+            format!(
+                "{},2009-01-03T18:15:05Z,1231006505,170,send,-10000,1000,\"Rent, \"\"March\"\"\"",
+                Txid::all_zeros()
+            )
+        );
+    }
+}
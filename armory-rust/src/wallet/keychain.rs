@@ -0,0 +1,211 @@
+use crate::error::{WalletError, WalletResult};
+/// Descriptor keychains
+///
+/// A keychain is a ranged output descriptor, such as
+/// `wsh(sortedmulti(2,[d34db33f/48'/0'/0'/2']xpub.../0/*,...))` or a Taproot
+/// descriptor with a script tree, from which the wallet derives addresses
+/// beyond the four built-in single-key address types. A receive/change pair
+/// can be given as one multipath descriptor (`.../<0;1>/*`) or as two
+/// descriptors. Keys carrying origin information of the wallet's master key
+/// are signed for by the wallet; the other keys belong to cosigners.
+use crate::wallet::descriptor_wallet::Chain;
+use bitcoin::{bip32::Xpub, Address, Network, NetworkKind};
+use miniscript::descriptor::{DefiniteDescriptorKey, Descriptor, DescriptorPublicKey, Wildcard};
+use miniscript::ForEachKey;
+use std::str::FromStr;
+
+/// Named pair of ranged descriptors deriving receive and change addresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keychain {
+    /// Keychain name, unique within a wallet
+    pub name: String,
+    /// Receive chain descriptor
+    receive: Descriptor<DescriptorPublicKey>,
+    /// Change chain descriptor, if the keychain has one
+    change: Option<Descriptor<DescriptorPublicKey>>,
+}
+
+impl Keychain {
+    /// Parse a keychain from a receive descriptor and an optional change descriptor
+    ///
+    /// A multipath receive descriptor with two paths supplies both chains and
+    /// must not be combined with a separate change descriptor. Descriptors
+    /// must be ranged over unhardened indices and must not contain private
+    /// keys; a checksum is validated when present.
+    pub fn new(name: &str, descriptor: &str, change: Option<&str>) -> WalletResult<Self> {
+        if name.trim().is_empty() {
+            return Err(WalletError::InvalidInput(
+                "Keychain name must not be empty".to_string(),
+            ));
+        }
+
+        let parsed = parse(descriptor)?;
+        let (receive, change) = if parsed.is_multipath() {
+            if change.is_some() {
+                return Err(WalletError::InvalidInput(
+                    "A multipath descriptor already includes the change chain".to_string(),
+                ));
+            }
+            let mut chains = parsed
+                .into_single_descriptors()
+                .map_err(|e| WalletError::InvalidInput(format!("Invalid descriptor: {e}")))?;
+            if chains.len() != 2 {
+                return Err(WalletError::InvalidInput(format!(
+                    "Multipath descriptors must have exactly two paths (receive;change): {descriptor}"
+                )));
+            }
+            let change = chains.pop();
+            (chains.remove(0), change)
+        } else {
+            (parsed, change.map(parse).transpose()?)
+        };
+
+        let keychain = Self {
+            name: name.to_string(),
+            receive,
+            change,
+        };
+        // Both chains must derive addresses (this rejects bare descriptors)
+        for chain in [Chain::External, Chain::Internal] {
+            if keychain.descriptor(chain).is_some() {
+                keychain.address(chain, 0, Network::Bitcoin)?;
+            }
+        }
+        Ok(keychain)
+    }
+
+    /// Ranged descriptor of a chain, if the keychain has one
+    pub fn descriptor(&self, chain: Chain) -> Option<&Descriptor<DescriptorPublicKey>> {
+        match chain {
+            Chain::External => Some(&self.receive),
+            Chain::Internal => self.change.as_ref(),
+        }
+    }
+
+    /// Checksummed descriptor strings, receive first
+    pub fn descriptors(&self) -> Vec<String> {
+        std::iter::once(&self.receive)
+            .chain(&self.change)
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// Concrete descriptor at an index of a chain
+    pub fn derive(
+        &self,
+        chain: Chain,
+        index: u32,
+    ) -> WalletResult<Descriptor<DefiniteDescriptorKey>> {
+        let descriptor = self.descriptor(chain).ok_or_else(|| {
+            WalletError::InvalidInput(format!("Keychain '{}' has no change descriptor", self.name))
+        })?;
+        descriptor
+            .at_derivation_index(index)
+            .map_err(|e| WalletError::InvalidInput(format!("Cannot derive index {index}: {e}")))
+    }
+
+    /// Address at an index of a chain
+    pub fn address(&self, chain: Chain, index: u32, network: Network) -> WalletResult<Address> {
+        self.derive(chain, index)?
+            .address(network)
+            .map_err(|e| WalletError::InvalidInput(format!("Descriptor has no address: {e}")))
+    }
+
+    /// Whether every extended key in the keychain belongs to the given network kind
+    pub fn matches_network(&self, kind: NetworkKind) -> bool {
+        let matches = |xkey: &Xpub| xkey.network == kind;
+        std::iter::once(&self.receive)
+            .chain(&self.change)
+            .all(|descriptor| {
+                descriptor.for_each_key(|key| match key {
+                    DescriptorPublicKey::XPub(xpub) => matches(&xpub.xkey),
+                    DescriptorPublicKey::MultiXPub(xpub) => matches(&xpub.xkey),
+                    DescriptorPublicKey::Single(_) => true,
+                })
+            })
+    }
+}
+
+fn parse(descriptor: &str) -> WalletResult<Descriptor<DescriptorPublicKey>> {
+    let parsed = Descriptor::<DescriptorPublicKey>::from_str(descriptor.trim())
+        .map_err(|e| WalletError::InvalidInput(format!("Invalid descriptor: {e}")))?;
+    if !parsed.has_wildcard() {
+        return Err(WalletError::InvalidInput(format!(
+            "Keychain descriptors must be ranged (end in /*): {descriptor}"
+        )));
+    }
+    // Hardened children cannot be derived from extended public keys
+    let unhardened = parsed.for_each_key(|key| match key {
+        DescriptorPublicKey::XPub(xpub) => xpub.wildcard != Wildcard::Hardened,
+        DescriptorPublicKey::MultiXPub(xpub) => xpub.wildcard != Wildcard::Hardened,
+        DescriptorPublicKey::Single(_) => true,
+    });
+    if !unhardened {
+        return Err(WalletError::InvalidInput(format!(
+            "Keychain descriptors cannot use hardened wildcards: {descriptor}"
+        )));
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // BIP-84 test vector account key for the "abandon ... about" mnemonic
+    const XPUB: &str = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+
+    #[test]
+    fn test_multipath_keychain_splits_chains() {
+        let keychain = Keychain::new(
+            "main",
+            &format!("wpkh([73c5da0a/84'/0'/0']{XPUB}/<0;1>/*)"),
+            None,
+        )
+        .unwrap();
+        let descriptors = keychain.descriptors();
+        assert_eq!(descriptors.len(), 2);
+        assert!(descriptors[0].contains(&format!("{XPUB}/0/*")));
+        assert!(descriptors[1].contains(&format!("{XPUB}/1/*")));
+
+        // Same addresses as the BIP-84 vectors
+        assert_eq!(
+            keychain
+                .address(Chain::External, 0, Network::Bitcoin)
+                .unwrap()
+                .to_string(),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert_eq!(
+            keychain
+                .address(Chain::Internal, 0, Network::Bitcoin)
+                .unwrap()
+                .to_string(),
+            "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el"
+        );
+        assert!(keychain.matches_network(NetworkKind::Main));
+        assert!(!keychain.matches_network(NetworkKind::Test));
+    }
+
+    #[test]
+    fn test_rejects_unusable_descriptors() {
+        for descriptor in [
+            format!("wpkh({XPUB}/0/5)"),
+            format!("wpkh({XPUB}/0/*')"),
+            format!("wpkh({XPUB}/<0;1;2>/*)"),
+            "not a descriptor".to_string(),
+        ] {
+            assert!(
+                Keychain::new("bad", &descriptor, None).is_err(),
+                "{descriptor} should be rejected"
+            );
+        }
+
+        let multipath = format!("wpkh({XPUB}/<0;1>/*)");
+        assert!(Keychain::new("bad", &multipath, Some(&multipath)).is_err());
+        assert!(Keychain::new(" ", &multipath, None).is_err());
+
+        let receive_only = Keychain::new("receive", &format!("wpkh({XPUB}/0/*)"), None).unwrap();
+        assert!(receive_only.derive(Chain::Internal, 0).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use crate::error::{WalletError, WalletResult};
+/// BIP-329 wallet label export format
+///
+/// Labels are exchanged as JSON Lines, one record per line, each naming the
+/// kind of object it labels (`tx`, `addr`, `pubkey`, `input`, `output` or
+/// `xpub`) and a reference to it. This is the format used by Sparrow,
+/// Bitcoin Core tooling and other wallets to carry labels between them.
+use crate::wallet::{descriptor_wallet::AddressType, watch_only::AccountXpub};
+use serde::{Deserialize, Serialize};
+
+/// Kind of object a label refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelType {
+    /// Transaction, referenced by txid
+    Tx,
+    /// Address
+    Addr,
+    /// Public key, referenced by its hex encoding
+    Pubkey,
+    /// Transaction input, referenced by the outpoint it spends (`txid:vout`)
+    Input,
+    /// Transaction output, referenced by its outpoint (`txid:vout`)
+    Output,
+    /// Extended public key
+    Xpub,
+}
+
+/// Single BIP-329 label record
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Label {
+    /// Kind of the labelled object
+    #[serde(rename = "type")]
+    pub label_type: LabelType,
+    /// Reference to the labelled object
+    #[serde(rename = "ref")]
+    pub reference: String,
+    /// Label text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Key origin of the object as an abbreviated descriptor, e.g. `wpkh([d34db33f/84'/0'/0'])`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Whether an output may be spent (outputs only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spendable: Option<bool>,
+}
+
+impl Label {
+    /// Create a plain label without origin or spendable flag
+    pub fn new(
+        label_type: LabelType,
+        reference: impl Into<String>,
+        label: impl Into<String>,
+    ) -> Self {
+        Self {
+            label_type,
+            reference: reference.into(),
+            label: Some(label.into()),
+            origin: None,
+            spendable: None,
+        }
+    }
+}
+
+/// Abbreviated descriptor naming the key origin of an account, e.g. `wpkh([d34db33f/84'/0'/0'])`
+pub fn origin_descriptor(account: &AccountXpub) -> String {
+    let origin = if account.origin.is_empty() {
+        account.fingerprint.to_string()
+    } else {
+        format!("{}/{}", account.fingerprint, account.origin)
+    };
+
+    match account.address_type {
+        AddressType::Legacy => format!("pkh([{origin}])"),
+        AddressType::NestedSegwit => format!("sh(wpkh([{origin}]))"),
+        AddressType::NativeSegwit => format!("wpkh([{origin}])"),
+        AddressType::Taproot => format!("tr([{origin}])"),
+    }
+}
+
+/// Serialize labels as BIP-329 JSON Lines
+pub fn to_jsonl(labels: &[Label]) -> WalletResult<String> {
+    let mut output = String::new();
+    for label in labels {
+        let line = serde_json::to_string(label)
+            .map_err(|e| WalletError::InvalidInput(format!("Failed to encode label: {e}")))?;
+        output.push_str(&line);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Parse BIP-329 JSON Lines, skipping blank lines
+pub fn parse_jsonl(input: &str) -> WalletResult<Vec<Label>> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                WalletError::InvalidInput(format!("Invalid label on line {}: {e}", number + 1))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Records from the BIP-329 specification
+    const SPEC_EXAMPLE: &str = r#"{ "type": "tx", "ref": "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd", "label": "Transaction", "origin": "wpkh([d34db33f/84'/0'/0'])" }
+{ "type": "addr", "ref": "bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c", "label": "Address" }
+{ "type": "pubkey", "ref": "0283409659355b6d1cc3c32decd5d561abaac86c37a353b52895a5e6c196d6f448", "label": "Public Key" }
+{ "type": "input", "ref": "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:0", "label": "Input" }
+{ "type": "output", "ref": "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd:1", "label": "Output", "spendable": false }
+{ "type": "xpub", "ref": "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8", "label": "Extended Public Key" }
+"#;
+
+    #[test]
+    fn test_parse_spec_example() {
+        let labels = parse_jsonl(SPEC_EXAMPLE).unwrap();
+        assert_eq!(labels.len(), 6);
+        assert_eq!(labels[0].label_type, LabelType::Tx);
+        assert_eq!(
+            labels[0].origin.as_deref(),
+            Some("wpkh([d34db33f/84'/0'/0'])")
+        );
+        assert_eq!(labels[4].label_type, LabelType::Output);
+        assert_eq!(labels[4].spendable, Some(false));
+        assert_eq!(labels[5].label.as_deref(), Some("Extended Public Key"));
+    }
+
+    #[test]
+    fn test_jsonl_roundtrip() {
+        let labels = parse_jsonl(SPEC_EXAMPLE).unwrap();
+        let encoded = to_jsonl(&labels).unwrap();
+        assert_eq!(encoded.lines().count(), 6);
+        assert!(encoded
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with(r#"{"type":"addr","ref":"bc1q34aq5drpuwy3wgl9lhup9892qp6svr8ldzyy7c""#));
+        assert_eq!(parse_jsonl(&encoded).unwrap(), labels);
+    }
+
+    #[test]
+    fn test_parse_reports_line_numbers() {
+        let input = format!("{SPEC_EXAMPLE}\n{{\"type\": \"utxo\", \"ref\": \"x\"}}\n");
+        let error = parse_jsonl(&input).unwrap_err().to_string();
+        assert!(error.contains("line 8"), "{error}");
+    }
+}
@@ -0,0 +1,369 @@
+use crate::error::{WalletError, WalletResult};
+/// Bitcoin signed messages
+///
+/// Two ways of proving control of an address are supported:
+///
+/// - BIP-137 ("legacy") signatures: a 65-byte recoverable ECDSA signature
+///   over the `Bitcoin Signed Message:` hash, whose header byte names the
+///   address type. Only single-key P2PKH, P2SH-P2WPKH and P2WPKH addresses
+///   can be signed this way.
+/// - BIP-322 generic signatures: the message is committed to by a virtual
+///   `to_spend` transaction paying the address, and the signature is a
+///   virtual `to_sign` transaction spending it. The "simple" format carries
+///   only the witness stack (native segwit and Taproot addresses); the "full"
+///   format carries the whole `to_sign` transaction and works for any script.
+use bitcoin::{
+    absolute::LockTime,
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    consensus::{deserialize, serialize},
+    hashes::{sha256, Hash, HashEngine},
+    opcodes::all::{OP_PUSHBYTES_0, OP_RETURN},
+    psbt::Psbt,
+    script::Builder,
+    secp256k1::{
+        ecdsa::{RecoverableSignature, RecoveryId},
+        Message, Secp256k1, SecretKey, Signing, Verification,
+    },
+    sighash::Prevouts,
+    sign_message::signed_msg_hash,
+    transaction::Version,
+    Address, Amount, CompressedPublicKey, OutPoint, PublicKey, Script, ScriptBuf, Sequence,
+    Transaction, TxIn, TxOut, Witness,
+};
+use miniscript::interpreter::Interpreter;
+use serde::{Deserialize, Serialize};
+
+/// Tag of the BIP-322 message hash
+pub const BIP322_TAG: &[u8] = b"BIP0322-signed-message";
+
+/// Signed message encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageFormat {
+    /// BIP-137 recoverable ECDSA signature
+    Legacy,
+    /// BIP-322 witness stack
+    Simple,
+    /// BIP-322 `to_sign` transaction
+    Full,
+}
+
+/// Single-key address types a BIP-137 header can name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LegacyKind {
+    P2pkh,
+    P2shP2wpkh,
+    P2wpkh,
+}
+
+impl LegacyKind {
+    const ALL: [LegacyKind; 3] = [
+        LegacyKind::P2pkh,
+        LegacyKind::P2shP2wpkh,
+        LegacyKind::P2wpkh,
+    ];
+
+    /// Header byte of recovery ID 0 for a compressed key
+    fn header_base(self) -> u8 {
+        match self {
+            LegacyKind::P2pkh => 31,
+            LegacyKind::P2shP2wpkh => 35,
+            LegacyKind::P2wpkh => 39,
+        }
+    }
+
+    fn script_pubkey(self, key: &CompressedPublicKey) -> ScriptBuf {
+        match self {
+            LegacyKind::P2pkh => ScriptBuf::new_p2pkh(&key.pubkey_hash()),
+            LegacyKind::P2shP2wpkh => {
+                ScriptBuf::new_p2sh(&ScriptBuf::new_p2wpkh(&key.wpubkey_hash()).script_hash())
+            }
+            LegacyKind::P2wpkh => ScriptBuf::new_p2wpkh(&key.wpubkey_hash()),
+        }
+    }
+}
+
+/// BIP-322 tagged hash of a message
+pub fn message_hash(message: &[u8]) -> sha256::Hash {
+    let tag = sha256::Hash::hash(BIP322_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(message);
+    sha256::Hash::from_engine(engine)
+}
+
+/// Virtual BIP-322 transaction committing to a message and paying a script
+pub fn to_spend(script_pubkey: &Script, message: &[u8]) -> Transaction {
+    let script_sig = Builder::new()
+        .push_opcode(OP_PUSHBYTES_0)
+        .push_slice(message_hash(message).to_byte_array())
+        .into_script();
+
+    Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig,
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: script_pubkey.to_owned(),
+        }],
+    }
+}
+
+/// Unsigned virtual BIP-322 transaction spending `to_spend`
+pub fn to_sign(to_spend: &Transaction) -> Transaction {
+    Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(to_spend.compute_txid(), 0),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
+
+/// PSBT of the BIP-322 `to_sign` transaction for an address and message
+///
+/// The spent output is attached so any PSBT signer can sign it.
+pub fn to_sign_psbt(address: &Address, message: &str) -> WalletResult<Psbt> {
+    let to_spend = to_spend(&address.script_pubkey(), message.as_bytes());
+    let mut psbt = Psbt::from_unsigned_tx(to_sign(&to_spend))
+        .map_err(|e| WalletError::InvalidInput(format!("Invalid message transaction: {e}")))?;
+    psbt.inputs[0].witness_utxo = Some(to_spend.output[0].clone());
+    if !to_spend.output[0].script_pubkey.is_witness_program() {
+        psbt.inputs[0].non_witness_utxo = Some(to_spend);
+    }
+    Ok(psbt)
+}
+
+/// Encode a signed BIP-322 `to_sign` transaction in the simple or full format
+pub fn encode_bip322(signed: &Transaction, format: MessageFormat) -> WalletResult<String> {
+    let input = signed.input.first().ok_or_else(|| {
+        WalletError::InvalidInput("Message transaction has no inputs".to_string())
+    })?;
+    match format {
+        MessageFormat::Simple if input.script_sig.is_empty() => {
+            Ok(BASE64.encode(serialize(&input.witness)))
+        }
+        MessageFormat::Simple => Err(WalletError::InvalidInput(
+            "Address type needs a scriptSig; use the full BIP-322 format".to_string(),
+        )),
+        MessageFormat::Full => Ok(BASE64.encode(serialize(signed))),
+        MessageFormat::Legacy => Err(WalletError::InvalidInput(
+            "BIP-137 signatures are not transactions".to_string(),
+        )),
+    }
+}
+
+/// Sign a message for a single-key address with BIP-137
+pub fn sign_legacy<C: Signing>(
+    secp: &Secp256k1<C>,
+    key: &SecretKey,
+    address: &Address,
+    message: &str,
+) -> WalletResult<String> {
+    let public_key = CompressedPublicKey(key.public_key(secp));
+    let kind = LegacyKind::ALL
+        .into_iter()
+        .find(|kind| address.script_pubkey() == kind.script_pubkey(&public_key))
+        .ok_or_else(|| {
+            WalletError::InvalidInput(format!(
+                "BIP-137 signs P2PKH, P2SH-P2WPKH and P2WPKH addresses only; use BIP-322 for {address}"
+            ))
+        })?;
+
+    let digest = Message::from_digest(signed_msg_hash(message).to_byte_array());
+    let (recovery_id, compact) = secp
+        .sign_ecdsa_recoverable(&digest, key)
+        .serialize_compact();
+
+    let mut signature = [0u8; 65];
+    signature[0] = kind.header_base() + recovery_id.to_i32() as u8;
+    signature[1..].copy_from_slice(&compact);
+    Ok(BASE64.encode(signature))
+}
+
+/// Verify a BIP-137 or BIP-322 message signature for an address
+///
+/// The format is detected from the signature: 65 bytes with a BIP-137
+/// header byte are a legacy signature, anything else is decoded as a
+/// BIP-322 witness stack or `to_sign` transaction. Returns an error only
+/// when the signature cannot be decoded at all.
+pub fn verify_message(address: &Address, message: &str, signature: &str) -> WalletResult<bool> {
+    let bytes = BASE64
+        .decode(signature.trim())
+        .map_err(|e| WalletError::InvalidInput(format!("Invalid signature encoding: {e}")))?;
+    let secp = Secp256k1::verification_only();
+
+    if bytes.len() == 65 && (27..=42).contains(&bytes[0]) {
+        return Ok(verify_legacy(&secp, address, message, &bytes));
+    }
+    verify_bip322(&secp, address, message, &bytes)
+}
+
+fn verify_legacy<C: Verification>(
+    secp: &Secp256k1<C>,
+    address: &Address,
+    message: &str,
+    signature: &[u8],
+) -> bool {
+    let header = signature[0] - 27;
+    let Ok(recovery_id) = RecoveryId::from_i32(i32::from(header % 4)) else {
+        return false;
+    };
+    let Ok(signature) = RecoverableSignature::from_compact(&signature[1..], recovery_id) else {
+        return false;
+    };
+    let digest = Message::from_digest(signed_msg_hash(message).to_byte_array());
+    let Ok(key) = secp.recover_ecdsa(&digest, &signature) else {
+        return false;
+    };
+
+    let script_pubkey = address.script_pubkey();
+    if header < 4 {
+        // Uncompressed P2PKH key
+        let key = PublicKey::new_uncompressed(key);
+        return script_pubkey == ScriptBuf::new_p2pkh(&key.pubkey_hash());
+    }
+    // Electrum and others sign segwit addresses with P2PKH headers, so the
+    // header's address type is not enforced for compressed keys
+    let key = CompressedPublicKey(key);
+    LegacyKind::ALL
+        .into_iter()
+        .any(|kind| script_pubkey == kind.script_pubkey(&key))
+}
+
+fn verify_bip322<C: Verification>(
+    secp: &Secp256k1<C>,
+    address: &Address,
+    message: &str,
+    signature: &[u8],
+) -> WalletResult<bool> {
+    let script_pubkey = address.script_pubkey();
+    let to_spend = to_spend(&script_pubkey, message.as_bytes());
+    let mut to_sign = to_sign(&to_spend);
+
+    if let Ok(witness) = deserialize::<Witness>(signature) {
+        to_sign.input[0].witness = witness;
+    } else if let Ok(full) = deserialize::<Transaction>(signature) {
+        // Proofs of funds with additional inputs are not supported
+        if full.input.len() != 1
+            || full.input[0].previous_output != to_sign.input[0].previous_output
+            || full.output != to_sign.output
+        {
+            return Ok(false);
+        }
+        to_sign = full;
+    } else {
+        return Err(WalletError::InvalidInput(
+            "Signature is neither BIP-137 nor BIP-322".to_string(),
+        ));
+    }
+
+    let input = &to_sign.input[0];
+    let Ok(interpreter) = Interpreter::from_txdata(
+        &script_pubkey,
+        &input.script_sig,
+        &input.witness,
+        input.sequence,
+        to_sign.lock_time,
+    ) else {
+        return Ok(false);
+    };
+    // The interpreter fails on any invalid signature or an unsatisfied script
+    let prevouts = Prevouts::All(&to_spend.output);
+    let satisfied = interpreter
+        .iter(secp, &to_sign, 0, &prevouts)
+        .all(|constraint| constraint.is_ok());
+    Ok(satisfied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{Network, PrivateKey};
+    use std::str::FromStr;
+
+    // BIP-322 test vectors
+    const ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+    const WIF: &str = "L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k";
+
+    fn address() -> Address {
+        Address::from_str(ADDRESS)
+            .unwrap()
+            .require_network(Network::Bitcoin)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_bip322_vectors() {
+        assert_eq!(
+            message_hash(b"").to_string(),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            message_hash(b"Hello World").to_string(),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+
+        let to_spend = to_spend(&address().script_pubkey(), b"");
+        assert_eq!(
+            to_spend.compute_txid().to_string(),
+            "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7"
+        );
+        assert_eq!(
+            to_sign(&to_spend).compute_txid().to_string(),
+            "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6"
+        );
+
+        let signature = "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+        assert!(verify_message(&address(), "Hello World", signature).unwrap());
+        assert!(!verify_message(&address(), "Hello World!", signature).unwrap());
+        assert!(verify_message(&address(), "Hello World", "not base64!").is_err());
+    }
+
+    #[test]
+    fn test_legacy_roundtrip() {
+        let secp = Secp256k1::new();
+        let key = PrivateKey::from_wif(WIF).unwrap();
+        let compressed = CompressedPublicKey::from_private_key(&secp, &key).unwrap();
+        assert_eq!(Address::p2wpkh(&compressed, Network::Bitcoin), address());
+
+        for address in [
+            Address::p2pkh(compressed, Network::Bitcoin),
+            Address::p2shwpkh(&compressed, Network::Bitcoin),
+            Address::p2wpkh(&compressed, Network::Bitcoin),
+        ] {
+            let signature = sign_legacy(&secp, &key.inner, &address, "Armory").unwrap();
+            let header = BASE64.decode(&signature).unwrap()[0];
+            assert!((31..=42).contains(&header));
+            assert!(verify_message(&address, "Armory", &signature).unwrap());
+            assert!(!verify_message(&address, "armory", &signature).unwrap());
+        }
+
+        let taproot = Address::p2tr(&secp, compressed.0.into(), None, Network::Bitcoin);
+        assert!(sign_legacy(&secp, &key.inner, &taproot, "Armory").is_err());
+
+        // A signature by another key does not verify for this address
+        let other = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let other_address = Address::p2wpkh(
+            &CompressedPublicKey(other.public_key(&secp)),
+            Network::Bitcoin,
+        );
+        let signature = sign_legacy(&secp, &other, &other_address, "Armory").unwrap();
+        assert!(verify_message(&other_address, "Armory", &signature).unwrap());
+        assert!(!verify_message(&address(), "Armory", &signature).unwrap());
+    }
+}
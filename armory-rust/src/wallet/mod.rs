@@ -3,8 +3,28 @@
 /// This module implements the main wallet functionality using modern
 /// descriptor-based design patterns.
 pub mod descriptor_wallet;
+pub mod discovery;
+pub mod events;
 pub mod hd_wallet;
+pub mod history;
+pub mod keychain;
+pub mod labels;
+pub mod message;
+pub mod policy;
+pub mod privacy;
+pub mod silent_payments;
+pub mod watch_only;
 
 // Re-exports for convenience
-pub use descriptor_wallet::Wallet;
+pub use descriptor_wallet::{DescriptorImport, UtxoMetadata, Wallet};
+pub use discovery::{AddressDiscovery, ChainBackend, DiscoveryConfig, DiscoveryReport};
+pub use events::WalletEvent;
 pub use hd_wallet::HdWallet;
+pub use history::{HistoryEntry, HistoryFilter, TransactionKind};
+pub use keychain::Keychain;
+pub use labels::{Label, LabelType};
+pub use message::MessageFormat;
+pub use policy::{SpendSummary, SpendingPolicy};
+pub use privacy::{PrivacyFinding, PrivacyReport};
+pub use silent_payments::{SilentPaymentAddress, SilentPaymentScanner};
+pub use watch_only::AccountXpub;
@@ -0,0 +1,324 @@
+use crate::error::{PolicyError, WalletError, WalletResult};
+/// Spending policy
+///
+/// A wallet can carry a policy that every PSBT must pass before the wallet
+/// signs it: per-transaction and rolling 24-hour limits on the value leaving
+/// the wallet, a whitelist of destinations, caps on the fee rate and on the
+/// fee as a share of the amount sent, a ban on outputs that claim to be
+/// change but do not pay the wallet, and an approval threshold above which
+/// spends must be approved explicitly. It is a guardrail for hot wallets
+/// driven by scripts, not a substitute for keeping keys offline.
+use bitcoin::{Address, Network, ScriptBuf, Txid};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Length of the rolling window of the daily limit, in seconds
+pub const DAILY_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+/// Limits a PSBT must respect before the wallet signs it
+///
+/// Every rule is optional; the default policy allows everything.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpendingPolicy {
+    /// Maximum sats leaving the wallet per transaction, fee included
+    pub max_per_transaction: Option<u64>,
+    /// Maximum sats leaving the wallet within any 24 hours
+    pub daily_limit: Option<u64>,
+    /// Addresses payments may go to; empty allows any destination
+    pub allowed_destinations: Vec<String>,
+    /// Maximum estimated fee rate in sat/vB
+    pub max_fee_rate: Option<f64>,
+    /// Maximum fee as a percentage of the amount sent
+    pub max_fee_percent: Option<f64>,
+    /// Reject outputs carrying this wallet's key origins that are not wallet scripts
+    pub forbid_unknown_change: bool,
+    /// Spends above this many sats need explicit approval
+    pub approval_threshold: Option<u64>,
+}
+
+/// Effect of a PSBT on the wallet, as seen by the policy
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpendSummary {
+    /// ID of the unsigned transaction
+    pub txid: Txid,
+    /// Value leaving the wallet: owned inputs minus owned outputs
+    pub spent: u64,
+    /// Outputs paying scripts outside the wallet
+    pub payments: Vec<(ScriptBuf, u64)>,
+    /// Value of outputs paying the wallet
+    pub change: u64,
+    /// Transaction fee
+    pub fee: u64,
+    /// Estimated virtual size once signed
+    pub vsize: u64,
+    /// Outputs carrying this wallet's key origins that are not wallet scripts
+    pub unknown_change: Vec<usize>,
+}
+
+impl SpendSummary {
+    /// Total paid to scripts outside the wallet
+    pub fn paid(&self) -> u64 {
+        self.payments.iter().map(|(_, value)| value).sum()
+    }
+
+    /// Estimated fee rate in sat/vB
+    pub fn fee_rate(&self) -> f64 {
+        self.fee as f64 / self.vsize.max(1) as f64
+    }
+}
+
+/// Spend counted against the daily limit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicySpend {
+    /// ID of the signed transaction
+    pub txid: Txid,
+    /// Value that left the wallet
+    pub amount: u64,
+    /// Unix time of signing
+    pub timestamp: u64,
+}
+
+impl SpendingPolicy {
+    /// Check the policy's values and normalize its destination addresses
+    pub fn validate(&mut self, network: Network) -> WalletResult<()> {
+        for destination in &mut self.allowed_destinations {
+            let address = Address::from_str(destination.trim())
+                .and_then(|address| address.require_network(network))
+                .map_err(|e| {
+                    WalletError::InvalidInput(format!("Invalid allowed destination: {e}"))
+                })?;
+            *destination = address.to_string();
+        }
+        for (name, value) in [
+            ("fee rate", self.max_fee_rate),
+            ("fee percentage", self.max_fee_percent),
+        ] {
+            if value.is_some_and(|value| !value.is_finite() || value < 0.0) {
+                return Err(WalletError::InvalidInput(format!(
+                    "Maximum {name} must be a non-negative number"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluate a spend, given the amount already spent in the daily window
+    ///
+    /// Returns the first rule the spend violates.
+    pub fn evaluate(
+        &self,
+        summary: &SpendSummary,
+        spent_today: u64,
+        approved: bool,
+        network: Network,
+    ) -> Result<(), PolicyError> {
+        if self.forbid_unknown_change {
+            if let Some(&index) = summary.unknown_change.first() {
+                return Err(PolicyError::UnknownChange { index });
+            }
+        }
+
+        if !self.allowed_destinations.is_empty() {
+            for (script, _) in &summary.payments {
+                let destination = match Address::from_script(script, network) {
+                    Ok(address) => address.to_string(),
+                    Err(_) => script.to_hex_string(),
+                };
+                if !self.allowed_destinations.contains(&destination) {
+                    return Err(PolicyError::DestinationNotAllowed { destination });
+                }
+            }
+        }
+
+        let amount = summary.spent;
+        if let Some(limit) = self.max_per_transaction {
+            if amount > limit {
+                return Err(PolicyError::TransactionLimit { amount, limit });
+            }
+        }
+        if let Some(limit) = self.daily_limit {
+            if spent_today.saturating_add(amount) > limit {
+                return Err(PolicyError::DailyLimit {
+                    amount,
+                    spent: spent_today,
+                    limit,
+                });
+            }
+        }
+
+        if let Some(limit) = self.max_fee_rate {
+            let fee_rate = summary.fee_rate();
+            if fee_rate > limit {
+                return Err(PolicyError::FeeRate { fee_rate, limit });
+            }
+        }
+        if let Some(limit) = self.max_fee_percent {
+            // Self-transfers send nothing out, so their fee is weighed against the value moved
+            let base = match summary.paid() {
+                0 => summary.change,
+                paid => paid,
+            };
+            let percent = summary.fee as f64 * 100.0 / base.max(1) as f64;
+            if percent > limit {
+                return Err(PolicyError::FeePercentage {
+                    fee: summary.fee,
+                    percent,
+                    limit,
+                });
+            }
+        }
+
+        if let Some(threshold) = self.approval_threshold {
+            if amount > threshold && !approved {
+                return Err(PolicyError::ApprovalRequired { amount, threshold });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sats spent in the daily window ending at `now`, not counting `txid`
+///
+/// Re-signing a transaction (for example a second signing pass) is not
+/// counted twice.
+pub fn spent_within_day(spends: &[PolicySpend], now: u64, txid: &Txid) -> u64 {
+    spends
+        .iter()
+        .filter(|spend| spend.txid != *txid && spend.timestamp + DAILY_WINDOW_SECS > now)
+        .map(|spend| spend.amount)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+
+    fn summary(spent: u64, fee: u64, vsize: u64) -> SpendSummary {
+        let payee = Address::p2wsh(&ScriptBuf::new(), Network::Regtest);
+        SpendSummary {
+            txid: Txid::all_zeros(),
+            spent,
+            payments: vec![(payee.script_pubkey(), spent - fee)],
+            change: 10_000,
+            fee,
+            vsize,
+            unknown_change: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_policy_rules() {
+        let network = Network::Regtest;
+        let spend = summary(50_000, 1_000, 100);
+        assert!(SpendingPolicy::default()
+            .evaluate(&spend, 0, false, network)
+            .is_ok());
+
+        let policy = SpendingPolicy {
+            max_per_transaction: Some(40_000),
+            ..Default::default()
+        };
+        assert!(matches!(
+            policy.evaluate(&spend, 0, false, network),
+            Err(PolicyError::TransactionLimit {
+                amount: 50_000,
+                limit: 40_000
+            })
+        ));
+
+        let policy = SpendingPolicy {
+            daily_limit: Some(100_000),
+            ..Default::default()
+        };
+        assert!(policy.evaluate(&spend, 50_000, false, network).is_ok());
+        assert!(matches!(
+            policy.evaluate(&spend, 50_001, false, network),
+            Err(PolicyError::DailyLimit { spent: 50_001, .. })
+        ));
+
+        let policy = SpendingPolicy {
+            max_fee_rate: Some(5.0),
+            max_fee_percent: Some(5.0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            policy.evaluate(&spend, 0, false, network),
+            Err(PolicyError::FeeRate { limit, .. }) if limit == 5.0
+        ));
+        let policy = SpendingPolicy {
+            max_fee_percent: Some(1.0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            policy.evaluate(&spend, 0, false, network),
+            Err(PolicyError::FeePercentage { fee: 1_000, .. })
+        ));
+
+        let mut policy = SpendingPolicy {
+            allowed_destinations: vec![Address::p2wsh(&ScriptBuf::from(vec![0x51]), network)
+                .to_string()
+                .to_uppercase()],
+            forbid_unknown_change: true,
+            approval_threshold: Some(20_000),
+            ..Default::default()
+        };
+        policy.validate(network).unwrap();
+        assert!(matches!(
+            policy.evaluate(&spend, 0, true, network),
+            Err(PolicyError::DestinationNotAllowed { .. })
+        ));
+        policy.allowed_destinations.clear();
+        assert!(matches!(
+            policy.evaluate(&spend, 0, false, network),
+            Err(PolicyError::ApprovalRequired {
+                amount: 50_000,
+                threshold: 20_000
+            })
+        ));
+        assert!(policy.evaluate(&spend, 0, true, network).is_ok());
+
+        let mut tampered = spend.clone();
+        tampered.unknown_change.push(1);
+        assert!(matches!(
+            policy.evaluate(&tampered, 0, true, network),
+            Err(PolicyError::UnknownChange { index: 1 })
+        ));
+
+        let mut invalid = SpendingPolicy {
+            allowed_destinations: vec!["not an address".to_string()],
+            ..Default::default()
+        };
+        assert!(invalid.validate(network).is_err());
+    }
+
+    #[test]
+    fn test_daily_window() {
+        let txid = |byte| Txid::from_byte_array([byte; 32]);
+        let now = 1_700_000_000;
+        let spends = vec![
+            PolicySpend {
+                txid: txid(1),
+                amount: 10_000,
+                timestamp: now - DAILY_WINDOW_SECS,
+            },
+            PolicySpend {
+                txid: txid(2),
+                amount: 20_000,
+                timestamp: now - 60,
+            },
+            PolicySpend {
+                txid: txid(3),
+                amount: 30_000,
+                timestamp: now,
+            },
+        ];
+        assert_eq!(spent_within_day(&spends, now, &txid(9)), 50_000);
+        assert_eq!(spent_within_day(&spends, now, &txid(3)), 20_000);
+        assert_eq!(
+            spent_within_day(&spends, now + DAILY_WINDOW_SECS, &txid(9)),
+            0
+        );
+    }
+}
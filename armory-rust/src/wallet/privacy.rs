@@ -0,0 +1,524 @@
+use crate::wallet::descriptor_wallet::{Wallet, WalletTransaction};
+/// Wallet privacy analysis
+///
+/// The analyzer looks through the wallet's recorded transactions and UTXOs
+/// for patterns that let an observer link coins or tell the wallet's change
+/// from its payments: addresses that received more than once, payments of
+/// round amounts, change outputs given away by their amount or script type,
+/// coins with different labels spent together (and every UTXO descending
+/// from such a merge), and tiny unsolicited outputs sent to track the wallet.
+/// The results are heuristics an observer could apply, not proof that coins
+/// were linked.
+use bitcoin::{bip32::DerivationPath, Address, AddressType, OutPoint, Script, ScriptBuf, Txid};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Payments that are a multiple of this many sats (0.0001 BTC) look round
+pub const ROUND_AMOUNT_SATS: u64 = 10_000;
+
+/// Unsolicited outputs up to this many sats are treated as dust
+pub const DUST_THRESHOLD_SATS: u64 = 1_000;
+
+/// Way an observer can tell a change output from the payments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeHeuristic {
+    /// The payments are round amounts and the change is not
+    RoundAmount,
+    /// The change pays the script type of the inputs and the payments do not
+    ScriptType,
+}
+
+impl fmt::Display for ChangeHeuristic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChangeHeuristic::RoundAmount => "round payment amounts",
+            ChangeHeuristic::ScriptType => "input script type",
+        })
+    }
+}
+
+/// Privacy issue found in a wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PrivacyFinding {
+    /// An address received in more than one transaction
+    AddressReuse {
+        /// Reused address
+        address: String,
+        /// Wallet derivation path of the address, when known
+        derivation_path: Option<DerivationPath>,
+        /// Transactions paying the address
+        txids: Vec<Txid>,
+    },
+    /// A payment of a round amount, which stands out from the change
+    RoundPayment {
+        /// Paying transaction
+        txid: Txid,
+        /// Output index of the payment
+        vout: u32,
+        /// Payment amount in satoshis
+        value: u64,
+    },
+    /// A change output an observer can identify
+    ChangeLeak {
+        /// Paying transaction
+        txid: Txid,
+        /// Output index of the change
+        vout: u32,
+        /// Heuristics identifying the output as change
+        heuristics: Vec<ChangeHeuristic>,
+    },
+    /// Coins carrying different labels were spent together
+    MixedLabels {
+        /// Merging transaction
+        txid: Txid,
+        /// Labels of the merged coins
+        labels: Vec<String>,
+        /// Current UTXOs created by the merge or descending from it
+        utxos: Vec<OutPoint>,
+    },
+    /// A tiny output received from outside, likely sent to track the wallet
+    Dust {
+        /// Dust output
+        outpoint: OutPoint,
+        /// Value in satoshis
+        value: u64,
+        /// Whether the output was already spent, linking it to other coins
+        spent: bool,
+        /// Whether the output is frozen against coin selection
+        frozen: bool,
+    },
+}
+
+impl fmt::Display for PrivacyFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrivacyFinding::AddressReuse {
+                address,
+                derivation_path,
+                txids,
+            } => {
+                write!(
+                    f,
+                    "Address {address} received in {} transactions",
+                    txids.len()
+                )?;
+                if let Some(path) = derivation_path {
+                    write!(f, " (m/{path})")?;
+                }
+                Ok(())
+            }
+            PrivacyFinding::RoundPayment { txid, vout, value } => {
+                write!(f, "Round payment of {value} sats in {txid}:{vout}")
+            }
+            PrivacyFinding::ChangeLeak {
+                txid,
+                vout,
+                heuristics,
+            } => {
+                let heuristics: Vec<String> = heuristics.iter().map(ToString::to_string).collect();
+                write!(
+                    f,
+                    "Change {txid}:{vout} is identifiable by {}",
+                    heuristics.join(" and ")
+                )
+            }
+            PrivacyFinding::MixedLabels {
+                txid,
+                labels,
+                utxos,
+            } => write!(
+                f,
+                "Transaction {txid} merged coins labelled {}; {} UTXOs descend from it",
+                labels.join(", "),
+                utxos.len()
+            ),
+            PrivacyFinding::Dust {
+                outpoint,
+                value,
+                spent,
+                frozen,
+            } => {
+                let state = match (spent, frozen) {
+                    (true, _) => "spent",
+                    (false, true) => "frozen",
+                    (false, false) => "unspent, freeze it",
+                };
+                write!(
+                    f,
+                    "Possible dust attack: {value} sats at {outpoint} ({state})"
+                )
+            }
+        }
+    }
+}
+
+/// Result of analyzing a wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PrivacyReport {
+    /// Number of transactions analyzed
+    pub transactions: usize,
+    /// Number of UTXOs analyzed
+    pub utxos: usize,
+    /// Issues found, grouped by kind
+    pub findings: Vec<PrivacyFinding>,
+}
+
+impl PrivacyReport {
+    /// UTXOs descending from a merge of differently labelled coins
+    pub fn tainted_utxos(&self) -> BTreeSet<OutPoint> {
+        self.findings
+            .iter()
+            .flat_map(|finding| match finding {
+                PrivacyFinding::MixedLabels { utxos, .. } => utxos.as_slice(),
+                _ => &[],
+            })
+            .copied()
+            .collect()
+    }
+}
+
+/// Analyze a wallet's transactions and UTXOs
+pub fn analyze(wallet: &Wallet) -> PrivacyReport {
+    let mut transactions = wallet.transactions();
+    // Oldest first, so merges are followed forward through later spends
+    transactions.sort_by_key(|transaction| {
+        (
+            transaction.block_height.unwrap_or(u32::MAX),
+            transaction.timestamp,
+            transaction.txid,
+        )
+    });
+
+    let mut findings = address_reuse(wallet, &transactions);
+    for transaction in &transactions {
+        findings.extend(payment_leaks(wallet, transaction));
+    }
+    findings.extend(mixed_labels(wallet, &transactions));
+    findings.extend(dust(wallet, &transactions));
+
+    PrivacyReport {
+        transactions: transactions.len(),
+        utxos: wallet.utxos().len(),
+        findings,
+    }
+}
+
+fn address_reuse(wallet: &Wallet, transactions: &[&WalletTransaction]) -> Vec<PrivacyFinding> {
+    let mut receipts: BTreeMap<ScriptBuf, BTreeSet<Txid>> = BTreeMap::new();
+    for transaction in transactions {
+        for output in transaction.outputs.iter().filter(|output| output.owned) {
+            receipts
+                .entry(output.script_pubkey.clone())
+                .or_default()
+                .insert(transaction.txid);
+        }
+    }
+    for utxo in wallet.utxos() {
+        receipts
+            .entry(utxo.script_pubkey.clone())
+            .or_default()
+            .insert(utxo.txid);
+    }
+
+    let network = bitcoin::Network::from(wallet.network);
+    receipts
+        .into_iter()
+        .filter(|(_, txids)| txids.len() > 1)
+        .filter_map(|(script, txids)| {
+            let address = Address::from_script(&script, network).ok()?;
+            Some(PrivacyFinding::AddressReuse {
+                derivation_path: wallet.owns_address(&address),
+                address: address.to_string(),
+                txids: txids.into_iter().collect(),
+            })
+        })
+        .collect()
+}
+
+/// Round payments and identifiable change of a payment funded by the wallet
+fn payment_leaks(wallet: &Wallet, transaction: &WalletTransaction) -> Vec<PrivacyFinding> {
+    // Collaborative transactions mix in inputs the heuristics know nothing about
+    if transaction.inputs.is_empty() || !transaction.inputs.iter().all(|input| input.owned) {
+        return Vec::new();
+    }
+    let (change, payments): (Vec<_>, Vec<_>) = transaction
+        .outputs
+        .iter()
+        .enumerate()
+        .partition(|(_, output)| output.owned);
+    if payments.is_empty() {
+        return Vec::new();
+    }
+
+    let round = |value: u64| value.is_multiple_of(ROUND_AMOUNT_SATS);
+    let mut findings: Vec<PrivacyFinding> = payments
+        .iter()
+        .filter(|(_, output)| round(output.value))
+        .map(|(vout, output)| PrivacyFinding::RoundPayment {
+            txid: transaction.txid,
+            vout: *vout as u32,
+            value: output.value,
+        })
+        .collect();
+
+    let round_payments = payments.iter().all(|(_, output)| round(output.value));
+    let input_types: BTreeSet<_> = transaction
+        .inputs
+        .iter()
+        .filter_map(|input| script_of(wallet, &input.previous_output))
+        .map(|script| script_type(&script))
+        .collect();
+    for (vout, output) in change {
+        let mut heuristics = Vec::new();
+        if round_payments && !round(output.value) {
+            heuristics.push(ChangeHeuristic::RoundAmount);
+        }
+        let change_type = script_type(&output.script_pubkey);
+        if input_types.len() == 1
+            && input_types.contains(&change_type)
+            && payments
+                .iter()
+                .all(|(_, payment)| script_type(&payment.script_pubkey) != change_type)
+        {
+            heuristics.push(ChangeHeuristic::ScriptType);
+        }
+        if !heuristics.is_empty() {
+            findings.push(PrivacyFinding::ChangeLeak {
+                txid: transaction.txid,
+                vout: vout as u32,
+                heuristics,
+            });
+        }
+    }
+    findings
+}
+
+fn mixed_labels(wallet: &Wallet, transactions: &[&WalletTransaction]) -> Vec<PrivacyFinding> {
+    let owned_outputs = |transaction: &WalletTransaction| -> Vec<OutPoint> {
+        transaction
+            .outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.owned)
+            .map(|(vout, _)| OutPoint::new(transaction.txid, vout as u32))
+            .collect()
+    };
+
+    let mut findings = Vec::new();
+    for (position, transaction) in transactions.iter().enumerate() {
+        let labels: BTreeSet<String> = transaction
+            .inputs
+            .iter()
+            .filter(|input| input.owned)
+            .filter_map(|input| label_of(wallet, &input.previous_output))
+            .collect();
+        if labels.len() < 2 {
+            continue;
+        }
+
+        // Follow the merged coins through every later wallet spend
+        let mut descendants: BTreeSet<OutPoint> = owned_outputs(transaction).into_iter().collect();
+        for later in &transactions[position + 1..] {
+            if later
+                .inputs
+                .iter()
+                .any(|input| descendants.contains(&input.previous_output))
+            {
+                descendants.extend(owned_outputs(later));
+            }
+        }
+
+        findings.push(PrivacyFinding::MixedLabels {
+            txid: transaction.txid,
+            labels: labels.into_iter().collect(),
+            utxos: descendants
+                .into_iter()
+                .filter(|outpoint| wallet.utxo(outpoint).is_some())
+                .collect(),
+        });
+    }
+    findings
+}
+
+fn dust(wallet: &Wallet, transactions: &[&WalletTransaction]) -> Vec<PrivacyFinding> {
+    let mut found: BTreeMap<OutPoint, u64> = BTreeMap::new();
+    // Outputs the wallet paid to itself are change, not dust sent by others
+    for transaction in transactions
+        .iter()
+        .filter(|transaction| !transaction.inputs.iter().any(|input| input.owned))
+    {
+        for (vout, output) in transaction.outputs.iter().enumerate() {
+            if output.owned && output.value <= DUST_THRESHOLD_SATS {
+                found.insert(OutPoint::new(transaction.txid, vout as u32), output.value);
+            }
+        }
+    }
+    for utxo in wallet.utxos() {
+        if utxo.value <= DUST_THRESHOLD_SATS && wallet.transaction(&utxo.txid).is_none() {
+            found.insert(utxo.outpoint(), utxo.value);
+        }
+    }
+
+    found
+        .into_iter()
+        .map(|(outpoint, value)| PrivacyFinding::Dust {
+            outpoint,
+            value,
+            spent: wallet.utxo(&outpoint).is_none(),
+            frozen: wallet.is_frozen(&outpoint),
+        })
+        .collect()
+}
+
+/// Script of a wallet output, from the UTXO set or the recorded history
+fn script_of(wallet: &Wallet, outpoint: &OutPoint) -> Option<ScriptBuf> {
+    wallet
+        .utxo(outpoint)
+        .map(|utxo| utxo.script_pubkey.clone())
+        .or_else(|| {
+            wallet
+                .transaction(&outpoint.txid)?
+                .outputs
+                .get(outpoint.vout as usize)
+                .map(|output| output.script_pubkey.clone())
+        })
+}
+
+/// Label of a coin: its own label, else the label of the address it paid
+fn label_of(wallet: &Wallet, outpoint: &OutPoint) -> Option<String> {
+    if let Some(label) = wallet
+        .utxo_metadata(outpoint)
+        .and_then(|metadata| metadata.label.clone())
+    {
+        return Some(label);
+    }
+    let script = script_of(wallet, outpoint)?;
+    let address = Address::from_script(&script, bitcoin::Network::from(wallet.network)).ok()?;
+    wallet
+        .address_label(&address.to_string())
+        .map(str::to_string)
+}
+
+/// Standard address type of a script, if it has one
+fn script_type(script: &Script) -> Option<AddressType> {
+    // The address type does not depend on the network
+    Address::from_script(script, bitcoin::Network::Bitcoin)
+        .ok()?
+        .address_type()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{StorageConfig, WalletStorage};
+    use crate::wallet::descriptor_wallet::AddressType;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{absolute::LockTime, transaction::Version, Amount, Transaction, TxIn, TxOut};
+    use tempfile::tempdir;
+
+    fn transaction(inputs: Vec<OutPoint>, outputs: Vec<(&Address, u64)>) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: inputs
+                .into_iter()
+                .map(|previous_output| TxIn {
+                    previous_output,
+                    ..Default::default()
+                })
+                .collect(),
+            output: outputs
+                .into_iter()
+                .map(|(address, value)| TxOut {
+                    value: Amount::from_sat(value),
+                    script_pubkey: address.script_pubkey(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_privacy_report() {
+        let temp_dir = tempdir().unwrap();
+        let storage = WalletStorage::new(StorageConfig {
+            storage_path: temp_dir.path().to_path_buf(),
+            auto_backup: false,
+            backup_count: 1,
+        })
+        .unwrap();
+        let mut wallet =
+            Wallet::create_new("privacy".to_string(), crate::Network::Regtest, storage).unwrap();
+        let reused = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        let tracked = wallet.get_new_address(AddressType::NativeSegwit).unwrap();
+        let change = wallet
+            .get_change_address(AddressType::NativeSegwit)
+            .unwrap();
+        let merchant = Address::p2wsh(&ScriptBuf::new(), bitcoin::Network::Regtest);
+
+        let outside = |n| OutPoint::new(Txid::all_zeros(), n);
+        let salary = transaction(vec![outside(0)], vec![(&reused, 60_000)]);
+        let exchange = transaction(vec![outside(1)], vec![(&reused, 35_000)]);
+        let dusting = transaction(vec![outside(2)], vec![(&tracked, 546)]);
+        for (tx, height) in [(&salary, 100), (&exchange, 101), (&dusting, 102)] {
+            wallet.record_transaction(tx, Some(height), 0).unwrap();
+        }
+        let salary = OutPoint::new(salary.compute_txid(), 0);
+        let exchange = OutPoint::new(exchange.compute_txid(), 0);
+        wallet
+            .set_utxo_label(&salary, Some("Salary".to_string()))
+            .unwrap();
+        wallet
+            .set_utxo_label(&exchange, Some("Exchange".to_string()))
+            .unwrap();
+
+        // Pay a round amount, merging the two labelled coins
+        let payment = transaction(
+            vec![salary, exchange],
+            vec![(&merchant, 50_000), (&change, 44_200)],
+        );
+        let payment_id = payment.compute_txid();
+        wallet.record_transaction(&payment, Some(103), 0).unwrap();
+
+        let report = analyze(&wallet);
+        assert_eq!(report.transactions, 4);
+        assert_eq!(report.utxos, 2);
+        assert!(matches!(
+            &report.findings[0],
+            PrivacyFinding::AddressReuse { address, derivation_path: Some(_), txids }
+                if *address == reused.to_string() && txids.len() == 2
+        ));
+        assert_eq!(
+            report.findings[1..],
+            [
+                PrivacyFinding::RoundPayment {
+                    txid: payment_id,
+                    vout: 0,
+                    value: 50_000
+                },
+                PrivacyFinding::ChangeLeak {
+                    txid: payment_id,
+                    vout: 1,
+                    heuristics: vec![ChangeHeuristic::RoundAmount, ChangeHeuristic::ScriptType]
+                },
+                PrivacyFinding::MixedLabels {
+                    txid: payment_id,
+                    labels: vec!["Exchange".to_string(), "Salary".to_string()],
+                    utxos: vec![OutPoint::new(payment_id, 1)]
+                },
+                PrivacyFinding::Dust {
+                    outpoint: OutPoint::new(dusting.compute_txid(), 0),
+                    value: 546,
+                    spent: false,
+                    frozen: false
+                },
+            ]
+        );
+        assert_eq!(
+            report.tainted_utxos(),
+            BTreeSet::from([OutPoint::new(payment_id, 1)])
+        );
+    }
+}
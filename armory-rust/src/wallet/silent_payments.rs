@@ -0,0 +1,1053 @@
+use crate::error::{NetworkError, NetworkResult, WalletError, WalletResult};
+/// Silent payments (BIP-352)
+///
+/// A silent payment address publishes a scan key and a spend key. For every
+/// payment the sender combines the private keys of its inputs with the scan
+/// key (ECDH) into a shared secret and pays a fresh Taproot output derived
+/// from the spend key, so the static address never appears on chain. The
+/// receiver redoes the ECDH with its scan key against the input public keys
+/// of each transaction to find its outputs, and spends them with the spend
+/// key tweaked by the shared secret. Labels (BIP-352 `m`) give one receiver
+/// several addresses it can tell apart; label 0 is reserved for change.
+use crate::network::rpc::RpcClient;
+use crate::Network;
+use bitcoin::{
+    bech32::{primitives::decode::CheckedHrpstring, Bech32m, ByteIterExt, Fe32, Fe32IterExt, Hrp},
+    bip32::{ChildNumber, DerivationPath},
+    consensus::serialize,
+    hashes::{hash160, sha256, Hash, HashEngine},
+    key::{Parity, TweakedPublicKey, XOnlyPublicKey},
+    secp256k1::{All, PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification},
+    Block, BlockHash, OutPoint, ScriptBuf, Transaction, TxIn, TxOut, Txid,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Purpose of the BIP-352 scan and spend key derivation paths
+pub const PURPOSE: u32 = 352;
+
+/// X coordinate of the BIP-341 NUMS point; script path spends with this
+/// internal key do not contribute to the shared secret
+pub const NUMS_H: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+/// Highest address version; version 31 is reserved for a breaking change
+const MAX_VERSION: u8 = 30;
+
+/// Length of the scan and spend keys of a version 0 address
+const KEYS_LEN: usize = 66;
+
+/// Static silent payment address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SilentPaymentAddress {
+    /// Key the receiver scans with
+    pub scan_key: PublicKey,
+    /// Key the outputs are derived from (labelled if the address carries a label)
+    pub spend_key: PublicKey,
+    /// Network; testnet and signet share the `tsp` prefix and parse as testnet
+    pub network: Network,
+}
+
+impl SilentPaymentAddress {
+    /// Create an address from its scan and spend keys
+    pub fn new(scan_key: PublicKey, spend_key: PublicKey, network: Network) -> Self {
+        Self {
+            scan_key,
+            spend_key,
+            network,
+        }
+    }
+
+    /// Whether the address can be paid from a wallet on the given network
+    pub fn is_valid_for_network(&self, network: Network) -> bool {
+        hrp(self.network) == hrp(network)
+    }
+}
+
+impl fmt::Display for SilentPaymentAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hrp = Hrp::parse_unchecked(hrp(self.network));
+        let keys = [self.scan_key.serialize(), self.spend_key.serialize()].concat();
+        for c in keys
+            .into_iter()
+            .bytes_to_fes()
+            .with_checksum::<Bech32m>(&hrp)
+            .with_witness_version(Fe32::Q)
+            .chars()
+        {
+            write!(f, "{c}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SilentPaymentAddress {
+    type Err = WalletError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| {
+            WalletError::InvalidInput(format!("Invalid silent payment address: {reason}"))
+        };
+        let checked = CheckedHrpstring::new::<Bech32m>(s).map_err(|e| invalid(&e.to_string()))?;
+        let network = match checked.hrp().to_lowercase().as_str() {
+            "sp" => Network::Bitcoin,
+            "tsp" => Network::Testnet,
+            "sprt" => Network::Regtest,
+            other => return Err(invalid(&format!("unknown prefix {other}"))),
+        };
+
+        let mut data = checked
+            .data_part_ascii_no_checksum()
+            .iter()
+            .map(|&c| Fe32::from_char(c.into()).map_err(|e| invalid(&e.to_string())));
+        let version = data.next().ok_or_else(|| invalid("missing version"))??;
+        if version.to_u8() > MAX_VERSION {
+            return Err(invalid(&format!("unsupported version {}", version.to_u8())));
+        }
+        let payload: Vec<u8> = data
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .fes_to_bytes()
+            .collect();
+        // Later versions may append data after the keys, which version 0 readers ignore
+        if payload.len() < KEYS_LEN || (version == Fe32::Q && payload.len() != KEYS_LEN) {
+            return Err(invalid(&format!("unexpected length {}", payload.len())));
+        }
+
+        let key = |bytes: &[u8]| PublicKey::from_slice(bytes).map_err(|e| invalid(&e.to_string()));
+        Ok(Self {
+            scan_key: key(&payload[..33])?,
+            spend_key: key(&payload[33..KEYS_LEN])?,
+            network,
+        })
+    }
+}
+
+/// Derivation path of an account's scan key (`m/352'/coin'/account'/1'/0`) or spend key (`.../0'/0`)
+///
+/// The coin type is 0' on mainnet and 1' on the test networks, as in BIP-44.
+pub fn key_path(network: Network, account: u32, scan: bool) -> DerivationPath {
+    let coin_type = match network {
+        Network::Bitcoin => 0,
+        Network::Testnet | Network::Signet | Network::Regtest => 1,
+    };
+    DerivationPath::from(vec![
+        ChildNumber::from_hardened_idx(PURPOSE).unwrap(),
+        ChildNumber::from_hardened_idx(coin_type).unwrap(),
+        ChildNumber::from_hardened_idx(account).unwrap(),
+        ChildNumber::from_hardened_idx(u32::from(scan)).unwrap(),
+        ChildNumber::from_normal_idx(0).unwrap(),
+    ])
+}
+
+/// Human-readable prefix of addresses on a network
+fn hrp(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "sp",
+        Network::Testnet | Network::Signet => "tsp",
+        Network::Regtest => "sprt",
+    }
+}
+
+/// Output paying the wallet found by the scanner
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SilentPaymentOutput {
+    /// Outpoint of the output
+    pub outpoint: OutPoint,
+    /// The output itself
+    pub txout: TxOut,
+    /// Scalar added to the spend private key to spend the output
+    pub tweak: [u8; 32],
+    /// Label of the address that was paid, if any
+    pub label: Option<u32>,
+    /// Wallet account whose keys received the output
+    pub account: u32,
+    /// Confirmation height, when known
+    pub block_height: Option<u32>,
+}
+
+impl SilentPaymentOutput {
+    /// Private key spending the output, given the receiver's spend key
+    pub fn spending_key(&self, spend_key: &SecretKey) -> WalletResult<SecretKey> {
+        spend_key
+            .add_tweak(&scalar(self.tweak)?)
+            .map_err(|_| WalletError::KeyDerivation)
+    }
+}
+
+/// Derive the output scripts paying silent payment addresses
+///
+/// `outpoints` are all outpoints the transaction spends; `input_keys` are the
+/// private keys of its inputs that contribute to the shared secret (P2TR,
+/// P2WPKH, P2SH-P2WPKH and P2PKH), flagged when they spend a Taproot output.
+/// Scripts are returned in the order of `recipients`.
+pub fn derive_outputs<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    outpoints: &[OutPoint],
+    input_keys: &[(SecretKey, bool)],
+    recipients: &[SilentPaymentAddress],
+) -> WalletResult<Vec<ScriptBuf>> {
+    let mut sum: Option<SecretKey> = None;
+    for &(key, taproot) in input_keys {
+        // Taproot outputs commit to the even-Y key
+        let key = if taproot && key.x_only_public_key(secp).1 == Parity::Odd {
+            key.negate()
+        } else {
+            key
+        };
+        sum = Some(match sum {
+            Some(sum) => sum
+                .add_tweak(&Scalar::from(key))
+                .map_err(|_| WalletError::InvalidInput("Input keys sum to zero".to_string()))?,
+            None => key,
+        });
+    }
+    let sum = sum.ok_or_else(|| {
+        WalletError::InvalidInput("Silent payments need at least one key-spend input".to_string())
+    })?;
+    let tweaked = sum
+        .mul_tweak(&input_hash(outpoints, &sum.public_key(secp))?)
+        .map_err(|_| WalletError::KeyDerivation)?;
+
+    let mut counters: HashMap<PublicKey, u32> = HashMap::new();
+    recipients
+        .iter()
+        .map(|recipient| {
+            let k = counters.entry(recipient.scan_key).or_insert(0);
+            let shared = recipient
+                .scan_key
+                .mul_tweak(secp, &Scalar::from(tweaked))
+                .map_err(|_| WalletError::KeyDerivation)?;
+            let output = recipient
+                .spend_key
+                .add_exp_tweak(secp, &shared_secret_tweak(&shared, *k)?)
+                .map_err(|_| WalletError::KeyDerivation)?;
+            *k += 1;
+            Ok(taproot_script(output))
+        })
+        .collect()
+}
+
+/// Receiver side: finds outputs paying a scan/spend key pair
+pub struct SilentPaymentScanner {
+    /// Wallet account the keys belong to
+    account: u32,
+    /// Scan private key
+    scan_key: SecretKey,
+    /// Unlabelled spend public key
+    spend_key: PublicKey,
+    /// Label tweak points, with their label and tweak
+    labels: HashMap<PublicKey, (u32, [u8; 32])>,
+    secp: Secp256k1<All>,
+}
+
+impl SilentPaymentScanner {
+    /// Create a scanner for the keys of an account, watching the change label
+    pub fn new(account: u32, scan_key: SecretKey, spend_key: PublicKey) -> WalletResult<Self> {
+        let mut scanner = Self {
+            account,
+            scan_key,
+            spend_key,
+            labels: HashMap::new(),
+            secp: Secp256k1::new(),
+        };
+        scanner.add_label(0)?;
+        Ok(scanner)
+    }
+
+    /// Watch payments to the address with label `m`
+    pub fn add_label(&mut self, m: u32) -> WalletResult<()> {
+        let tweak = self.label_tweak(m)?;
+        let point = SecretKey::from_slice(&tweak)
+            .map_err(|_| WalletError::KeyDerivation)?
+            .public_key(&self.secp);
+        self.labels.insert(point, (m, tweak));
+        Ok(())
+    }
+
+    /// The address to publish, or its labelled variant
+    ///
+    /// Label 0 is for the wallet's own change and should not be published.
+    pub fn address(
+        &self,
+        network: Network,
+        label: Option<u32>,
+    ) -> WalletResult<SilentPaymentAddress> {
+        let spend_key = match label {
+            Some(m) => self
+                .spend_key
+                .add_exp_tweak(&self.secp, &scalar(self.label_tweak(m)?)?)
+                .map_err(|_| WalletError::KeyDerivation)?,
+            None => self.spend_key,
+        };
+        let scan_key = self.scan_key.public_key(&self.secp);
+        Ok(SilentPaymentAddress::new(scan_key, spend_key, network))
+    }
+
+    /// Find the outputs of a transaction paying these keys
+    ///
+    /// `prevouts` are the outputs spent by the transaction's inputs, in order.
+    pub fn scan_transaction(
+        &self,
+        tx: &Transaction,
+        prevouts: &[TxOut],
+    ) -> WalletResult<Vec<SilentPaymentOutput>> {
+        if prevouts.len() != tx.input.len() {
+            return Err(WalletError::InvalidInput(format!(
+                "Transaction {} spends {} outputs but {} were given",
+                tx.compute_txid(),
+                tx.input.len(),
+                prevouts.len()
+            )));
+        }
+        let Some(shared) = self.shared_secret(tx, prevouts) else {
+            return Ok(Vec::new());
+        };
+
+        let txid = tx.compute_txid();
+        let mut candidates: Vec<(u32, XOnlyPublicKey)> = tx
+            .output
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.script_pubkey.is_p2tr())
+            .filter_map(|(vout, output)| {
+                let key = XOnlyPublicKey::from_slice(&output.script_pubkey.as_bytes()[2..]).ok()?;
+                Some((vout as u32, key))
+            })
+            .collect();
+
+        // Outputs are numbered k = 0, 1, ... per scan key; stop at the first k without a match
+        let mut found = Vec::new();
+        for k in 0.. {
+            let Ok(t_k) = shared_secret_tweak(&shared, k) else {
+                break;
+            };
+            let Ok(expected) = self.spend_key.add_exp_tweak(&self.secp, &t_k) else {
+                break;
+            };
+            let Some((position, tweak, label)) =
+                candidates
+                    .iter()
+                    .enumerate()
+                    .find_map(|(position, (_, key))| {
+                        self.match_output(key, &expected, t_k)
+                            .map(|(tweak, label)| (position, tweak, label))
+                    })
+            else {
+                break;
+            };
+
+            let (vout, _) = candidates.remove(position);
+            found.push(SilentPaymentOutput {
+                outpoint: OutPoint::new(txid, vout),
+                txout: tx.output[vout as usize].clone(),
+                tweak,
+                label,
+                account: self.account,
+                block_height: None,
+            });
+        }
+        Ok(found)
+    }
+
+    /// Find the outputs of a block paying these keys
+    ///
+    /// `prevouts` must hold the outputs spent by the block's transactions;
+    /// transactions with missing prevouts are skipped.
+    pub fn scan_block(
+        &self,
+        block: &Block,
+        prevouts: &HashMap<OutPoint, TxOut>,
+    ) -> WalletResult<Vec<SilentPaymentOutput>> {
+        let height = block
+            .bip34_block_height()
+            .ok()
+            .and_then(|height| u32::try_from(height).ok());
+        let mut found = Vec::new();
+        for tx in block.txdata.iter().filter(|tx| is_candidate(tx)) {
+            let spent: Option<Vec<TxOut>> = tx
+                .input
+                .iter()
+                .map(|input| prevouts.get(&input.previous_output).cloned())
+                .collect();
+            if let Some(spent) = spent {
+                found.extend(
+                    self.scan_transaction(tx, &spent)?
+                        .into_iter()
+                        .map(|mut output| {
+                            output.block_height = height;
+                            output
+                        }),
+                );
+            }
+        }
+        Ok(found)
+    }
+
+    /// Fetch a block and the outputs it spends from a node, and scan it
+    ///
+    /// Prevouts created outside the block are looked up with
+    /// `getrawtransaction`, so the node needs `-txindex`.
+    pub async fn scan_rpc_block(
+        &self,
+        rpc: &mut RpcClient,
+        block_hash: &BlockHash,
+    ) -> NetworkResult<Vec<SilentPaymentOutput>> {
+        let block = rpc.get_block(block_hash).await?;
+
+        let mut prevouts = HashMap::new();
+        for tx in &block.txdata {
+            insert_outputs(&mut prevouts, tx.compute_txid(), tx);
+        }
+        let missing: Vec<Txid> = block
+            .txdata
+            .iter()
+            .filter(|tx| is_candidate(tx))
+            .flat_map(|tx| &tx.input)
+            .filter(|input| !prevouts.contains_key(&input.previous_output))
+            .map(|input| input.previous_output.txid)
+            .collect();
+        for txid in missing {
+            if !prevouts.contains_key(&OutPoint::new(txid, 0)) {
+                let previous = rpc.get_transaction(&txid).await?;
+                insert_outputs(&mut prevouts, txid, &previous);
+            }
+        }
+
+        self.scan_block(&block, &prevouts)
+            .map_err(|e| NetworkError::InvalidResponse(e.to_string()))
+    }
+
+    /// ECDH shared secret of a transaction, if it can pay silent payments
+    fn shared_secret(&self, tx: &Transaction, prevouts: &[TxOut]) -> Option<PublicKey> {
+        if !is_candidate(tx) {
+            return None;
+        }
+        // Spends of future witness versions make the transaction ineligible
+        if prevouts.iter().any(|prevout| {
+            prevout
+                .script_pubkey
+                .witness_version()
+                .is_some_and(|version| version.to_num() > 1)
+        }) {
+            return None;
+        }
+
+        let keys: Vec<PublicKey> = tx
+            .input
+            .iter()
+            .zip(prevouts)
+            .filter_map(|(input, prevout)| input_public_key(input, prevout))
+            .collect();
+        if keys.is_empty() {
+            return None;
+        }
+        let sum = PublicKey::combine_keys(&keys.iter().collect::<Vec<_>>()).ok()?;
+        let outpoints: Vec<OutPoint> = tx.input.iter().map(|input| input.previous_output).collect();
+        let tweak = self
+            .scan_key
+            .mul_tweak(&input_hash(&outpoints, &sum).ok()?)
+            .ok()?;
+        sum.mul_tweak(&self.secp, &Scalar::from(tweak)).ok()
+    }
+
+    /// Tweak and label of an output, if it is `expected` or a labelled variant of it
+    fn match_output(
+        &self,
+        output: &XOnlyPublicKey,
+        expected: &PublicKey,
+        t_k: Scalar,
+    ) -> Option<([u8; 32], Option<u32>)> {
+        if expected.x_only_public_key().0 == *output {
+            return Some((t_k.to_be_bytes(), None));
+        }
+
+        // output = expected + label point, for either parity of the output key
+        let output = PublicKey::from_x_only_public_key(*output, Parity::Even);
+        let negated = expected.negate(&self.secp);
+        [output, output.negate(&self.secp)]
+            .iter()
+            .filter_map(|candidate| candidate.combine(&negated).ok())
+            .find_map(|label_point| self.labels.get(&label_point))
+            .and_then(|&(m, label_tweak)| {
+                let tweak = SecretKey::from_slice(&t_k.to_be_bytes())
+                    .ok()?
+                    .add_tweak(&scalar(label_tweak).ok()?)
+                    .ok()?;
+                Some((tweak.secret_bytes(), Some(m)))
+            })
+    }
+
+    /// Label tweak `hash_BIP0352/Label(ser256(b_scan) || ser32(m))`
+    fn label_tweak(&self, m: u32) -> WalletResult<[u8; 32]> {
+        let tweak = tagged_hash(
+            "BIP0352/Label",
+            &[&self.scan_key.secret_bytes(), &m.to_be_bytes()],
+        );
+        scalar(tweak)?;
+        Ok(tweak)
+    }
+}
+
+/// Public key an input contributes to the shared secret, if any
+fn input_public_key(input: &TxIn, prevout: &TxOut) -> Option<PublicKey> {
+    let script = prevout.script_pubkey.as_bytes();
+    if prevout.script_pubkey.is_p2tr() {
+        let mut stack: Vec<&[u8]> = input.witness.iter().collect();
+        if stack.len() > 1 && stack.last().is_some_and(|item| item.first() == Some(&0x50)) {
+            stack.pop(); // annex
+        }
+        // Script path spends through the NUMS internal key have no key to share
+        if stack.len() > 1 {
+            let control_block = stack.last()?;
+            if control_block.get(1..33) == Some(NUMS_H.as_slice()) {
+                return None;
+            }
+        }
+        let key = XOnlyPublicKey::from_slice(&script[2..]).ok()?;
+        return Some(PublicKey::from_x_only_public_key(key, Parity::Even));
+    }
+
+    let compressed = |bytes: &[u8]| {
+        if bytes.len() == 33 {
+            PublicKey::from_slice(bytes).ok()
+        } else {
+            None
+        }
+    };
+    if prevout.script_pubkey.is_p2wpkh() {
+        return compressed(input.witness.last()?);
+    }
+    if prevout.script_pubkey.is_p2sh() {
+        let redeem_script = ScriptBuf::from_bytes(input.script_sig.as_bytes().get(1..)?.to_vec());
+        if redeem_script.is_p2wpkh() {
+            return compressed(input.witness.last()?);
+        }
+        return None;
+    }
+    if prevout.script_pubkey.is_p2pkh() {
+        // Find the key matching the hash, wherever it sits in a malleated scriptSig
+        let hash = &script[3..23];
+        let script_sig = input.script_sig.as_bytes();
+        return (33..=script_sig.len())
+            .rev()
+            .map(|end| &script_sig[end - 33..end])
+            .find(|key| hash160::Hash::hash(key).as_byte_array() == hash)
+            .and_then(compressed);
+    }
+    None
+}
+
+/// Whether a transaction could pay a silent payment at all
+fn is_candidate(tx: &Transaction) -> bool {
+    !tx.is_coinbase()
+        && tx
+            .output
+            .iter()
+            .any(|output| output.script_pubkey.is_p2tr())
+}
+
+fn insert_outputs(prevouts: &mut HashMap<OutPoint, TxOut>, txid: Txid, tx: &Transaction) {
+    for (vout, output) in tx.output.iter().enumerate() {
+        prevouts.insert(OutPoint::new(txid, vout as u32), output.clone());
+    }
+}
+
+/// `hash_BIP0352/Inputs(outpoint_L || A)` with the smallest serialized outpoint
+fn input_hash(outpoints: &[OutPoint], sum: &PublicKey) -> WalletResult<Scalar> {
+    let smallest = outpoints
+        .iter()
+        .map(serialize)
+        .min()
+        .ok_or_else(|| WalletError::InvalidInput("Transaction has no inputs".to_string()))?;
+    scalar(tagged_hash(
+        "BIP0352/Inputs",
+        &[&smallest, &sum.serialize()],
+    ))
+}
+
+/// `t_k = hash_BIP0352/SharedSecret(serP(ecdh_shared_secret) || ser32(k))`
+fn shared_secret_tweak(shared: &PublicKey, k: u32) -> WalletResult<Scalar> {
+    scalar(tagged_hash(
+        "BIP0352/SharedSecret",
+        &[&shared.serialize(), &k.to_be_bytes()],
+    ))
+}
+
+/// BIP-340 tagged hash
+fn tagged_hash(tag: &str, parts: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    for part in parts {
+        engine.input(part);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+fn scalar(bytes: [u8; 32]) -> WalletResult<Scalar> {
+    Scalar::from_be_bytes(bytes).map_err(|_| WalletError::KeyDerivation)
+}
+
+/// P2TR script paying an output key directly, without a BIP-341 tweak
+fn taproot_script(output_key: PublicKey) -> ScriptBuf {
+    let key = TweakedPublicKey::dangerous_assume_tweaked(output_key.x_only_public_key().0);
+    ScriptBuf::new_p2tr_tweaked(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{
+        absolute::LockTime, block::Header, key::Keypair, taproot::TapTweakHash,
+        transaction::Version, Amount, CompactTarget, CompressedPublicKey, Sequence, TxMerkleNode,
+        Witness,
+    };
+
+    fn secret(byte: u8) -> SecretKey {
+        SecretKey::from_slice(&[byte; 32]).unwrap()
+    }
+
+    fn input(previous: u8, script_sig: ScriptBuf, witness: Witness) -> TxIn {
+        TxIn {
+            previous_output: OutPoint::new(Txid::from_byte_array([previous; 32]), 1),
+            script_sig,
+            sequence: Sequence::MAX,
+            witness,
+        }
+    }
+
+    #[test]
+    fn test_tagged_hash_and_nums_point() {
+        // Same construction as the BIP-341 tweak hash
+        let secp = Secp256k1::new();
+        let key = secret(3).x_only_public_key(&secp).0;
+        assert_eq!(
+            tagged_hash("TapTweak", &[&key.serialize()]),
+            TapTweakHash::from_key_and_tweak(key, None).to_byte_array()
+        );
+
+        // H is the hash of the uncompressed generator point
+        let generator = SecretKey::from_slice(&[[0u8; 31].as_slice(), &[1]].concat())
+            .unwrap()
+            .public_key(&secp);
+        assert_eq!(
+            sha256::Hash::hash(&generator.serialize_uncompressed()).to_byte_array(),
+            NUMS_H
+        );
+    }
+
+    #[test]
+    fn test_key_paths() {
+        assert_eq!(
+            key_path(Network::Bitcoin, 2, true).to_string(),
+            "352'/0'/2'/1'/0"
+        );
+        assert_eq!(
+            key_path(Network::Bitcoin, 2, false).to_string(),
+            "352'/0'/2'/0'/0"
+        );
+        for network in [Network::Testnet, Network::Signet, Network::Regtest] {
+            assert_eq!(key_path(network, 0, true).to_string(), "352'/1'/0'/1'/0");
+        }
+    }
+
+    #[test]
+    fn test_address_encoding() {
+        let secp = Secp256k1::new();
+        let address = SilentPaymentAddress::new(
+            secret(1).public_key(&secp),
+            secret(2).public_key(&secp),
+            Network::Bitcoin,
+        );
+        let encoded = address.to_string();
+        assert!(encoded.starts_with("sp1q"));
+        assert_eq!(encoded.parse::<SilentPaymentAddress>().unwrap(), address);
+        assert_eq!(
+            encoded
+                .to_uppercase()
+                .parse::<SilentPaymentAddress>()
+                .unwrap(),
+            address
+        );
+        assert!(address.is_valid_for_network(Network::Bitcoin));
+        assert!(!address.is_valid_for_network(Network::Signet));
+
+        let signet =
+            SilentPaymentAddress::new(address.scan_key, address.spend_key, Network::Signet);
+        assert!(signet.to_string().starts_with("tsp1q"));
+        assert!(signet.is_valid_for_network(Network::Testnet));
+
+        // Later versions may carry extra data; version 0 and the reserved version may not
+        let hrp = Hrp::parse_unchecked("sp");
+        let encode = |version: u8, payload: &[u8]| {
+            payload
+                .iter()
+                .copied()
+                .bytes_to_fes()
+                .with_checksum::<Bech32m>(&hrp)
+                .with_witness_version(Fe32::try_from(version).unwrap())
+                .chars()
+                .collect::<String>()
+        };
+        let keys = [address.scan_key.serialize(), address.spend_key.serialize()].concat();
+        let extended = [keys.as_slice(), &[0u8; 4]].concat();
+        assert_eq!(
+            encode(1, &extended)
+                .parse::<SilentPaymentAddress>()
+                .unwrap(),
+            address
+        );
+        assert!(encode(0, &extended)
+            .parse::<SilentPaymentAddress>()
+            .is_err());
+        assert!(encode(31, &keys).parse::<SilentPaymentAddress>().is_err());
+        assert!(encode(0, &keys[..65])
+            .parse::<SilentPaymentAddress>()
+            .is_err());
+        assert!("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+            .parse::<SilentPaymentAddress>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_sender_and_receiver_agree() {
+        let secp = Secp256k1::new();
+        let network = Network::Regtest;
+        let (scan, spend) = (secret(0x11), secret(0x22));
+        let mut scanner = SilentPaymentScanner::new(0, scan, spend.public_key(&secp)).unwrap();
+        scanner.add_label(7).unwrap();
+        let address = scanner.address(network, None).unwrap();
+        let labelled = scanner.address(network, Some(7)).unwrap();
+        assert_ne!(address.spend_key, labelled.spend_key);
+
+        // One input of each eligible type: P2WPKH, P2TR key path and P2PKH
+        let keys = [
+            (secret(0x31), false),
+            (secret(0x32), true),
+            (secret(0x33), false),
+        ];
+        let public = |index: usize| CompressedPublicKey(keys[index].0.public_key(&secp));
+        let taproot_key = Keypair::from_secret_key(&secp, &keys[1].0)
+            .x_only_public_key()
+            .0;
+        let prevouts: Vec<TxOut> = [
+            ScriptBuf::new_p2wpkh(&public(0).wpubkey_hash()),
+            ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(taproot_key)),
+            ScriptBuf::new_p2pkh(&public(2).pubkey_hash()),
+        ]
+        .into_iter()
+        .map(|script_pubkey| TxOut {
+            value: Amount::from_sat(50_000),
+            script_pubkey,
+        })
+        .collect();
+        let signature = [0x30u8; 71];
+        let input = vec![
+            input(
+                3,
+                ScriptBuf::new(),
+                Witness::from_slice(&[&signature[..], &public(0).to_bytes()]),
+            ),
+            input(1, ScriptBuf::new(), Witness::from_slice(&[[0u8; 64]])),
+            input(
+                2,
+                bitcoin::script::Builder::new()
+                    .push_slice(signature)
+                    .push_slice(public(2).to_bytes())
+                    .into_script(),
+                Witness::new(),
+            ),
+        ];
+        let outpoints: Vec<OutPoint> = input.iter().map(|input| input.previous_output).collect();
+
+        let recipients = [address, labelled, address];
+        let scripts = derive_outputs(&secp, &outpoints, &keys, &recipients).unwrap();
+        assert_eq!(scripts.len(), 3);
+        assert!(scripts.iter().all(|script| script.is_p2tr()));
+        assert_ne!(scripts[0], scripts[2]);
+
+        let mut output: Vec<TxOut> = scripts
+            .iter()
+            .map(|script| TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: script.clone(),
+            })
+            .collect();
+        // Output order does not matter to the receiver
+        output.reverse();
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input,
+            output,
+        };
+
+        let found = scanner.scan_transaction(&tx, &prevouts).unwrap();
+        assert_eq!(found.len(), 3);
+        assert_eq!(
+            found
+                .iter()
+                .filter(|output| output.label == Some(7))
+                .count(),
+            1
+        );
+        for output in &found {
+            // The tweaked spend key controls the output key directly
+            let key = output.spending_key(&spend).unwrap();
+            assert_eq!(
+                taproot_script(key.public_key(&secp)),
+                output.txout.script_pubkey
+            );
+        }
+
+        // Other keys see nothing, and missing prevouts are rejected
+        let stranger = SilentPaymentScanner::new(0, secret(0x44), spend.public_key(&secp)).unwrap();
+        assert!(stranger
+            .scan_transaction(&tx, &prevouts)
+            .unwrap()
+            .is_empty());
+        assert!(scanner.scan_transaction(&tx, &prevouts[..2]).is_err());
+
+        // A NUMS script path spend does not count, so the shared secret changes
+        let mut nums_tx = tx.clone();
+        let control_block = [&[0xc0u8][..], &NUMS_H].concat();
+        nums_tx.input[1].witness = Witness::from_slice(&[&[0x51u8][..], &control_block]);
+        assert!(scanner
+            .scan_transaction(&nums_tx, &prevouts)
+            .unwrap()
+            .is_empty());
+
+        // Spending a future witness version makes the transaction ineligible
+        let mut future = prevouts.clone();
+        future[0].script_pubkey = ScriptBuf::from_bytes([&[0x52u8, 0x02][..], &[0u8; 2]].concat());
+        assert!(scanner.scan_transaction(&tx, &future).unwrap().is_empty());
+
+        // Block scans take the height from the coinbase and skip the coinbase itself
+        let coinbase = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: bitcoin::script::Builder::new().push_int(101).into_script(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![tx.output[0].clone()],
+        };
+        let block = Block {
+            header: Header {
+                version: bitcoin::block::Version::TWO,
+                prev_blockhash: BlockHash::all_zeros(),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 0,
+                bits: CompactTarget::from_consensus(0x207fffff),
+                nonce: 0,
+            },
+            txdata: vec![coinbase, tx.clone()],
+        };
+        let spent: HashMap<OutPoint, TxOut> = outpoints.into_iter().zip(prevouts).collect();
+        let found = scanner.scan_block(&block, &spent).unwrap();
+        assert_eq!(found.len(), 3);
+        assert!(found
+            .iter()
+            .all(|output| output.block_height == Some(101)
+                && output.outpoint.txid == tx.compute_txid()));
+        assert!(scanner
+            .scan_block(&block, &HashMap::new())
+            .unwrap()
+            .is_empty());
+    }
+
+    /// `send_and_receive_test_vectors.json` from the BIP-352 directory of the BIPs repository
+    const VECTORS: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/wallet/fixtures/bip352_send_and_receive_test_vectors.json"
+    );
+
+    fn hex_bytes(value: &serde_json::Value) -> Vec<u8> {
+        hex::decode(value.as_str().unwrap_or_default()).unwrap()
+    }
+
+    /// Inputs of a vector case with the outputs they spend
+    fn vector_inputs(vin: &serde_json::Value) -> (Vec<TxIn>, Vec<TxOut>) {
+        vin.as_array()
+            .unwrap()
+            .iter()
+            .map(|input| {
+                let witness = hex_bytes(&input["txinwitness"]);
+                let txin = TxIn {
+                    previous_output: OutPoint::new(
+                        input["txid"].as_str().unwrap().parse().unwrap(),
+                        input["vout"].as_u64().unwrap() as u32,
+                    ),
+                    script_sig: ScriptBuf::from_bytes(hex_bytes(&input["scriptSig"])),
+                    sequence: Sequence::MAX,
+                    witness: if witness.is_empty() {
+                        Witness::new()
+                    } else {
+                        bitcoin::consensus::deserialize(&witness).unwrap()
+                    },
+                };
+                let prevout = TxOut {
+                    value: Amount::ZERO,
+                    script_pubkey: ScriptBuf::from_bytes(hex_bytes(
+                        &input["prevout"]["scriptPubKey"]["hex"],
+                    )),
+                };
+                (txin, prevout)
+            })
+            .unzip()
+    }
+
+    /// X-only output keys, as the vectors list them
+    fn output_key(script: &ScriptBuf) -> String {
+        hex::encode(&script.as_bytes()[2..])
+    }
+
+    #[test]
+    #[ignore = "needs the BIP-352 send_and_receive_test_vectors.json at VECTORS"]
+    fn test_bip352_vectors() {
+        let secp = Secp256k1::new();
+        let vectors: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(VECTORS).expect("BIP-352 test vectors not found"),
+        )
+        .unwrap();
+
+        for case in vectors.as_array().unwrap() {
+            let comment = case["comment"].as_str().unwrap_or_default();
+
+            for sending in case["sending"].as_array().unwrap() {
+                let given = &sending["given"];
+                let (inputs, prevouts) = vector_inputs(&given["vin"]);
+                let keys: Vec<(SecretKey, bool)> = inputs
+                    .iter()
+                    .zip(&prevouts)
+                    .zip(given["vin"].as_array().unwrap())
+                    .filter(|((input, prevout), _)| input_public_key(input, prevout).is_some())
+                    .map(|((_, prevout), vin)| {
+                        let key = SecretKey::from_slice(&hex_bytes(&vin["private_key"])).unwrap();
+                        (key, prevout.script_pubkey.is_p2tr())
+                    })
+                    .collect();
+                let outpoints: Vec<OutPoint> =
+                    inputs.iter().map(|input| input.previous_output).collect();
+                // Older vector files pair each address with an amount
+                let recipients: Vec<SilentPaymentAddress> = given["recipients"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|recipient| {
+                        let address = recipient.get(0).unwrap_or(recipient);
+                        address.as_str().unwrap().parse().unwrap()
+                    })
+                    .collect();
+
+                let derived = derive_outputs(&secp, &outpoints, &keys, &recipients);
+                // Either one list of outputs or every acceptable list
+                let expected = &sending["expected"]["outputs"];
+                let candidates: Vec<Vec<String>> = match expected.get(0) {
+                    Some(serde_json::Value::Array(_)) => expected
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|set| serde_json::from_value(set.clone()).unwrap())
+                        .collect(),
+                    _ => vec![serde_json::from_value(expected.clone()).unwrap()],
+                };
+                // No outputs means the sender must refuse (no eligible inputs, or keys summing to zero)
+                if candidates.iter().all(Vec::is_empty) {
+                    assert!(derived.is_err(), "sending: {comment}");
+                    continue;
+                }
+                let mut derived: Vec<String> = derived
+                    .unwrap_or_else(|e| panic!("sending: {comment}: {e}"))
+                    .iter()
+                    .map(output_key)
+                    .collect();
+                derived.sort();
+                assert!(
+                    candidates.into_iter().any(|mut candidate| {
+                        candidate.sort();
+                        candidate == derived
+                    }),
+                    "sending: {comment}"
+                );
+            }
+
+            for receiving in case["receiving"].as_array().unwrap() {
+                let given = &receiving["given"];
+                let expected = &receiving["expected"];
+                let key = |name: &str| {
+                    SecretKey::from_slice(&hex_bytes(&given["key_material"][name])).unwrap()
+                };
+                let spend = key("spend_priv_key");
+                let mut scanner =
+                    SilentPaymentScanner::new(0, key("scan_priv_key"), spend.public_key(&secp))
+                        .unwrap();
+                let labels: Vec<u32> = serde_json::from_value(given["labels"].clone()).unwrap();
+                let mut addresses = vec![scanner.address(Network::Bitcoin, None).unwrap()];
+                for &m in &labels {
+                    scanner.add_label(m).unwrap();
+                    addresses.push(scanner.address(Network::Bitcoin, Some(m)).unwrap());
+                }
+                let mut addresses: Vec<String> =
+                    addresses.iter().map(ToString::to_string).collect();
+                let mut expected_addresses: Vec<String> =
+                    serde_json::from_value(expected["addresses"].clone()).unwrap();
+                addresses.sort();
+                expected_addresses.sort();
+                assert_eq!(addresses, expected_addresses, "receiving: {comment}");
+
+                let (input, prevouts) = vector_inputs(&given["vin"]);
+                let output = given["outputs"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|key| TxOut {
+                        value: Amount::from_sat(1_000),
+                        script_pubkey: ScriptBuf::from_bytes(
+                            [&[0x51, 0x20][..], &hex_bytes(key)].concat(),
+                        ),
+                    })
+                    .collect();
+                let tx = Transaction {
+                    version: Version::TWO,
+                    lock_time: LockTime::ZERO,
+                    input,
+                    output,
+                };
+                let mut found: Vec<(String, String)> = scanner
+                    .scan_transaction(&tx, &prevouts)
+                    .unwrap_or_else(|e| panic!("receiving: {comment}: {e}"))
+                    .iter()
+                    .map(|output| {
+                        (
+                            output_key(&output.txout.script_pubkey),
+                            hex::encode(output.tweak),
+                        )
+                    })
+                    .collect();
+                let mut wanted: Vec<(String, String)> = expected["outputs"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|output| {
+                        (
+                            output["pub_key"].as_str().unwrap().to_string(),
+                            output["priv_key_tweak"].as_str().unwrap().to_string(),
+                        )
+                    })
+                    .collect();
+                found.sort();
+                wanted.sort();
+                assert_eq!(found, wanted, "receiving: {comment}");
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use crate::error::{WalletError, WalletResult};
+/// Watch-only account keys
+///
+/// An online machine only needs the account-level extended public key of each
+/// address type to generate addresses, track UTXOs and build unsigned PSBTs.
+/// This module models such an account key together with its key origin
+/// (master fingerprint and derivation path), and converts it to and from
+/// single-key output descriptors such as `wpkh([d34db33f/84'/0'/0']xpub.../0/*)`.
+use crate::wallet::descriptor_wallet::AddressType;
+
+use bitcoin::{
+    bip32::{ChildNumber, DerivationPath, Fingerprint, Xpub},
+    secp256k1::{Secp256k1, Verification},
+    PublicKey,
+};
+use miniscript::descriptor::{
+    DerivPaths, Descriptor, DescriptorMultiXKey, DescriptorPublicKey, DescriptorXKey, ShInner,
+    Wildcard,
+};
+use std::str::FromStr;
+
+/// Account-level extended public key with key origin information
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountXpub {
+    /// Address type (script template) used for this account
+    pub address_type: AddressType,
+    /// Account extended public key
+    pub xpub: Xpub,
+    /// Fingerprint of the master key the account was derived from
+    pub fingerprint: Fingerprint,
+    /// Path from the master key to the account key (e.g. `84'/0'/0'`)
+    pub origin: DerivationPath,
+}
+
+impl AccountXpub {
+    /// Create an account key from its parts
+    pub fn new(
+        address_type: AddressType,
+        xpub: Xpub,
+        fingerprint: Fingerprint,
+        origin: DerivationPath,
+    ) -> Self {
+        Self {
+            address_type,
+            xpub,
+            fingerprint,
+            origin,
+        }
+    }
+
+    /// Parse a single-key account descriptor
+    ///
+    /// Supports `pkh`, `sh(wpkh)`, `wpkh` and key-path-only `tr` descriptors
+    /// over an xpub ending in `/0/*`, `/1/*` or `/<0;1>/*`. A descriptor
+    /// checksum is validated when present. Without origin information the
+    /// xpub itself is treated as the root.
+    pub fn from_descriptor(descriptor: &str) -> WalletResult<Self> {
+        let parsed = Descriptor::<DescriptorPublicKey>::from_str(descriptor.trim())
+            .map_err(|e| WalletError::InvalidInput(format!("Invalid descriptor: {e}")))?;
+
+        let (address_type, key) = match &parsed {
+            Descriptor::Pkh(pkh) => (AddressType::Legacy, pkh.as_inner()),
+            Descriptor::Wpkh(wpkh) => (AddressType::NativeSegwit, wpkh.as_inner()),
+            Descriptor::Sh(sh) => match sh.as_inner() {
+                ShInner::Wpkh(wpkh) => (AddressType::NestedSegwit, wpkh.as_inner()),
+                _ => return Err(unsupported(descriptor)),
+            },
+            Descriptor::Tr(tr) if tr.tap_tree().is_none() => {
+                (AddressType::Taproot, tr.internal_key())
+            }
+            _ => return Err(unsupported(descriptor)),
+        };
+
+        let (origin, xpub, chain_paths, wildcard) = match key {
+            DescriptorPublicKey::XPub(xkey) => (
+                xkey.origin.clone(),
+                xkey.xkey,
+                vec![xkey.derivation_path.clone()],
+                xkey.wildcard,
+            ),
+            DescriptorPublicKey::MultiXPub(xkey) => (
+                xkey.origin.clone(),
+                xkey.xkey,
+                xkey.derivation_paths.paths().clone(),
+                xkey.wildcard,
+            ),
+            DescriptorPublicKey::Single(_) => {
+                return Err(WalletError::InvalidInput(
+                    "Watch-only descriptors need an extended public key".to_string(),
+                ))
+            }
+        };
+
+        // Only the standard receive/change chains below the account key are supported
+        let is_chain = |path: &DerivationPath| {
+            matches!(
+                path.as_ref(),
+                [ChildNumber::Normal { index: 0 }] | [ChildNumber::Normal { index: 1 }]
+            )
+        };
+        if wildcard != Wildcard::Unhardened || !chain_paths.iter().all(is_chain) {
+            return Err(WalletError::InvalidInput(format!(
+                "Descriptor must derive from <xpub>/0/*, /1/* or /<0;1>/*: {descriptor}"
+            )));
+        }
+
+        let (fingerprint, origin) =
+            origin.unwrap_or_else(|| (xpub.fingerprint(), DerivationPath::master()));
+
+        Ok(Self::new(address_type, xpub, fingerprint, origin))
+    }
+
+    /// BIP-44 account number taken from the key origin (0 when unknown)
+    pub fn account(&self) -> u32 {
+        match self.origin.as_ref().get(2) {
+            Some(ChildNumber::Hardened { index }) => *index,
+            _ => 0,
+        }
+    }
+
+    /// Ranged descriptor for the receive (`change = false`) or change chain
+    pub fn descriptor(&self, change: bool) -> String {
+        self.ranged_descriptor(change).to_string()
+    }
+
+    /// Parsed form of `descriptor`
+    pub fn ranged_descriptor(&self, change: bool) -> Descriptor<DescriptorPublicKey> {
+        self.template(DescriptorPublicKey::XPub(DescriptorXKey {
+            origin: Some((self.fingerprint, self.origin.clone())),
+            xkey: self.xpub,
+            derivation_path: chain(u32::from(change)),
+            wildcard: Wildcard::Unhardened,
+        }))
+    }
+
+    /// Descriptor deriving both chains, `<xpub>/<0;1>/*`, with key origin and checksum
+    pub fn multipath_descriptor(&self) -> String {
+        let paths = DerivPaths::new(vec![chain(0), chain(1)]).expect("Two derivation paths");
+        self.template(DescriptorPublicKey::MultiXPub(DescriptorMultiXKey {
+            origin: Some((self.fingerprint, self.origin.clone())),
+            xkey: self.xpub,
+            derivation_paths: paths,
+            wildcard: Wildcard::Unhardened,
+        }))
+        .to_string()
+    }
+
+    /// Wrap a key in the script template of the address type
+    fn template(&self, key: DescriptorPublicKey) -> Descriptor<DescriptorPublicKey> {
+        let descriptor = match self.address_type {
+            AddressType::Legacy => Descriptor::new_pkh(key),
+            AddressType::NestedSegwit => Descriptor::new_sh_wpkh(key),
+            AddressType::NativeSegwit => Descriptor::new_wpkh(key),
+            AddressType::Taproot => Descriptor::new_tr(key, None),
+        };
+
+        // Single-key templates over a valid xpub cannot fail
+        descriptor.expect("Valid single-key descriptor")
+    }
+
+    /// Full derivation path from the master key to `change/index`
+    pub fn chain_path(&self, change: u32, index: u32) -> WalletResult<DerivationPath> {
+        Ok(self.origin.extend([
+            ChildNumber::from_normal_idx(change).map_err(|_| WalletError::KeyDerivation)?,
+            ChildNumber::from_normal_idx(index).map_err(|_| WalletError::KeyDerivation)?,
+        ]))
+    }
+
+    /// Derive the public key for a full path below this account, if it belongs to it
+    pub fn derive_public_key<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        path: &DerivationPath,
+    ) -> Option<WalletResult<PublicKey>> {
+        let suffix = path.as_ref().strip_prefix(self.origin.as_ref())?;
+        if suffix.len() != 2 {
+            return None;
+        }
+
+        Some(
+            self.xpub
+                .derive_pub(secp, &suffix)
+                .map(|xpub| PublicKey::new(xpub.public_key))
+                .map_err(|_| WalletError::KeyDerivation),
+        )
+    }
+}
+
+/// Path of a chain below the account key
+fn chain(index: u32) -> DerivationPath {
+    DerivationPath::from(vec![ChildNumber::Normal { index }])
+}
+
+fn unsupported(descriptor: &str) -> WalletError {
+    WalletError::InvalidInput(format!(
+        "Only single-key pkh, sh(wpkh), wpkh and tr descriptors can be watched: {descriptor}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // BIP-84 test vector account key for the "abandon ... about" mnemonic
+    const BIP84_ACCOUNT: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+    const BIP84_XPUB: &str = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+
+    #[test]
+    fn test_descriptor_roundtrip() {
+        let xpub = Xpub::from_str(BIP84_XPUB).unwrap();
+        let account = AccountXpub::new(
+            AddressType::NativeSegwit,
+            xpub,
+            Fingerprint::from_str("73c5da0a").unwrap(),
+            DerivationPath::from_str("m/84'/0'/0'").unwrap(),
+        );
+
+        let receive = account.descriptor(false);
+        assert!(receive.starts_with(&format!("wpkh([73c5da0a/84'/0'/0']{BIP84_XPUB}/0/*)#")));
+
+        assert_eq!(AccountXpub::from_descriptor(&receive).unwrap(), account);
+        assert_eq!(
+            AccountXpub::from_descriptor(&account.descriptor(true)).unwrap(),
+            account
+        );
+        assert_eq!(account.account(), 0);
+
+        let multipath = account.multipath_descriptor();
+        assert!(multipath.starts_with(&format!("wpkh([73c5da0a/84'/0'/0']{BIP84_XPUB}/<0;1>/*)#")));
+        assert_eq!(AccountXpub::from_descriptor(&multipath).unwrap(), account);
+    }
+
+    #[test]
+    fn test_descriptor_types_and_multipath() {
+        let nested = format!("sh(wpkh([73c5da0a/49'/0'/2']{BIP84_XPUB}/<0;1>/*))");
+        let account = AccountXpub::from_descriptor(&nested).unwrap();
+        assert_eq!(account.address_type, AddressType::NestedSegwit);
+        assert_eq!(account.account(), 2);
+
+        let taproot = format!("tr({BIP84_XPUB}/0/*)");
+        let account = AccountXpub::from_descriptor(&taproot).unwrap();
+        assert_eq!(account.address_type, AddressType::Taproot);
+        assert_eq!(account.origin, DerivationPath::master());
+        assert_eq!(account.fingerprint, account.xpub.fingerprint());
+    }
+
+    #[test]
+    fn test_rejects_unsupported_descriptors() {
+        let pubkey = "03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd";
+        for descriptor in [
+            format!("wpkh({pubkey})"),
+            format!("wsh(pk({BIP84_XPUB}/0/*))"),
+            format!("wpkh({BIP84_XPUB}/0/5)"),
+            format!("wpkh({BIP84_XPUB}/7/*)"),
+            BIP84_ACCOUNT.to_string(),
+        ] {
+            assert!(
+                AccountXpub::from_descriptor(&descriptor).is_err(),
+                "{descriptor} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_checksum() {
+        let xpub = Xpub::from_str(BIP84_XPUB).unwrap();
+        let account = AccountXpub::new(
+            AddressType::NativeSegwit,
+            xpub,
+            Fingerprint::from_str("73c5da0a").unwrap(),
+            DerivationPath::from_str("m/84'/0'/0'").unwrap(),
+        );
+        let mut descriptor = account.descriptor(false);
+        let last = descriptor.pop().unwrap();
+        descriptor.push(if last == 'q' { 'p' } else { 'q' });
+
+        assert!(AccountXpub::from_descriptor(&descriptor).is_err());
+    }
+}